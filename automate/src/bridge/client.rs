@@ -48,6 +48,7 @@ pub struct WsClient<W, R> {
     is_initialized: Option<bool>,
     ssh_connection_option: Option<SshConnectionOption>,
     assign_user_option: Option<AssignUserOption>,
+    labels: Option<String>,
     msg_box: Cache<u64, TransactionMsg>,
     bridge: Option<Bridge>,
     receiver: Option<Receiver<(Msg, Option<Sender<MsgState>>)>>,
@@ -71,6 +72,7 @@ impl<W, R> WsClient<W, R> {
             msg_box: cache,
             assign_user_option: None,
             ssh_connection_option: None,
+            labels: None,
             ws_writer: None,
             ws_reader: None,
             receiver: Some(receiver),
@@ -107,6 +109,12 @@ impl<W, R> WsClient<W, R> {
         self
     }
 
+    /// labels such as env=prod,role=web which the console tags the instance with
+    pub fn set_labels(&mut self, labels: String) -> &mut Self {
+        self.labels = Some(labels);
+        self
+    }
+
     pub fn sender(&self) -> Sender<(Msg, Option<Sender<MsgState>>)> {
         self.sender.clone()
     }
@@ -350,6 +358,10 @@ impl
                 .with_header("X-Ssh-Port", ssh_opt.port.to_string());
         }
 
+        if let Some(ref labels) = self.labels {
+            req = req.with_header("X-Agent-Labels", labels.clone());
+        }
+
         let (ws_stream, _b) = connect_async(req).await?;
         let (ws_writer, ws_reader) = ws_stream.split();
         self.ws_reader = Some(ws_reader);
@@ -9,8 +9,11 @@ use tokio::sync::mpsc::Sender;
 use crate::{
     comet::handler::SecretHeader,
     scheduler::types::{
-        BaseJob, BundleOutput, JobAction, RunStatus, RuntimeAction, ScheduleStatus, ScheduleType,
+        get_exit_code, BaseJob, BundleOutput, JobAction, ParallelPolicy, ProbeResult, RunStatus,
+        RuntimeAction, ScheduleStatus, ScheduleType, SupervisorOptions, TerminationReason,
+        TimerOptions,
     },
+    ssh::SshAuth,
 };
 
 pub enum MsgState {
@@ -33,7 +36,11 @@ impl TransactionMsg {
 #[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
 pub struct SftpReadDirParams {
     pub user: String,
-    pub password: String,
+    #[serde(flatten)]
+    pub auth: SshAuth,
+    /// pinned host key fingerprint, empty to trust the first key seen
+    #[serde(default)]
+    pub host_key: String,
     pub ip: String,
     pub port: u16,
     pub dir: Option<String>,
@@ -44,7 +51,10 @@ pub struct SftpUploadParams {
     pub ip: String,
     pub port: u16,
     pub user: String,
-    pub password: String,
+    #[serde(flatten)]
+    pub auth: SshAuth,
+    #[serde(default)]
+    pub host_key: String,
     pub filepath: String,
     pub data: Vec<u8>,
 }
@@ -54,7 +64,10 @@ pub struct SftpDownloadParams {
     pub ip: String,
     pub port: u16,
     pub user: String,
-    pub password: String,
+    #[serde(flatten)]
+    pub auth: SshAuth,
+    #[serde(default)]
+    pub host_key: String,
     pub filepath: String,
 }
 
@@ -63,11 +76,27 @@ pub struct SftpRemoveParams {
     pub ip: String,
     pub port: u16,
     pub user: String,
-    pub password: String,
+    #[serde(flatten)]
+    pub auth: SshAuth,
+    #[serde(default)]
+    pub host_key: String,
     pub remove_type: String,
     pub filepath: String,
 }
 
+/// read the host key fingerprint of the ssh server on the agent
+#[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct SshHostKeyParams {
+    pub ip: String,
+    pub port: u16,
+}
+
+/// timers the console expects on the agent, the others are stopped
+#[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
+pub struct ReconcileParams {
+    pub timers: Vec<String>,
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
 pub enum MsgReqKind {
     DispatchJobRequest(DispatchJobParams),
@@ -80,6 +109,32 @@ pub enum MsgReqKind {
     Auth(AuthParams),
     UpdateJobRequest(UpdateJobParams),
     HeartbeatRequest(HeartbeatParams),
+    JobOutputRequest(JobOutputParams),
+    ReadJobLogRequest(ReadJobLogParams),
+    SshHostKeyRequest(SshHostKeyParams),
+    ReconcileRequest(ReconcileParams),
+}
+
+impl MsgReqKind {
+    /// name of the request kind, safe to log unlike the params which may carry secrets
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::DispatchJobRequest(_) => "DispatchJobRequest",
+            Self::RuntimeActionRequest(_) => "RuntimeActionRequest",
+            Self::PullJobRequest(_) => "PullJobRequest",
+            Self::SftpReadDirRequest(_) => "SftpReadDirRequest",
+            Self::SftpUploadRequest(_) => "SftpUploadRequest",
+            Self::SftpDownloadRequest(_) => "SftpDownloadRequest",
+            Self::SftpRemoveRequest(_) => "SftpRemoveRequest",
+            Self::Auth(_) => "Auth",
+            Self::UpdateJobRequest(_) => "UpdateJobRequest",
+            Self::HeartbeatRequest(_) => "HeartbeatRequest",
+            Self::JobOutputRequest(_) => "JobOutputRequest",
+            Self::ReadJobLogRequest(_) => "ReadJobLogRequest",
+            Self::SshHostKeyRequest(_) => "SshHostKeyRequest",
+            Self::ReconcileRequest(_) => "ReconcileRequest",
+        }
+    }
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
@@ -101,10 +156,17 @@ pub struct DispatchJobParams {
     pub instance_id: Option<String>,
     pub fields: Option<HashMap<String, serde_json::Value>>,
     pub timer_expr: Option<String>,
+    #[serde(default)]
+    pub timer_options: TimerOptions,
     pub restart_interval: Option<Duration>,
+    #[serde(default)]
+    pub supervisor_options: SupervisorOptions,
     pub is_sync: bool,
     pub created_user: String,
     pub action: JobAction,
+    /// the schedule type of the dispatch, an exec of a flow node is reported as a flow run
+    #[serde(default)]
+    pub schedule_type: Option<ScheduleType>,
 }
 
 #[derive(Deserialize, Serialize, PartialEq, Debug, Clone)]
@@ -153,6 +215,54 @@ pub struct UpdateJobParams {
     pub end_time: Option<DateTime<Utc>>,
     pub prev_time: Option<DateTime<Utc>>,
     pub next_time: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub attempt: Option<u32>,
+    /// set when a run hits max_parallel, tells which policy was applied to it
+    #[serde(default)]
+    pub parallel_decision: Option<ParallelPolicy>,
+    /// why the run ended, set when run_status is Stop or Retrying
+    #[serde(default)]
+    pub termination_reason: Option<TerminationReason>,
+    /// name of the calendar which did not allow the timer run at prev_time
+    #[serde(default)]
+    pub skipped_by_calendar: Option<String>,
+    /// how long the first attempt of a timer run started after its tick, jitter included
+    #[serde(default)]
+    pub start_offset_ms: Option<u64>,
+    /// how many times the daemon was restarted since its supervisor started
+    #[serde(default)]
+    pub restart_count: Option<u32>,
+    /// the last liveness probe of a daemon, sent when it fails or the daemon becomes healthy
+    #[serde(default)]
+    pub probe_result: Option<ProbeResult>,
+    /// names the log of the attempt kept in the agent's output_dir
+    #[serde(default)]
+    pub run_id: Option<String>,
+}
+
+/// a chunk of output produced by a running job
+#[derive(Deserialize, Serialize, PartialEq, Debug, Clone, Default)]
+pub struct JobOutputParams {
+    pub schedule_id: String,
+    pub instance_id: String,
+    pub eid: String,
+    pub attempt: u32,
+    #[serde(default)]
+    pub run_id: String,
+    pub data: String,
+}
+
+/// read the untruncated output of a run kept in the agent's output_dir
+#[derive(Deserialize, Serialize, PartialEq, Debug, Clone, Default)]
+pub struct ReadJobLogParams {
+    pub eid: String,
+    pub schedule_id: String,
+    pub attempt: u32,
+    /// empty for the runs logged before run ids, their log is found by the attempt
+    #[serde(default)]
+    pub run_id: String,
+    pub offset: u64,
+    pub limit: u64,
 }
 
 impl UpdateJobParams {
@@ -178,21 +288,15 @@ pub struct BundleOutputParams {
 impl BundleOutputParams {
     pub fn parse(value: &BundleOutput) -> Option<Vec<BundleOutputParams>> {
         match value {
-            BundleOutput::Output(_) => None,
+            BundleOutput::Output(..) => None,
             BundleOutput::Bundle(v) => Some(
                 v.iter()
-                    .map(|v| BundleOutputParams {
-                        eid: v.0.to_owned(),
-                        exit_code: {
-                            if v.1.status.success() {
-                                v.1.status.code()
-                            } else {
-                                v.1.status.code().or(Some(9))
-                            }
-                        },
-                        exit_status: Some(v.1.status.to_string()),
-                        stdout: Some(String::from_utf8_lossy(&v.1.stdout).to_string()),
-                        stderr: Some(String::from_utf8_lossy(&v.1.stderr).to_string()),
+                    .map(|(eid, (output, _))| BundleOutputParams {
+                        eid: eid.to_owned(),
+                        exit_code: get_exit_code(&output.status),
+                        exit_status: Some(output.status.to_string()),
+                        stdout: Some(String::from_utf8_lossy(&output.stdout).to_string()),
+                        stderr: Some(String::from_utf8_lossy(&output.stderr).to_string()),
                     })
                     .collect::<Vec<BundleOutputParams>>(),
             ),
@@ -1,7 +1,7 @@
 use std::pin::Pin;
 
 use anyhow::Result;
-use futures::Future;
+use futures::{Future, Stream, StreamExt};
 use local_ip_address::local_ip;
 use redis::{
     from_redis_value,
@@ -13,7 +13,9 @@ use serde::{Deserialize, Serialize};
 
 use tracing::{error, info, warn};
 
-use crate::bridge::msg::{AgentOfflineParams, AgentOnlineParams, HeartbeatParams, UpdateJobParams};
+use crate::bridge::msg::{
+    AgentOfflineParams, AgentOnlineParams, HeartbeatParams, JobOutputParams, UpdateJobParams,
+};
 
 #[derive(Debug, Serialize, Deserialize, FromRedisValue, ToRedisArgs)]
 pub enum Msg {
@@ -31,6 +33,7 @@ pub struct Bus {
 impl Bus {
     pub const JOB_TOPIC: &'static str = "jiascheduler:job:event";
     pub const CONSUMER_GROUP: &'static str = "jiascheduler-group";
+    pub const JOB_OUTPUT_CHANNEL: &'static str = "jiascheduler:job:output";
 
     pub fn new(redis_client: Client) -> Self {
         Self { redis_client }
@@ -52,6 +55,40 @@ impl Bus {
         self.send_msg(&[("event", Msg::AgentOffline(msg))]).await
     }
 
+    fn job_output_channel(schedule_id: &str, instance_id: &str) -> String {
+        format!("{}:{schedule_id}:{instance_id}", Self::JOB_OUTPUT_CHANNEL)
+    }
+
+    /// job output is published instead of added to the stream, every console subscribed
+    /// to the schedule gets a copy and nothing is kept once the job finished
+    pub async fn publish_job_output(&self, msg: JobOutputParams) -> Result<()> {
+        let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
+        let channel = Self::job_output_channel(&msg.schedule_id, &msg.instance_id);
+        let _: i64 = conn.publish(channel, serde_json::to_string(&msg)?).await?;
+        Ok(())
+    }
+
+    pub async fn subscribe_job_output(
+        &self,
+        schedule_id: &str,
+        instance_id: &str,
+    ) -> Result<impl Stream<Item = JobOutputParams>> {
+        let mut pubsub = self.redis_client.get_async_pubsub().await?;
+        pubsub
+            .subscribe(Self::job_output_channel(schedule_id, instance_id))
+            .await?;
+
+        Ok(pubsub.into_on_message().filter_map(|msg| async move {
+            let payload: String = msg
+                .get_payload()
+                .map_err(|e| error!("failed to get job output payload - {e}"))
+                .ok()?;
+            serde_json::from_str(&payload)
+                .map_err(|e| error!("failed to parse job output - {e}"))
+                .ok()
+        }))
+    }
+
     pub async fn send_msg<'a>(&self, items: &'a [(&'a str, Msg)]) -> Result<String> {
         let mut conn = self.redis_client.get_multiplexed_async_connection().await?;
 
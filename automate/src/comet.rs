@@ -7,14 +7,14 @@ use handler::SecretHeader;
 use poem::web::websocket::WebSocketStream;
 use serde_json::{json, Value};
 use tokio::sync::{mpsc::Sender, Mutex};
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use types::SshLoginParams;
 
 use crate::{
     bridge::{
         msg::{
-            AgentOfflineParams, AgentOnlineParams, HeartbeatParams, Msg, MsgReqKind, MsgState,
-            UpdateJobParams,
+            AgentOfflineParams, AgentOnlineParams, HeartbeatParams, JobOutputParams, Msg,
+            MsgReqKind, MsgState, UpdateJobParams,
         },
         Bridge,
     },
@@ -158,6 +158,24 @@ impl Comet {
         Ok(ret)
     }
 
+    pub async fn read_job_log(&self, req: types::ReadJobLogRequest) -> Result<Value> {
+        let val = self.logic.read_job_log(req).await?;
+        let ret = self.bridge.send_msg(&val.0, val.1).await?;
+        Ok(ret)
+    }
+
+    pub async fn ssh_host_key(&self, req: types::SshHostKeyRequest) -> Result<Value> {
+        let val = self.logic.ssh_host_key(req).await?;
+        let ret = self.bridge.send_msg(&val.0, val.1).await?;
+        Ok(ret)
+    }
+
+    pub async fn reconcile(&self, req: types::ReconcileRequest) -> Result<Value> {
+        let val = self.logic.reconcile(req).await?;
+        let ret = self.bridge.send_msg(&val.0, val.1).await?;
+        Ok(ret)
+    }
+
     pub async fn heartbeat(&self, req: HeartbeatParams) -> Result<Value> {
         let v = self.logic.heartbeat(req, self.port).await?;
         Ok(v)
@@ -168,12 +186,21 @@ impl Comet {
         Ok(ret)
     }
 
+    pub async fn job_output(&self, req: JobOutputParams) -> Result<Value> {
+        let ret = self.logic.job_output(req).await?;
+        Ok(ret)
+    }
+
     pub async fn handle(&self, msg: MsgReqKind) -> Value {
         match msg {
             MsgReqKind::PullJobRequest(v) => self.pull_job(v).await,
             MsgReqKind::HeartbeatRequest(v) => self.heartbeat(v).await,
             MsgReqKind::UpdateJobRequest(v) => self.update_job(v).await,
-            _ => todo!(),
+            MsgReqKind::JobOutputRequest(v) => self.job_output(v).await,
+            other => {
+                warn!("ignore unsupported msg {}", other.name());
+                Err(anyhow::anyhow!("unsupported msg"))
+            }
         }
         .map_or_else(
             |e| {
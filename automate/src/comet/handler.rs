@@ -36,7 +36,7 @@ use crate::{
         Comet,
     },
     return_response,
-    scheduler::types::{SshConnectionOption, UploadFile},
+    scheduler::types::{parse_labels, SshConnectionOption, UploadFile},
 };
 
 pub mod middleware {
@@ -90,6 +90,9 @@ pub struct SecretHeader {
     pub mac_addr: String,
     pub assign_user: Option<(String, String)>,
     pub ssh_connection_params: Option<SshConnectionOption>,
+    /// labels reported by the agent, the console tags the instance with them
+    #[serde(default)]
+    pub labels: Vec<(String, String)>,
 }
 
 // Implements a token extractor
@@ -123,16 +126,26 @@ impl<'a> FromRequest<'a> for SecretHeader {
                 .flatten()
         });
 
+        let labels = match header
+            .get("X-Agent-Labels")
+            .and_then(|value| value.to_str().ok())
+        {
+            Some(v) => parse_labels(v)?,
+            None => vec![],
+        };
+
         let mut assign = match (username, password) {
             (Some(u), Some(p)) => SecretHeader {
                 assign_user: Some((u.to_string(), p.to_string())),
                 ssh_connection_params: None,
                 mac_addr: mac_addr.to_string(),
+                labels,
             },
             _ => SecretHeader {
                 assign_user: None,
                 ssh_connection_params: None,
                 mac_addr: mac_addr.to_string(),
+                labels,
             },
         };
 
@@ -409,3 +422,45 @@ pub async fn sftp_remove(
         Err(e) => return_response!(code: 50000, e.to_string()),
     }
 }
+
+#[handler]
+pub async fn read_job_log(
+    comet: Data<&Comet>,
+    Json(req): Json<types::ReadJobLogRequest>,
+) -> Json<serde_json::Value> {
+    let ret = comet.read_job_log(req).await;
+    match ret {
+        Ok(v) => {
+            return_response!(json:v);
+        }
+        Err(e) => return_response!(code: 50000, e.to_string()),
+    }
+}
+
+#[handler]
+pub async fn ssh_host_key(
+    comet: Data<&Comet>,
+    Json(req): Json<types::SshHostKeyRequest>,
+) -> Json<serde_json::Value> {
+    let ret = comet.ssh_host_key(req).await;
+    match ret {
+        Ok(v) => {
+            return_response!(json:v);
+        }
+        Err(e) => return_response!(code: 50000, e.to_string()),
+    }
+}
+
+#[handler]
+pub async fn reconcile(
+    comet: Data<&Comet>,
+    Json(req): Json<types::ReconcileRequest>,
+) -> Json<serde_json::Value> {
+    let ret = comet.reconcile(req).await;
+    match ret {
+        Ok(v) => {
+            return_response!(json:v);
+        }
+        Err(e) => return_response!(code: 50000, e.to_string()),
+    }
+}
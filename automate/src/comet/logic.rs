@@ -2,7 +2,8 @@ use std::net::IpAddr;
 
 use crate::{
     bridge::msg::{
-        AgentOfflineParams, AgentOnlineParams, HeartbeatParams, MsgReqKind, UpdateJobParams,
+        AgentOfflineParams, AgentOnlineParams, HeartbeatParams, JobOutputParams, MsgReqKind,
+        UpdateJobParams,
     },
     bus::Bus,
     get_endpoint, LinkPair,
@@ -113,6 +114,29 @@ impl Logic {
         Ok((key, msg))
     }
 
+    pub async fn read_job_log(
+        &self,
+        req: types::ReadJobLogRequest,
+    ) -> Result<(String, MsgReqKind)> {
+        let key = self.get_agent_key(&req.agent_ip, &req.mac_addr);
+        let msg = MsgReqKind::ReadJobLogRequest(req.params);
+        Ok((key, msg))
+    }
+
+    pub async fn ssh_host_key(
+        &self,
+        req: types::SshHostKeyRequest,
+    ) -> Result<(String, MsgReqKind)> {
+        let key = self.get_agent_key(&req.agent_ip, &req.mac_addr);
+        let msg = MsgReqKind::SshHostKeyRequest(req.params);
+        Ok((key, msg))
+    }
+
+    pub async fn reconcile(&self, req: types::ReconcileRequest) -> Result<(String, MsgReqKind)> {
+        let pair = self.get_link_pair(&req.agent_ip, &req.mac_addr).await?;
+        Ok((pair.0, MsgReqKind::ReconcileRequest(req.params)))
+    }
+
     pub async fn runtime_action(
         &self,
         req: types::RuntimeActionRequest,
@@ -126,6 +150,11 @@ impl Logic {
         Ok(json!(null))
     }
 
+    pub async fn job_output(&self, req: JobOutputParams) -> Result<Value> {
+        self.bus.publish_job_output(req).await?;
+        Ok(json!(null))
+    }
+
     pub async fn agent_online(&self, req: AgentOnlineParams) -> Result<Value> {
         self.bus.agent_online(req).await?;
         Ok(json!(null))
@@ -1,8 +1,8 @@
 use serde::{Deserialize, Serialize};
 
 use crate::bridge::msg::{
-    DispatchJobParams, RuntimeActionParams, SftpDownloadParams, SftpReadDirParams,
-    SftpRemoveParams, SftpUploadParams,
+    DispatchJobParams, ReadJobLogParams, ReconcileParams, RuntimeActionParams, SftpDownloadParams,
+    SftpReadDirParams, SftpRemoveParams, SftpUploadParams, SshHostKeyParams,
 };
 use redis_macros::{FromRedisValue, ToRedisArgs};
 use serde_repr::*;
@@ -53,6 +53,29 @@ pub struct SftpDownloadRequest {
     pub params: SftpDownloadParams,
 }
 
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SshHostKeyRequest {
+    pub agent_ip: String,
+    pub mac_addr: String,
+    pub namespace: String,
+    pub params: SshHostKeyParams,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReconcileRequest {
+    pub agent_ip: String,
+    pub mac_addr: String,
+    pub params: ReconcileParams,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ReadJobLogRequest {
+    pub agent_ip: String,
+    pub mac_addr: String,
+    pub namespace: String,
+    pub params: ReadJobLogParams,
+}
+
 #[derive(Serialize, Clone, FromRedisValue, Deserialize, ToRedisArgs)]
 pub struct LinkPair {
     pub namespace: String,
@@ -97,6 +120,13 @@ pub struct SshLoginParams {
     pub namespace: String,
     pub user: String,
     pub password: String,
+    #[serde(default)]
+    pub private_key: String,
+    #[serde(default)]
+    pub passphrase: String,
+    /// pinned host key fingerprint, empty to trust the first key seen
+    #[serde(default)]
+    pub host_key: String,
     pub port: u16,
     pub ip: String,
     pub mac_addr: String,
@@ -10,8 +10,8 @@ pub mod ssh;
 pub use bridge::msg::DispatchJobParams;
 pub use comet::logic::Logic;
 pub use comet::types::{
-    DispatchJobRequest, LinkPair, SftpDownloadRequest, SftpReadDirRequest, SftpRemoveRequest,
-    SftpUploadRequest,
+    DispatchJobRequest, LinkPair, ReadJobLogRequest, ReconcileRequest, SftpDownloadRequest,
+    SftpReadDirRequest, SftpRemoveRequest, SftpUploadRequest, SshHostKeyRequest,
 };
 use reqwest::Client;
 pub use scheduler::types::BaseJob;
@@ -1,7 +1,9 @@
 mod cmd;
 pub(self) mod executor;
 pub(self) mod file;
+mod probe;
 pub mod scheduler;
+mod state;
 pub mod types;
 
 pub use scheduler::*;
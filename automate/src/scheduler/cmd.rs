@@ -1,44 +1,124 @@
-use std::{ffi::OsStr, process::Output, time::Duration};
+use std::{collections::VecDeque, ffi::OsStr, fmt, process::Output, time::Duration};
 
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use bytes::BufMut;
 
 use tokio::{
-    io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt},
+    io::{AsyncRead, AsyncReadExt, AsyncWriteExt},
     process::Command,
-    sync::mpsc::{Receiver, UnboundedSender},
+    sync::mpsc::{Receiver, Sender},
 };
 use tracing::{error, info};
 
+use super::types::TerminationReason;
+
+/// error of a process that could not be started, lets the caller tell it apart from agent errors
+#[derive(Debug)]
+pub struct SpawnFailed(pub String);
+
+impl fmt::Display for SpawnFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "failed to start process - {}", self.0)
+    }
+}
+
+/// keeps the first and the last `limit / 2` bytes of a stream, the middle part is dropped
+/// and replaced by a marker telling how many bytes were omitted. limit 0 means no limit.
+struct OutputBuffer {
+    limit: usize,
+    head: Vec<u8>,
+    tail: VecDeque<u8>,
+    omitted: usize,
+}
+
+impl OutputBuffer {
+    fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            head: Vec::new(),
+            tail: VecDeque::new(),
+            omitted: 0,
+        }
+    }
+
+    fn put(&mut self, data: &[u8]) {
+        if self.limit == 0 {
+            self.head.put(data);
+            return;
+        }
+
+        let head_limit = self.limit / 2;
+        let n = head_limit.saturating_sub(self.head.len()).min(data.len());
+        self.head.put(&data[..n]);
+
+        self.tail.extend(&data[n..]);
+        let tail_limit = self.limit - head_limit;
+        if self.tail.len() > tail_limit {
+            let overflow = self.tail.len() - tail_limit;
+            self.tail.drain(..overflow);
+            self.omitted += overflow;
+        }
+    }
+
+    fn into_bytes(self) -> Vec<u8> {
+        let mut vec = self.head;
+        if self.omitted > 0 {
+            vec.put(format!("\n...... {} bytes omitted ......\n", self.omitted).as_bytes());
+        }
+        vec.extend(self.tail);
+        vec
+    }
+}
+
 async fn read_to_end<A: AsyncRead + Unpin>(
-    io: &mut Option<A>,
-    tx: UnboundedSender<String>,
+    io: Option<A>,
+    tx: Sender<String>,
+    limit: usize,
 ) -> std::io::Result<Vec<u8>> {
-    let mut vec = Vec::new();
-    if let Some(io) = io.as_mut() {
-        let mut reader = tokio::io::BufReader::new(io);
+    let mut output = OutputBuffer::new(limit);
+    if let Some(mut io) = io {
+        // read in chunks rather than lines, a single huge line must not be buffered whole
+        let mut buf = vec![0u8; 8192];
+        let mut pending = Vec::new();
         loop {
-            let mut line = String::new();
-            let n = reader.read_line(&mut line).await?;
+            let n = io.read(&mut buf).await?;
 
             if n == 0 {
                 break;
             }
 
-            if let Err(e) = tx.send(line.clone()) {
-                error!("failed send job lot - {e}");
+            output.put(&buf[..n]);
+            pending.put(&buf[..n]);
+
+            // an incomplete utf-8 sequence at the end waits for the next read
+            let valid = match std::str::from_utf8(&pending) {
+                Err(e) if e.error_len().is_none() => e.valid_up_to(),
+                _ => pending.len(),
+            };
+            if valid == 0 {
+                continue;
             }
 
-            vec.put(line.as_bytes());
+            let chunk: Vec<u8> = pending.drain(..valid).collect();
+            if let Err(e) = tx.send(String::from_utf8_lossy(&chunk).to_string()).await {
+                error!("failed send job log - {e}");
+            }
+        }
+
+        if !pending.is_empty() {
+            if let Err(e) = tx.send(String::from_utf8_lossy(&pending).to_string()).await {
+                error!("failed send job log - {e}");
+            }
         }
     }
 
-    std::result::Result::Ok(vec)
+    std::result::Result::Ok(output.into_bytes())
 }
 
 pub struct Cmd<'a> {
     inner: Command,
     timeout: Option<Duration>,
+    max_output_bytes: usize,
     read_code_from_stdin: (bool, &'a str),
 }
 
@@ -48,6 +128,7 @@ impl<'a> Cmd<'a> {
             inner: Command::new(program),
             read_code_from_stdin: (false, ""),
             timeout: None,
+            max_output_bytes: 0,
         }
     }
 
@@ -65,9 +146,17 @@ impl<'a> Cmd<'a> {
         self
     }
 
+    /// cap of bytes kept for each of stdout and stderr
+    pub fn max_output_bytes(&mut self, max_output_bytes: usize) -> &mut Self {
+        self.max_output_bytes = max_output_bytes;
+        self
+    }
+
     #[cfg(unix)]
     pub fn work_user(&mut self, user: &str) -> Result<&mut Self> {
-        let u = users::get_user_by_name(user).ok_or(anyhow!("invalid system user {user}"))?;
+        let u = users::get_user_by_name(user).ok_or(anyhow::Error::msg(SpawnFailed(format!(
+            "invalid system user {user}"
+        ))))?;
         self.inner.uid(u.uid());
         Ok(self)
     }
@@ -96,14 +185,15 @@ impl<'a> Cmd<'a> {
 
     pub async fn wait_with_output(
         &mut self,
-        tx: UnboundedSender<String>,
-        mut kill_signal_rx: Receiver<()>,
-    ) -> Result<Output> {
+        tx: Sender<String>,
+        mut kill_signal_rx: Receiver<TerminationReason>,
+    ) -> Result<(Output, TerminationReason)> {
         // kill process group See https://github.com/rust-lang/rust/issues/115241
         #[cfg(unix)]
-        let mut child = self.inner.process_group(0).spawn()?;
+        let child = self.inner.process_group(0).spawn();
         #[cfg(windows)]
-        let mut child = self.inner.spawn()?;
+        let child = self.inner.spawn();
+        let mut child = child.map_err(|e| anyhow::Error::msg(SpawnFailed(e.to_string())))?;
 
         if self.read_code_from_stdin.0 {
             if let Some(mut stdin_pipe) = child.stdin.take() {
@@ -113,11 +203,18 @@ impl<'a> Cmd<'a> {
             }
         }
 
-        let mut stdout_pipe = child.stdout.take();
-        let mut stderr_pipe = child.stderr.take();
-
-        let stdout_fut = read_to_end(&mut stdout_pipe, tx.clone());
-        let stderr_fut = read_to_end(&mut stderr_pipe, tx.clone());
+        // read the pipes while the child is running, so that each line is sent out as soon as it's printed
+        let stdout_handle = tokio::spawn(read_to_end(
+            child.stdout.take(),
+            tx.clone(),
+            self.max_output_bytes,
+        ));
+        let stderr_handle = tokio::spawn(read_to_end(
+            child.stderr.take(),
+            tx.clone(),
+            self.max_output_bytes,
+        ));
+        drop(tx);
 
         let sleep = self
             .timeout
@@ -127,35 +224,53 @@ impl<'a> Cmd<'a> {
         tokio::pin!(sleep);
 
         let pid = child.id().unwrap();
-        tokio::select! {
+        let killed_by = tokio::select! {
             _ = &mut sleep =>  {
                 info!("timeout kill");
                 child.kill().await?;
                 Self::killpg(pid)?;
-
+                Some(TerminationReason::Timeout)
             },
-            _ = kill_signal_rx.recv() => {
+            reason = kill_signal_rx.recv() => {
                 info!("manual kill");
                 child.kill().await?;
                 Self::killpg(pid)?;
+                Some(reason.unwrap_or(TerminationReason::KilledByUser))
             },
             ret = child.wait() =>{
                 ret?;
+                None
             },
 
         };
 
-        let (status, stdout, stderr) =
-            futures_util::future::try_join3(child.wait(), stdout_fut, stderr_fut).await?;
-
-        // Drop happens after `try_join` due to <https://github.com/tokio-rs/tokio/issues/4309>
-        drop(stdout_pipe);
-        drop(stderr_pipe);
+        let status = child.wait().await?;
+        let stdout = stdout_handle.await??;
+        let stderr = stderr_handle.await??;
 
-        Ok(Output {
-            status,
-            stderr,
-            stdout,
-        })
+        Ok((
+            Output {
+                status,
+                stderr,
+                stdout,
+            },
+            killed_by.unwrap_or_else(|| TerminationReason::from_status(&status)),
+        ))
     }
 }
+
+#[test]
+fn test_output_buffer() {
+    let mut output = OutputBuffer::new(8);
+    output.put(b"abc");
+    output.put(b"defghijkl");
+    output.put(b"mn");
+    assert_eq!(
+        String::from_utf8(output.into_bytes()).unwrap(),
+        "abcd\n...... 6 bytes omitted ......\nklmn"
+    );
+
+    let mut output = OutputBuffer::new(0);
+    output.put(b"abcdefghijkl");
+    assert_eq!(output.into_bytes(), b"abcdefghijkl");
+}
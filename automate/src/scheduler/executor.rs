@@ -9,20 +9,21 @@ use std::{
     collections::HashMap,
     process::{Output, Stdio},
 };
-use tokio::sync::mpsc::Receiver;
+use tokio::sync::mpsc::{Receiver, Sender};
 
 use tokio::sync::{mpsc, Mutex};
 use tracing::error;
 
 use crate::scheduler::cmd::Cmd;
 
-use super::types::{BaseJob, BundleOutput};
+use super::types::{BaseJob, BundleOutput, SecretMasker, TerminationReason};
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct ExecutorBuilder {
     pub job: BaseJob,
     output_dir: String,
     disable_log: bool,
+    max_output_bytes: usize,
     pub env: HashMap<String, String>,
 }
 
@@ -50,6 +51,12 @@ impl ExecutorBuilder {
         self
     }
 
+    /// cap of bytes kept in the output for each of stdout and stderr, 0 means no limit
+    pub fn max_output_bytes(mut self, max_output_bytes: usize) -> Self {
+        self.max_output_bytes = max_output_bytes;
+        self
+    }
+
     pub fn env(mut self, k: String, v: String) -> Self {
         self.env.insert(k, v);
         self
@@ -61,18 +68,26 @@ impl ExecutorBuilder {
             output_dir: self.output_dir,
             env: self.env,
             disable_log: self.disable_log,
+            max_output_bytes: self.max_output_bytes,
         }
     }
 }
 
+/// output chunks queued between the job and its readers, a full queue holds back the reading
+/// of the job's pipes instead of buffering its output in memory
+pub const OUTPUT_QUEUE_SIZE: usize = 64;
+
 pub struct Ctx {
-    pub kill_signal_rx: Receiver<()>,
+    pub kill_signal_rx: Receiver<TerminationReason>,
+    /// receives every output line while the job is running
+    pub output_tx: Option<Sender<String>>,
 }
 
 pub struct Executor {
     job: BaseJob,
     output_dir: String,
     disable_log: bool,
+    max_output_bytes: usize,
     env: HashMap<String, String>,
 }
 
@@ -87,7 +102,7 @@ impl Executor {
 
     pub async fn run(&self, mut ctx: Ctx) -> Result<BundleOutput> {
         if self.job.bundle_script.is_none() {
-            let output = self
+            let (output, reason) = self
                 .exec(
                     ctx,
                     self.job.cmd_name.clone(),
@@ -96,11 +111,13 @@ impl Executor {
                 )
                 .await?;
 
-            return Ok(BundleOutput::Output(output));
+            return Ok(BundleOutput::Output(output, reason));
         }
 
-        let kill_signal_tx: Arc<Mutex<Vec<mpsc::Sender<()>>>> = Arc::new(Mutex::new(vec![]));
+        let kill_signal_tx: Arc<Mutex<Vec<mpsc::Sender<TerminationReason>>>> =
+            Arc::new(Mutex::new(vec![]));
         let kill_signal_tx_clone = kill_signal_tx.clone();
+        let output_tx = ctx.output_tx.take();
         let mut outputs = HashMap::new();
 
         let handler = tokio::spawn(async move {
@@ -119,11 +136,14 @@ impl Executor {
         });
 
         for v in self.job.bundle_script.clone().unwrap().clone().into_iter() {
-            let (tx, kill_signal_rx) = mpsc::channel::<()>(1);
+            let (tx, kill_signal_rx) = mpsc::channel::<TerminationReason>(1);
             kill_signal_tx.lock().await.push(tx);
             let output = self
                 .exec(
-                    Ctx { kill_signal_rx },
+                    Ctx {
+                        kill_signal_rx,
+                        output_tx: output_tx.clone(),
+                    },
                     v.cmd_name.clone(),
                     v.args.clone(),
                     v.code.clone(),
@@ -142,7 +162,7 @@ impl Executor {
         cmd_name: String,
         args: Vec<String>,
         code: String,
-    ) -> Result<Output> {
+    ) -> Result<(Output, TerminationReason)> {
         let mut cmd = Cmd::new(cmd_name);
         let mut args = args;
         if self.job.read_code_from_stdin {
@@ -162,6 +182,11 @@ impl Executor {
         if self.job.timeout > 0 {
             cmd.timeout(self.job.timeout);
         }
+        cmd.max_output_bytes(self.max_output_bytes);
+
+        for v in self.job.env.iter() {
+            cmd.get_ref().env(&v.name, &v.value);
+        }
 
         for (key, val) in self.env.iter() {
             cmd.get_ref().env(key, val);
@@ -169,7 +194,7 @@ impl Executor {
 
         cmd.get_ref().args(&args);
 
-        let (tx, mut rx) = mpsc::unbounded_channel::<String>();
+        let (tx, mut rx) = mpsc::channel::<String>(OUTPUT_QUEUE_SIZE);
 
         let filepath = self.get_log_file_path();
         let mut logfile = if self.disable_log {
@@ -185,8 +210,25 @@ impl Executor {
             ))
         };
 
+        let output_tx = ctx.output_tx;
+        let job = self.job.clone();
         tokio::spawn(async move {
-            while let Some(line) = rx.recv().await {
+            let mut masker = SecretMasker::new(job);
+            let mut closed = false;
+            while !closed {
+                let line = match rx.recv().await {
+                    Some(line) => masker.push(&line),
+                    None => {
+                        closed = true;
+                        masker.finish()
+                    }
+                };
+                if line.is_empty() {
+                    continue;
+                }
+                if let Some(ref output_tx) = output_tx {
+                    let _ = output_tx.send(line.clone()).await;
+                }
                 if let Some(f) = logfile.as_mut() {
                     if let Err(e) = write!(f, "{}", line) {
                         error!("cannot write to log file - {e}");
@@ -198,9 +240,11 @@ impl Executor {
         cmd.get_ref().stdout(Stdio::piped());
         cmd.get_ref().stderr(Stdio::piped());
 
-        let output = cmd.wait_with_output(tx, ctx.kill_signal_rx).await?;
+        let (mut output, reason) = cmd.wait_with_output(tx, ctx.kill_signal_rx).await?;
+        output.stdout = self.job.mask_secrets(&output.stdout);
+        output.stderr = self.job.mask_secrets(&output.stderr);
 
-        Ok(output)
+        Ok((output, reason))
     }
 }
 
@@ -226,20 +270,31 @@ async fn test_command_exec() {
             work_user: None,
             max_retry: 1,
             max_parallel: 1,
+            ..Default::default()
         })
         .build();
 
-    let (kill_signal_tx, kill_signal_rx) = mpsc::channel::<()>(1);
+    let (kill_signal_tx, kill_signal_rx) = mpsc::channel::<TerminationReason>(1);
     tokio::spawn(async move {
         sleep(Duration::from_secs(1)).await;
         info!("start manual kill");
-        kill_signal_tx.send(()).await.unwrap();
+        kill_signal_tx
+            .send(TerminationReason::KilledByUser)
+            .await
+            .unwrap();
         info!("end manual kill");
     });
-    let output = c.run(Ctx { kill_signal_rx }).await.unwrap();
+    let output = c
+        .run(Ctx {
+            kill_signal_rx,
+            output_tx: None,
+        })
+        .await
+        .unwrap();
 
     println!("stdout: {:?}", output.get_stdout());
     println!("stderr: {:?}", output.get_stderr());
     println!("exit_status: {:?}", output.get_exit_status());
-    println!("exit_code: {:?}", output.get_exit_code())
+    println!("exit_code: {:?}", output.get_exit_code());
+    println!("termination_reason: {}", output.get_termination_reason())
 }
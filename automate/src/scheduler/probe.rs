@@ -0,0 +1,81 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use tokio::{net::TcpStream, process::Command, time::timeout};
+
+use super::types::{LivenessProbe, ProbeType};
+use crate::get_http_client;
+
+impl LivenessProbe {
+    /// run the probe once, an error tells why the daemon is unhealthy
+    pub async fn check(&self) -> Result<()> {
+        let dur = Duration::from_secs(self.timeout.max(1));
+        match timeout(dur, self.run()).await {
+            Ok(ret) => ret,
+            Err(_) => anyhow::bail!(
+                "{} probe timed out after {}s",
+                self.probe_type,
+                dur.as_secs()
+            ),
+        }
+    }
+
+    async fn run(&self) -> Result<()> {
+        match self.probe_type {
+            ProbeType::Http => {
+                let expected = match self.expected_status {
+                    0 => 200,
+                    v => v,
+                };
+                let status = get_http_client().get(&self.target).send().await?.status();
+                if status.as_u16() != expected {
+                    anyhow::bail!("http probe got status {status}, expected {expected}");
+                }
+            }
+            ProbeType::Tcp => {
+                TcpStream::connect(&self.target).await?;
+            }
+            ProbeType::Exec => {
+                #[cfg(unix)]
+                let mut cmd = Command::new("sh");
+                #[cfg(unix)]
+                cmd.arg("-c");
+                #[cfg(windows)]
+                let mut cmd = Command::new("cmd");
+                #[cfg(windows)]
+                cmd.arg("/C");
+
+                let status = cmd.arg(&self.target).kill_on_drop(true).status().await?;
+                if !status.success() {
+                    anyhow::bail!("exec probe exited with {status}");
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_liveness_probe() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let mut probe = LivenessProbe {
+        probe_type: ProbeType::Tcp,
+        target: listener.local_addr().unwrap().to_string(),
+        timeout: 1,
+        ..Default::default()
+    };
+    assert!(probe.check().await.is_ok());
+    drop(listener);
+    assert!(probe.check().await.is_err());
+
+    #[cfg(unix)]
+    {
+        probe.probe_type = ProbeType::Exec;
+        probe.target = "exit 0".to_string();
+        assert!(probe.check().await.is_ok());
+        probe.target = "exit 3".to_string();
+        assert!(probe.check().await.is_err());
+        probe.target = "sleep 5".to_string();
+        assert!(probe.check().await.is_err());
+    }
+}
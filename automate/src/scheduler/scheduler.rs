@@ -1,47 +1,62 @@
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, VecDeque},
+    io::SeekFrom,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use anyhow::{anyhow, Result};
 use chrono::{DateTime, Local, Utc};
 use futures::{SinkExt, StreamExt};
+use nanoid::nanoid;
 
 use crate::{
     bridge::msg::{
-        BundleOutputParams, RuntimeActionParams, SftpDownloadParams, SftpReadDirParams,
-        SftpRemoveParams, SftpUploadParams, UpdateJobParams,
+        BundleOutputParams, JobOutputParams, ReadJobLogParams, ReconcileParams,
+        RuntimeActionParams, SftpDownloadParams, SftpReadDirParams, SftpRemoveParams,
+        SftpUploadParams, SshHostKeyParams, UpdateJobParams,
     },
     comet::types::SshLoginParams,
     get_comet_addr, get_local_ip, get_mac_address,
     scheduler::types::JobAction,
     set_comet_addr,
-    ssh::{self, ConnectParams, Session},
+    ssh::{self, ConnectParams, Session, SshAuth},
 };
 use futures_util::stream::{SplitSink, SplitStream};
 
 use serde_json::{json, Value};
 use tokio::{
+    fs::{self, File},
+    io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt},
     net::TcpStream,
     select,
     sync::{
-        mpsc::{channel, unbounded_channel, Receiver, Sender, UnboundedSender},
-        Mutex,
+        mpsc::{
+            channel, error::TrySendError, unbounded_channel, Receiver, Sender, UnboundedSender,
+        },
+        Mutex, OwnedSemaphorePermit, Semaphore,
     },
     task,
     time::{sleep, timeout},
 };
-use tokio_cron_scheduler::{Job, JobScheduler};
+use tokio_cron_scheduler::{Job, JobScheduler, JobToRunAsync};
 use tokio_tungstenite::{
     connect_async,
     tungstenite::{ClientRequestBuilder, Message},
     MaybeTlsStream, WebSocketStream,
 };
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
 use super::{
-    executor::Ctx,
+    cmd::SpawnFailed,
+    executor::{Ctx, ExecutorBuilder, OUTPUT_QUEUE_SIZE},
     file::try_download_file,
+    state::{AgentState, STATE_FILE},
     types::{
-        self, AssignUserOption, BundleOutput, RuntimeAction, ScheduleType, SshConnectionOption,
+        self, AssignUserOption, BundleOutput, LivenessProbe, ParallelPolicy, ProbeResult,
+        RuntimeAction, ScheduleType, SshConnectionOption, TerminationReason,
     },
 };
 
@@ -55,19 +70,42 @@ use crate::{
     scheduler::executor::Executor,
 };
 
+/// output is sent to comet once this many bytes are buffered, or on every flush interval
+const JOB_OUTPUT_CHUNK_SIZE: usize = 8 * 1024;
+const JOB_OUTPUT_FLUSH_INTERVAL: Duration = Duration::from_millis(500);
+/// chunks waiting for the bridge, the output produced while it is full is dropped
+const JOB_OUTPUT_QUEUE_CHUNKS: usize = 16;
+/// max bytes of job log returned by one read
+const JOB_LOG_READ_LIMIT: u64 = 1 << 20;
+/// a job log stops growing at this size, the rest of the output only goes to comet
+const MAX_JOB_LOG_BYTES: u64 = 64 << 20;
+/// logs kept for each job in output_dir, the oldest are removed first
+const MAX_JOB_LOGS: usize = 100;
+/// max job status updates kept for replay while comet is unreachable, the oldest are dropped first
+const MAX_PENDING_UPDATES: usize = 1000;
+
 #[derive(Clone)]
 pub struct React {
     sched: JobScheduler,
     bridge: Bridge,
     output_dir: String,
+    max_output_bytes: usize,
     namespace: String,
     local_ip: String,
     client_key: String,
     schedule_uuid_mapping: Arc<Mutex<HashMap<String, Uuid>>>,
     supervisor_jobs: Arc<Mutex<HashMap<String, UnboundedSender<()>>>>,
-    kill_signal_mapping: Arc<Mutex<HashMap<String, Vec<Sender<()>>>>>,
+    kill_signal_mapping: Arc<Mutex<HashMap<String, Vec<Sender<TerminationReason>>>>>,
+    /// kill signal of the run started by the supervisor of a daemon
+    daemon_runs: Arc<Mutex<HashMap<String, Sender<TerminationReason>>>>,
+    parallel_slots: Arc<Mutex<HashMap<String, ParallelSlot>>>,
+    pending_updates: Arc<Mutex<VecDeque<UpdateJobParams>>>,
+    state: Arc<Mutex<AgentState>>,
 }
 
+/// max_parallel of a job and the semaphore created for it
+type ParallelSlot = (u8, Arc<Semaphore>);
+
 impl React {
     async fn new(
         bridge: Bridge,
@@ -75,12 +113,25 @@ impl React {
         local_ip: String,
         client_key: String,
         output_dir: String,
+        max_output_bytes: usize,
     ) -> Self {
+        let state = AgentState::load(&PathBuf::from(&output_dir).join(STATE_FILE))
+            .await
+            .unwrap_or_else(|e| {
+                error!("failed load agent state, start with an empty one - {e}");
+                AgentState::default()
+            });
+
         Self {
             sched: JobScheduler::new().await.unwrap(),
+            state: Arc::new(Mutex::new(state)),
             output_dir,
+            max_output_bytes,
             schedule_uuid_mapping: Arc::new(Mutex::new(HashMap::new())),
             kill_signal_mapping: Arc::new(Mutex::new(HashMap::new())),
+            daemon_runs: Arc::new(Mutex::new(HashMap::new())),
+            parallel_slots: Arc::new(Mutex::new(HashMap::new())),
+            pending_updates: Arc::new(Mutex::new(VecDeque::new())),
             supervisor_jobs: Arc::new(Mutex::new(HashMap::new())),
             bridge,
             client_key,
@@ -89,9 +140,47 @@ impl React {
         }
     }
 
-    async fn send_update_job_msg(&self, data: UpdateJobParams) -> Result<Value> {
-        self.send_bridge_msg(MsgReqKind::UpdateJobRequest(data))
-            .await
+    /// report the job status to comet, a run never fails on it, an update which cannot be sent
+    /// is kept and replayed in order once comet is reachable again
+    async fn send_update_job_msg(&self, data: UpdateJobParams) {
+        let mut pending = self.pending_updates.lock().await;
+        self.flush_pending_updates(&mut pending).await;
+        if pending.is_empty() {
+            drop(pending);
+            let eid = data.base_job.eid.clone();
+            match self
+                .send_bridge_msg(MsgReqKind::UpdateJobRequest(data.clone()))
+                .await
+            {
+                Ok(_) => return,
+                Err(e) => error!("failed report status of job {eid}, keep it for replay - {e}"),
+            }
+            pending = self.pending_updates.lock().await;
+        }
+
+        if pending.len() >= MAX_PENDING_UPDATES {
+            pending.pop_front();
+        }
+        pending.push_back(data);
+    }
+
+    /// send the job status updates kept while comet was unreachable
+    async fn replay_pending_updates(&self) {
+        let mut pending = self.pending_updates.lock().await;
+        self.flush_pending_updates(&mut pending).await;
+    }
+
+    async fn flush_pending_updates(&self, pending: &mut VecDeque<UpdateJobParams>) {
+        while let Some(data) = pending.front() {
+            if let Err(e) = self
+                .send_bridge_msg(MsgReqKind::UpdateJobRequest(data.clone()))
+                .await
+            {
+                debug!("failed replay job status, {} left - {e}", pending.len());
+                return;
+            }
+            pending.pop_front();
+        }
     }
 
     async fn send_bridge_msg(&self, data: MsgReqKind) -> Result<Value> {
@@ -123,7 +212,7 @@ impl React {
         Ok(())
     }
 
-    async fn add_kill_signal_tx(&mut self, eid: String, kill_signal_tx: Sender<()>) {
+    async fn add_kill_signal_tx(&mut self, eid: String, kill_signal_tx: Sender<TerminationReason>) {
         let mut locked_map = self.kill_signal_mapping.lock().await;
         if let Some(val) = locked_map.get_mut(&eid) {
             val.append(&mut vec![kill_signal_tx]);
@@ -140,15 +229,142 @@ impl React {
         };
 
         for tx in senders {
-            if let Err(e) = tx.send(()).await {
+            if let Err(e) = tx.send(TerminationReason::KilledByUser).await {
                 error!("failed send kill signal, eid: {eid} {}", e);
             }
         }
     }
 
-    async fn remove_job(&mut self, eid: String) {
+    /// kill the run started by the supervisor of the daemon, the other runs of the job keep running
+    async fn kill_daemon_run(&mut self, eid: &str, reason: TerminationReason) {
+        let Some(tx) = self.daemon_runs.lock().await.get(eid).cloned() else {
+            return;
+        };
+        if let Err(e) = tx.send(reason).await {
+            error!("failed send kill signal, eid: {eid} {}", e);
+        }
+    }
+
+    /// kill the run of the job which started first, the others keep running
+    async fn replace_oldest_run(&mut self, eid: &str) {
+        let tx = {
+            let mut locked_map = self.kill_signal_mapping.lock().await;
+            let Some(senders) = locked_map.get_mut(eid).filter(|v| !v.is_empty()) else {
+                return;
+            };
+            senders.remove(0)
+        };
+        if let Err(e) = tx.send(TerminationReason::Replaced).await {
+            error!("failed send kill signal, eid: {eid} {}", e);
+        }
+    }
+
+    /// `{eid}/{schedule_id}_{run_id}.log`, the runs logged before run ids use the attempt
+    fn job_log_path(
+        &self,
+        eid: &str,
+        schedule_id: &str,
+        run_id: &str,
+        attempt: u32,
+    ) -> Result<PathBuf> {
+        let is_valid = |v: &str| {
+            !v.is_empty()
+                && v.chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        };
+        if !is_valid(eid) || !is_valid(schedule_id) || !(run_id.is_empty() || is_valid(run_id)) {
+            anyhow::bail!("invalid job log {eid} {schedule_id} {run_id}");
+        }
+        let filename = match run_id {
+            "" => format!("{schedule_id}_{attempt}.log"),
+            v => format!("{schedule_id}_{v}.log"),
+        };
+        Ok(PathBuf::from(&self.output_dir).join(eid).join(filename))
+    }
+
+    async fn create_job_log(&self, params: &JobOutputParams) -> Result<File> {
+        let path = self.job_log_path(
+            &params.eid,
+            &params.schedule_id,
+            &params.run_id,
+            params.attempt,
+        )?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).await?;
+            if let Err(e) = Self::cleanup_job_logs(dir).await {
+                error!("failed clean up job logs in {} - {e}", dir.display());
+            }
+        }
+        Ok(File::create(path).await?)
+    }
+
+    /// remove the oldest logs of a job so that at most MAX_JOB_LOGS - 1 are left before a new one
+    async fn cleanup_job_logs(dir: &Path) -> Result<()> {
+        let mut logs = Vec::new();
+        let mut entries = fs::read_dir(dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let meta = entry.metadata().await?;
+            if meta.is_file() {
+                logs.push((meta.modified()?, entry.path()));
+            }
+        }
+        if logs.len() < MAX_JOB_LOGS {
+            return Ok(());
+        }
+        logs.sort();
+        for (_, path) in logs.iter().take(logs.len() + 1 - MAX_JOB_LOGS) {
+            fs::remove_file(path).await?;
+        }
+        Ok(())
+    }
+
+    async fn remove_kill_signal_tx(
+        &mut self,
+        eid: &str,
+        kill_signal_tx: &Sender<TerminationReason>,
+    ) {
         let mut locked_map = self.kill_signal_mapping.lock().await;
-        locked_map.remove(&eid);
+        if let Some(senders) = locked_map.get_mut(eid) {
+            senders.retain(|v| !v.same_channel(kill_signal_tx));
+            if senders.is_empty() {
+                locked_map.remove(eid);
+            }
+        }
+    }
+
+    /// semaphore limiting the concurrent runs of a job, max_parallel 0 means unlimited.
+    /// a changed max_parallel takes effect once the running ones finished, so that the
+    /// runs holding a permit of the old semaphore are still counted
+    async fn get_parallel_semaphore(&self, eid: &str, max_parallel: u8) -> Arc<Semaphore> {
+        let permits = |n: u8| match n {
+            0 => Semaphore::MAX_PERMITS,
+            n => n as usize,
+        };
+        let mut locked_map = self.parallel_slots.lock().await;
+        match locked_map.get(eid) {
+            Some((n, semaphore))
+                if *n == max_parallel || semaphore.available_permits() < permits(*n) =>
+            {
+                semaphore.clone()
+            }
+            _ => {
+                let semaphore = Arc::new(Semaphore::new(permits(max_parallel)));
+                locked_map.insert(eid.to_string(), (max_parallel, semaphore.clone()));
+                semaphore
+            }
+        }
+    }
+
+    /// change the agent state and save it to output_dir
+    async fn update_state(&self, f: impl FnOnce(&mut AgentState)) {
+        let mut state = self.state.lock().await;
+        f(&mut state);
+        if let Err(e) = state
+            .save(&PathBuf::from(&self.output_dir).join(STATE_FILE))
+            .await
+        {
+            error!("failed save agent state - {e}");
+        }
     }
 
     async fn start(&mut self) -> Result<()> {
@@ -188,12 +404,14 @@ pub struct Scheduler<T> {
     comet_secret: String,
     mac_addr: String,
     output_dir: String,
+    max_output_bytes: usize,
     is_initialized: bool,
     client: Option<T>,
     pub namespace: String,
     bridge: Bridge,
     ssh_connection_option: Option<SshConnectionOption>,
     assign_user_option: Option<AssignUserOption>,
+    labels: Option<String>,
 }
 
 impl
@@ -209,13 +427,16 @@ impl
         comet_addr: Vec<String>,
         comet_secret: String,
         output_dir: String,
+        max_output_bytes: usize,
         ssh_connection_option: Option<SshConnectionOption>,
         assign_user_option: Option<AssignUserOption>,
+        labels: Option<String>,
     ) -> Self {
         Scheduler {
             comet_addr,
             comet_secret,
             output_dir,
+            max_output_bytes,
             client: None,
             mac_addr: get_mac_address().expect("failed get mac address"),
             is_initialized: false,
@@ -223,6 +444,7 @@ impl
             bridge: Bridge::new(),
             ssh_connection_option,
             assign_user_option,
+            labels,
         }
     }
 
@@ -302,8 +524,13 @@ impl
         tokio::spawn(async move {
             let sess = match Session::connect(ConnectParams {
                 user: login_params.user,
-                password: login_params.password,
+                auth: SshAuth {
+                    password: login_params.password,
+                    private_key: login_params.private_key,
+                    passphrase: login_params.passphrase,
+                },
                 addrs: (local_ip, login_params.port),
+                host_key: login_params.host_key,
             })
             .await
             {
@@ -363,6 +590,10 @@ impl
             client.set_ssh_connection(opt.to_owned());
         }
 
+        if let Some(ref labels) = self.labels {
+            client.set_labels(labels.to_owned());
+        }
+
         let ws_addr = format!("{}/evt/{}", addr, self.namespace);
 
         client.connect(&ws_addr, &self.comet_secret).await?;
@@ -381,155 +612,442 @@ impl
         Ok(())
     }
 
+    /// JIASCHEDULER_* variables telling the script which run it is, the attempt is added per run
+    fn runtime_env(
+        react: &React,
+        job_params: &DispatchJobParams,
+        schedule_type: Option<&ScheduleType>,
+        prev_time: Option<DateTime<Utc>>,
+        next_time: Option<DateTime<Utc>>,
+    ) -> Vec<(String, String)> {
+        let format_time = |v: Option<DateTime<Utc>>| v.map_or(String::new(), |v| v.to_rfc3339());
+        [
+            ("JIASCHEDULER_EID", job_params.base_job.eid.clone()),
+            ("JIASCHEDULER_SCHEDULE_ID", job_params.schedule_id.clone()),
+            (
+                "JIASCHEDULER_INSTANCE_ID",
+                job_params.instance_id.clone().unwrap_or_default(),
+            ),
+            ("JIASCHEDULER_NAMESPACE", react.namespace.clone()),
+            (
+                "JIASCHEDULER_SCHEDULE_TYPE",
+                schedule_type.map_or(String::new(), |v| v.to_string()),
+            ),
+            ("JIASCHEDULER_CREATED_USER", job_params.created_user.clone()),
+            ("JIASCHEDULER_PREV_TIME", format_time(prev_time)),
+            ("JIASCHEDULER_NEXT_TIME", format_time(next_time)),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v))
+        .collect()
+    }
+
     async fn exec_job(
-        e: Executor,
+        executor_builder: ExecutorBuilder,
         react: React,
         schedule_type: Option<ScheduleType>,
-        kill_signal_rx: Receiver<()>,
+        mut kill_signal_rx: Receiver<TerminationReason>,
         prev_time: Option<DateTime<Utc>>,
         next_time: Option<DateTime<Utc>>,
         job_params: DispatchJobParams,
     ) -> Result<BundleOutput> {
-        let start_time = Utc::now();
+        let runtime_env = Self::runtime_env(
+            &react,
+            &job_params,
+            schedule_type.as_ref(),
+            prev_time,
+            next_time,
+        );
         let schedule_id = job_params.schedule_id;
         let base_job = job_params.base_job;
         let instance_id = job_params.instance_id.to_owned().unwrap();
+        let max_attempt = base_job.max_retry as u32 + 1;
+        let mut attempt = 1;
 
-        let _ = react
-            .send_update_job_msg(UpdateJobParams {
+        let new_update_params =
+            |run_status, start_time: DateTime<Utc>, attempt, run_id: &str| UpdateJobParams {
                 base_job: base_job.to_pure_job(),
-                run_status: Some(types::RunStatus::Running),
+                run_status: Some(run_status),
                 schedule_id: schedule_id.clone(),
-                exit_status: None,
-                exit_code: None,
-                stdout: None,
-                stderr: None,
                 next_time,
                 prev_time,
                 bind_namespace: react.namespace.clone(),
                 bind_ip: react.local_ip.clone(),
                 schedule_type: schedule_type.clone(),
                 created_user: job_params.created_user.clone(),
-                start_time: Some(start_time.clone()),
+                start_time: Some(start_time),
                 instance_id: instance_id.clone(),
+                attempt: Some(attempt),
+                run_id: Some(run_id.to_string()).filter(|v| !v.is_empty()),
+                start_offset_ms: prev_time
+                    .filter(|_| attempt == 1)
+                    .map(|v| (start_time - v).num_milliseconds().max(0) as u64),
                 ..Default::default()
-            })
-            .await?;
+            };
+
+        loop {
+            let start_time = Utc::now();
+            // every attempt has its own log, runs of timers and concurrent runs must not share one
+            let run_id = format!("{}-{attempt}-{}", start_time.timestamp_millis(), nanoid!(8));
+
+            react
+                .send_update_job_msg(new_update_params(
+                    types::RunStatus::Running,
+                    start_time,
+                    attempt,
+                    &run_id,
+                ))
+                .await;
+
+            let (output_tx, output_rx) = channel::<String>(OUTPUT_QUEUE_SIZE);
+            let forwarder = task::spawn(Self::forward_job_output(
+                react.clone(),
+                JobOutputParams {
+                    schedule_id: schedule_id.clone(),
+                    instance_id: instance_id.clone(),
+                    eid: base_job.eid.clone(),
+                    attempt,
+                    run_id: run_id.clone(),
+                    ..Default::default()
+                },
+                output_rx,
+            ));
+
+            // each attempt gets its own kill signal, the outer one is forwarded so that
+            // a manual kill can be told apart from a failure and stops the retrying
+            let (attempt_kill_tx, attempt_kill_rx) = channel::<TerminationReason>(1);
+            let e = runtime_env
+                .iter()
+                .fold(executor_builder.clone(), |b, (k, v)| {
+                    b.env(k.clone(), v.clone())
+                })
+                .env("JIASCHEDULER_ATTEMPT".to_string(), attempt.to_string())
+                .build();
+            let run = e.run(Ctx {
+                kill_signal_rx: attempt_kill_rx,
+                output_tx: Some(output_tx),
+            });
+            tokio::pin!(run);
+
+            let mut killed = false;
+            let ret = select! {
+                ret = &mut run => ret,
+                Some(reason) = kill_signal_rx.recv() => {
+                    killed = true;
+                    let _ = attempt_kill_tx.send(reason).await;
+                    run.await
+                }
+            };
+
+            // make sure the console got the whole output before the run is reported as finished
+            let _ = forwarder.await;
+
+            let retry = !killed
+                && attempt < max_attempt
+                && !matches!(ret, Ok(ref output) if output.is_success());
+            let run_status = if retry {
+                types::RunStatus::Retrying
+            } else {
+                types::RunStatus::Stop
+            };
+
+            match ret {
+                Ok(output) => {
+                    react
+                        .send_update_job_msg(UpdateJobParams {
+                            exit_status: output.get_exit_status(),
+                            exit_code: output.get_exit_code(),
+                            stdout: output.get_stdout(),
+                            stderr: output.get_stderr(),
+                            end_time: Some(Utc::now()),
+                            bundle_output: BundleOutputParams::parse(&output),
+                            termination_reason: Some(output.get_termination_reason()),
+                            ..new_update_params(run_status, start_time, attempt, &run_id)
+                        })
+                        .await;
+                    if !retry {
+                        return Ok(output);
+                    }
+                }
+                Err(e) => {
+                    let bundle_output = if base_job.bundle_script.is_none() {
+                        None
+                    } else {
+                        Some(vec![])
+                    };
+                    let termination_reason = if e.downcast_ref::<SpawnFailed>().is_some() {
+                        TerminationReason::SpawnFailed
+                    } else {
+                        TerminationReason::AgentError
+                    };
+                    react
+                        .send_update_job_msg(UpdateJobParams {
+                            exit_status: Some(e.to_string()),
+                            exit_code: Some(-1),
+                            stderr: Some(e.to_string()),
+                            end_time: Some(Utc::now()),
+                            bundle_output,
+                            termination_reason: Some(termination_reason),
+                            ..new_update_params(run_status, start_time, attempt, &run_id)
+                        })
+                        .await;
+                    if !retry {
+                        return Err(e);
+                    }
+                }
+            }
+
+            let delay = base_job.retry_delay(attempt);
+            attempt += 1;
+            info!(
+                "job {} failed, retry attempt {attempt}/{max_attempt} after {delay:?}",
+                base_job.eid
+            );
+
+            select! {
+                _ = sleep(delay) => {},
+                Some(reason) = kill_signal_rx.recv() => {
+                    let now = Utc::now();
+                    react
+                        .send_update_job_msg(UpdateJobParams {
+                            exit_status: Some("killed while waiting for retry".to_string()),
+                            exit_code: Some(-1),
+                            termination_reason: Some(reason),
+                            end_time: Some(now),
+                            ..new_update_params(types::RunStatus::Stop, now, attempt, "")
+                        })
+                        .await;
+                    return Err(anyhow!("job {} was killed while waiting for retry", base_job.eid));
+                }
+            }
+        }
+    }
 
-        let output = match e.run(Ctx { kill_signal_rx }).await {
-            Ok(v) => v,
+    /// forward job output to comet in chunks until the job exits, the untruncated output
+    /// is also kept in output_dir so that it can be read later. The chunks wait for the bridge
+    /// in a bounded queue, when it is full they are dropped and a marker tells how many bytes
+    async fn forward_job_output(
+        react: React,
+        params: JobOutputParams,
+        mut output_rx: Receiver<String>,
+    ) {
+        let mut logfile = match react.create_job_log(&params).await {
+            Ok(v) => Some(v),
             Err(e) => {
-                let bundle_output = if base_job.bundle_script.is_none() {
-                    None
-                } else {
-                    Some(vec![])
-                };
-                let _ = react
-                    .send_update_job_msg(UpdateJobParams {
-                        base_job: base_job.to_pure_job(),
-                        run_status: Some(types::RunStatus::Stop),
-                        schedule_id: schedule_id.clone(),
-                        exit_status: Some(e.to_string()),
-                        exit_code: Some(99),
-                        prev_time,
-                        next_time,
-                        bind_namespace: react.namespace.clone(),
-                        instance_id: instance_id.clone(),
-                        bind_ip: react.local_ip.clone(),
-                        start_time: Some(start_time),
-                        schedule_type: schedule_type.clone(),
-                        stdout: Some(e.to_string()),
-                        stderr: Some(e.to_string()),
-                        end_time: Some(Utc::now()),
-                        created_user: job_params.created_user.clone(),
-                        bundle_output,
-                        ..Default::default()
-                    })
-                    .await?;
-                return Err(e);
+                error!("failed create job log {} - {e}", params.eid);
+                None
             }
         };
-
-        let _ = react
-            .send_update_job_msg(UpdateJobParams {
-                base_job: base_job.to_pure_job(),
-                run_status: Some(types::RunStatus::Stop),
-                schedule_id: schedule_id.clone(),
-                exit_status: output.get_exit_status(),
-                exit_code: output.get_exit_code(),
-                instance_id: instance_id.clone(),
-                prev_time,
-                next_time,
-                bind_namespace: react.namespace.clone(),
-                bind_ip: react.local_ip.clone(),
-                start_time: Some(start_time),
-                schedule_type: schedule_type.clone(),
-                stdout: output.get_stdout(),
-                stderr: output.get_stderr(),
-                end_time: Some(Utc::now()),
-                created_user: job_params.created_user.clone(),
-                bundle_output: BundleOutputParams::parse(&output),
-                ..Default::default()
+        let mut log_bytes = 0u64;
+        let mut buf = String::new();
+        let mut dropped = 0usize;
+        let mut interval = tokio::time::interval(JOB_OUTPUT_FLUSH_INTERVAL);
+        let mut closed = false;
+
+        let (chunk_tx, mut chunk_rx) = channel::<String>(JOB_OUTPUT_QUEUE_CHUNKS);
+        let sender = {
+            let react = react.clone();
+            let params = params.clone();
+            task::spawn(async move {
+                while let Some(data) = chunk_rx.recv().await {
+                    let msg = MsgReqKind::JobOutputRequest(JobOutputParams {
+                        data,
+                        ..params.clone()
+                    });
+                    if let Err(e) = react.send_bridge_msg(msg).await {
+                        error!("failed send job output {} - {e}", params.eid);
+                    }
+                }
             })
-            .await?;
+        };
+
+        while !closed {
+            select! {
+                line = output_rx.recv() => match line {
+                    Some(line) => {
+                        if let Some(f) = logfile.as_mut() {
+                            let full = log_bytes + line.len() as u64 > MAX_JOB_LOG_BYTES;
+                            let data = if full {
+                                format!("\n[log truncated at {MAX_JOB_LOG_BYTES} bytes]\n")
+                            } else {
+                                line.clone()
+                            };
+                            log_bytes += data.len() as u64;
+                            if let Err(e) = f.write_all(data.as_bytes()).await {
+                                error!("cannot write to job log - {e}");
+                            }
+                            if full {
+                                logfile = None;
+                            }
+                        }
+                        buf.push_str(&line);
+                        if buf.len() < JOB_OUTPUT_CHUNK_SIZE {
+                            continue;
+                        }
+                    }
+                    None => closed = true,
+                },
+                _ = interval.tick() => {},
+            }
+
+            if buf.is_empty() {
+                continue;
+            }
+
+            let size = buf.len();
+            let mut data = std::mem::take(&mut buf);
+            if dropped > 0 {
+                data = format!("\n[{dropped} bytes dropped]\n{data}");
+            }
+            if closed {
+                // the end of the output waits for the queue so that it is never dropped
+                let _ = chunk_tx.send(data).await;
+                continue;
+            }
+            match chunk_tx.try_send(data) {
+                Ok(_) => dropped = 0,
+                Err(TrySendError::Full(_)) => dropped += size,
+                Err(TrySendError::Closed(_)) => break,
+            }
+        }
 
-        Ok(output)
+        drop(chunk_tx);
+        let _ = sender.await;
     }
 
-    async fn start_timer(dispatch_params: DispatchJobParams, mut react: React) -> Result<Value> {
+    /// add the cron job of the timer and keep it in the agent state, returns the next run time
+    async fn schedule_timer(
+        dispatch_params: DispatchJobParams,
+        mut react: React,
+    ) -> Result<Option<DateTime<Utc>>> {
         let timer_expr = dispatch_params.timer_expr.clone().unwrap_or_default();
         let base_job = dispatch_params.base_job.clone();
-        let pure_job = base_job.to_pure_job();
         let euid = dispatch_params.base_job.eid.clone();
         let react_clone = react.clone();
-        let created_user = dispatch_params.created_user.clone();
-        let schedule_id = dispatch_params.schedule_id.clone();
-        let instance_id = dispatch_params.instance_id.to_owned().unwrap();
+        let saved_params = dispatch_params.clone();
 
-        let job = Job::new_cron_job_async_tz(
-            timer_expr.as_str(),
-            Local,
-            move |job_id, mut job_scheduler| {
-                let base_job = base_job.clone();
-                let (kill_signal_tx, kill_signal_rx) = channel::<()>(1);
-                let mut react_clone = react_clone.clone();
-                let dispatch_params = dispatch_params.clone();
-
-                Box::pin(async move {
-                    let next_time = job_scheduler.next_tick_for_job(job_id).await.unwrap();
-                    let prev_time = Some(Local::now().into());
-
-                    react_clone
-                        .add_kill_signal_tx(base_job.eid.clone(), kill_signal_tx)
-                        .await;
+        let timezone = types::parse_timezone(&dispatch_params.timer_options.timezone)?;
 
-                    let e = Executor::builder()
-                        .job(base_job.clone())
-                        .output_dir(react_clone.output_dir.clone())
-                        .disable_write_log(true)
-                        .build();
-                    match Self::exec_job(
-                        e,
-                        react_clone,
-                        Some(ScheduleType::Timer),
-                        kill_signal_rx,
-                        prev_time,
-                        next_time,
-                        dispatch_params,
-                    )
-                    .await
-                    {
-                        Ok(_) => {}
-                        Err(e) => error!("failed exec {} - detail: {e}", base_job.eid),
+        let run: Box<JobToRunAsync> = Box::new(move |job_id, mut job_scheduler| {
+            let base_job = base_job.clone();
+            let mut react_clone = react_clone.clone();
+            let dispatch_params = dispatch_params.clone();
+
+            Box::pin(async move {
+                let next_time = job_scheduler.next_tick_for_job(job_id).await.unwrap();
+                let prev_time = Some(Local::now().into());
+                react_clone
+                    .update_state(|state| {
+                        state.last_ticks.insert(base_job.eid.clone(), Utc::now());
+                    })
+                    .await;
+
+                if let Some(ref calendar) = dispatch_params.timer_options.calendar {
+                    if !calendar.allows(Utc::now(), &dispatch_params.timer_options.timezone) {
+                        info!(
+                            "timer {} skipped, outside the calendar {}",
+                            base_job.eid, calendar.name
+                        );
+                        react_clone
+                            .send_update_job_msg(UpdateJobParams {
+                                base_job: base_job.to_pure_job(),
+                                schedule_id: dispatch_params.schedule_id.clone(),
+                                schedule_type: Some(ScheduleType::Timer),
+                                instance_id: dispatch_params
+                                    .instance_id
+                                    .clone()
+                                    .unwrap_or_default(),
+                                bind_namespace: react_clone.namespace.clone(),
+                                bind_ip: react_clone.local_ip.clone(),
+                                created_user: dispatch_params.created_user.clone(),
+                                prev_time,
+                                next_time,
+                                skipped_by_calendar: Some(calendar.name.clone()),
+                                ..Default::default()
+                            })
+                            .await;
+                        return;
                     }
-                })
-            },
-        )
+                }
+
+                let delay = dispatch_params
+                    .timer_options
+                    .start_delay(dispatch_params.instance_id.as_deref().unwrap_or_default());
+                if !delay.is_zero() {
+                    debug!("timer {} delayed {:?} by jitter", base_job.eid, delay);
+                    sleep(delay).await;
+                }
+
+                let _permit = match Self::acquire_parallel_permit(
+                    &mut react_clone,
+                    &dispatch_params,
+                    ScheduleType::Timer,
+                )
+                .await
+                {
+                    Ok(Some(v)) => v,
+                    Ok(None) => return,
+                    Err(e) => {
+                        error!("failed acquire parallel permit {} - {e}", base_job.eid);
+                        return;
+                    }
+                };
+
+                let (kill_signal_tx, kill_signal_rx) = channel::<TerminationReason>(1);
+                react_clone
+                    .add_kill_signal_tx(base_job.eid.clone(), kill_signal_tx.clone())
+                    .await;
+
+                let e = Executor::builder()
+                    .job(base_job.clone())
+                    .output_dir(react_clone.output_dir.clone())
+                    .max_output_bytes(react_clone.max_output_bytes)
+                    .disable_write_log(true);
+                match Self::exec_job(
+                    e,
+                    react_clone.clone(),
+                    Some(ScheduleType::Timer),
+                    kill_signal_rx,
+                    prev_time,
+                    next_time,
+                    dispatch_params,
+                )
+                .await
+                {
+                    Ok(_) => {}
+                    Err(e) => error!("failed exec {} - detail: {e}", base_job.eid),
+                }
+                react_clone
+                    .remove_kill_signal_tx(&base_job.eid, &kill_signal_tx)
+                    .await;
+            })
+        });
+        // the timer follows its own time zone when it is set, otherwise the one of the agent
+        let job = match (types::parse_interval(&timer_expr)?, timezone) {
+            (Some(interval), _) => Job::new_repeated_async(interval, run),
+            (None, Some(tz)) => Job::new_cron_job_async_tz(timer_expr.as_str(), tz, run),
+            (None, None) => Job::new_cron_job_async_tz(timer_expr.as_str(), Local, run),
+        }
         .map_err(|v| anyhow!("failed parse timer expr {} - {}", timer_expr, v))?;
 
-        let next_time = react.add_job_schedule(euid, job).await?;
+        let next_time = react.add_job_schedule(euid.clone(), job).await?;
+        react
+            .update_state(|state| {
+                state.timers.insert(euid, saved_params);
+            })
+            .await;
+        Ok(next_time)
+    }
+
+    async fn start_timer(dispatch_params: DispatchJobParams, react: React) -> Result<Value> {
+        let pure_job = dispatch_params.base_job.to_pure_job();
+        let created_user = dispatch_params.created_user.clone();
+        let schedule_id = dispatch_params.schedule_id.clone();
+        let instance_id = dispatch_params.instance_id.to_owned().unwrap();
 
-        let _ = react
+        let next_time = Self::schedule_timer(dispatch_params, react.clone()).await?;
+
+        react
             .send_update_job_msg(UpdateJobParams {
                 base_job: pure_job,
                 run_status: Some(types::RunStatus::Prepare),
@@ -549,7 +1067,7 @@ impl
                 start_time: None,
                 ..Default::default()
             })
-            .await?;
+            .await;
 
         Ok(json!(null))
     }
@@ -559,7 +1077,13 @@ impl
         react
             .remove_job_schedule(&dispatch_params.base_job.eid)
             .await?;
-        let _ = react
+        react
+            .update_state(|state| {
+                state.timers.remove(&dispatch_params.base_job.eid);
+                state.last_ticks.remove(&dispatch_params.base_job.eid);
+            })
+            .await;
+        react
             .send_update_job_msg(UpdateJobParams {
                 base_job: dispatch_params.base_job.to_pure_job(),
                 schedule_status: Some(types::ScheduleStatus::Unscheduled),
@@ -578,7 +1102,7 @@ impl
                 start_time: None,
                 ..Default::default()
             })
-            .await?;
+            .await;
         Ok(json!(null))
     }
 
@@ -588,26 +1112,90 @@ impl
     ) -> Result<Value> {
         let eid = dispatch_params.base_job.eid.clone();
         let (tx, mut rx) = unbounded_channel();
+        let probe_tx = tx.clone();
         if !react.start_supervising(eid.clone(), tx).await {
             return Ok(json!(null));
         }
+        react
+            .update_state(|state| {
+                state.supervisors.insert(eid, dispatch_params.clone());
+            })
+            .await;
 
-        let dur = dispatch_params
+        let interval = dispatch_params
             .restart_interval
-            .filter(|v| v.as_millis() > 0);
+            .filter(|v| v.as_millis() > 0)
+            .unwrap_or(Duration::from_millis(50));
+        let options = dispatch_params.supervisor_options.clone();
+
+        if let Some(probe) = options.liveness_probe.clone() {
+            tokio::spawn(Self::watch_liveness(
+                probe,
+                dispatch_params.clone(),
+                react.clone(),
+                probe_tx,
+            ));
+        }
 
         tokio::spawn(async move {
-            'main: loop {
+            let mut restarts: VecDeque<Instant> = VecDeque::new();
+            let mut restart_count = 0u32;
+            loop {
                 let ret = Scheduler::wait_exec(dispatch_params.clone(), react.clone()).await;
-                if let Err(e) = ret {
-                    error!("supervising: failed exec job - {e}");
+                let exit_code = match ret {
+                    Ok(Some(ref output)) => output
+                        .get_exit_code()
+                        .or_else(|| output.is_success().then_some(0)),
+                    Ok(None) => None,
+                    Err(ref e) => {
+                        error!("supervising: failed exec job - {e}");
+                        None
+                    }
+                };
+
+                // a stop asked while the daemon was running is not a policy decision
+                if rx.try_recv().is_ok() {
+                    info!("supervising: exited");
+                    return;
+                }
+
+                if options.restart_window > 0 {
+                    let window = Duration::from_secs(options.restart_window);
+                    restarts.retain(|v| v.elapsed() < window);
                 }
-                let sleep_time = if let Some(ref d) = dur {
-                    sleep(d.to_owned())
+
+                let schedule_status = if !options.should_restart(exit_code) {
+                    Some(types::ScheduleStatus::Unsupervised)
+                } else if options.is_fatal(restarts.len()) {
+                    Some(types::ScheduleStatus::Fatal)
                 } else {
-                    sleep(Duration::from_millis(50))
+                    None
                 };
 
+                if let Some(schedule_status) = schedule_status {
+                    let eid = &dispatch_params.base_job.eid;
+                    info!(
+                        "supervising: {eid} is {schedule_status} after {restart_count} restarts, \
+                         exit code {exit_code:?}"
+                    );
+                    let _ = react.stop_supervising(eid).await;
+                    react
+                        .update_state(|state| {
+                            state.supervisors.remove(eid);
+                        })
+                        .await;
+                    react
+                        .send_update_job_msg(Self::supervisor_update_params(
+                            &dispatch_params,
+                            &react,
+                            schedule_status,
+                            restart_count,
+                        ))
+                        .await;
+                    return;
+                }
+
+                let sleep_time = sleep(options.restart_delay(interval, restarts.len()));
                 tokio::pin!(sleep_time);
 
                 select! {
@@ -619,12 +1207,115 @@ impl
                         return;
                     },
                 }
-                continue 'main;
+
+                restarts.push_back(Instant::now());
+                restart_count += 1;
+                react
+                    .send_update_job_msg(Self::supervisor_update_params(
+                        &dispatch_params,
+                        &react,
+                        types::ScheduleStatus::Supervising,
+                        restart_count,
+                    ))
+                    .await;
             }
         });
         Ok(json!(null))
     }
 
+    /// probe the daemon until its supervisor stopped, which closes the supervisor channel.
+    /// after failure_threshold failures in a row the daemon is killed, and the supervising
+    /// loop restarts it according to the restart policy
+    async fn watch_liveness(
+        probe: LivenessProbe,
+        dispatch_params: DispatchJobParams,
+        mut react: React,
+        supervisor_tx: UnboundedSender<()>,
+    ) {
+        let eid = dispatch_params.base_job.eid.clone();
+        let interval = Duration::from_secs(probe.interval.max(1));
+        let initial_delay = Duration::from_secs(probe.initial_delay);
+        let mut failures = 0u32;
+        let mut last_healthy = None;
+
+        sleep(initial_delay).await;
+        while !supervisor_tx.is_closed() {
+            let ret = probe.check().await;
+            if supervisor_tx.is_closed() {
+                return;
+            }
+
+            let mut result = ProbeResult {
+                healthy: ret.is_ok(),
+                time: Utc::now(),
+                ..Default::default()
+            };
+            if let Err(e) = ret {
+                failures += 1;
+                result.message = e.to_string();
+            } else {
+                failures = 0;
+            }
+            result.failures = failures;
+            result.killed = failures >= probe.failure_threshold.max(1);
+
+            // a healthy daemon is only reported when it was not before
+            if !result.healthy || last_healthy != Some(true) {
+                if result.killed {
+                    info!(
+                        "supervising: {eid} failed {failures} liveness probes, kill it - {}",
+                        result.message
+                    );
+                    react
+                        .kill_daemon_run(&eid, TerminationReason::LivenessFailed)
+                        .await;
+                }
+                react
+                    .send_update_job_msg(UpdateJobParams {
+                        base_job: dispatch_params.base_job.to_pure_job(),
+                        schedule_id: dispatch_params.schedule_id.clone(),
+                        schedule_type: Some(ScheduleType::Daemon),
+                        instance_id: dispatch_params.instance_id.clone().unwrap_or_default(),
+                        bind_namespace: react.namespace.clone(),
+                        bind_ip: react.local_ip.clone(),
+                        created_user: dispatch_params.created_user.clone(),
+                        probe_result: Some(result.clone()),
+                        ..Default::default()
+                    })
+                    .await;
+            }
+            last_healthy = Some(result.healthy);
+
+            if result.killed {
+                failures = 0;
+                sleep(initial_delay + interval).await;
+            } else {
+                sleep(interval).await;
+            }
+        }
+    }
+
+    /// the schedule status and restart count of a daemon reported by its supervisor
+    fn supervisor_update_params(
+        dispatch_params: &DispatchJobParams,
+        react: &React,
+        schedule_status: types::ScheduleStatus,
+        restart_count: u32,
+    ) -> UpdateJobParams {
+        UpdateJobParams {
+            base_job: dispatch_params.base_job.to_pure_job(),
+            schedule_id: dispatch_params.schedule_id.clone(),
+            schedule_type: Some(ScheduleType::Daemon),
+            schedule_status: Some(schedule_status),
+            instance_id: dispatch_params.instance_id.clone().unwrap_or_default(),
+            bind_namespace: react.namespace.clone(),
+            bind_ip: react.local_ip.clone(),
+            created_user: dispatch_params.created_user.clone(),
+            restart_count: Some(restart_count),
+            ..Default::default()
+        }
+    }
+
     async fn stop_supervising(
         dispatch_params: DispatchJobParams,
         mut react: React,
@@ -632,6 +1323,11 @@ impl
         let eid = dispatch_params.base_job.eid.clone();
         react.kill_job(&eid).await;
         react.stop_supervising(&eid).await?;
+        react
+            .update_state(|state| {
+                state.supervisors.remove(&eid);
+            })
+            .await;
         Ok(json!(null))
     }
 
@@ -643,30 +1339,127 @@ impl
         Scheduler::start_supervising(dispatch_params.clone(), react).await
     }
 
-    async fn wait_exec(dispatch_params: DispatchJobParams, mut react: React) -> Result<()> {
+    /// take a run slot of the job according to its max_parallel and parallel_policy,
+    /// returns None when the run should not happen
+    async fn acquire_parallel_permit(
+        react: &mut React,
+        dispatch_params: &DispatchJobParams,
+        schedule_type: ScheduleType,
+    ) -> Result<Option<OwnedSemaphorePermit>> {
+        let base_job = &dispatch_params.base_job;
+        let semaphore = react
+            .get_parallel_semaphore(&base_job.eid, base_job.max_parallel)
+            .await;
+
+        if let Ok(permit) = semaphore.clone().try_acquire_owned() {
+            return Ok(Some(permit));
+        }
+
+        let policy = base_job.parallel_policy;
+        info!(
+            "job {} reached max_parallel {}, {policy} the new run",
+            base_job.eid, base_job.max_parallel
+        );
+
+        let decision = UpdateJobParams {
+            base_job: base_job.to_pure_job(),
+            schedule_id: dispatch_params.schedule_id.clone(),
+            schedule_type: Some(schedule_type),
+            instance_id: dispatch_params.instance_id.clone().unwrap_or_default(),
+            bind_namespace: react.namespace.clone(),
+            bind_ip: react.local_ip.clone(),
+            created_user: dispatch_params.created_user.clone(),
+            parallel_decision: Some(policy),
+            ..Default::default()
+        };
+
+        match policy {
+            ParallelPolicy::Skip => {
+                // the skipped run is finished right away, so that a flow does not wait for it
+                let now = Utc::now();
+                react
+                    .send_update_job_msg(UpdateJobParams {
+                        run_status: Some(types::RunStatus::Stop),
+                        exit_status: Some("skipped, the job reached max_parallel".to_string()),
+                        exit_code: Some(-1),
+                        termination_reason: Some(TerminationReason::Skipped),
+                        start_time: Some(now),
+                        end_time: Some(now),
+                        ..decision
+                    })
+                    .await;
+                Ok(None)
+            }
+            ParallelPolicy::Replace => {
+                react.send_update_job_msg(decision).await;
+                react.replace_oldest_run(&base_job.eid).await;
+                Ok(Some(semaphore.acquire_owned().await?))
+            }
+            ParallelPolicy::Queue => {
+                react.send_update_job_msg(decision).await;
+                // a queued run can still be killed before it starts
+                let (kill_signal_tx, mut kill_signal_rx) = channel::<TerminationReason>(1);
+                react
+                    .add_kill_signal_tx(base_job.eid.clone(), kill_signal_tx.clone())
+                    .await;
+                let permit = select! {
+                    permit = semaphore.acquire_owned() => Some(permit?),
+                    Some(_) = kill_signal_rx.recv() => None,
+                };
+                react
+                    .remove_kill_signal_tx(&base_job.eid, &kill_signal_tx)
+                    .await;
+                Ok(permit)
+            }
+        }
+    }
+
+    async fn wait_exec(
+        dispatch_params: DispatchJobParams,
+        mut react: React,
+    ) -> Result<Option<BundleOutput>> {
         let mut base_job = dispatch_params.base_job.clone();
-        let (kill_signal_tx, kill_signal_rx) = channel::<()>(1);
 
         let schedule_type = match dispatch_params.action {
             JobAction::StartSupervising => {
                 base_job.timeout = 0;
                 ScheduleType::Daemon
             }
+            JobAction::Exec if dispatch_params.schedule_type == Some(ScheduleType::Flow) => {
+                ScheduleType::Flow
+            }
             JobAction::Exec => ScheduleType::Once,
             _ => unreachable!(),
         };
 
+        let Some(_permit) =
+            Self::acquire_parallel_permit(&mut react, &dispatch_params, schedule_type.clone())
+                .await?
+        else {
+            return Ok(None);
+        };
+
+        let (kill_signal_tx, kill_signal_rx) = channel::<TerminationReason>(1);
+
         let e = Executor::builder()
             .job(base_job.clone())
             .output_dir(react.output_dir.clone())
-            .disable_write_log(true)
-            .build();
+            .max_output_bytes(react.max_output_bytes)
+            .disable_write_log(true);
 
         react
-            .add_kill_signal_tx(base_job.eid.clone(), kill_signal_tx)
+            .add_kill_signal_tx(base_job.eid.clone(), kill_signal_tx.clone())
             .await;
+        let is_daemon = schedule_type == ScheduleType::Daemon;
+        if is_daemon {
+            react
+                .daemon_runs
+                .lock()
+                .await
+                .insert(base_job.eid.clone(), kill_signal_tx.clone());
+        }
 
-        Self::exec_job(
+        let ret = Self::exec_job(
             e,
             react.clone(),
             Some(schedule_type),
@@ -675,72 +1468,47 @@ impl
             None,
             dispatch_params,
         )
-        .await?;
-        react.remove_job(base_job.eid.clone()).await;
+        .await;
+        react
+            .remove_kill_signal_tx(&base_job.eid, &kill_signal_tx)
+            .await;
+        if is_daemon {
+            let mut daemon_runs = react.daemon_runs.lock().await;
+            if daemon_runs
+                .get(&base_job.eid)
+                .is_some_and(|v| v.same_channel(&kill_signal_tx))
+            {
+                daemon_runs.remove(&base_job.eid);
+            }
+        }
 
-        Ok(())
+        Ok(Some(ret?))
     }
 
-    async fn exec(dispatch_params: DispatchJobParams, mut react: React) -> Result<Value> {
-        let mut base_job = dispatch_params.base_job.clone();
-        let (kill_signal_tx, kill_signal_rx) = channel::<()>(1);
-
-        let schedule_type = match dispatch_params.action {
-            JobAction::StartSupervising => {
-                base_job.timeout = 0;
-                ScheduleType::Daemon
+    async fn exec(dispatch_params: DispatchJobParams, react: React) -> Result<Value> {
+        if let Some(ref calendar) = dispatch_params.timer_options.calendar {
+            if !calendar.allows(Utc::now(), &dispatch_params.timer_options.timezone) {
+                anyhow::bail!("skipped, now is outside the calendar {}", calendar.name);
             }
-            JobAction::Exec => ScheduleType::Once,
-            _ => unreachable!(),
-        };
-
-        let e = Executor::builder()
-            .job(base_job.clone())
-            .output_dir(react.output_dir.clone())
-            .disable_write_log(true)
-            .build();
-
-        react
-            .add_kill_signal_tx(base_job.eid.clone(), kill_signal_tx)
-            .await;
+        }
 
         if dispatch_params.is_sync {
-            let output = Self::exec_job(
-                e,
-                react.clone(),
-                Some(schedule_type),
-                kill_signal_rx,
-                None,
-                None,
-                dispatch_params,
-            )
-            .await?;
-            react.remove_job(base_job.eid.clone()).await;
+            let Some(output) = Self::wait_exec(dispatch_params, react).await? else {
+                return Ok(json!(null));
+            };
             return Ok(json!({
                 "stdout":output.get_stdout(),
                 "exit_code":output.get_exit_code(),
+                "termination_reason":output.get_termination_reason().to_string(),
                 "stderr":output.get_stderr(),
             }));
         }
-        let juid = base_job.eid.clone();
 
+        let eid = dispatch_params.base_job.eid.clone();
         task::spawn(async move {
-            match Self::exec_job(
-                e,
-                react.clone(),
-                Some(schedule_type),
-                kill_signal_rx,
-                None,
-                None,
-                dispatch_params,
-            )
-            .await
-            {
-                Ok(_) => {}
-                Err(e) => error!("failed exec {} - detail: {e}", base_job.eid),
+            if let Err(e) = Self::wait_exec(dispatch_params, react).await {
+                error!("failed exec {eid} - detail: {e}");
             }
-
-            react.remove_job(juid).await
         });
 
         return Ok(json!(null));
@@ -784,20 +1552,85 @@ impl
         mut react: React,
     ) -> Result<Value> {
         match action_params.action {
-            RuntimeAction::StopTimer => react.remove_job_schedule(&action_params.eid).await?,
-            RuntimeAction::StopSupervising => react.stop_supervising(&action_params.eid).await?,
+            RuntimeAction::StopTimer => {
+                react.remove_job_schedule(&action_params.eid).await?;
+                react
+                    .update_state(|state| {
+                        state.timers.remove(&action_params.eid);
+                        state.last_ticks.remove(&action_params.eid);
+                    })
+                    .await;
+            }
+            RuntimeAction::StopSupervising => {
+                react.stop_supervising(&action_params.eid).await?;
+                react
+                    .update_state(|state| {
+                        state.supervisors.remove(&action_params.eid);
+                    })
+                    .await;
+            }
             RuntimeAction::Kill => react.kill_job(&action_params.eid).await,
             _ => unimplemented!(),
         };
         Ok(json!(null))
     }
 
+    /// stop the timers the console no longer expects, returns their eid and the last fire time
+    /// of the timers kept
+    pub async fn reconcile(req: ReconcileParams, mut react: React) -> Result<Value> {
+        let stale: Vec<String> = react
+            .schedule_uuid_mapping
+            .lock()
+            .await
+            .keys()
+            .filter(|v| !req.timers.contains(v))
+            .cloned()
+            .collect();
+
+        for eid in stale.iter() {
+            info!("reconcile: stop timer {eid} which is not scheduled on the console");
+            react.remove_job_schedule(eid).await?;
+        }
+        react
+            .update_state(|state| {
+                state.timers.retain(|k, _| !stale.contains(k));
+                state.last_ticks.retain(|k, _| !stale.contains(k));
+            })
+            .await;
+        let last_ticks = react.state.lock().await.last_ticks.clone();
+        Ok(json!({
+            "stopped": stale,
+            "last_ticks": last_ticks,
+        }))
+    }
+
+    /// start the timers and supervisors saved before the agent restarted, the console
+    /// dispatches its runnable jobs again once connected and reconciles the timers
+    async fn restore_state(react: React) {
+        let state = react.state.lock().await.clone();
+
+        for (eid, dispatch_params) in state.timers {
+            match Self::schedule_timer(dispatch_params, react.clone()).await {
+                Ok(next_time) => info!("restore timer {eid}, next time {next_time:?}"),
+                Err(e) => error!("failed restore timer {eid} - {e}"),
+            }
+        }
+
+        for (eid, dispatch_params) in state.supervisors {
+            info!("restore supervisor {eid}");
+            if let Err(e) = Self::start_supervising(dispatch_params, react.clone()).await {
+                error!("failed restore supervisor {eid} - {e}");
+            }
+        }
+    }
+
     pub async fn sftp_read_dir(req: SftpReadDirParams) -> Result<Value> {
         let ret = ssh::read_dir(
             &req.ip,
             req.port,
             &req.user,
-            &req.password,
+            &req.auth,
+            &req.host_key,
             req.dir.filter(|v| v != "").as_deref(),
         )
         .await?;
@@ -810,7 +1643,8 @@ impl
             &req.ip,
             req.port,
             &req.user,
-            &req.password,
+            &req.auth,
+            &req.host_key,
             &req.filepath,
             req.data,
         )
@@ -820,7 +1654,15 @@ impl
     }
 
     pub async fn sftp_download(req: SftpDownloadParams) -> Result<Value> {
-        let ret = ssh::download(&req.ip, req.port, &req.user, &req.password, &req.filepath).await?;
+        let ret = ssh::download(
+            &req.ip,
+            req.port,
+            &req.user,
+            &req.auth,
+            &req.host_key,
+            &req.filepath,
+        )
+        .await?;
         let ret = serde_json::to_value(ret)?;
         Ok(ret)
     }
@@ -830,7 +1672,8 @@ impl
             &req.ip,
             req.port,
             &req.user,
-            &req.password,
+            &req.auth,
+            &req.host_key,
             &req.remove_type,
             &req.filepath,
         )
@@ -839,6 +1682,34 @@ impl
         Ok(ret)
     }
 
+    pub async fn ssh_host_key(req: SshHostKeyParams) -> Result<Value> {
+        let host_key = ssh::scan_host_key(req.port).await?;
+        Ok(json!(host_key))
+    }
+
+    pub async fn read_job_log(req: ReadJobLogParams, react: React) -> Result<Value> {
+        let path = react.job_log_path(&req.eid, &req.schedule_id, &req.run_id, req.attempt)?;
+        let mut file = File::open(&path)
+            .await
+            .map_err(|e| anyhow!("job log {} is not available - {e}", path.display()))?;
+        let size = file.metadata().await?.len();
+        let limit = match req.limit {
+            0 => JOB_LOG_READ_LIMIT,
+            n => n.min(JOB_LOG_READ_LIMIT),
+        };
+
+        file.seek(SeekFrom::Start(req.offset)).await?;
+        let mut buf = Vec::new();
+        file.take(limit).read_to_end(&mut buf).await?;
+
+        Ok(json!({
+            "content": String::from_utf8_lossy(&buf),
+            "offset": req.offset,
+            "size": size,
+            "eof": req.offset + buf.len() as u64 >= size,
+        }))
+    }
+
     pub async fn handle(msg: MsgReqKind, _bridge: Bridge, react: React) -> Value {
         let ret = match msg {
             MsgReqKind::DispatchJobRequest(v) => Self::dispath_job(v, react.clone()).await,
@@ -847,9 +1718,13 @@ impl
             MsgReqKind::SftpUploadRequest(v) => Self::sftp_upload(v).await,
             MsgReqKind::SftpRemoveRequest(v) => Self::sftp_remove(v).await,
             MsgReqKind::SftpDownloadRequest(v) => Self::sftp_download(v).await,
-            MsgReqKind::PullJobRequest(_) => todo!(),
-            MsgReqKind::HeartbeatRequest(_) => todo!(),
-            _ => todo!(),
+            MsgReqKind::ReadJobLogRequest(v) => Self::read_job_log(v, react.clone()).await,
+            MsgReqKind::SshHostKeyRequest(v) => Self::ssh_host_key(v).await,
+            MsgReqKind::ReconcileRequest(v) => Self::reconcile(v, react.clone()).await,
+            other => {
+                warn!("ignore unsupported msg {}", other.name());
+                Err(anyhow!("unsupported msg"))
+            }
         };
 
         match ret {
@@ -916,10 +1791,12 @@ impl
             get_local_ip().to_string(),
             self.client_key(),
             self.output_dir.clone(),
+            self.max_output_bytes,
         )
         .await;
         let mut react_clone: React = react.clone();
 
+        Self::restore_state(react.clone()).await;
         self.ssh_poll().await;
 
         tokio::spawn(async move {
@@ -933,9 +1810,518 @@ impl
             self.recv(react.clone()).await;
             info!("reconnect after 1s");
             sleep(Duration::from_secs(1)).await;
-            if let Err(e) = self.connect_comet().await {
-                error!("failed reconnect to comet {:?} - {e}", self.comet_addr);
+            match self.connect_comet().await {
+                Ok(_) => {
+                    let react = react.clone();
+                    tokio::spawn(async move { react.replay_pending_updates().await });
+                }
+                Err(e) => error!("failed reconnect to comet {:?} - {e}", self.comet_addr),
             }
         }
     }
 }
+
+/// a new temp dir for the state and the logs of a test react
+#[cfg(test)]
+fn test_output_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("jiascheduler-{}", nanoid!()))
+}
+
+#[cfg(test)]
+async fn test_react(bridge: Bridge, namespace: &str, output_dir: &Path) -> React {
+    React::new(
+        bridge,
+        namespace.to_string(),
+        "127.0.0.1".to_string(),
+        "client_key".to_string(),
+        output_dir.to_string_lossy().to_string(),
+        0,
+    )
+    .await
+}
+
+/// a job running the code with bash, executed once on instance_id
+#[cfg(test)]
+fn test_params(code: &str) -> DispatchJobParams {
+    DispatchJobParams {
+        base_job: types::BaseJob {
+            eid: "eid".to_string(),
+            cmd_name: "bash".to_string(),
+            args: vec!["-c".to_string()],
+            code: code.to_string(),
+            ..Default::default()
+        },
+        schedule_id: "schedule_id".to_string(),
+        instance_id: Some("instance_id".to_string()),
+        fields: None,
+        timer_expr: None,
+        timer_options: Default::default(),
+        restart_interval: None,
+        supervisor_options: Default::default(),
+        is_sync: true,
+        created_user: "admin".to_string(),
+        action: JobAction::Exec,
+        schedule_type: None,
+    }
+}
+
+#[tokio::test]
+async fn test_restore_state_without_comet() {
+    let output_dir = test_output_dir();
+    let marker = output_dir.join("ran");
+    let mut state = AgentState::default();
+    state.supervisors.insert(
+        "eid".to_string(),
+        DispatchJobParams {
+            restart_interval: Some(Duration::from_secs(60)),
+            is_sync: false,
+            action: JobAction::StartSupervising,
+            ..test_params(&format!("echo ok > {}", marker.display()))
+        },
+    );
+    state.save(&output_dir.join(STATE_FILE)).await.unwrap();
+
+    let react = test_react(Bridge::new(), "default", &output_dir).await;
+    Scheduler::restore_state(react.clone()).await;
+
+    for _ in 0..50 {
+        if fs::try_exists(&marker).await.unwrap() {
+            break;
+        }
+        sleep(Duration::from_millis(100)).await;
+    }
+    assert!(fs::try_exists(&marker).await.unwrap());
+    assert!(react.supervisor_jobs.lock().await.contains_key("eid"));
+    assert!(!react.pending_updates.lock().await.is_empty());
+
+    fs::remove_dir_all(output_dir).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_parallel_policy() {
+    use crate::scheduler::types::RunStatus;
+
+    let output_dir = test_output_dir();
+    let mut react = test_react(Bridge::new(), "default", &output_dir).await;
+    let params = |eid: &str, parallel_policy| {
+        let mut params = test_params("");
+        params.base_job.eid = eid.to_string();
+        params.base_job.max_parallel = 1;
+        params.base_job.parallel_policy = parallel_policy;
+        params
+    };
+
+    // skip finishes the new run right away
+    let skip = params("skip", ParallelPolicy::Skip);
+    let held = Scheduler::acquire_parallel_permit(&mut react, &skip, ScheduleType::Once)
+        .await
+        .unwrap();
+    assert!(held.is_some());
+    let skipped = Scheduler::acquire_parallel_permit(&mut react, &skip, ScheduleType::Once)
+        .await
+        .unwrap();
+    assert!(skipped.is_none());
+    let update = react.pending_updates.lock().await.pop_back().unwrap();
+    assert_eq!(update.run_status, Some(RunStatus::Stop));
+    assert_eq!(update.termination_reason, Some(TerminationReason::Skipped));
+
+    // a changed max_parallel waits for the running ones
+    let semaphore = react.get_parallel_semaphore("skip", 2).await;
+    assert_eq!(semaphore.available_permits(), 0);
+    drop(held);
+    assert_eq!(
+        react
+            .get_parallel_semaphore("skip", 2)
+            .await
+            .available_permits(),
+        2
+    );
+
+    // queue starts the new run once a running one finished
+    let queue = params("queue", ParallelPolicy::Queue);
+    let held = Scheduler::acquire_parallel_permit(&mut react, &queue, ScheduleType::Once)
+        .await
+        .unwrap();
+    let mut react_clone = react.clone();
+    let queued = tokio::spawn(async move {
+        Scheduler::acquire_parallel_permit(&mut react_clone, &queue, ScheduleType::Once).await
+    });
+    sleep(Duration::from_millis(100)).await;
+    assert!(!queued.is_finished());
+    drop(held);
+    assert!(queued.await.unwrap().unwrap().is_some());
+
+    // replace only kills the oldest run
+    let replace = params("replace", ParallelPolicy::Replace);
+    let held = Scheduler::acquire_parallel_permit(&mut react, &replace, ScheduleType::Once)
+        .await
+        .unwrap();
+    let (oldest_tx, mut oldest_rx) = channel::<TerminationReason>(1);
+    let (newer_tx, mut newer_rx) = channel::<TerminationReason>(1);
+    react
+        .add_kill_signal_tx("replace".to_string(), oldest_tx)
+        .await;
+    react
+        .add_kill_signal_tx("replace".to_string(), newer_tx)
+        .await;
+    let release = tokio::spawn(async move {
+        assert_eq!(oldest_rx.recv().await, Some(TerminationReason::Replaced));
+        drop(held);
+    });
+    let replaced = Scheduler::acquire_parallel_permit(&mut react, &replace, ScheduleType::Once)
+        .await
+        .unwrap();
+    assert!(replaced.is_some());
+    release.await.unwrap();
+    assert!(newer_rx.try_recv().is_err());
+    assert_eq!(
+        react
+            .kill_signal_mapping
+            .lock()
+            .await
+            .get("replace")
+            .map(Vec::len),
+        Some(1)
+    );
+}
+
+#[tokio::test]
+async fn test_kill_daemon_run() {
+    let output_dir = test_output_dir();
+    let mut react = test_react(Bridge::new(), "default", &output_dir).await;
+
+    // a one-off run of the daemon's job is not killed by a failed liveness probe
+    let (exec_tx, mut exec_rx) = channel::<TerminationReason>(1);
+    let (daemon_tx, mut daemon_rx) = channel::<TerminationReason>(1);
+    react
+        .add_kill_signal_tx("daemon".to_string(), exec_tx)
+        .await;
+    react
+        .add_kill_signal_tx("daemon".to_string(), daemon_tx.clone())
+        .await;
+    react
+        .daemon_runs
+        .lock()
+        .await
+        .insert("daemon".to_string(), daemon_tx);
+
+    react
+        .kill_daemon_run("daemon", TerminationReason::LivenessFailed)
+        .await;
+    assert_eq!(
+        daemon_rx.try_recv().ok(),
+        Some(TerminationReason::LivenessFailed)
+    );
+    assert!(exec_rx.try_recv().is_err());
+    assert_eq!(
+        TerminationReason::LivenessFailed.to_string(),
+        "liveness_failed"
+    );
+}
+
+#[tokio::test]
+async fn test_job_log() {
+    let output_dir = test_output_dir();
+    let react = test_react(Bridge::new(), "default", &output_dir).await;
+
+    let path = react
+        .job_log_path("eid", "sid", "1700000000000-1-abc", 1)
+        .unwrap();
+    assert_eq!(
+        path,
+        output_dir.join("eid").join("sid_1700000000000-1-abc.log")
+    );
+    let path = react.job_log_path("eid", "sid", "", 2).unwrap();
+    assert_eq!(path, output_dir.join("eid").join("sid_2.log"));
+    assert!(react.job_log_path("eid", "sid", "../x", 1).is_err());
+
+    let dir = output_dir.join("eid");
+    fs::create_dir_all(&dir).await.unwrap();
+    for i in 0..MAX_JOB_LOGS {
+        fs::write(dir.join(format!("sid_{i}.log")), "")
+            .await
+            .unwrap();
+    }
+    let mut params = JobOutputParams {
+        eid: "eid".to_string(),
+        schedule_id: "sid".to_string(),
+        run_id: "new".to_string(),
+        ..Default::default()
+    };
+    react.create_job_log(&params).await.unwrap();
+    params.run_id = "newer".to_string();
+    react.create_job_log(&params).await.unwrap();
+
+    let mut count = 0;
+    let mut entries = fs::read_dir(&dir).await.unwrap();
+    while entries.next_entry().await.unwrap().is_some() {
+        count += 1;
+    }
+    assert_eq!(count, MAX_JOB_LOGS);
+    assert!(fs::try_exists(dir.join("sid_new.log")).await.unwrap());
+    assert!(fs::try_exists(dir.join("sid_newer.log")).await.unwrap());
+
+    fs::remove_dir_all(output_dir).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_job_output_chunks() {
+    use crate::bridge::msg::{Msg, MsgKind, MsgState};
+
+    let output_dir = test_output_dir();
+    let mut bridge = Bridge::new();
+    let (client_tx, mut client_rx) = channel::<(Msg, Option<Sender<MsgState>>)>(16);
+    bridge.append_client("client_key", client_tx).await;
+    let react = test_react(bridge, "default", &output_dir).await;
+
+    let ret = Scheduler::handle(
+        MsgReqKind::PullJobRequest(Value::Null),
+        Bridge::new(),
+        react.clone(),
+    )
+    .await;
+    assert_eq!(ret["code"], 50000);
+
+    let params = JobOutputParams {
+        eid: "eid".to_string(),
+        schedule_id: "sid".to_string(),
+        instance_id: "instance".to_string(),
+        attempt: 1,
+        run_id: "run".to_string(),
+        data: String::new(),
+    };
+    let (output_tx, output_rx) = channel(OUTPUT_QUEUE_SIZE);
+    let forward = tokio::spawn(Scheduler::forward_job_output(
+        react,
+        params.clone(),
+        output_rx,
+    ));
+
+    let line = "x".repeat(1000);
+    for _ in 0..10 {
+        output_tx.send(line.clone()).await.unwrap();
+    }
+    output_tx.send("tail".to_string()).await.unwrap();
+    drop(output_tx);
+
+    let mut chunks = vec![];
+    while let Some((msg, resp_tx)) = client_rx.recv().await {
+        let MsgKind::Request(MsgReqKind::JobOutputRequest(v)) = msg.data else {
+            panic!("unexpected msg {}", msg.id);
+        };
+        resp_tx
+            .unwrap()
+            .send(MsgState::Completed(Value::Null))
+            .await
+            .unwrap();
+        assert_eq!(
+            JobOutputParams {
+                data: String::new(),
+                ..v.clone()
+            },
+            params
+        );
+        chunks.push(v.data);
+        if chunks.concat().ends_with("tail") {
+            break;
+        }
+    }
+    forward.await.unwrap();
+
+    let expected = format!("{}tail", line.repeat(10));
+    assert!(chunks[0].len() >= JOB_OUTPUT_CHUNK_SIZE);
+    assert_eq!(chunks.concat(), expected);
+    let log = fs::read_to_string(output_dir.join("eid").join("sid_run.log"))
+        .await
+        .unwrap();
+    assert_eq!(log, expected);
+
+    // chunks of agents without run ids are still accepted
+    let v: JobOutputParams = serde_json::from_value(json!({
+        "schedule_id": "sid",
+        "instance_id": "instance",
+        "eid": "eid",
+        "attempt": 1,
+        "data": "output",
+    }))
+    .unwrap();
+    assert_eq!(v.run_id, "");
+
+    fs::remove_dir_all(output_dir).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_exec_schedule_type() {
+    let output_dir = test_output_dir();
+    let react = test_react(Bridge::new(), "default", &output_dir).await;
+    let params = |schedule_type| DispatchJobParams {
+        schedule_type,
+        ..test_params("true")
+    };
+
+    for (schedule_type, expected) in [
+        (Some(ScheduleType::Flow), ScheduleType::Flow),
+        (Some(ScheduleType::Timer), ScheduleType::Once),
+        (None, ScheduleType::Once),
+    ] {
+        Scheduler::wait_exec(params(schedule_type), react.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        let updates = std::mem::take(&mut *react.pending_updates.lock().await);
+        assert!(!updates.is_empty());
+        assert!(updates
+            .iter()
+            .all(|v| v.schedule_type.as_ref() == Some(&expected)));
+    }
+
+    let _ = fs::remove_dir_all(output_dir).await;
+}
+
+#[tokio::test]
+async fn test_runtime_env() {
+    use chrono::TimeZone;
+
+    let output_dir = test_output_dir();
+    let react = test_react(Bridge::new(), "prod", &output_dir).await;
+    let params = test_params(
+        "echo -n $JIASCHEDULER_EID,$JIASCHEDULER_SCHEDULE_ID,$JIASCHEDULER_INSTANCE_ID,\
+         $JIASCHEDULER_NAMESPACE,$JIASCHEDULER_SCHEDULE_TYPE,$JIASCHEDULER_ATTEMPT",
+    );
+
+    let prev_time = Utc.with_ymd_and_hms(2024, 1, 1, 8, 0, 0).unwrap();
+    let env: HashMap<String, String> = Scheduler::runtime_env(
+        &react,
+        &params,
+        Some(&ScheduleType::Timer),
+        Some(prev_time),
+        None,
+    )
+    .into_iter()
+    .collect();
+    assert_eq!(env["JIASCHEDULER_EID"], "eid");
+    assert_eq!(env["JIASCHEDULER_SCHEDULE_ID"], "schedule_id");
+    assert_eq!(env["JIASCHEDULER_INSTANCE_ID"], "instance_id");
+    assert_eq!(env["JIASCHEDULER_NAMESPACE"], "prod");
+    assert_eq!(
+        env["JIASCHEDULER_SCHEDULE_TYPE"],
+        ScheduleType::Timer.to_string()
+    );
+    assert_eq!(env["JIASCHEDULER_CREATED_USER"], "admin");
+    assert_eq!(env["JIASCHEDULER_PREV_TIME"], "2024-01-01T08:00:00+00:00");
+    assert_eq!(env["JIASCHEDULER_NEXT_TIME"], "");
+
+    let output = Scheduler::wait_exec(params, react).await.unwrap().unwrap();
+    assert_eq!(
+        output.get_stdout().unwrap(),
+        format!("eid,schedule_id,instance_id,prod,{},1", ScheduleType::Once)
+    );
+
+    let _ = fs::remove_dir_all(output_dir).await;
+}
+
+#[tokio::test]
+async fn test_reconcile_last_ticks() {
+    use chrono::TimeZone;
+
+    let output_dir = test_output_dir();
+    let react = test_react(Bridge::new(), "default", &output_dir).await;
+    let last_tick = Utc.with_ymd_and_hms(2024, 1, 31, 23, 0, 0).unwrap();
+    react
+        .update_state(|state| {
+            state.last_ticks.insert("kept".to_string(), last_tick);
+            state.last_ticks.insert("stale".to_string(), last_tick);
+        })
+        .await;
+    react
+        .schedule_uuid_mapping
+        .lock()
+        .await
+        .insert("stale".to_string(), Uuid::new_v4());
+
+    let ret = Scheduler::reconcile(
+        ReconcileParams {
+            timers: vec!["kept".to_string()],
+        },
+        react.clone(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(ret["stopped"], json!(["stale"]));
+    let last_ticks: HashMap<String, DateTime<Utc>> =
+        serde_json::from_value(ret["last_ticks"].clone()).unwrap();
+    assert_eq!(last_ticks, HashMap::from([("kept".to_string(), last_tick)]));
+
+    let state = AgentState::load(&output_dir.join(STATE_FILE))
+        .await
+        .unwrap();
+    assert_eq!(state.last_ticks, last_ticks);
+
+    let _ = fs::remove_dir_all(output_dir).await;
+}
+
+#[tokio::test]
+async fn test_job_output_backlog() {
+    use crate::bridge::msg::{MsgKind, MsgState};
+
+    let output_dir = test_output_dir();
+    let mut bridge = Bridge::new();
+    // the bridge takes one msg and answers nothing until the job has finished
+    let (client_tx, mut client_rx) = channel(1);
+    bridge.append_client("client_key", client_tx).await;
+    let react = test_react(bridge, "default", &output_dir).await;
+
+    let params = JobOutputParams {
+        eid: "eid".to_string(),
+        schedule_id: "sid".to_string(),
+        run_id: "run".to_string(),
+        ..Default::default()
+    };
+    let (output_tx, output_rx) = channel(OUTPUT_QUEUE_SIZE);
+    let forward = tokio::spawn(Scheduler::forward_job_output(react, params, output_rx));
+
+    let line = "x".repeat(JOB_OUTPUT_CHUNK_SIZE);
+    let lines = JOB_OUTPUT_QUEUE_CHUNKS * 4;
+    for _ in 0..lines {
+        output_tx.send(line.clone()).await.unwrap();
+    }
+    output_tx.send("tail".to_string()).await.unwrap();
+    drop(output_tx);
+
+    // every chunk has been queued or dropped once the job log holds the whole output
+    let log_path = output_dir.join("eid").join("sid_run.log");
+    let total = (lines * JOB_OUTPUT_CHUNK_SIZE + "tail".len()) as u64;
+    while fs::metadata(&log_path).await.map_or(0, |v| v.len()) < total {
+        sleep(Duration::from_millis(10)).await;
+    }
+
+    let mut output = String::new();
+    while !output.ends_with("tail") {
+        let (msg, resp_tx) = client_rx.recv().await.unwrap();
+        let MsgKind::Request(MsgReqKind::JobOutputRequest(v)) = msg.data else {
+            panic!("unexpected msg {}", msg.id);
+        };
+        resp_tx
+            .unwrap()
+            .send(MsgState::Completed(Value::Null))
+            .await
+            .unwrap();
+        output.push_str(&v.data);
+    }
+    forward.await.unwrap();
+
+    let sent = output.matches('x').count();
+    let dropped: usize = output
+        .split('[')
+        .filter_map(|v| v.split_once(" bytes dropped]"))
+        .map(|v| v.0.parse::<usize>().unwrap())
+        .sum();
+    assert!(dropped > 0);
+    assert_eq!(sent + dropped, lines * JOB_OUTPUT_CHUNK_SIZE);
+
+    // the job log keeps the whole output
+    let log = fs::read_to_string(log_path).await.unwrap();
+    assert_eq!(log.len() as u64, total);
+
+    let _ = fs::remove_dir_all(output_dir).await;
+}
@@ -0,0 +1,101 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::{fs, io::AsyncWriteExt};
+
+use crate::bridge::msg::DispatchJobParams;
+
+/// name of the state file under output_dir
+pub const STATE_FILE: &str = "agent_state.json";
+
+/// timers and supervisors running on the agent, keyed by eid. It is saved on every change
+/// so that the agent can restore them after a restart without the console
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+pub struct AgentState {
+    #[serde(default)]
+    pub timers: HashMap<String, DispatchJobParams>,
+    #[serde(default)]
+    pub supervisors: HashMap<String, DispatchJobParams>,
+    /// last fire time of each timer, the console only catches up the ticks after it
+    #[serde(default)]
+    pub last_ticks: HashMap<String, DateTime<Utc>>,
+}
+
+impl AgentState {
+    /// a missing file is an empty state
+    pub async fn load(path: &Path) -> Result<Self> {
+        match fs::read(path).await {
+            Ok(data) => Ok(serde_json::from_slice(&data)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// the job env may hold decrypted secrets, so the file is only readable by the agent user.
+    /// it is written to a temporary file first and renamed to never leave a partial state
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).await?;
+        }
+        let tmp = path.with_extension("json.tmp");
+
+        let mut opts = fs::OpenOptions::new();
+        opts.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        opts.mode(0o600);
+
+        let mut file = opts.open(&tmp).await?;
+        file.write_all(&serde_json::to_vec(self)?).await?;
+        file.sync_all().await?;
+        fs::rename(&tmp, path).await?;
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn test_agent_state() {
+    use crate::scheduler::types::{BaseJob, JobAction};
+    use nanoid::nanoid;
+
+    let path = std::env::temp_dir()
+        .join(format!("jiascheduler-{}", nanoid!()))
+        .join(STATE_FILE);
+    assert_eq!(
+        AgentState::load(&path).await.unwrap(),
+        AgentState::default()
+    );
+
+    let mut state = AgentState::default();
+    state.timers.insert(
+        "eid".to_string(),
+        DispatchJobParams {
+            base_job: BaseJob {
+                eid: "eid".to_string(),
+                ..Default::default()
+            },
+            schedule_id: "schedule_id".to_string(),
+            instance_id: Some("instance_id".to_string()),
+            fields: None,
+            timer_expr: Some("0 * * * * *".to_string()),
+            timer_options: Default::default(),
+            restart_interval: None,
+            supervisor_options: Default::default(),
+            is_sync: false,
+            created_user: "admin".to_string(),
+            action: JobAction::StartTimer,
+            schedule_type: None,
+        },
+    );
+    state.save(&path).await.unwrap();
+    assert_eq!(AgentState::load(&path).await.unwrap(), state);
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&path).await.unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+    fs::remove_dir_all(path.parent().unwrap()).await.unwrap();
+}
@@ -1,6 +1,15 @@
-use std::{collections::HashMap, fmt, process::Output};
+use std::{
+    collections::HashMap,
+    fmt,
+    process::{ExitStatus, Output},
+    time::Duration,
+};
 
-use anyhow::anyhow;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveDateTime, Utc};
+use chrono_tz::Tz;
+use croner::Cron;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Copy)]
@@ -73,6 +82,7 @@ pub enum RunStatus {
     #[default]
     Prepare,
     Running,
+    Retrying,
     Stop,
 }
 
@@ -81,6 +91,7 @@ impl fmt::Display for RunStatus {
         match self {
             RunStatus::Prepare => write!(f, "prepare"),
             RunStatus::Running => write!(f, "running"),
+            RunStatus::Retrying => write!(f, "retrying"),
             RunStatus::Stop => write!(f, "stop"),
         }
     }
@@ -94,6 +105,8 @@ pub enum ScheduleStatus {
     Unsupervised,
     Scheduling,
     Unscheduled,
+    /// the daemon restarted more than max_restarts times within the restart window
+    Fatal,
 }
 
 impl fmt::Display for ScheduleStatus {
@@ -104,6 +117,7 @@ impl fmt::Display for ScheduleStatus {
             ScheduleStatus::Unscheduled => write!(f, "unscheduled"),
             ScheduleStatus::Supervising => write!(f, "supervising"),
             ScheduleStatus::Unsupervised => write!(f, "unsupervised"),
+            ScheduleStatus::Fatal => write!(f, "fatal"),
         }
     }
 }
@@ -122,6 +136,14 @@ pub struct BaseJob {
     pub work_user: Option<String>,
     pub max_retry: u8,
     pub max_parallel: u8,
+    #[serde(default)]
+    pub retry_interval: u64,
+    #[serde(default)]
+    pub retry_backoff: RetryBackoff,
+    #[serde(default)]
+    pub parallel_policy: ParallelPolicy,
+    #[serde(default)]
+    pub env: Vec<EnvVar>,
 }
 
 impl BaseJob {
@@ -140,8 +162,698 @@ impl BaseJob {
             work_user: self.work_user.clone(),
             max_retry: self.max_retry,
             max_parallel: self.max_parallel,
+            retry_interval: self.retry_interval,
+            retry_backoff: self.retry_backoff,
+            parallel_policy: self.parallel_policy,
+            env: vec![],
         }
     }
+
+    /// replace every secret env value in output with the mask
+    pub fn mask_secrets(&self, output: &[u8]) -> Vec<u8> {
+        let mut output = output.to_vec();
+        for v in self
+            .env
+            .iter()
+            .filter(|v| v.is_secret && !v.value.is_empty())
+        {
+            output = replace_bytes(&output, v.value.as_bytes(), SECRET_MASK.as_bytes());
+        }
+        output
+    }
+
+    /// delay before the next attempt after the given failed attempt
+    pub fn retry_delay(&self, attempt: u32) -> Duration {
+        let secs = match self.retry_backoff {
+            RetryBackoff::Fixed => self.retry_interval,
+            RetryBackoff::Exponential => self
+                .retry_interval
+                .saturating_mul(1u64 << attempt.saturating_sub(1).min(16)),
+        };
+        Duration::from_secs(secs.min(MAX_RETRY_DELAY_SECS))
+    }
+}
+
+/// upper bound of the delay between two attempts
+const MAX_RETRY_DELAY_SECS: u64 = 3600;
+
+/// placeholder shown instead of a secret value
+pub const SECRET_MASK: &str = "******";
+
+#[derive(Default, Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct EnvVar {
+    pub name: String,
+    pub value: String,
+    #[serde(default)]
+    pub is_secret: bool,
+}
+
+fn replace_bytes(haystack: &[u8], needle: &[u8], replacement: &[u8]) -> Vec<u8> {
+    let mut ret = Vec::with_capacity(haystack.len());
+    let mut i = 0;
+    while i < haystack.len() {
+        if haystack[i..].starts_with(needle) {
+            ret.extend_from_slice(replacement);
+            i += needle.len();
+        } else {
+            ret.push(haystack[i]);
+            i += 1;
+        }
+    }
+    ret
+}
+
+/// masks the secrets of a job in output which arrives in chunks, the tail which may be the
+/// beginning of a secret is held back until the next chunk so that a split secret is masked too
+pub struct SecretMasker {
+    job: BaseJob,
+    max_len: usize,
+    pending: String,
+}
+
+impl SecretMasker {
+    pub fn new(job: BaseJob) -> Self {
+        let max_len = job
+            .env
+            .iter()
+            .filter(|v| v.is_secret)
+            .map(|v| v.value.len())
+            .max()
+            .unwrap_or_default();
+        Self {
+            job,
+            max_len,
+            pending: String::new(),
+        }
+    }
+
+    /// masked output which is safe to emit after appending the chunk
+    pub fn push(&mut self, chunk: &str) -> String {
+        self.pending.push_str(chunk);
+        if self.max_len == 0 {
+            return std::mem::take(&mut self.pending);
+        }
+
+        let bytes = self.pending.as_bytes();
+        let mut split = bytes.len().saturating_sub(self.max_len - 1);
+        // a secret starting before the split point but crossing it is held back as a whole
+        let needles = self
+            .job
+            .env
+            .iter()
+            .filter(|v| v.is_secret && !v.value.is_empty())
+            .map(|v| v.value.as_bytes())
+            .collect::<Vec<_>>();
+        while let Some(i) = needles.iter().find_map(|needle| {
+            (split.saturating_sub(needle.len() - 1)..split)
+                .find(|&i| i + needle.len() > split && bytes[i..].starts_with(needle))
+        }) {
+            split = i;
+        }
+        while !self.pending.is_char_boundary(split) {
+            split -= 1;
+        }
+
+        let rest = self.pending.split_off(split);
+        let ready = std::mem::replace(&mut self.pending, rest);
+        String::from_utf8_lossy(&self.job.mask_secrets(ready.as_bytes())).to_string()
+    }
+
+    /// masked output held back so far, called once the output is closed
+    pub fn finish(&mut self) -> String {
+        let rest = std::mem::take(&mut self.pending);
+        String::from_utf8_lossy(&self.job.mask_secrets(rest.as_bytes())).to_string()
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Copy, Default)]
+pub enum RetryBackoff {
+    #[default]
+    Fixed,
+    Exponential,
+}
+
+impl TryFrom<&str> for RetryBackoff {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let backoff = match value {
+            "fixed" => RetryBackoff::Fixed,
+            "exponential" => RetryBackoff::Exponential,
+            _ => return Err(anyhow!("invalid retry backoff {value}")),
+        };
+        Ok(backoff)
+    }
+}
+
+impl fmt::Display for RetryBackoff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RetryBackoff::Fixed => write!(f, "fixed"),
+            RetryBackoff::Exponential => write!(f, "exponential"),
+        }
+    }
+}
+
+/// what to do with a new run when the job already has max_parallel runs
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Copy, Default)]
+pub enum ParallelPolicy {
+    /// drop the new run
+    #[default]
+    Skip,
+    /// wait until a running one finished
+    Queue,
+    /// kill the oldest running one and start the new run
+    Replace,
+}
+
+impl TryFrom<&str> for ParallelPolicy {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let policy = match value {
+            "skip" => ParallelPolicy::Skip,
+            "queue" => ParallelPolicy::Queue,
+            "replace" => ParallelPolicy::Replace,
+            _ => return Err(anyhow!("invalid parallel policy {value}")),
+        };
+        Ok(policy)
+    }
+}
+
+impl fmt::Display for ParallelPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParallelPolicy::Skip => write!(f, "skip"),
+            ParallelPolicy::Queue => write!(f, "queue"),
+            ParallelPolicy::Replace => write!(f, "replace"),
+        }
+    }
+}
+
+/// what to do with the ticks of a timer missed while its agent was offline
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Copy, Default)]
+pub enum MisfirePolicy {
+    /// only record the missed ticks
+    #[default]
+    Skip,
+    /// run once for all the missed ticks
+    RunOnce,
+    /// run for every missed tick, up to misfire_limit runs
+    RunAll,
+}
+
+impl TryFrom<&str> for MisfirePolicy {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let policy = match value {
+            "skip" => MisfirePolicy::Skip,
+            "run_once" => MisfirePolicy::RunOnce,
+            "run_all" => MisfirePolicy::RunAll,
+            _ => return Err(anyhow!("invalid misfire policy {value}")),
+        };
+        Ok(policy)
+    }
+}
+
+impl fmt::Display for MisfirePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MisfirePolicy::Skip => write!(f, "skip"),
+            MisfirePolicy::RunOnce => write!(f, "run_once"),
+            MisfirePolicy::RunAll => write!(f, "run_all"),
+        }
+    }
+}
+
+/// when a supervised daemon is started again after it exited
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Copy, Default)]
+pub enum RestartPolicy {
+    #[default]
+    Always,
+    /// restart unless the exit code is an expected one
+    OnFailure,
+    Never,
+}
+
+impl TryFrom<&str> for RestartPolicy {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let policy = match value {
+            "always" => RestartPolicy::Always,
+            "on_failure" => RestartPolicy::OnFailure,
+            "never" => RestartPolicy::Never,
+            _ => return Err(anyhow!("invalid restart policy {value}")),
+        };
+        Ok(policy)
+    }
+}
+
+impl fmt::Display for RestartPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RestartPolicy::Always => write!(f, "always"),
+            RestartPolicy::OnFailure => write!(f, "on_failure"),
+            RestartPolicy::Never => write!(f, "never"),
+        }
+    }
+}
+
+/// settings of a supervisor besides its restart interval
+#[derive(Default, Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct SupervisorOptions {
+    #[serde(default)]
+    pub restart_policy: RestartPolicy,
+    /// exit codes on_failure does not restart on, only 0 when empty
+    #[serde(default)]
+    pub expected_exit_codes: Vec<i32>,
+    #[serde(default)]
+    pub restart_backoff: RetryBackoff,
+    /// upper bound of the restart delay in seconds, 0 for the default bound
+    #[serde(default)]
+    pub max_restart_delay: u64,
+    /// the daemon becomes fatal after this many restarts within restart_window, 0 means unlimited
+    #[serde(default)]
+    pub max_restarts: u32,
+    /// in seconds, 0 counts every restart since the supervisor started
+    #[serde(default)]
+    pub restart_window: u64,
+    /// the daemon is killed when the probe fails failure_threshold times in a row
+    #[serde(default)]
+    pub liveness_probe: Option<LivenessProbe>,
+}
+
+impl SupervisorOptions {
+    /// whether the daemon is started again after it exited with `exit_code`
+    pub fn should_restart(&self, exit_code: Option<i32>) -> bool {
+        match self.restart_policy {
+            RestartPolicy::Always => true,
+            RestartPolicy::Never => false,
+            RestartPolicy::OnFailure => match exit_code {
+                Some(code) if self.expected_exit_codes.is_empty() => code != 0,
+                Some(code) => !self.expected_exit_codes.contains(&code),
+                None => true,
+            },
+        }
+    }
+
+    /// delay before the restart, `restarts` is the number of restarts within the window
+    pub fn restart_delay(&self, interval: Duration, restarts: usize) -> Duration {
+        let max_delay = Duration::from_secs(match self.max_restart_delay {
+            0 => MAX_RETRY_DELAY_SECS,
+            v => v,
+        });
+        let delay = match self.restart_backoff {
+            RetryBackoff::Fixed => interval,
+            RetryBackoff::Exponential => interval.saturating_mul(1u32 << restarts.min(16)),
+        };
+        delay.min(max_delay)
+    }
+
+    /// whether one more restart is over the limit
+    pub fn is_fatal(&self, restarts: usize) -> bool {
+        self.max_restarts > 0 && restarts >= self.max_restarts as usize
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Copy, Default)]
+pub enum ProbeType {
+    /// GET the url, healthy when the response has the expected status
+    #[default]
+    Http,
+    /// healthy when host:port accepts a connection
+    Tcp,
+    /// healthy when the command exits with 0
+    Exec,
+}
+
+impl TryFrom<&str> for ProbeType {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let probe_type = match value {
+            "http" => ProbeType::Http,
+            "tcp" => ProbeType::Tcp,
+            "exec" => ProbeType::Exec,
+            _ => return Err(anyhow!("invalid probe type {value}")),
+        };
+        Ok(probe_type)
+    }
+}
+
+impl fmt::Display for ProbeType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ProbeType::Http => write!(f, "http"),
+            ProbeType::Tcp => write!(f, "tcp"),
+            ProbeType::Exec => write!(f, "exec"),
+        }
+    }
+}
+
+/// a check run periodically against a supervised daemon
+#[derive(Default, Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct LivenessProbe {
+    pub probe_type: ProbeType,
+    /// url of an http probe, host:port of a tcp probe or command line of an exec probe
+    pub target: String,
+    /// status expected by an http probe, 200 when 0
+    #[serde(default)]
+    pub expected_status: u16,
+    /// seconds between two probes
+    pub interval: u64,
+    /// seconds a probe may take before it fails
+    pub timeout: u64,
+    /// failures in a row before the daemon is killed
+    pub failure_threshold: u32,
+    /// seconds to wait after the daemon started before the first probe
+    #[serde(default)]
+    pub initial_delay: u64,
+}
+
+/// outcome of the last liveness probe of a daemon
+#[derive(Default, Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct ProbeResult {
+    pub healthy: bool,
+    /// failures in a row
+    pub failures: u32,
+    pub message: String,
+    pub time: DateTime<Utc>,
+    /// whether the daemon was killed because of this failure
+    #[serde(default)]
+    pub killed: bool,
+}
+
+/// how the start of each timer run is delayed so that many instances do not fire at once
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Copy, Default)]
+pub enum JitterMode {
+    /// start on the tick
+    #[default]
+    None,
+    /// a random delay per run, up to jitter_secs
+    Random,
+    /// a fixed delay per instance derived from the instance id, up to jitter_secs
+    Spread,
+}
+
+impl TryFrom<&str> for JitterMode {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        let mode = match value {
+            "none" => JitterMode::None,
+            "random" => JitterMode::Random,
+            "spread" => JitterMode::Spread,
+            _ => return Err(anyhow!("invalid jitter mode {value}")),
+        };
+        Ok(mode)
+    }
+}
+
+impl fmt::Display for JitterMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JitterMode::None => write!(f, "none"),
+            JitterMode::Random => write!(f, "random"),
+            JitterMode::Spread => write!(f, "spread"),
+        }
+    }
+}
+
+/// settings of a timer besides its cron expression
+#[derive(Default, Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct TimerOptions {
+    #[serde(default)]
+    pub misfire_policy: MisfirePolicy,
+    #[serde(default)]
+    pub misfire_limit: u32,
+    /// IANA name of the time zone the timer expr follows, empty for the local zone of the agent
+    #[serde(default)]
+    pub timezone: String,
+    /// runs outside the calendar are skipped, it also applies to a manual exec
+    #[serde(default)]
+    pub calendar: Option<TimerCalendar>,
+    #[serde(default)]
+    pub jitter_mode: JitterMode,
+    /// upper bound of the start delay in seconds
+    #[serde(default)]
+    pub jitter_secs: u64,
+}
+
+impl TimerOptions {
+    /// number of catch-up runs for the missed ticks
+    pub fn catch_up_runs(&self, missed: usize) -> usize {
+        match self.misfire_policy {
+            MisfirePolicy::Skip => 0,
+            MisfirePolicy::RunOnce => missed.min(1),
+            MisfirePolicy::RunAll => missed.min(self.misfire_limit.max(1) as usize),
+        }
+    }
+
+    /// delay of a run after its tick on the instance
+    pub fn start_delay(&self, instance_id: &str) -> Duration {
+        let max_millis = self.jitter_secs * 1000;
+        if max_millis == 0 {
+            return Duration::ZERO;
+        }
+        let millis = match self.jitter_mode {
+            JitterMode::None => 0,
+            JitterMode::Random => rand::thread_rng().gen_range(0..=max_millis),
+            // FNV-1a keeps the offset of an instance stable across agent versions and restarts
+            JitterMode::Spread => {
+                let hash = instance_id.bytes().fold(0xcbf29ce484222325u64, |h, b| {
+                    (h ^ b as u64).wrapping_mul(0x100000001b3)
+                });
+                hash % (max_millis + 1)
+            }
+        };
+        Duration::from_millis(millis)
+    }
+}
+
+/// days and windows in which a job may run, the dates are in the time zone of the timer
+#[derive(Default, Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct TimerCalendar {
+    /// id of the calendar in the console, 0 in the timers dispatched before calendars had one
+    #[serde(default)]
+    pub id: u64,
+    pub name: String,
+    /// weekdays allowed with Sunday as 0, empty means every day
+    #[serde(default)]
+    pub weekdays: Vec<u32>,
+    /// dates allowed whatever the weekday, such as a working Saturday
+    #[serde(default)]
+    pub include_dates: Vec<NaiveDate>,
+    /// dates never allowed, such as holidays
+    #[serde(default)]
+    pub exclude_dates: Vec<NaiveDate>,
+    /// maintenance freezes, nothing runs from start until end
+    #[serde(default)]
+    pub freeze_windows: Vec<FreezeWindow>,
+}
+
+#[derive(Default, Serialize, Deserialize, PartialEq, Debug, Clone)]
+pub struct FreezeWindow {
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+}
+
+impl TimerCalendar {
+    /// whether a run at `time` is allowed, an exclusion wins over an inclusion
+    pub fn allows(&self, time: DateTime<Utc>, timezone: &str) -> bool {
+        let time = match parse_timezone(timezone).ok().flatten() {
+            Some(tz) => time.with_timezone(&tz).naive_local(),
+            None => time.with_timezone(&Local).naive_local(),
+        };
+        let date = time.date();
+
+        if self.exclude_dates.contains(&date)
+            || self
+                .freeze_windows
+                .iter()
+                .any(|v| v.start <= time && time < v.end)
+        {
+            return false;
+        }
+        self.include_dates.contains(&date)
+            || self.weekdays.is_empty()
+            || self
+                .weekdays
+                .contains(&date.weekday().num_days_from_sunday())
+    }
+}
+
+/// prefix of a fixed interval timer, such as @every 90s
+pub const EVERY_PREFIX: &str = "@every";
+
+/// parse the timer expr the same way as the cron scheduler of the agent
+pub fn parse_cron(timer_expr: &str) -> Result<Cron> {
+    Cron::new(timer_expr)
+        .with_seconds_required()
+        .with_dom_and_dow()
+        .parse()
+        .map_err(|e| anyhow!("failed parse timer expr {timer_expr} - {e}"))
+}
+
+/// interval of an @every timer such as @every 90s or @every 1h30m, None for other exprs
+pub fn parse_interval(timer_expr: &str) -> Result<Option<Duration>> {
+    let Some(value) = timer_expr.trim().strip_prefix(EVERY_PREFIX) else {
+        return Ok(None);
+    };
+    let value = value.trim();
+    let invalid = || anyhow!("invalid interval {value}, use such as 90s or 1h30m");
+
+    let mut secs = 0u64;
+    let mut num = String::new();
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            num.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            _ => return Err(invalid()),
+        };
+        secs = num
+            .parse::<u64>()
+            .ok()
+            .and_then(|v| v.checked_mul(unit))
+            .and_then(|v| v.checked_add(secs))
+            .ok_or_else(invalid)?;
+        num.clear();
+    }
+    if !num.is_empty() || secs == 0 {
+        return Err(invalid());
+    }
+    Ok(Some(Duration::from_secs(secs)))
+}
+
+/// normalize a timer expr into the form scheduled by the agent. Standard 5-field cron,
+/// 6-field cron with seconds, Quartz cron, macros such as @hourly and intervals such as
+/// @every 90s are accepted. The result is either a 6-field cron with seconds whose weekdays
+/// start from Sunday as 0, or @every with the interval in seconds
+pub fn normalize_timer_expr(timer_expr: &str) -> Result<String> {
+    let timer_expr = timer_expr.trim();
+    if let Some(interval) = parse_interval(timer_expr)? {
+        return Ok(format!("{EVERY_PREFIX} {}s", interval.as_secs()));
+    }
+
+    let normalized = match timer_expr.to_lowercase().as_str() {
+        "@yearly" | "@annually" => "0 0 0 1 1 *".to_string(),
+        "@monthly" => "0 0 0 1 * *".to_string(),
+        "@weekly" => "0 0 0 * * 0".to_string(),
+        "@daily" | "@midnight" => "0 0 0 * * *".to_string(),
+        "@hourly" => "0 0 * * * *".to_string(),
+        _ => {
+            let fields: Vec<&str> = timer_expr.split_whitespace().collect();
+            match fields.len() {
+                5 => format!("0 {}", fields.join(" ")),
+                // only Quartz uses ? for the day it does not care about
+                6 if timer_expr.contains('?') => quartz_to_cron(&fields)?,
+                6 => fields.join(" "),
+                7 => quartz_to_cron(&fields)?,
+                _ => anyhow::bail!("timer expr {timer_expr} must have 5, 6 or 7 fields"),
+            }
+        }
+    };
+    parse_cron(&normalized)?;
+    Ok(normalized)
+}
+
+/// Quartz numbers the weekdays from Sunday as 1 and may end with a year
+fn quartz_to_cron(fields: &[&str]) -> Result<String> {
+    if let Some(year) = fields.get(6).filter(|v| !matches!(**v, "*" | "?")) {
+        anyhow::bail!("the year field {year} is not supported");
+    }
+    let mut fields: Vec<String> = fields[..6].iter().map(|v| v.replace('?', "*")).collect();
+    fields[5] = quartz_weekdays(&fields[5])?;
+    Ok(fields.join(" "))
+}
+
+fn quartz_weekdays(field: &str) -> Result<String> {
+    let shift = |v: &str| match v.parse::<u8>() {
+        Ok(n @ 1..=7) => Ok((n - 1).to_string()),
+        Ok(n) => Err(anyhow!("invalid weekday {n}, Quartz weekdays are 1-7")),
+        // * and names such as MON mean the same in both forms
+        Err(_) => Ok(v.to_string()),
+    };
+
+    let items = field
+        .split(',')
+        .map(|item| {
+            let (value, step) = match item.split_once('/') {
+                Some((v, step)) => (v, Some(step)),
+                None => (item, None),
+            };
+            let value = if let Some((day, nth)) = value.split_once('#') {
+                format!("{}#{nth}", shift(day)?)
+            } else if let Some(day) = value.strip_suffix('L').filter(|v| !v.is_empty()) {
+                format!("{}#L", shift(day)?)
+            } else if let Some((start, end)) = value.split_once('-') {
+                format!("{}-{}", shift(start)?, shift(end)?)
+            } else {
+                shift(value)?
+            };
+            Ok(match step {
+                Some(step) => format!("{value}/{step}"),
+                None => value,
+            })
+        })
+        .collect::<Result<Vec<String>>>()?;
+    Ok(items.join(","))
+}
+
+/// parse an IANA time zone name, an empty name means the local zone
+pub fn parse_timezone(name: &str) -> Result<Option<Tz>> {
+    if name.is_empty() {
+        return Ok(None);
+    }
+    name.parse::<Tz>()
+        .map(Some)
+        .map_err(|_| anyhow!("invalid timezone {name}"))
+}
+
+/// ticks of a normalized timer expr after `after`
+pub fn ticks_after(
+    timer_expr: &str,
+    timezone: &str,
+    after: DateTime<Utc>,
+) -> Result<Box<dyn Iterator<Item = DateTime<Utc>>>> {
+    if let Some(interval) = parse_interval(timer_expr)? {
+        let interval = chrono::Duration::from_std(interval)?;
+        return Ok(Box::new((1..).map(move |n| after + interval * n)));
+    }
+
+    let cron = parse_cron(timer_expr)?;
+    Ok(match parse_timezone(timezone)? {
+        Some(tz) => Box::new(
+            cron.iter_after(after.with_timezone(&tz))
+                .map(|v| v.with_timezone(&Utc)),
+        ),
+        None => Box::new(
+            cron.iter_after(after.with_timezone(&Local))
+                .map(|v| v.with_timezone(&Utc)),
+        ),
+    })
+}
+
+/// ticks of the timer after `after` and before `until`, at most `limit` of them
+pub fn missed_ticks(
+    timer_expr: &str,
+    timezone: &str,
+    after: DateTime<Utc>,
+    until: DateTime<Utc>,
+    limit: usize,
+) -> Result<Vec<DateTime<Utc>>> {
+    Ok(ticks_after(timer_expr, timezone, after)?
+        .take_while(|v| *v < until)
+        .take(limit)
+        .collect())
 }
 
 #[derive(Default, Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -193,43 +905,135 @@ impl fmt::Display for ScheduleType {
     }
 }
 
+/// why a run ended, the exit code alone cannot tell a timeout from an oom kill
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum TerminationReason {
+    /// the process exited by itself
+    Exited,
+    /// killed after running longer than the job timeout
+    Timeout,
+    /// killed by a kill action of the user
+    KilledByUser,
+    /// killed by a signal which was not sent by the scheduler, e.g. the oom killer
+    KilledBySignal(i32),
+    /// the process could not be started
+    SpawnFailed,
+    /// the agent failed to run the job
+    AgentError,
+    /// killed to make room for a newer run of a job with the replace parallel policy
+    Replaced,
+    /// not started because the job already had max_parallel runs
+    Skipped,
+    /// killed by the supervisor after the daemon failed its liveness probes
+    LivenessFailed,
+}
+
+impl TerminationReason {
+    pub fn from_status(status: &ExitStatus) -> Self {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::ExitStatusExt;
+            if let Some(signal) = status.signal() {
+                return TerminationReason::KilledBySignal(signal);
+            }
+        }
+        let _ = status;
+        TerminationReason::Exited
+    }
+
+    /// the reason without the signal number
+    pub fn name(&self) -> &'static str {
+        match self {
+            TerminationReason::Exited => "exited",
+            TerminationReason::Timeout => "timeout",
+            TerminationReason::KilledByUser => "killed_by_user",
+            TerminationReason::KilledBySignal(_) => "killed_by_signal",
+            TerminationReason::SpawnFailed => "spawn_failed",
+            TerminationReason::AgentError => "agent_error",
+            TerminationReason::Replaced => "replaced",
+            TerminationReason::Skipped => "skipped",
+            TerminationReason::LivenessFailed => "liveness_failed",
+        }
+    }
+
+    pub fn signal(&self) -> Option<i32> {
+        match self {
+            TerminationReason::KilledBySignal(v) => Some(*v),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for TerminationReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TerminationReason::KilledBySignal(v) => write!(f, "killed_by_signal({v})"),
+            v => write!(f, "{}", v.name()),
+        }
+    }
+}
+
+/// exit code of the process, a process killed by a signal gets 128 + signal like a shell does
+pub fn get_exit_code(status: &ExitStatus) -> Option<i32> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        status.code().or(status.signal().map(|v| 128 + v))
+    }
+    #[cfg(windows)]
+    status.code()
+}
+
 pub enum BundleOutput {
-    Output(Output),
-    Bundle(HashMap<String, Output>),
+    Output(Output, TerminationReason),
+    Bundle(HashMap<String, (Output, TerminationReason)>),
 }
 
 impl BundleOutput {
     pub fn get_exit_status(&self) -> Option<String> {
         match self {
-            BundleOutput::Output(v) => Some(v.status.to_string()),
+            BundleOutput::Output(v, _) => Some(v.status.to_string()),
             BundleOutput::Bundle(_) => None,
         }
     }
 
+    pub fn is_success(&self) -> bool {
+        match self {
+            BundleOutput::Output(v, _) => v.status.success(),
+            BundleOutput::Bundle(v) => v.values().all(|(o, _)| o.status.success()),
+        }
+    }
+
     pub fn get_exit_code(&self) -> Option<i32> {
         match self {
-            BundleOutput::Output(v) => {
-                if v.status.success() {
-                    v.status.code()
-                } else {
-                    // killed, return 9
-                    v.status.code().or(Some(9))
-                }
-            }
+            BundleOutput::Output(v, _) => get_exit_code(&v.status),
             BundleOutput::Bundle(_) => None,
         }
     }
 
+    /// the reason of a bundle is the first script that did not exit by itself
+    pub fn get_termination_reason(&self) -> TerminationReason {
+        match self {
+            BundleOutput::Output(_, reason) => *reason,
+            BundleOutput::Bundle(v) => v
+                .values()
+                .map(|(_, reason)| *reason)
+                .find(|v| *v != TerminationReason::Exited)
+                .unwrap_or(TerminationReason::Exited),
+        }
+    }
+
     pub fn get_stdout(&self) -> Option<String> {
         match self {
-            BundleOutput::Output(v) => Some(String::from_utf8_lossy(&v.stdout).to_string()),
+            BundleOutput::Output(v, _) => Some(String::from_utf8_lossy(&v.stdout).to_string()),
             BundleOutput::Bundle(_) => None,
         }
     }
 
     pub fn get_stderr(&self) -> Option<String> {
         match self {
-            BundleOutput::Output(v) => Some(String::from_utf8_lossy(&v.stderr).to_string()),
+            BundleOutput::Output(v, _) => Some(String::from_utf8_lossy(&v.stderr).to_string()),
             BundleOutput::Bundle(_) => None,
         }
     }
@@ -275,3 +1079,328 @@ impl AssignUserOption {
         }
     }
 }
+
+/// parse labels such as env=prod,role=web, also used as a tag selector by the console
+pub fn parse_labels(labels: &str) -> Result<Vec<(String, String)>> {
+    labels
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(|v| match v.split_once('=') {
+            Some((key, val)) if !key.trim().is_empty() && !val.trim().is_empty() => {
+                Ok((key.trim().to_string(), val.trim().to_string()))
+            }
+            _ => anyhow::bail!("invalid label {v}, expected key=value"),
+        })
+        .collect()
+}
+
+#[cfg(unix)]
+#[test]
+fn test_termination_reason() {
+    use std::os::unix::process::ExitStatusExt;
+
+    let status = ExitStatus::from_raw(9);
+    assert_eq!(
+        TerminationReason::from_status(&status),
+        TerminationReason::KilledBySignal(9)
+    );
+    assert_eq!(get_exit_code(&status), Some(137));
+    assert_eq!(
+        TerminationReason::KilledBySignal(9).to_string(),
+        "killed_by_signal(9)"
+    );
+
+    let status = ExitStatus::from_raw(2 << 8);
+    assert_eq!(
+        TerminationReason::from_status(&status),
+        TerminationReason::Exited
+    );
+    assert_eq!(get_exit_code(&status), Some(2));
+}
+
+#[test]
+fn test_missed_ticks() {
+    use chrono::TimeZone;
+
+    let after = Local
+        .with_ymd_and_hms(2024, 1, 31, 23, 59, 0)
+        .unwrap()
+        .to_utc();
+    let until = Local
+        .with_ymd_and_hms(2024, 2, 1, 0, 3, 30)
+        .unwrap()
+        .to_utc();
+    let ticks = missed_ticks("0 * * * * *", "", after, until, 100).unwrap();
+    assert_eq!(ticks.len(), 4);
+    assert_eq!(
+        ticks[0],
+        Local
+            .with_ymd_and_hms(2024, 2, 1, 0, 0, 0)
+            .unwrap()
+            .to_utc()
+    );
+    assert_eq!(
+        missed_ticks("0 * * * * *", "", after, until, 2)
+            .unwrap()
+            .len(),
+        2
+    );
+    assert!(missed_ticks(
+        "0 0 0 1 * *",
+        "",
+        until,
+        until + chrono::Duration::days(1),
+        10
+    )
+    .unwrap()
+    .is_empty());
+    assert!(missed_ticks("invalid", "", after, until, 10).is_err());
+
+    let after = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+    let ticks = missed_ticks(
+        "0 0 9 * * *",
+        "Asia/Shanghai",
+        after,
+        after + chrono::Duration::days(1),
+        10,
+    )
+    .unwrap();
+    assert_eq!(
+        ticks,
+        vec![Utc.with_ymd_and_hms(2024, 1, 1, 1, 0, 0).unwrap()]
+    );
+    assert!(missed_ticks("0 0 9 * * *", "Mars/Olympus", after, until, 10).is_err());
+
+    let mut opts = TimerOptions::default();
+    assert_eq!(opts.catch_up_runs(4), 0);
+    opts.misfire_policy = MisfirePolicy::RunOnce;
+    assert_eq!(opts.catch_up_runs(4), 1);
+    assert_eq!(opts.catch_up_runs(0), 0);
+    opts.misfire_policy = MisfirePolicy::RunAll;
+    opts.misfire_limit = 3;
+    assert_eq!(opts.catch_up_runs(4), 3);
+    assert_eq!(opts.catch_up_runs(2), 2);
+}
+
+#[test]
+fn test_normalize_timer_expr() {
+    let cases = [
+        ("*/5 * * * *", "0 */5 * * * *"),
+        ("30 0 9 * * 1-5", "30 0 9 * * 1-5"),
+        ("0 15 10 ? * MON-FRI", "0 15 10 * * MON-FRI"),
+        ("0 0 12 ? * 2-6 *", "0 0 12 * * 1-5"),
+        ("0 0 12 ? * 1,7", "0 0 12 * * 0,6"),
+        ("0 0 12 ? * 6#3", "0 0 12 * * 5#3"),
+        ("0 0 12 ? * 6L", "0 0 12 * * 5#L"),
+        ("@hourly", "0 0 * * * *"),
+        ("@WEEKLY", "0 0 0 * * 0"),
+        ("@every 90s", "@every 90s"),
+        ("@every 1h30m", "@every 5400s"),
+    ];
+    for (expr, normalized) in cases {
+        assert_eq!(normalize_timer_expr(expr).unwrap(), normalized, "{expr}");
+    }
+
+    for expr in [
+        "",
+        "* * * *",
+        "0 0 12 ? * 8",
+        "0 0 12 ? * * 2030",
+        "@every",
+        "@every 0s",
+        "@every 10x",
+        "@fortnightly",
+    ] {
+        assert!(normalize_timer_expr(expr).is_err(), "{expr}");
+    }
+
+    assert_eq!(
+        parse_interval("@every 2d").unwrap(),
+        Some(Duration::from_secs(172800))
+    );
+    assert_eq!(parse_interval("0 * * * * *").unwrap(), None);
+
+    let after = chrono::DateTime::from_timestamp(0, 0).unwrap();
+    let ticks: Vec<_> = ticks_after("@every 90s", "", after)
+        .unwrap()
+        .take(2)
+        .collect();
+    assert_eq!(
+        ticks,
+        vec![
+            after + chrono::Duration::seconds(90),
+            after + chrono::Duration::seconds(180)
+        ]
+    );
+}
+
+#[test]
+fn test_timer_calendar() {
+    use chrono::TimeZone;
+
+    let date = |d: &str| d.parse::<NaiveDate>().unwrap();
+    let calendar = TimerCalendar {
+        id: 1,
+        name: "workday".to_string(),
+        weekdays: vec![1, 2, 3, 4, 5],
+        include_dates: vec![date("2024-10-12")],
+        exclude_dates: vec![date("2024-10-01")],
+        freeze_windows: vec![FreezeWindow {
+            start: "2024-10-08T22:00:00".parse().unwrap(),
+            end: "2024-10-09T02:00:00".parse().unwrap(),
+        }],
+    };
+    let at = |y, m, d, h| Utc.with_ymd_and_hms(y, m, d, h, 0, 0).unwrap();
+
+    // 2024-09-30 is a Monday, the 1st of October a holiday and the 12th a working Saturday
+    assert!(calendar.allows(at(2024, 9, 30, 12), "UTC"));
+    assert!(!calendar.allows(at(2024, 10, 1, 12), "UTC"));
+    assert!(!calendar.allows(at(2024, 10, 6, 12), "UTC"));
+    assert!(calendar.allows(at(2024, 10, 12, 12), "UTC"));
+    assert!(!calendar.allows(at(2024, 10, 8, 23), "UTC"));
+    assert!(calendar.allows(at(2024, 10, 9, 2), "UTC"));
+    // 2024-09-30 20:00 UTC is already the holiday in Shanghai
+    assert!(!calendar.allows(at(2024, 9, 30, 20), "Asia/Shanghai"));
+    assert!(TimerCalendar::default().allows(at(2024, 10, 6, 12), "UTC"));
+}
+
+#[test]
+fn test_start_delay() {
+    let mut options = TimerOptions {
+        jitter_mode: JitterMode::Spread,
+        jitter_secs: 60,
+        ..Default::default()
+    };
+    let delay = options.start_delay("instance-1");
+    assert!(delay <= Duration::from_secs(60));
+    assert_eq!(delay, options.start_delay("instance-1"));
+    assert_ne!(delay, options.start_delay("instance-2"));
+
+    options.jitter_mode = JitterMode::Random;
+    assert!((0..100).all(|_| options.start_delay("instance-1") <= Duration::from_secs(60)));
+
+    options.jitter_secs = 0;
+    assert_eq!(options.start_delay("instance-1"), Duration::ZERO);
+    assert_eq!(
+        TimerOptions::default().start_delay("instance-1"),
+        Duration::ZERO
+    );
+}
+
+#[test]
+fn test_supervisor_options() {
+    let mut options = SupervisorOptions {
+        restart_policy: RestartPolicy::OnFailure,
+        expected_exit_codes: vec![0, 2],
+        restart_backoff: RetryBackoff::Exponential,
+        max_restart_delay: 10,
+        max_restarts: 3,
+        ..Default::default()
+    };
+    assert!(!options.should_restart(Some(2)));
+    assert!(options.should_restart(Some(1)));
+    assert!(options.should_restart(None));
+
+    let interval = Duration::from_secs(1);
+    assert_eq!(options.restart_delay(interval, 0), Duration::from_secs(1));
+    assert_eq!(options.restart_delay(interval, 3), Duration::from_secs(8));
+    assert_eq!(options.restart_delay(interval, 20), Duration::from_secs(10));
+    assert!(!options.is_fatal(2));
+    assert!(options.is_fatal(3));
+
+    options.expected_exit_codes.clear();
+    assert!(!options.should_restart(Some(0)));
+    options.restart_policy = RestartPolicy::Never;
+    assert!(!options.should_restart(Some(1)));
+
+    let options = SupervisorOptions::default();
+    assert!(options.should_restart(Some(0)));
+    assert_eq!(options.restart_delay(interval, 5), interval);
+    assert!(!options.is_fatal(1000));
+}
+
+#[test]
+fn test_parse_labels() {
+    assert_eq!(
+        parse_labels("env=prod, role=web,").unwrap(),
+        vec![
+            ("env".to_string(), "prod".to_string()),
+            ("role".to_string(), "web".to_string())
+        ]
+    );
+    assert!(parse_labels("").unwrap().is_empty());
+    assert!(parse_labels("env").is_err());
+    assert!(parse_labels("=prod").is_err());
+}
+
+#[test]
+fn test_retry_delay() {
+    let job = BaseJob {
+        retry_interval: 10,
+        ..Default::default()
+    };
+    assert_eq!(job.retry_delay(1), Duration::from_secs(10));
+    assert_eq!(job.retry_delay(5), Duration::from_secs(10));
+
+    let job = BaseJob {
+        retry_interval: 10,
+        retry_backoff: RetryBackoff::Exponential,
+        ..Default::default()
+    };
+    assert_eq!(job.retry_delay(1), Duration::from_secs(10));
+    assert_eq!(job.retry_delay(2), Duration::from_secs(20));
+    assert_eq!(job.retry_delay(4), Duration::from_secs(80));
+    assert_eq!(
+        job.retry_delay(30),
+        Duration::from_secs(MAX_RETRY_DELAY_SECS)
+    );
+    assert_eq!(BaseJob::default().retry_delay(3), Duration::ZERO);
+}
+
+#[test]
+fn test_secret_masker() {
+    let job = BaseJob {
+        env: vec![
+            EnvVar {
+                name: "TOKEN".to_string(),
+                value: "s3cr3t-token".to_string(),
+                is_secret: true,
+            },
+            EnvVar {
+                name: "USER".to_string(),
+                value: "admin".to_string(),
+                is_secret: false,
+            },
+        ],
+        ..Default::default()
+    };
+    assert_eq!(
+        String::from_utf8_lossy(&job.mask_secrets(b"admin s3cr3t-token")),
+        "admin ******"
+    );
+
+    let output = "login admin with s3cr3t-token, then s3cr3t-token again ü";
+    for size in 1..output.len() {
+        let mut masker = SecretMasker::new(job.clone());
+        let mut masked = String::new();
+        let mut chunk = String::new();
+        for c in output.chars() {
+            chunk.push(c);
+            if chunk.len() >= size {
+                masked.push_str(&masker.push(&chunk));
+                chunk.clear();
+            }
+        }
+        masked.push_str(&masker.push(&chunk));
+        masked.push_str(&masker.finish());
+        assert_eq!(
+            masked, "login admin with ******, then ****** again ü",
+            "chunk size {size}"
+        );
+    }
+
+    let mut masker = SecretMasker::new(BaseJob::default());
+    assert_eq!(masker.push("no secrets"), "no secrets");
+    assert_eq!(masker.finish(), "");
+}
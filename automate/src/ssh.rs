@@ -1,8 +1,8 @@
 use std::env;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex as StdMutex};
 use std::time::{Duration, UNIX_EPOCH};
 
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
@@ -24,7 +24,65 @@ use tokio_tungstenite::{MaybeTlsStream, WebSocketStream as TWebSocketStream};
 use crate::comet::types::{Msg, MsgType};
 use crate::local_time;
 
-struct Client {}
+/// password or private key login of a ssh session
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct SshAuth {
+    #[serde(default)]
+    pub password: String,
+    /// private key in openssh or pem format, used instead of the password when set
+    #[serde(default)]
+    pub private_key: String,
+    #[serde(default)]
+    pub passphrase: String,
+}
+
+impl SshAuth {
+    pub async fn authenticate<H: client::Handler>(
+        &self,
+        session: &mut client::Handle<H>,
+        user: String,
+    ) -> Result<()> {
+        let auth_res = if self.private_key.is_empty() {
+            session
+                .authenticate_password(user, self.password.as_str())
+                .await?
+        } else {
+            let passphrase = Some(self.passphrase.as_str()).filter(|v| !v.is_empty());
+            let key = decode_secret_key(&self.private_key, passphrase)
+                .map_err(|e| anyhow!("invalid private key - {e}"))?;
+            session.authenticate_publickey(user, Arc::new(key)).await?
+        };
+
+        if !auth_res {
+            anyhow::bail!("Authentication failed");
+        }
+        Ok(())
+    }
+}
+
+pub fn host_key_fingerprint(key: &key::PublicKey) -> String {
+    format!("SHA256:{}", key.fingerprint())
+}
+
+/// verifies the server against the pinned host key fingerprint,
+/// an empty fingerprint trusts the first key seen
+pub struct Client {
+    host_key: String,
+    server_key: Arc<StdMutex<String>>,
+}
+
+impl Client {
+    pub fn new(host_key: impl Into<String>) -> (Self, Arc<StdMutex<String>>) {
+        let server_key = Arc::new(StdMutex::new(String::new()));
+        (
+            Self {
+                host_key: host_key.into(),
+                server_key: server_key.clone(),
+            },
+            server_key,
+        )
+    }
+}
 
 #[async_trait]
 impl client::Handler for Client {
@@ -32,29 +90,66 @@ impl client::Handler for Client {
 
     async fn check_server_key(
         &mut self,
-        _server_public_key: &key::PublicKey,
+        server_public_key: &key::PublicKey,
     ) -> Result<bool, Self::Error> {
-        Ok(true)
+        let fingerprint = host_key_fingerprint(server_public_key);
+        let trusted = self.host_key.is_empty() || self.host_key == fingerprint;
+        *self.server_key.lock().unwrap() = fingerprint;
+        Ok(trusted)
     }
 }
 
+/// connect and login, returns the session and the fingerprint of the host key
+pub async fn handshake<F, R>(
+    host_key: String,
+    auth: &SshAuth,
+    user: String,
+    connect: F,
+) -> Result<(client::Handle<Client>, String)>
+where
+    F: FnOnce(Client) -> R,
+    R: std::future::Future<Output = Result<client::Handle<Client>, russh::Error>>,
+{
+    let (sh, server_key) = Client::new(host_key.clone());
+
+    let ret = timeout(Duration::from_secs(1), connect(sh)).await?;
+    let server_key = server_key.lock().unwrap().clone();
+    let mut session = match ret {
+        Ok(v) => v,
+        Err(_) if !host_key.is_empty() && !server_key.is_empty() && server_key != host_key => {
+            anyhow::bail!(
+                "host key changed, expected {host_key} but the server offered {server_key}, reset the known host key of the instance if the change is expected"
+            )
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    auth.authenticate(&mut session, user).await?;
+    Ok((session, server_key))
+}
+
 pub struct Session {
     session: client::Handle<Client>,
+    /// fingerprint of the host key offered by the server
+    pub host_key: String,
 }
 
-pub struct ConnectParams<A: ToSocketAddrs, U: Into<String>, P: Into<String>> {
+pub struct ConnectParams<A: ToSocketAddrs, U: Into<String>> {
     pub user: U,
-    pub password: P,
+    pub auth: SshAuth,
     pub addrs: A,
+    /// pinned host key fingerprint, empty to trust the first key seen
+    pub host_key: String,
 }
 
 impl Session {
-    pub async fn connect<A: ToSocketAddrs, U: Into<String>, P: Into<String>>(
+    pub async fn connect<A: ToSocketAddrs, U: Into<String>>(
         ConnectParams {
             user,
-            password,
+            auth,
             addrs,
-        }: ConnectParams<A, U, P>,
+            host_key,
+        }: ConnectParams<A, U>,
     ) -> Result<Self> {
         let config = client::Config {
             inactivity_timeout: Some(Duration::from_secs(90)),
@@ -63,23 +158,19 @@ impl Session {
         };
 
         let config = Arc::new(config);
-        let sh = Client {};
 
-        let mut session =
-            timeout(Duration::from_secs(1), client::connect(config, addrs, sh)).await??;
-
-        let auth_res = session.authenticate_password(user, password).await?;
-
-        if !auth_res {
-            anyhow::bail!("Authentication failed");
-        }
+        let (session, host_key) = handshake(host_key, &auth, user.into(), |sh| {
+            client::connect(config, addrs, sh)
+        })
+        .await?;
 
-        Ok(Self { session })
+        Ok(Self { session, host_key })
     }
 
     pub async fn connect_stream<T: AsyncRead + AsyncWrite + Unpin + Send + 'static>(
         user: String,
-        password: String,
+        auth: SshAuth,
+        host_key: String,
         stream: T,
     ) -> Result<Self> {
         let config = client::Config {
@@ -89,21 +180,13 @@ impl Session {
         };
 
         let config = Arc::new(config);
-        let sh = Client {};
-
-        let mut session = timeout(
-            Duration::from_secs(1),
-            client::connect_stream(config, stream, sh),
-        )
-        .await??;
 
-        let auth_res = session.authenticate_password(user, password).await?;
-
-        if !auth_res {
-            anyhow::bail!("Authentication failed");
-        }
+        let (session, host_key) = handshake(host_key, &auth, user, |sh| {
+            client::connect_stream(config, stream, sh)
+        })
+        .await?;
 
-        Ok(Self { session })
+        Ok(Self { session, host_key })
     }
 
     // call for websocket proxy request
@@ -318,13 +401,15 @@ pub async fn read_dir(
     _ip: &str,
     port: u16,
     user: &str,
-    password: &str,
+    auth: &SshAuth,
+    host_key: &str,
     dir: Option<&str>,
 ) -> Result<DirDetail> {
     let ssh_session = Session::connect(ConnectParams {
         user,
-        password,
+        auth: auth.clone(),
         addrs: ("127.0.0.1", port),
+        host_key: host_key.to_string(),
     })
     .await?;
 
@@ -382,7 +467,8 @@ pub async fn upload(
     _ip: &str,
     port: u16,
     user: &str,
-    password: &str,
+    auth: &SshAuth,
+    host_key: &str,
     filepath: &str,
     data: Vec<u8>,
 ) -> Result<()> {
@@ -393,8 +479,9 @@ pub async fn upload(
 
     let ssh_session = Session::connect(ConnectParams {
         user,
-        password,
+        auth: auth.clone(),
         addrs: ("127.0.0.1", port),
+        host_key: host_key.to_string(),
     })
     .await?;
 
@@ -415,14 +502,16 @@ pub async fn remove(
     _ip: &str,
     port: u16,
     user: &str,
-    password: &str,
+    auth: &SshAuth,
+    host_key: &str,
     remove_type: &str,
     filepath: &str,
 ) -> Result<()> {
     let ssh_session = Session::connect(ConnectParams {
         user,
-        password,
+        auth: auth.clone(),
         addrs: ("127.0.0.1", port),
+        host_key: host_key.to_string(),
     })
     .await?;
 
@@ -441,13 +530,15 @@ pub async fn download(
     _ip: &str,
     port: u16,
     user: &str,
-    password: &str,
+    auth: &SshAuth,
+    host_key: &str,
     filepath: &str,
 ) -> Result<Vec<u8>> {
     let ssh_session = Session::connect(ConnectParams {
         user,
-        password,
+        auth: auth.clone(),
         addrs: ("127.0.0.1", port),
+        host_key: host_key.to_string(),
     })
     .await?;
 
@@ -456,3 +547,85 @@ pub async fn download(
     let data = sftp_session.read(filepath).await?;
     Ok(data)
 }
+
+/// fingerprint of the host key offered by the local ssh server, no login is done
+pub async fn scan_host_key(port: u16) -> Result<String> {
+    let config = Arc::new(client::Config::default());
+    let (sh, server_key) = Client::new("");
+    let session = timeout(
+        Duration::from_secs(1),
+        client::connect(config, ("127.0.0.1", port), sh),
+    )
+    .await??;
+    let _ = session
+        .disconnect(Disconnect::ByApplication, "", "English")
+        .await;
+    let host_key = server_key.lock().unwrap().clone();
+    Ok(host_key)
+}
+
+#[tokio::test]
+async fn test_host_key_pin() {
+    use russh::client::Handler;
+
+    let offered = key::KeyPair::generate_ed25519()
+        .unwrap()
+        .clone_public_key()
+        .unwrap();
+    let fingerprint = host_key_fingerprint(&offered);
+    let other = host_key_fingerprint(
+        &key::KeyPair::generate_ed25519()
+            .unwrap()
+            .clone_public_key()
+            .unwrap(),
+    );
+    assert!(fingerprint.starts_with("SHA256:"));
+    assert_ne!(fingerprint, other);
+
+    // the first key seen is trusted and reported so that it can be pinned
+    let (mut sh, server_key) = Client::new("");
+    assert!(sh.check_server_key(&offered).await.unwrap());
+    assert_eq!(*server_key.lock().unwrap(), fingerprint);
+
+    let (mut sh, server_key) = Client::new(fingerprint.clone());
+    assert!(sh.check_server_key(&offered).await.unwrap());
+    assert_eq!(*server_key.lock().unwrap(), fingerprint);
+
+    let (mut sh, server_key) = Client::new(other.clone());
+    assert!(!sh.check_server_key(&offered).await.unwrap());
+    assert_eq!(*server_key.lock().unwrap(), fingerprint);
+
+    // a rejected key fails the handshake with both fingerprints
+    let connect = |mut sh: Client| {
+        let offered = offered.clone();
+        async move {
+            let _ = sh.check_server_key(&offered).await;
+            Err::<client::Handle<Client>, _>(russh::Error::UnknownKey)
+        }
+    };
+    let err = handshake(
+        other.clone(),
+        &SshAuth::default(),
+        "root".to_string(),
+        connect,
+    )
+    .await
+    .err()
+    .unwrap()
+    .to_string();
+    assert!(err.starts_with("host key changed"));
+    assert!(err.contains(&other) && err.contains(&fingerprint));
+
+    // other connect errors are returned as they are
+    let err = handshake(
+        fingerprint.clone(),
+        &SshAuth::default(),
+        "root".to_string(),
+        connect,
+    )
+    .await
+    .err()
+    .unwrap()
+    .to_string();
+    assert!(!err.starts_with("host key changed"));
+}
@@ -0,0 +1,25 @@
+DROP TABLE IF EXISTS `terminal_recording`;
+
+CREATE TABLE `terminal_recording` (
+    `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT COMMENT '自增id',
+    `recording_id` varchar(50) NOT NULL DEFAULT '' COMMENT '录像id',
+    `instance_id` varchar(50) NOT NULL DEFAULT '' COMMENT '实例id',
+    `ip` char(20) NOT NULL DEFAULT '' COMMENT '实例ip',
+    `namespace` varchar(50) NOT NULL DEFAULT '' COMMENT '实例命名空间',
+    `user_id` varchar(50) NOT NULL DEFAULT '' COMMENT '登录用户id',
+    `username` varchar(50) NOT NULL DEFAULT '' COMMENT '登录用户名',
+    `sys_user` varchar(50) NOT NULL DEFAULT '' COMMENT '目标机器系统用户',
+    `via_tunnel` tinyint(1) NOT NULL DEFAULT '0' COMMENT '是否通过agent隧道连接',
+    `filepath` varchar(500) NOT NULL DEFAULT '' COMMENT '录像文件路径',
+    `file_size` bigint(20) unsigned NOT NULL DEFAULT '0' COMMENT '录像文件大小',
+    `width` int(10) unsigned NOT NULL DEFAULT '0' COMMENT '终端初始列数',
+    `height` int(10) unsigned NOT NULL DEFAULT '0' COMMENT '终端初始行数',
+    `start_time` timestamp NULL DEFAULT NULL COMMENT '会话开始时间',
+    `end_time` timestamp NULL DEFAULT NULL COMMENT '会话结束时间',
+    `created_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    PRIMARY KEY (`id`),
+    UNIQUE KEY `uk_recording_id` (`recording_id`),
+    KEY `idx_instance_id` (`instance_id`),
+    KEY `idx_user_id` (`user_id`),
+    KEY `idx_start_time` (`start_time`)
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4 COMMENT = '终端会话录像';
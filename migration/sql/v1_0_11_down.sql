@@ -0,0 +1,2 @@
+ALTER TABLE `job_timer`
+    DROP COLUMN `timezone`;
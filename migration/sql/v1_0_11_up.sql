@@ -0,0 +1,2 @@
+ALTER TABLE `job_timer`
+    ADD `timezone` VARCHAR(50) NOT NULL DEFAULT '' COMMENT 'IANA时区名称,为空时使用agent所在时区' AFTER `timer_expr`;
@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS `job_calendar`;
+
+ALTER TABLE `job_timer`
+    DROP COLUMN `calendar_id`;
@@ -0,0 +1,20 @@
+DROP TABLE IF EXISTS `job_calendar`;
+
+CREATE TABLE `job_calendar` (
+    `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT COMMENT '自增id',
+    `name` varchar(100) NOT NULL DEFAULT '' COMMENT '日历名称',
+    `weekdays` json DEFAULT NULL COMMENT '允许运行的星期 0表示周日 为空表示每天',
+    `include_dates` json DEFAULT NULL COMMENT '额外允许运行的日期 如调休工作日',
+    `exclude_dates` json DEFAULT NULL COMMENT '不允许运行的日期 如节假日',
+    `freeze_windows` json DEFAULT NULL COMMENT '冻结时间窗口 窗口内不运行',
+    `info` varchar(500) NOT NULL DEFAULT '' COMMENT '描述信息',
+    `created_user` varchar(50) NOT NULL DEFAULT '' COMMENT '创建人',
+    `updated_user` varchar(50) NOT NULL DEFAULT '' COMMENT '修改人',
+    `created_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    `updated_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT '修改时间',
+    PRIMARY KEY (`id`),
+    UNIQUE KEY `uk_name` (`name`)
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4 COMMENT = '定时器日历';
+
+ALTER TABLE `job_timer`
+    ADD `calendar_id` bigint(20) unsigned NOT NULL DEFAULT '0' COMMENT '日历id 0表示不限制' AFTER `timezone`;
@@ -0,0 +1,6 @@
+ALTER TABLE `job_timer`
+    DROP COLUMN `jitter_mode`,
+    DROP COLUMN `jitter_secs`;
+
+ALTER TABLE `job_exec_history`
+    DROP COLUMN `start_offset_ms`;
@@ -0,0 +1,6 @@
+ALTER TABLE `job_timer`
+    ADD `jitter_mode` VARCHAR(10) NOT NULL DEFAULT 'none' COMMENT '启动延迟方式 none不延迟 random每次随机 spread按实例固定偏移' AFTER `calendar_id`,
+    ADD `jitter_secs` INT UNSIGNED NOT NULL DEFAULT 0 COMMENT '启动延迟上限 单位秒' AFTER `jitter_mode`;
+
+ALTER TABLE `job_exec_history`
+    ADD `start_offset_ms` BIGINT UNSIGNED NOT NULL DEFAULT 0 COMMENT '定时任务实际启动时间相对触发时间的偏移 单位毫秒' AFTER `attempt`;
@@ -0,0 +1,11 @@
+ALTER TABLE `job_supervisor`
+    DROP COLUMN `restart_policy`,
+    DROP COLUMN `expected_exit_codes`,
+    DROP COLUMN `restart_backoff`,
+    DROP COLUMN `max_restart_delay`,
+    DROP COLUMN `max_restarts`,
+    DROP COLUMN `restart_window`;
+
+ALTER TABLE `job_running_status`
+    DROP COLUMN `restart_count`,
+    DROP COLUMN `termination_reason`;
@@ -0,0 +1,11 @@
+ALTER TABLE `job_supervisor`
+    ADD `restart_policy` VARCHAR(20) NOT NULL DEFAULT 'always' COMMENT '重启策略 always总是重启 on_failure非预期退出码时重启 never不重启' AFTER `restart_interval`,
+    ADD `expected_exit_codes` JSON DEFAULT NULL COMMENT 'on_failure策略下不重启的退出码 为空时为0' AFTER `restart_policy`,
+    ADD `restart_backoff` VARCHAR(20) NOT NULL DEFAULT 'fixed' COMMENT '重启间隔退避方式 fixed或exponential' AFTER `expected_exit_codes`,
+    ADD `max_restart_delay` BIGINT UNSIGNED NOT NULL DEFAULT 0 COMMENT '重启间隔上限 单位秒 0使用默认上限' AFTER `restart_backoff`,
+    ADD `max_restarts` INT UNSIGNED NOT NULL DEFAULT 0 COMMENT '时间窗口内最大重启次数 超过后进入fatal状态 0表示不限制' AFTER `max_restart_delay`,
+    ADD `restart_window` BIGINT UNSIGNED NOT NULL DEFAULT 0 COMMENT '重启次数统计窗口 单位秒 0表示自启动以来' AFTER `max_restarts`;
+
+ALTER TABLE `job_running_status`
+    ADD `restart_count` INT UNSIGNED NOT NULL DEFAULT 0 COMMENT '守护作业自启动以来的重启次数' AFTER `parallel_decision`,
+    ADD `termination_reason` VARCHAR(30) NOT NULL DEFAULT '' COMMENT '最近一次运行的结束原因' AFTER `restart_count`;
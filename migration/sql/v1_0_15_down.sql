@@ -0,0 +1,5 @@
+ALTER TABLE `job_supervisor`
+    DROP COLUMN `liveness_probe`;
+
+ALTER TABLE `job_running_status`
+    DROP COLUMN `probe_result`;
@@ -0,0 +1,5 @@
+ALTER TABLE `job_supervisor`
+    ADD `liveness_probe` JSON DEFAULT NULL COMMENT '存活探针 {probe_type,target,expected_status,interval,timeout,failure_threshold,initial_delay}' AFTER `restart_window`;
+
+ALTER TABLE `job_running_status`
+    ADD `probe_result` JSON DEFAULT NULL COMMENT '守护作业最近一次存活探测结果' AFTER `termination_reason`;
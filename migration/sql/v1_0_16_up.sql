@@ -0,0 +1,19 @@
+DROP TABLE IF EXISTS `user_api_token`;
+
+CREATE TABLE `user_api_token` (
+    `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT COMMENT '自增id',
+    `user_id` varchar(50) NOT NULL DEFAULT '' COMMENT '所属用户id',
+    `name` varchar(100) NOT NULL DEFAULT '' COMMENT '令牌名称',
+    `token_prefix` varchar(20) NOT NULL DEFAULT '' COMMENT '令牌前缀 用于识别令牌',
+    `token_hash` char(64) NOT NULL DEFAULT '' COMMENT '令牌sha256摘要',
+    `scopes` json DEFAULT NULL COMMENT '授权范围 如job:read job:write *',
+    `expire_time` timestamp NULL DEFAULT NULL COMMENT '过期时间 为空表示不过期',
+    `is_revoked` tinyint(1) NOT NULL DEFAULT '0' COMMENT '是否已吊销',
+    `last_used_time` timestamp NULL DEFAULT NULL COMMENT '最近使用时间',
+    `last_used_ip` varchar(50) NOT NULL DEFAULT '' COMMENT '最近使用ip',
+    `created_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    `updated_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT '修改时间',
+    PRIMARY KEY (`id`),
+    UNIQUE KEY `uk_token_hash` (`token_hash`),
+    KEY `idx_user_id` (`user_id`)
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4 COMMENT = '用户API令牌';
@@ -0,0 +1,3 @@
+ALTER TABLE `user`
+    DROP INDEX `uk_oidc_subject`,
+    DROP COLUMN `oidc_subject`;
@@ -0,0 +1,3 @@
+ALTER TABLE `user`
+    ADD `oidc_subject` varchar(255) DEFAULT NULL COMMENT 'OIDC登录用户的issuer与sub 用于关联单点登录账号' AFTER `introduction`,
+    ADD UNIQUE KEY `uk_oidc_subject` (`oidc_subject`);
@@ -0,0 +1,2 @@
+ALTER TABLE `user`
+    ADD `ldap_dn` varchar(255) DEFAULT NULL COMMENT 'LDAP用户的dn 为空表示本地用户' AFTER `oidc_subject`;
@@ -0,0 +1,3 @@
+ALTER TABLE `tag_resource`
+    DROP KEY `idx_tag_id`,
+    DROP KEY `idx_resource`;
@@ -0,0 +1,3 @@
+ALTER TABLE `tag_resource`
+    ADD KEY `idx_tag_id` (`tag_id`),
+    ADD KEY `idx_resource` (`resource_type`, `resource_val`);
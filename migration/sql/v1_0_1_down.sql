@@ -0,0 +1,13 @@
+DROP TABLE IF EXISTS `job_organizer`;
+
+DROP TABLE IF EXISTS `job_organizer_release`;
+
+DROP TABLE IF EXISTS `job_organizer_release_edge`;
+
+DROP TABLE IF EXISTS `job_organizer_release_node`;
+
+DROP TABLE IF EXISTS `job_organizer_process`;
+
+DROP TABLE IF EXISTS `job_organizer_task`;
+
+DROP TABLE IF EXISTS `job_organizer_task_result`;
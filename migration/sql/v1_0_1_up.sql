@@ -0,0 +1,140 @@
+DROP TABLE IF EXISTS `job_organizer`;
+
+CREATE TABLE `job_organizer` (
+    `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT COMMENT '自增id',
+    `eid` varchar(100) NOT NULL DEFAULT '' COMMENT '执行id',
+    `name` varchar(100) NOT NULL DEFAULT '' COMMENT '流程',
+    `nodes` json DEFAULT NULL COMMENT '节点',
+    `edges` json DEFAULT NULL COMMENT '边线',
+    `info` varchar(500) NOT NULL DEFAULT '' COMMENT '描述信息',
+    `is_public` tinyint(1) NOT NULL DEFAULT '0' COMMENT '是否公开',
+    `created_user` varchar(50) NOT NULL DEFAULT '' COMMENT '创建人',
+    `updated_user` varchar(50) NOT NULL DEFAULT '' COMMENT '修改人',
+    `created_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    `updated_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT '修改时间',
+    PRIMARY KEY (`id`),
+    UNIQUE KEY `uk_name` (`name`, `created_user`)
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4 COMMENT = '作业编排';
+
+DROP TABLE IF EXISTS `job_organizer_release`;
+
+CREATE TABLE `job_organizer_release` (
+    `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT COMMENT '自增id',
+    `organizer_id` bigint(20) unsigned NOT NULL DEFAULT '0' COMMENT '编排id',
+    `version` varchar(100) NOT NULL DEFAULT '' COMMENT '版本',
+    `name` varchar(100) NOT NULL DEFAULT '' COMMENT '版本名称',
+    `info` varchar(500) NOT NULL DEFAULT '' COMMENT '描述信息',
+    `is_public` tinyint(1) NOT NULL DEFAULT '0' COMMENT '是否公开',
+    `nodes` json DEFAULT NULL COMMENT '节点数据',
+    `edges` json DEFAULT NULL COMMENT '边线数据',
+    `created_user` varchar(50) NOT NULL DEFAULT '' COMMENT '创建人',
+    `created_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    `updated_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT '修改时间',
+    PRIMARY KEY (`id`),
+    UNIQUE KEY `uk_name` (
+        `name`,
+        `organizer_id`,
+        `created_user`
+    ),
+    UNIQUE KEY `uk_version` (`version`),
+    KEY `idx_organizer_id` (`organizer_id`)
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4 COMMENT = '作业编排发布版本';
+
+DROP TABLE IF EXISTS `job_organizer_release_edge`;
+
+CREATE TABLE `job_organizer_release_edge` (
+    `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT COMMENT '自增id',
+    `version` varchar(100) NOT NULL DEFAULT '' COMMENT '版本',
+    `edge_id` varchar(100) NOT NULL DEFAULT '' COMMENT '边线id',
+    `edge_type` varchar(50) NOT NULL DEFAULT '' COMMENT '边线类型',
+    `props` json DEFAULT NULL COMMENT '属性',
+    `source_node_id` varchar(100) NOT NULL DEFAULT '' COMMENT '源节点id',
+    `target_node_id` varchar(100) NOT NULL DEFAULT '' COMMENT '目标节点id',
+    `edge_val` varchar(100) NOT NULL DEFAULT '' COMMENT '边值, 为空时源节点成功即通过, 否则需等于源节点输出',
+    `created_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    `updated_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT '修改时间',
+    PRIMARY KEY (`id`),
+    KEY `idx_version` (`version`),
+    KEY `idx_source_node_id` (`source_node_id`)
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4 COMMENT = '已发布的作业边线集合';
+
+DROP TABLE IF EXISTS `job_organizer_release_node`;
+
+CREATE TABLE `job_organizer_release_node` (
+    `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT COMMENT '自增id',
+    `version` varchar(100) NOT NULL DEFAULT '' COMMENT '版本',
+    `node_id` varchar(100) NOT NULL DEFAULT '' COMMENT '节点',
+    `name` varchar(100) NOT NULL DEFAULT '' COMMENT '节点名称',
+    `node_type` varchar(50) NOT NULL DEFAULT '' COMMENT '节点类型 start end task',
+    `flow_type` varchar(50) NOT NULL DEFAULT '' COMMENT '流程类型',
+    `task_type` varchar(50) NOT NULL DEFAULT '' COMMENT '任务类型',
+    `dispatch_data` json DEFAULT NULL COMMENT '发送给执行引擎的可以直接执行的数据',
+    `props` json DEFAULT NULL COMMENT '属性',
+    `condition` text NOT NULL COMMENT '条件表达式',
+    `bind_instance` json DEFAULT NULL COMMENT '绑定的执行节点实例id',
+    `created_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    `updated_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT '修改时间',
+    PRIMARY KEY (`id`),
+    KEY `idx_version` (`version`, `node_id`)
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4 COMMENT = '已发布的作业节点集合';
+
+DROP TABLE IF EXISTS `job_organizer_process`;
+
+CREATE TABLE `job_organizer_process` (
+    `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT COMMENT '自增id',
+    `name` varchar(100) NOT NULL DEFAULT '' COMMENT '流程名',
+    `organizer_id` bigint(20) unsigned NOT NULL DEFAULT '0' COMMENT '编排id',
+    `organizer_version` varchar(100) NOT NULL DEFAULT '' COMMENT '版本',
+    `process_id` varchar(100) NOT NULL DEFAULT '' COMMENT '流程id',
+    `status` varchar(100) NOT NULL DEFAULT 'running' COMMENT '流程状态 running finished failed',
+    `current_node` varchar(100) NOT NULL DEFAULT '' COMMENT '当前节点',
+    `created_user` varchar(50) NOT NULL DEFAULT '' COMMENT '创建人',
+    `updated_user` varchar(50) NOT NULL DEFAULT '' COMMENT '修改人',
+    `created_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    `updated_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT '修改时间',
+    PRIMARY KEY (`id`),
+    UNIQUE KEY `uk_process_id` (`process_id`),
+    KEY `idx_organizer_id` (`organizer_id`, `created_user`)
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4 COMMENT = '运行中的任务进程';
+
+DROP TABLE IF EXISTS `job_organizer_task`;
+
+CREATE TABLE `job_organizer_task` (
+    `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT COMMENT '自增id',
+    `process_id` varchar(100) NOT NULL DEFAULT '' COMMENT '流程id',
+    `node_id` varchar(100) NOT NULL DEFAULT '' COMMENT '流程节点id',
+    `schedule_id` varchar(40) NOT NULL DEFAULT '' COMMENT '调度id',
+    `status` varchar(100) NOT NULL DEFAULT '' COMMENT '任务状态 running success failure skipped',
+    `output` varchar(100) NOT NULL DEFAULT '' COMMENT '当为条件节点时,值不为空: true false',
+    `bind_total` int(11) NOT NULL DEFAULT '0' COMMENT '绑定的节点数量',
+    `restart_num` int(11) NOT NULL DEFAULT '0' COMMENT '重启次数',
+    `created_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    `updated_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT '修改时间',
+    PRIMARY KEY (`id`),
+    UNIQUE KEY `uk_process_node` (`process_id`, `node_id`),
+    KEY `idx_schedule_id` (`schedule_id`)
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4 COMMENT = '任务进程中的各个任务';
+
+DROP TABLE IF EXISTS `job_organizer_task_result`;
+
+CREATE TABLE `job_organizer_task_result` (
+    `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT COMMENT '自增id',
+    `process_id` varchar(100) NOT NULL DEFAULT '' COMMENT '流程id',
+    `node_id` varchar(100) NOT NULL DEFAULT '' COMMENT '流程节点id',
+    `instance_id` varchar(40) NOT NULL DEFAULT '' COMMENT '实例id',
+    `bind_ip` char(20) NOT NULL DEFAULT '' COMMENT '节点ip',
+    `exit_code` int NOT NULL DEFAULT 0 COMMENT '退出码',
+    `exit_status` varchar(200) NOT NULL DEFAULT '' COMMENT '退出状态',
+    `output` text NOT NULL COMMENT '执行输出',
+    `status` varchar(100) NOT NULL DEFAULT '' COMMENT '任务状态 success failure',
+    `restart_num` int(11) NOT NULL DEFAULT '0' COMMENT '重启次数',
+    `dispatch_result` json DEFAULT NULL COMMENT '调度派送结果',
+    `created_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    `updated_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT '修改时间',
+    PRIMARY KEY (`id`),
+    UNIQUE KEY `uk_process_node_instance` (
+        `process_id`,
+        `node_id`,
+        `instance_id`
+    )
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4 COMMENT = '任务执行结果';
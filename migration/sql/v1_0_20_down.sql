@@ -0,0 +1,2 @@
+ALTER TABLE `job_schedule_history`
+    DROP COLUMN `target_selector`;
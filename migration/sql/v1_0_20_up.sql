@@ -0,0 +1,2 @@
+ALTER TABLE `job_schedule_history`
+    ADD `target_selector` json DEFAULT NULL COMMENT '动态目标 实例组 标签 命名空间 为空表示静态目标' AFTER `dispatch_data`;
@@ -0,0 +1,2 @@
+ALTER TABLE `job_exec_history`
+    DROP COLUMN `run_id`;
@@ -0,0 +1,2 @@
+ALTER TABLE `job_exec_history`
+    ADD `run_id` VARCHAR(64) NOT NULL DEFAULT '' COMMENT '运行id 对应agent上的日志文件 为空表示按attempt查找' AFTER `attempt`;
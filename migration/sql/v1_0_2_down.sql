@@ -0,0 +1,6 @@
+ALTER TABLE `job`
+    MODIFY `max_retry` TINYINT UNSIGNED NOT NULL DEFAULT 1 COMMENT '最大重试次数',
+    DROP COLUMN `retry_interval`,
+    DROP COLUMN `retry_backoff`;
+
+ALTER TABLE `job_exec_history` DROP COLUMN `attempt`;
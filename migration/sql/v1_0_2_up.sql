@@ -0,0 +1,10 @@
+-- retry used to be unimplemented with a default of 1, those jobs must not start retrying
+UPDATE `job` SET `max_retry` = 0 WHERE `max_retry` = 1;
+
+ALTER TABLE `job`
+    MODIFY `max_retry` TINYINT UNSIGNED NOT NULL DEFAULT 0 COMMENT '最大重试次数',
+    ADD `retry_interval` BIGINT UNSIGNED NOT NULL DEFAULT 0 COMMENT '重试间隔,单位秒' AFTER `max_parallel`,
+    ADD `retry_backoff` VARCHAR(20) NOT NULL DEFAULT 'fixed' COMMENT '重试退避策略 fixed exponential' AFTER `retry_interval`;
+
+ALTER TABLE `job_exec_history`
+    ADD `attempt` INT UNSIGNED NOT NULL DEFAULT 1 COMMENT '第几次执行' AFTER `exit_code`;
@@ -0,0 +1,6 @@
+ALTER TABLE `job` DROP COLUMN `parallel_policy`;
+
+ALTER TABLE `job`
+    MODIFY `max_parallel` TINYINT UNSIGNED NOT NULL DEFAULT 1 COMMENT '进程最大并行数';
+
+ALTER TABLE `job_running_status` DROP COLUMN `parallel_decision`;
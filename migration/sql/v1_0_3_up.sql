@@ -0,0 +1,9 @@
+-- max_parallel was not enforced before, the stored values are kept and only new jobs are unlimited
+ALTER TABLE `job`
+    MODIFY `max_parallel` TINYINT UNSIGNED NOT NULL DEFAULT 0 COMMENT '进程最大并行数';
+
+ALTER TABLE `job`
+    ADD `parallel_policy` VARCHAR(20) NOT NULL DEFAULT 'skip' COMMENT '超过最大并行数时的策略 skip queue replace' AFTER `max_parallel`;
+
+ALTER TABLE `job_running_status`
+    ADD `parallel_decision` VARCHAR(20) NOT NULL DEFAULT '' COMMENT '最近一次超过最大并行数时采取的策略' AFTER `exit_code`;
@@ -0,0 +1,3 @@
+ALTER TABLE `job_exec_history`
+    MODIFY `output` text NOT NULL COMMENT '执行输出',
+    DROP COLUMN `stderr`;
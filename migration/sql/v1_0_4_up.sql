@@ -0,0 +1,3 @@
+ALTER TABLE `job_exec_history`
+    MODIFY `output` mediumtext NOT NULL COMMENT '标准输出',
+    ADD `stderr` mediumtext NOT NULL COMMENT '标准错误输出' AFTER `output`;
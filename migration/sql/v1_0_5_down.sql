@@ -0,0 +1,3 @@
+ALTER TABLE `job` DROP COLUMN `env`;
+
+ALTER TABLE `team` DROP COLUMN `env`;
@@ -0,0 +1,5 @@
+ALTER TABLE `job`
+    ADD `env` JSON DEFAULT NULL COMMENT '环境变量 [{name,value,is_secret}] 密钥类变量加密存储' AFTER `args`;
+
+ALTER TABLE `team`
+    ADD `env` JSON DEFAULT NULL COMMENT '团队环境变量 作业环境变量同名时覆盖团队变量' AFTER `info`;
@@ -0,0 +1,20 @@
+DROP TABLE IF EXISTS `job_trigger`;
+
+CREATE TABLE `job_trigger` (
+    `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT COMMENT '自增id',
+    `name` varchar(100) NOT NULL DEFAULT '' COMMENT '触发器名称',
+    `eid` varchar(100) NOT NULL DEFAULT '' COMMENT '上游作业id',
+    `instance_id` varchar(100) NOT NULL DEFAULT '' COMMENT '上游作业所在实例id 为空表示任意实例',
+    `trigger_on` varchar(20) NOT NULL DEFAULT 'success' COMMENT '触发条件 success failure complete',
+    `target_eid` varchar(100) NOT NULL DEFAULT '' COMMENT '下游作业id',
+    `target_instances` json DEFAULT NULL COMMENT '下游作业执行的实例id列表',
+    `is_enabled` tinyint(1) NOT NULL DEFAULT '1' COMMENT '是否启用',
+    `info` varchar(500) NOT NULL DEFAULT '' COMMENT '描述信息',
+    `created_user` varchar(50) NOT NULL DEFAULT '' COMMENT '创建人',
+    `updated_user` varchar(50) NOT NULL DEFAULT '' COMMENT '修改人',
+    `created_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    `updated_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT '修改时间',
+    PRIMARY KEY (`id`),
+    KEY `idx_eid` (`eid`),
+    UNIQUE KEY `uk_name` (`name`, `created_user`)
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4 COMMENT = '作业依赖触发器';
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS `notify_rule`;
+
+DROP TABLE IF EXISTS `notify_channel`;
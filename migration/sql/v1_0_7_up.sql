@@ -0,0 +1,36 @@
+DROP TABLE IF EXISTS `notify_channel`;
+
+CREATE TABLE `notify_channel` (
+    `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT COMMENT '自增id',
+    `name` varchar(100) NOT NULL DEFAULT '' COMMENT '通知渠道名称',
+    `channel_type` varchar(20) NOT NULL DEFAULT 'webhook' COMMENT '渠道类型 webhook email slack dingtalk feishu',
+    `config` json DEFAULT NULL COMMENT '渠道配置 地址 收件人 模板等',
+    `info` varchar(500) NOT NULL DEFAULT '' COMMENT '描述信息',
+    `created_user` varchar(50) NOT NULL DEFAULT '' COMMENT '创建人',
+    `updated_user` varchar(50) NOT NULL DEFAULT '' COMMENT '修改人',
+    `created_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    `updated_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT '修改时间',
+    PRIMARY KEY (`id`),
+    UNIQUE KEY `uk_name` (`name`, `created_user`)
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4 COMMENT = '通知渠道';
+
+DROP TABLE IF EXISTS `notify_rule`;
+
+CREATE TABLE `notify_rule` (
+    `id` bigint(20) unsigned NOT NULL AUTO_INCREMENT COMMENT '自增id',
+    `name` varchar(100) NOT NULL DEFAULT '' COMMENT '通知规则名称',
+    `event_type` varchar(50) NOT NULL DEFAULT '' COMMENT '事件类型 failure success timeout daemon_restart_storm agent_offline',
+    `eid` varchar(100) NOT NULL DEFAULT '' COMMENT '作业id 为空表示不限作业',
+    `team_id` bigint(20) unsigned NOT NULL DEFAULT '0' COMMENT '团队id 为0表示不限团队',
+    `channel_id` bigint(20) unsigned NOT NULL DEFAULT '0' COMMENT '通知渠道id',
+    `is_enabled` tinyint(1) NOT NULL DEFAULT '1' COMMENT '是否启用',
+    `info` varchar(500) NOT NULL DEFAULT '' COMMENT '描述信息',
+    `created_user` varchar(50) NOT NULL DEFAULT '' COMMENT '创建人',
+    `updated_user` varchar(50) NOT NULL DEFAULT '' COMMENT '修改人',
+    `created_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP COMMENT '创建时间',
+    `updated_time` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP COMMENT '修改时间',
+    PRIMARY KEY (`id`),
+    KEY `idx_event_type` (`event_type`),
+    KEY `idx_channel_id` (`channel_id`),
+    UNIQUE KEY `uk_name` (`name`, `created_user`)
+) ENGINE = InnoDB DEFAULT CHARSET = utf8mb4 COMMENT = '通知规则';
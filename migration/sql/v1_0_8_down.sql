@@ -0,0 +1,4 @@
+ALTER TABLE `job_exec_history`
+    DROP INDEX `idx_termination_reason`,
+    DROP COLUMN `termination_reason`,
+    DROP COLUMN `term_signal`;
@@ -0,0 +1,4 @@
+ALTER TABLE `job_exec_history`
+    ADD `termination_reason` VARCHAR(20) NOT NULL DEFAULT '' COMMENT '结束原因 exited timeout killed_by_user killed_by_signal spawn_failed agent_error' AFTER `exit_code`,
+    ADD `term_signal` INT NOT NULL DEFAULT 0 COMMENT '结束进程的信号,仅killed_by_signal时有值' AFTER `termination_reason`,
+    ADD INDEX `idx_termination_reason` (`termination_reason`);
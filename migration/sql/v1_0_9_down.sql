@@ -0,0 +1,4 @@
+ALTER TABLE `instance`
+    DROP COLUMN `private_key`,
+    DROP COLUMN `passphrase`,
+    DROP COLUMN `host_key`;
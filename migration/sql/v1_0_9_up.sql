@@ -0,0 +1,4 @@
+ALTER TABLE `instance`
+    ADD `private_key` TEXT NULL COMMENT '加密后的ssh私钥,设置后优先于密码登录' AFTER `password`,
+    ADD `passphrase` VARCHAR(1000) NOT NULL DEFAULT '' COMMENT '加密后的私钥口令' AFTER `private_key`,
+    ADD `host_key` VARCHAR(100) NOT NULL DEFAULT '' COMMENT '首次连接时记录的ssh主机公钥指纹' AFTER `ssh_port`;
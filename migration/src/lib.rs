@@ -1,12 +1,56 @@
 pub use sea_orm_migration::prelude::*;
 
 mod v1_0_0_create_table;
+mod v1_0_10_create_terminal_recording_table;
+mod v1_0_11_add_job_timer_timezone;
+mod v1_0_12_create_job_calendar_table;
+mod v1_0_13_add_job_timer_jitter;
+mod v1_0_14_add_job_supervisor_restart_policy;
+mod v1_0_15_add_job_supervisor_liveness_probe;
+mod v1_0_16_create_user_api_token_table;
+mod v1_0_17_add_user_oidc_subject;
+mod v1_0_18_add_user_ldap_dn;
+mod v1_0_19_add_tag_resource_index;
+mod v1_0_20_add_schedule_target_selector;
+mod v1_0_21_add_exec_history_run_id;
+mod v1_0_1_create_job_organizer_table;
+mod v1_0_2_add_job_retry;
+mod v1_0_3_add_job_parallel_policy;
+mod v1_0_4_split_job_exec_output;
+mod v1_0_5_add_job_env;
+mod v1_0_6_create_job_trigger_table;
+mod v1_0_7_create_notify_table;
+mod v1_0_8_add_termination_reason;
+mod v1_0_9_add_instance_ssh_key;
 
 pub struct Migrator;
 
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(v1_0_0_create_table::Migration)]
+        vec![
+            Box::new(v1_0_0_create_table::Migration),
+            Box::new(v1_0_1_create_job_organizer_table::Migration),
+            Box::new(v1_0_2_add_job_retry::Migration),
+            Box::new(v1_0_3_add_job_parallel_policy::Migration),
+            Box::new(v1_0_4_split_job_exec_output::Migration),
+            Box::new(v1_0_5_add_job_env::Migration),
+            Box::new(v1_0_6_create_job_trigger_table::Migration),
+            Box::new(v1_0_7_create_notify_table::Migration),
+            Box::new(v1_0_8_add_termination_reason::Migration),
+            Box::new(v1_0_9_add_instance_ssh_key::Migration),
+            Box::new(v1_0_10_create_terminal_recording_table::Migration),
+            Box::new(v1_0_11_add_job_timer_timezone::Migration),
+            Box::new(v1_0_12_create_job_calendar_table::Migration),
+            Box::new(v1_0_13_add_job_timer_jitter::Migration),
+            Box::new(v1_0_14_add_job_supervisor_restart_policy::Migration),
+            Box::new(v1_0_15_add_job_supervisor_liveness_probe::Migration),
+            Box::new(v1_0_16_create_user_api_token_table::Migration),
+            Box::new(v1_0_17_add_user_oidc_subject::Migration),
+            Box::new(v1_0_18_add_user_ldap_dn::Migration),
+            Box::new(v1_0_19_add_tag_resource_index::Migration),
+            Box::new(v1_0_20_add_schedule_target_selector::Migration),
+            Box::new(v1_0_21_add_exec_history_run_id::Migration),
+        ]
     }
 }
@@ -4,7 +4,10 @@ pub mod instance;
 pub mod job;
 pub mod manage;
 pub mod migration;
+pub mod notify;
+pub mod recording;
 pub mod role;
+pub mod tag;
 pub mod team;
 pub mod terminal;
 pub mod user;
@@ -41,6 +44,9 @@ pub enum Tag {
     Role,
     Admin,
     Migration,
+    Notify,
+    Recording,
+    Tag,
 }
 
 pub struct OneOfValidator(Vec<String>);
@@ -19,11 +19,7 @@ use tokio::{
 
 use crate::{
     error::NoPermission,
-    local_time,
-    logic::{
-        self,
-        ssh::{ConnectParams, Session as SshSession},
-    },
+    local_time, logic,
     response::{std_into_error, ApiStdResponse},
     return_err, return_ok, AppState,
 };
@@ -195,16 +191,11 @@ impl FileApi {
         )
         .map_or(Err(anyhow!("not found")), |v| Ok(v));
         let instance_record = unwrap_or_response!(instance_record);
-        let password =
-            unwrap_or_response!(state.decrypt(instance_record.password.unwrap_or_default()));
 
         let ssh_session = unwrap_or_response!(
-            SshSession::connect(ConnectParams {
-                user: instance_record.sys_user.unwrap_or_default(),
-                password,
-                addrs: (instance_record.ip, 22),
-            })
-            .await
+            svc.ssh
+                .connect(&instance_record.instance_id, instance_record.ip)
+                .await
         );
 
         let sftp_session = unwrap_or_response!(ssh_session.sftp_client().await);
@@ -235,13 +226,10 @@ impl FileApi {
             .get_one_user_server_with_permission(state.clone(), &user_info, instance_id.clone())
             .await?
             .map_or(Err(anyhow!("not found")), |v| Ok(v))?;
-        let password = state.decrypt(instance_record.password.unwrap_or_default())?;
-        let ssh_session = SshSession::connect(ConnectParams {
-            user: instance_record.sys_user.unwrap_or_default(),
-            password,
-            addrs: (instance_record.ip, 22),
-        })
-        .await?;
+        let ssh_session = svc
+            .ssh
+            .connect(&instance_record.instance_id, instance_record.ip)
+            .await?;
 
         let sft_session = ssh_session.sftp_client().await?;
 
@@ -308,13 +296,10 @@ impl FileApi {
             .get_one_user_server_with_permission(state.clone(), &user_info, req.instance_id)
             .await?
             .map_or(Err(anyhow!("not found")), |v| Ok(v))?;
-        let password = state.decrypt(instance_record.password.unwrap_or_default())?;
-        let ssh_session = SshSession::connect(ConnectParams {
-            user: instance_record.sys_user.unwrap_or_default(),
-            password,
-            addrs: (instance_record.ip, 22),
-        })
-        .await?;
+        let ssh_session = svc
+            .ssh
+            .connect(&instance_record.instance_id, instance_record.ip)
+            .await?;
 
         let dir = std::path::Path::new(&req.file_path)
             .parent()
@@ -357,13 +342,10 @@ impl FileApi {
             .get_one_user_server_with_permission(state.clone(), &user_info, req.instance_id)
             .await?
             .map_or(Err(anyhow!("not found")), |v| Ok(v))?;
-        let password = state.decrypt(instance_record.password.unwrap_or_default())?;
-        let ssh_session = SshSession::connect(ConnectParams {
-            user: instance_record.sys_user.unwrap_or_default(),
-            password,
-            addrs: (instance_record.ip, 22),
-        })
-        .await?;
+        let ssh_session = svc
+            .ssh
+            .connect(&instance_record.instance_id, instance_record.ip)
+            .await?;
 
         let sftp_session = ssh_session.sftp_client().await?;
 
@@ -398,30 +380,23 @@ impl FileApi {
             .get_one_user_server_with_permission(state.clone(), &user_info, instance_id)
             .await?
             .ok_or(anyhow!("not found instance"))?;
-        let user = instance_record
-            .sys_user
-            .filter(|v| v != "")
-            .ok_or(anyhow!("no system user"))?;
-        let password = instance_record
-            .password
-            .filter(|v| v != "")
-            .ok_or(anyhow!("no password"))?;
-        let port = instance_record
-            .ssh_port
-            .filter(|&v| v != 0)
-            .ok_or(anyhow!("no ssh port"))?;
-
-        let password = state.decrypt(password)?;
+        let cred = svc
+            .ssh
+            .tunnel_credential(
+                &instance_record.instance_id,
+                instance_record.namespace.clone(),
+                instance_record.ip.clone(),
+                instance_record.mac_addr.clone(),
+            )
+            .await?;
         let ret = svc
             .ssh
             .sftp_read_dir(
                 instance_record.namespace,
                 instance_record.ip,
                 instance_record.mac_addr,
-                port,
+                cred,
                 dir,
-                user,
-                password,
             )
             .await?;
 
@@ -444,20 +419,15 @@ impl FileApi {
             .await?
             .ok_or(anyhow!("not found instance"))?;
 
-        let user = instance_record
-            .sys_user
-            .filter(|v| v != "")
-            .ok_or(anyhow!("no sys user"))?;
-        let password = instance_record
-            .password
-            .filter(|v| v != "")
-            .ok_or(anyhow!("no password"))?;
-        let port = instance_record
-            .ssh_port
-            .filter(|&v| v != 0)
-            .ok_or(anyhow!("no ssh port"))?;
-
-        let password = state.decrypt(password)?;
+        let cred = svc
+            .ssh
+            .tunnel_credential(
+                &instance_record.instance_id,
+                instance_record.namespace.clone(),
+                instance_record.ip.clone(),
+                instance_record.mac_addr.clone(),
+            )
+            .await?;
 
         let data = req.file.into_vec().await.map_err(std_into_error)?;
 
@@ -467,9 +437,7 @@ impl FileApi {
                 req.namespace,
                 instance_record.ip,
                 instance_record.mac_addr,
-                port,
-                user,
-                password,
+                cred,
                 req.file_path,
                 data,
             )
@@ -496,20 +464,15 @@ impl FileApi {
             .get_one_user_server_with_permission(state.clone(), &user_info, req.instance_id)
             .await?
             .ok_or(anyhow!("not found instance"))?;
-        let user = instance_record
-            .sys_user
-            .filter(|v| v != "")
-            .ok_or(anyhow!("no sys user"))?;
-        let password = instance_record
-            .password
-            .filter(|v| v != "")
-            .ok_or(anyhow!("no password"))?;
-        let port = instance_record
-            .ssh_port
-            .filter(|&v| v != 0)
-            .ok_or(anyhow!("no ssh port"))?;
-
-        let password = state.decrypt(password)?;
+        let cred = svc
+            .ssh
+            .tunnel_credential(
+                &instance_record.instance_id,
+                instance_record.namespace.clone(),
+                instance_record.ip.clone(),
+                instance_record.mac_addr.clone(),
+            )
+            .await?;
 
         let ret = svc
             .ssh
@@ -517,9 +480,7 @@ impl FileApi {
                 instance_record.namespace,
                 instance_record.ip,
                 instance_record.mac_addr,
-                port,
-                user,
-                password,
+                cred,
                 req.path,
                 req.remove_type,
             )
@@ -546,21 +507,16 @@ impl FileApi {
         let instance_record =
             unwrap_or_response!(instance_record.ok_or(anyhow!("not found instance")));
 
-        let user = unwrap_or_response!(instance_record
-            .sys_user
-            .filter(|v| v != "")
-            .ok_or(anyhow!("no sys user")));
-
-        let password = unwrap_or_response!(instance_record
-            .password
-            .filter(|v| v != "")
-            .ok_or(anyhow!("no password")));
-        let port = unwrap_or_response!(instance_record
-            .ssh_port
-            .filter(|&v| v != 0)
-            .ok_or(anyhow!("no ssh port")));
-
-        let password = unwrap_or_response!(state.decrypt(password));
+        let cred = unwrap_or_response!(
+            svc.ssh
+                .tunnel_credential(
+                    &instance_record.instance_id,
+                    instance_record.namespace.clone(),
+                    instance_record.ip.clone(),
+                    instance_record.mac_addr.clone(),
+                )
+                .await
+        );
 
         let data = unwrap_or_response!(
             svc.ssh
@@ -568,9 +524,7 @@ impl FileApi {
                     instance_record.namespace,
                     instance_record.ip,
                     instance_record.mac_addr,
-                    port,
-                    user,
-                    password,
+                    cred,
                     file_path.clone()
                 )
                 .await
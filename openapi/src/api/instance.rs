@@ -29,6 +29,8 @@ pub mod types {
         pub namespace: String,
         pub instance_group: String,
         pub sys_user: String,
+        /// fingerprint of the pinned ssh host key, empty until the first connection
+        pub host_key: String,
         pub info: String,
         pub status: i8,
         pub role_id: u64,
@@ -83,6 +85,12 @@ pub mod types {
         pub status: i8,
         pub sys_user: Option<String>,
         pub password: Option<String>,
+        /// ssh private key, used instead of the password when set
+        pub private_key: Option<String>,
+        pub passphrase: Option<String>,
+        /// remove the saved private key and login with the password again
+        #[oai(default)]
+        pub clear_private_key: bool,
         pub ssh_port: Option<u16>,
     }
 
@@ -130,6 +138,16 @@ pub mod types {
         pub result: u64,
     }
 
+    #[derive(Object, Serialize, Deserialize)]
+    pub struct ResetHostKeyReq {
+        pub instance_id: String,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct ResetHostKeyResp {
+        pub result: u64,
+    }
+
     #[derive(Object, Serialize, Default)]
     pub struct GetInstanceStatsResp {
         pub instance_online_num: u64,
@@ -209,6 +227,7 @@ impl InstanceApi {
                 status: v.status,
                 updated_time: local_time!(v.updated_time),
                 sys_user: v.sys_user,
+                host_key: v.host_key,
                 info: v.info,
                 created_time: local_time!(v.created_time),
             })
@@ -363,6 +382,23 @@ impl InstanceApi {
             .transpose()?
             .map_or(NotSet, |v| Set(v));
 
+        let (private_key, passphrase) = if req.clear_private_key {
+            (Set(None), Set(String::new()))
+        } else {
+            (
+                req.private_key
+                    .filter(|v| v.trim() != "")
+                    .map(|v| state.encrypt(v))
+                    .transpose()?
+                    .map_or(NotSet, |v| Set(Some(v))),
+                req.passphrase
+                    .filter(|v| !v.is_empty())
+                    .map(|v| state.encrypt(v))
+                    .transpose()?
+                    .map_or(NotSet, Set),
+            )
+        };
+
         svc.instance
             .save_instance(instance::ActiveModel {
                 id: req.id.filter(|&v| v != 0).map_or(NotSet, |v| Set(v)),
@@ -376,10 +412,13 @@ impl InstanceApi {
                     .filter(|v| v.trim() != "")
                     .map_or(NotSet, |v| Set(v)),
                 password,
+                private_key,
+                passphrase,
                 ssh_port: req.ssh_port.filter(|&v| v != 0).map_or(NotSet, |v| Set(v)),
                 ..Default::default()
             })
             .await?;
+        crate::job::spawn_reconcile_targets(state.clone());
         return_ok!(types::SaveInstanceResp { result: 0 })
     }
 
@@ -489,6 +528,23 @@ impl InstanceApi {
         return_ok!(types::DeleteInstanceGroupResp { result: ret })
     }
 
+    /// forget the pinned ssh host key after the host was reinstalled or re-keyed
+    #[oai(path = "/reset-host-key", method = "post")]
+    pub async fn reset_host_key(
+        &self,
+        state: Data<&AppState>,
+        _session: &Session,
+        user_info: Data<&logic::types::UserInfo>,
+        Json(req): Json<types::ResetHostKeyReq>,
+    ) -> api_response!(types::ResetHostKeyResp) {
+        let svc = state.service();
+        if !state.can_manage_instance(&user_info.user_id).await? {
+            return Err(NoPermission().into());
+        }
+        let ret = svc.instance.reset_host_key(&req.instance_id).await?;
+        return_ok!(types::ResetHostKeyResp { result: ret })
+    }
+
     #[oai(path = "/instance-stats", method = "post")]
     pub async fn get_instance_stats(
         &self,
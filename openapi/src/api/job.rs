@@ -2,16 +2,26 @@ use std::time::Duration;
 
 use crate::{
     api_response, default_local_time,
-    entity::{job, job_bundle_script, job_supervisor},
+    entity::{job, job_bundle_script, job_calendar, job_organizer, job_supervisor, job_trigger},
     error::NoPermission,
     local_time,
-    logic::{self, job::types::BundleScriptRecord},
+    logic::{
+        self,
+        job::types::{BundleScriptRecord, TargetSelector},
+    },
     middleware,
     response::{std_into_error, ApiStdResponse},
-    return_err, return_ok, AppState, IdGenerator,
+    return_err, return_ok, zoned_time, AppState, IdGenerator,
 };
 
-use automate::JobAction;
+use automate::{
+    scheduler::types::{
+        parse_timezone, ticks_after, FreezeWindow, JitterMode, RestartPolicy, RetryBackoff,
+        SupervisorOptions, TimerCalendar, TimerOptions,
+    },
+    JobAction,
+};
+use chrono::{Local, NaiveDate, NaiveDateTime, Utc};
 use poem::{session::Session, web::Data, Endpoint, EndpointExt, Result};
 use poem_openapi::{
     param::{Header, Query},
@@ -20,12 +30,16 @@ use poem_openapi::{
 };
 use sea_orm::{ActiveValue::NotSet, Set};
 use serde_json::json;
+
+pub(crate) use types::EnvVar;
+
 mod types {
     use std::collections::HashMap;
 
+    use automate::scheduler::types::normalize_timer_expr;
     use poem_openapi::{Enum, Object};
 
-    use serde::Serialize;
+    use serde::{Deserialize, Serialize};
     use serde_json::Value;
 
     #[derive(Object, Serialize, Default)]
@@ -45,7 +59,16 @@ mod types {
         pub work_dir: Option<String>,
         pub timeout: Option<u64>,
         pub max_retry: Option<u8>,
+        /// runs allowed at the same time, 0 means unlimited and is the default of a new job
         pub max_parallel: Option<u8>,
+        /// skip, queue or replace when max_parallel runs are in progress
+        #[oai(validator(pattern = r"^(skip|queue|replace)$"))]
+        pub parallel_policy: Option<String>,
+        /// seconds to wait before retrying a failed run
+        pub retry_interval: Option<u64>,
+        /// fixed or exponential
+        #[oai(validator(pattern = r"^(fixed|exponential)$"))]
+        pub retry_backoff: Option<String>,
         pub code: Option<String>,
         pub info: Option<String>,
         pub bundle_script: Option<Vec<BundleScript>>,
@@ -54,6 +77,37 @@ mod types {
         pub is_public: Option<bool>,
         pub display_on_dashboard: Option<bool>,
         pub args: Option<HashMap<String, String>>,
+        /// secret values are encrypted at rest, submit ****** to keep the saved value
+        pub env: Option<Vec<EnvVar>>,
+    }
+
+    #[derive(Object, Serialize, Deserialize, Default, Clone)]
+    pub struct EnvVar {
+        #[oai(validator(pattern = r"^[A-Za-z_][A-Za-z0-9_]*$", max_length = 100))]
+        pub name: String,
+        pub value: String,
+        #[oai(default)]
+        pub is_secret: bool,
+    }
+
+    impl From<EnvVar> for automate::scheduler::types::EnvVar {
+        fn from(v: EnvVar) -> Self {
+            Self {
+                name: v.name,
+                value: v.value,
+                is_secret: v.is_secret,
+            }
+        }
+    }
+
+    impl From<automate::scheduler::types::EnvVar> for EnvVar {
+        fn from(v: automate::scheduler::types::EnvVar) -> Self {
+            Self {
+                name: v.name,
+                value: v.value,
+                is_secret: v.is_secret,
+            }
+        }
     }
 
     #[derive(Object, Serialize, Default)]
@@ -74,6 +128,10 @@ mod types {
         20
     }
 
+    pub fn default_attempt() -> u32 {
+        1
+    }
+
     #[derive(Object, Serialize, Default)]
     pub struct QueryJobResp {
         pub total: u64,
@@ -101,10 +159,14 @@ mod types {
         pub timeout: u64,
         pub max_retry: u8,
         pub max_parallel: u8,
+        pub parallel_policy: String,
+        pub retry_interval: u64,
+        pub retry_backoff: String,
         pub created_user: String,
         pub updated_user: String,
         pub upload_file: String,
         pub args: Option<Value>,
+        pub env: Vec<EnvVar>,
         pub created_time: String,
         pub updated_time: String,
     }
@@ -129,10 +191,19 @@ mod types {
         pub run_status: String,
         pub exit_status: String,
         pub exit_code: i32,
+        pub parallel_decision: String,
+        /// how many times the daemon was restarted since its supervisor started
+        pub restart_count: u32,
+        /// why the last run ended
+        pub termination_reason: String,
+        /// the last liveness probe of a daemon
+        pub probe_result: Option<serde_json::Value>,
         pub dispatch_result: Option<serde_json::Value>,
         pub dispatch_data: Option<serde_json::Value>,
         pub start_time: String,
         pub end_time: String,
+        /// time zone of the timer which next_time and prev_time are shown in
+        pub timezone: String,
         pub next_time: String,
         pub prev_time: String,
         pub updated_user: String,
@@ -150,14 +221,65 @@ mod types {
         pub instance_id: String,
     }
 
+    #[derive(Object, Serialize, Default)]
+    pub struct TargetSelector {
+        #[oai(default)]
+        pub instance_group_ids: Vec<u64>,
+        /// such as env=prod,role=web
+        #[oai(default, validator(max_length = 500))]
+        pub tag_selector: String,
+        #[oai(default)]
+        pub namespaces: Vec<String>,
+    }
+
     #[derive(Object, Serialize, Default)]
     pub struct DispatchJobReq {
         pub schedule_name: String,
         pub schedule_type: String,
+        #[oai(default)]
         pub endpoints: Vec<Endpoint>,
+        /// such as env=prod,role=web, the instances which have all the tags are added to endpoints
+        #[oai(validator(max_length = 500))]
+        pub tag_selector: Option<String>,
+        /// kept on a timer and re-evaluated when instance groups, tags or agents change,
+        /// the timer is started and stopped on the instances which join and leave its targets
+        pub target_selector: Option<TargetSelector>,
         pub eid: String,
         pub timer_expr: Option<TimerExpr>,
+        /// skip, run_once or run_all, what to do with the ticks missed while the agent was offline
+        #[oai(validator(pattern = r"^(skip|run_once|run_all)$"))]
+        pub misfire_policy: Option<String>,
+        /// max catch-up runs of the run_all policy
+        #[oai(validator(maximum(value = "1000")))]
+        pub misfire_limit: Option<u32>,
+        /// IANA time zone of the timer, such as Asia/Shanghai, the agent local zone when empty
+        #[oai(validator(max_length = 50))]
+        pub timezone: Option<String>,
+        /// runs outside the calendar are skipped, also applies to an exec action
+        pub calendar_id: Option<u64>,
+        /// none, random or spread, how the start of each timer run is delayed on the instances
+        #[oai(validator(pattern = r"^(none|random|spread)$"))]
+        pub jitter_mode: Option<String>,
+        /// upper bound of the start delay in seconds
+        #[oai(validator(maximum(value = "3600")))]
+        pub jitter_secs: Option<u64>,
         pub restart_interval: Option<u64>,
+        /// always, on_failure or never, when the daemon is started again after it exited
+        #[oai(validator(pattern = r"^(always|on_failure|never)$"))]
+        pub restart_policy: Option<String>,
+        /// exit codes on_failure does not restart on, only 0 when empty
+        pub expected_exit_codes: Option<Vec<i32>>,
+        /// fixed or exponential, how the restart interval grows with the restarts in the window
+        #[oai(validator(pattern = r"^(fixed|exponential)$"))]
+        pub restart_backoff: Option<String>,
+        /// upper bound of the restart interval in seconds
+        pub max_restart_delay: Option<u64>,
+        /// the daemon becomes fatal after this many restarts within restart_window, 0 means unlimited
+        pub max_restarts: Option<u32>,
+        /// in seconds, 0 counts every restart since the supervisor started
+        pub restart_window: Option<u64>,
+        /// the daemon is killed and restarted when the probe keeps failing
+        pub liveness_probe: Option<LivenessProbe>,
         pub is_sync: bool,
         pub action: String,
     }
@@ -225,15 +347,33 @@ mod types {
         pub bundle_script_result: Option<serde_json::Value>,
         pub exit_status: String,
         pub exit_code: i64,
+        /// exited, timeout, killed_by_user, killed_by_signal, spawn_failed, agent_error, replaced, skipped or liveness_failed
+        pub termination_reason: String,
+        /// the signal that killed the process when termination_reason is killed_by_signal
+        pub term_signal: i32,
+        pub attempt: u32,
+        /// pass it to exec-log to read the log of this attempt
+        pub run_id: String,
+        /// how long a timer run started after its tick, jitter included
+        pub start_offset_ms: u64,
         pub start_time: Option<String>,
         pub end_time: Option<String>,
         pub output: String,
+        pub stderr: String,
         pub created_user: String,
         pub created_time: String,
         pub updated_time: String,
         pub schedule_name: String,
     }
 
+    #[derive(Object, Serialize, Deserialize, Default)]
+    pub struct ReadExecLogResp {
+        pub content: String,
+        pub offset: u64,
+        pub size: u64,
+        pub eof: bool,
+    }
+
     #[derive(Object, Serialize, Default)]
     pub struct QueryExecResp {
         pub total: u64,
@@ -336,6 +476,10 @@ mod types {
         pub executor_name: String,
         pub executor_platform: String,
         pub timer_expr: serde_json::Value,
+        pub timezone: String,
+        pub calendar_id: u64,
+        pub jitter_mode: String,
+        pub jitter_secs: u32,
         pub info: String,
         pub created_user: String,
         pub updated_user: String,
@@ -358,47 +502,206 @@ mod types {
         #[oai(validator(min_length = 1, max_length = 50))]
         pub name: String,
         pub timer_expr: TimerExpr,
+        /// IANA time zone of the timer, such as Asia/Shanghai, the agent local zone when empty
+        #[oai(validator(max_length = 50))]
+        pub timezone: Option<String>,
+        /// calendar of the timer, 0 means no restriction
+        #[oai(default)]
+        pub calendar_id: u64,
+        /// none, random or spread, how the start of each run is delayed on the instances
+        #[oai(validator(pattern = r"^(none|random|spread)$"))]
+        pub jitter_mode: Option<String>,
+        /// upper bound of the start delay in seconds
+        #[oai(default, validator(maximum(value = "3600")))]
+        pub jitter_secs: u32,
         pub info: String,
     }
 
     #[derive(Object, Serialize, Default)]
     pub struct TimerExpr {
+        /// a whole timer expr, such as standard 5-field cron, Quartz cron, @hourly or @every 90s.
+        /// it takes precedence over the separate fields
+        pub expr: Option<String>,
+        #[oai(default)]
         pub second: String,
+        #[oai(default)]
         pub minute: String,
+        #[oai(default)]
         pub hour: String,
+        #[oai(default)]
         pub day_of_month: String,
+        #[oai(default)]
         pub month: String,
+        /// 0-7 with Sunday as 0 or 7, or names such as MON-FRI
+        #[oai(default)]
+        pub day_of_week: String,
+        /// only * is supported, timers saved before day_of_week existed keep their day of week here
+        #[oai(default)]
         pub year: String,
     }
 
-    impl From<String> for TimerExpr {
-        fn from(value: String) -> Self {
-            let vec: Vec<&str> = value.split(" ").collect();
-            Self {
-                second: vec.get(0).map_or("1".to_string(), |&v| v.to_string()),
-                minute: vec.get(1).map_or("1".to_string(), |&v| v.to_string()),
-                hour: vec.get(2).map_or("1".to_string(), |&v| v.to_string()),
-                day_of_month: vec.get(3).map_or("1".to_string(), |&v| v.to_string()),
-                month: vec.get(4).map_or("1".to_string(), |&v| v.to_string()),
-                year: vec.get(5).map_or("1".to_string(), |&v| v.to_string()),
+    impl TimerExpr {
+        /// the normalized timer expr sent to the agent, an empty field means every value
+        /// except the second which means 0
+        pub fn to_expr(&self) -> anyhow::Result<String> {
+            if let Some(expr) = self.expr.as_deref().filter(|v| !v.trim().is_empty()) {
+                return normalize_timer_expr(expr);
+            }
+            // the sixth field of legacy timers was saved as the year
+            let (day_of_week, year) = match (self.day_of_week.trim(), self.year.trim()) {
+                ("", year) => (year, ""),
+                v => v,
+            };
+            if !matches!(year, "" | "*" | "?") {
+                anyhow::bail!("the year field {year} is not supported");
             }
-        }
-    }
 
-    impl Into<String> for TimerExpr {
-        fn into(self) -> String {
-            format!(
+            let field = |v: &str, default: &str| match v.trim() {
+                "" | "?" => default.to_string(),
+                v => v.to_string(),
+            };
+            normalize_timer_expr(&format!(
                 "{} {} {} {} {} {}",
-                self.second, self.minute, self.hour, self.day_of_month, self.month, self.year
-            )
+                field(&self.second, "0"),
+                field(&self.minute, "*"),
+                field(&self.hour, "*"),
+                field(&self.day_of_month, "*"),
+                field(&self.month, "*"),
+                field(day_of_week, "*"),
+            ))
         }
     }
 
+    #[test]
+    fn test_timer_expr() {
+        let legacy = TimerExpr {
+            second: "0".to_string(),
+            minute: "30".to_string(),
+            hour: "9".to_string(),
+            day_of_month: "*".to_string(),
+            month: "*".to_string(),
+            year: "1-5".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(legacy.to_expr().unwrap(), "0 30 9 * * 1-5");
+
+        let timer = TimerExpr {
+            day_of_week: "MON".to_string(),
+            year: "*".to_string(),
+            ..legacy
+        };
+        assert_eq!(timer.to_expr().unwrap(), "0 30 9 * * MON");
+
+        let timer = TimerExpr {
+            year: "2030".to_string(),
+            ..timer
+        };
+        assert!(timer.to_expr().is_err());
+
+        let timer = TimerExpr {
+            expr: Some("@hourly".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(timer.to_expr().unwrap(), "0 0 * * * *");
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct PreviewTimerReq {
+        pub timer_expr: TimerExpr,
+        /// IANA time zone of the timer, the local zone of the console when empty
+        #[oai(validator(max_length = 50))]
+        pub timezone: Option<String>,
+        #[oai(default = "default_preview_count", validator(maximum(value = "100")))]
+        pub count: usize,
+    }
+
+    pub fn default_preview_count() -> usize {
+        5
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct PreviewTimerResp {
+        /// the normalized timer expr
+        pub expr: String,
+        /// next fire times in the time zone of the timer
+        pub times: Vec<String>,
+    }
+
     #[derive(Object, Serialize, Default)]
     pub struct SaveJobTimerResp {
         pub result: u64,
     }
 
+    #[derive(Object, Serialize, Default)]
+    pub struct FreezeWindow {
+        /// local time in the time zone of the timer, such as 2024-10-01T22:00:00
+        pub start: String,
+        pub end: String,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    #[oai(skip_serializing_if_is_none)]
+    pub struct SaveCalendarReq {
+        pub id: Option<u64>,
+        #[oai(validator(min_length = 1, max_length = 100))]
+        pub name: String,
+        /// weekdays allowed with Sunday as 0, empty means every day
+        #[oai(default)]
+        pub weekdays: Vec<u32>,
+        /// dates allowed whatever the weekday, such as 2024-10-12
+        #[oai(default)]
+        pub include_dates: Vec<String>,
+        /// dates never allowed, such as holidays
+        #[oai(default)]
+        pub exclude_dates: Vec<String>,
+        #[oai(default)]
+        pub freeze_windows: Vec<FreezeWindow>,
+        #[oai(validator(min_length = 0, max_length = 500))]
+        #[oai(default)]
+        pub info: String,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct SaveCalendarResp {
+        pub result: u64,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct CalendarRecord {
+        pub id: u64,
+        pub name: String,
+        pub weekdays: Option<Value>,
+        pub include_dates: Option<Value>,
+        pub exclude_dates: Option<Value>,
+        pub freeze_windows: Option<Value>,
+        pub info: String,
+        pub created_user: String,
+        pub updated_user: String,
+        pub created_time: String,
+        pub updated_time: String,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct QueryCalendarResp {
+        pub total: u64,
+        pub list: Vec<CalendarRecord>,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct DeleteCalendarReq {
+        pub id: u64,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct CheckCalendarReq {
+        pub id: u64,
+        /// IANA time zone the time is read in, the local zone of the console when empty
+        #[oai(validator(max_length = 50))]
+        pub timezone: Option<String>,
+        /// such as 2024-10-01 12:00:00, now when empty
+        pub time: Option<String>,
+    }
+
     #[derive(Object, Serialize, Default)]
     pub struct GetDashboardReq {
         // pub eid: String,
@@ -454,6 +757,13 @@ mod types {
         pub team_id: Option<u64>,
         pub team_name: Option<String>,
         pub restart_interval: u64,
+        pub restart_policy: String,
+        pub expected_exit_codes: Option<Value>,
+        pub restart_backoff: String,
+        pub max_restart_delay: u64,
+        pub max_restarts: u32,
+        pub restart_window: u64,
+        pub liveness_probe: Option<Value>,
         pub info: String,
         pub created_user: String,
         pub updated_user: String,
@@ -473,16 +783,289 @@ mod types {
         pub id: Option<u64>,
         pub eid: String,
         pub restart_interval: u64,
+        /// always, on_failure or never, when the daemon is started again after it exited
+        #[oai(validator(pattern = r"^(always|on_failure|never)$"))]
+        pub restart_policy: Option<String>,
+        /// exit codes on_failure does not restart on, only 0 when empty
+        #[oai(default)]
+        pub expected_exit_codes: Vec<i32>,
+        /// fixed or exponential
+        #[oai(validator(pattern = r"^(fixed|exponential)$"))]
+        pub restart_backoff: Option<String>,
+        /// upper bound of the restart interval in seconds, 0 for the default bound
+        #[oai(default)]
+        pub max_restart_delay: u64,
+        /// the daemon becomes fatal after this many restarts within restart_window, 0 means unlimited
+        #[oai(default)]
+        pub max_restarts: u32,
+        /// in seconds, 0 counts every restart since the supervisor started
+        #[oai(default)]
+        pub restart_window: u64,
+        /// the daemon is killed and restarted when the probe keeps failing
+        pub liveness_probe: Option<LivenessProbe>,
         #[oai(validator(min_length = 1, max_length = 50))]
         pub name: String,
         #[oai(validator(min_length = 0, max_length = 500))]
         pub info: String,
     }
 
+    #[derive(Object, Serialize, Deserialize, Default)]
+    pub struct LivenessProbe {
+        /// http, tcp or exec
+        #[oai(validator(pattern = r"^(http|tcp|exec)$"))]
+        pub probe_type: String,
+        /// url of an http probe, host:port of a tcp probe or command line of an exec probe
+        #[oai(validator(min_length = 1, max_length = 500))]
+        pub target: String,
+        /// status expected by an http probe, 200 when 0
+        #[oai(default)]
+        pub expected_status: u16,
+        /// seconds between two probes
+        #[oai(validator(minimum(value = "1")))]
+        pub interval: u64,
+        /// seconds a probe may take before it fails
+        #[oai(validator(minimum(value = "1")))]
+        pub timeout: u64,
+        /// failures in a row before the daemon is killed
+        #[oai(validator(minimum(value = "1")))]
+        pub failure_threshold: u32,
+        /// seconds to wait after the daemon started before the first probe
+        #[oai(default)]
+        pub initial_delay: u64,
+    }
+
+    impl TryFrom<LivenessProbe> for automate::scheduler::types::LivenessProbe {
+        type Error = anyhow::Error;
+
+        fn try_from(value: LivenessProbe) -> anyhow::Result<Self> {
+            Ok(Self {
+                probe_type: value.probe_type.as_str().try_into()?,
+                target: value.target,
+                expected_status: value.expected_status,
+                interval: value.interval,
+                timeout: value.timeout,
+                failure_threshold: value.failure_threshold,
+                initial_delay: value.initial_delay,
+            })
+        }
+    }
+
     #[derive(Object, Serialize, Default)]
     pub struct SaveJobSupervisorResp {
         pub result: u64,
     }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct OrganizerNode {
+        pub id: String,
+        pub name: String,
+        /// start, end or task
+        pub node_type: String,
+        #[oai(default)]
+        pub eid: String,
+        #[oai(default)]
+        pub bind_instance: Vec<String>,
+        /// boolean expression with `exit_code` and `stdout` variables,
+        /// the node outputs true or false when it is set
+        #[oai(default)]
+        pub condition: String,
+        pub props: Option<Value>,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct OrganizerEdge {
+        pub id: String,
+        pub source_node_id: String,
+        pub target_node_id: String,
+        /// empty means the edge is taken when the source node succeeds,
+        /// otherwise it must equal the source node output: success, failure, true or false
+        #[oai(default)]
+        pub edge_val: String,
+        pub props: Option<Value>,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    #[oai(skip_serializing_if_is_none)]
+    pub struct SaveOrganizerReq {
+        pub id: Option<u64>,
+        #[oai(validator(min_length = 1, max_length = 50))]
+        pub name: String,
+        #[oai(validator(min_length = 0, max_length = 500))]
+        pub info: String,
+        pub nodes: Vec<OrganizerNode>,
+        pub edges: Vec<OrganizerEdge>,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct SaveOrganizerResp {
+        pub result: u64,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct OrganizerRecord {
+        pub id: u64,
+        pub name: String,
+        pub info: String,
+        pub nodes: Option<Value>,
+        pub edges: Option<Value>,
+        pub created_user: String,
+        pub updated_user: String,
+        pub created_time: String,
+        pub updated_time: String,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct QueryOrganizerResp {
+        pub total: u64,
+        pub list: Vec<OrganizerRecord>,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct ReleaseOrganizerReq {
+        pub id: u64,
+        #[oai(validator(min_length = 1, max_length = 50))]
+        pub name: String,
+        #[oai(validator(min_length = 0, max_length = 500))]
+        pub info: String,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct ReleaseOrganizerResp {
+        pub version: String,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct StartProcessReq {
+        pub organizer_id: u64,
+        /// the latest release is used if empty
+        pub version: Option<String>,
+        #[oai(validator(min_length = 1, max_length = 50))]
+        pub name: String,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct CancelProcessReq {
+        pub process_id: String,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct StartProcessResp {
+        pub process_id: String,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct ProcessRecord {
+        pub id: u64,
+        pub name: String,
+        pub organizer_id: u64,
+        pub organizer_name: Option<String>,
+        pub organizer_version: String,
+        pub process_id: String,
+        pub status: String,
+        pub current_node: String,
+        pub created_user: String,
+        pub created_time: String,
+        pub updated_time: String,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct QueryProcessResp {
+        pub total: u64,
+        pub list: Vec<ProcessRecord>,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct ProcessTaskRecord {
+        pub node_id: String,
+        pub schedule_id: String,
+        pub status: String,
+        pub output: String,
+        pub bind_total: i32,
+        pub created_time: String,
+        pub updated_time: String,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct ProcessTaskResultRecord {
+        pub node_id: String,
+        pub instance_id: String,
+        pub bind_ip: String,
+        pub exit_code: i32,
+        pub exit_status: String,
+        pub output: String,
+        pub status: String,
+        pub created_time: String,
+        pub updated_time: String,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct GetProcessDetailResp {
+        pub process: ProcessRecord,
+        pub tasks: Vec<ProcessTaskRecord>,
+        pub results: Vec<ProcessTaskResultRecord>,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    #[oai(skip_serializing_if_is_none)]
+    pub struct SaveTriggerReq {
+        pub id: Option<u64>,
+        #[oai(validator(min_length = 1, max_length = 50))]
+        pub name: String,
+        /// upstream job
+        pub eid: String,
+        /// upstream instance, empty means any instance
+        #[oai(default)]
+        pub instance_id: String,
+        /// success, failure or complete
+        #[oai(validator(pattern = r"^(success|failure|complete)$"))]
+        pub trigger_on: String,
+        /// downstream job dispatched when the trigger fires
+        pub target_eid: String,
+        #[oai(validator(min_items = 1))]
+        pub target_instances: Vec<String>,
+        #[oai(default = "default_true")]
+        pub is_enabled: bool,
+        #[oai(validator(min_length = 0, max_length = 500))]
+        #[oai(default)]
+        pub info: String,
+    }
+
+    pub fn default_true() -> bool {
+        true
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct SaveTriggerResp {
+        pub result: u64,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct TriggerRecord {
+        pub id: u64,
+        pub name: String,
+        pub eid: String,
+        pub instance_id: String,
+        pub trigger_on: String,
+        pub target_eid: String,
+        pub target_instances: Option<Value>,
+        pub is_enabled: bool,
+        pub info: String,
+        pub created_user: String,
+        pub updated_user: String,
+        pub created_time: String,
+        pub updated_time: String,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct QueryTriggerResp {
+        pub total: u64,
+        pub list: Vec<TriggerRecord>,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct DeleteTriggerReq {
+        pub id: u64,
+    }
 }
 
 fn set_middleware(ep: impl Endpoint) -> impl Endpoint {
@@ -524,6 +1107,15 @@ impl JobApi {
             .transpose()
             .map_err(std_into_error)?;
 
+        let env = match req.env {
+            Some(v) => Set(Some(
+                svc.job
+                    .encrypt_job_env(req.id, v.into_iter().map(Into::into).collect())
+                    .await?,
+            )),
+            None => NotSet,
+        };
+
         let (job_type, bundle_script) = match req.bundle_script {
             Some(v) => {
                 let list: Vec<BundleScriptRecord> = v
@@ -562,8 +1154,11 @@ impl JobApi {
                 info: Set(req.info.unwrap_or_default()),
                 work_dir: Set(req.work_dir.unwrap_or_default()),
                 work_user: Set(req.work_user.unwrap_or_default()),
-                max_retry: Set(req.max_retry.unwrap_or(1)),
-                max_parallel: Set(req.max_parallel.unwrap_or(1)),
+                max_retry: Set(req.max_retry.unwrap_or(0)),
+                max_parallel: req.max_parallel.map_or(NotSet, Set),
+                parallel_policy: Set(req.parallel_policy.unwrap_or("skip".to_string())),
+                retry_interval: Set(req.retry_interval.unwrap_or(0)),
+                retry_backoff: Set(req.retry_backoff.unwrap_or("fixed".to_string())),
                 timeout: Set(req.timeout.unwrap_or(60)),
                 bundle_script,
                 job_type,
@@ -576,6 +1171,7 @@ impl JobApi {
                 created_user: Set(user_info.username.clone()),
                 updated_user: Set(user_info.username.clone()),
                 args: Set(args),
+                env,
                 team_id: Set(team_id.unwrap_or_default()),
                 ..Default::default()
             })
@@ -655,11 +1251,18 @@ impl JobApi {
                 created_user: v.created_user,
                 updated_user: v.updated_user,
                 args: v.args,
+                env: logic::job::JobLogic::mask_env(v.env)
+                    .into_iter()
+                    .map(Into::into)
+                    .collect(),
                 work_dir: v.work_dir,
                 work_user: v.work_user,
                 timeout: v.timeout,
                 max_retry: v.max_retry,
                 max_parallel: v.max_parallel,
+                parallel_policy: v.parallel_policy,
+                retry_interval: v.retry_interval,
+                retry_backoff: v.retry_backoff,
                 upload_file: v.upload_file,
                 created_time: local_time!(v.created_time),
                 updated_time: local_time!(v.updated_time),
@@ -700,6 +1303,8 @@ impl JobApi {
         let action = req.action.as_str().try_into()?;
         let schedule_type = req.schedule_type.as_str().try_into()?;
         let secret = state.conf.comet_secret.clone();
+        let timezone = req.timezone.unwrap_or_default();
+        parse_timezone(&timezone)?;
 
         if !svc
             .job
@@ -709,19 +1314,82 @@ impl JobApi {
             return Err(NoPermission().into());
         }
 
-        let ret = svc
-            .job
-            .dispatch_job(
+        let calendar = match req.calendar_id.filter(|v| *v > 0) {
+            Some(id) => Some(svc.job.get_timer_calendar(id).await?),
+            None => None,
+        };
+
+        let mut instance_ids: Vec<String> =
+            req.endpoints.into_iter().map(|v| v.instance_id).collect();
+        if let Some(selector) = req.tag_selector.filter(|v| !v.trim().is_empty()) {
+            let matched = svc.tag.resolve_selector(&selector).await?;
+            if matched.is_empty() {
+                return_err!(format!("no instance matches the tag selector {selector}"));
+            }
+            instance_ids.extend(matched);
+            instance_ids.sort();
+            instance_ids.dedup();
+        }
+
+        let target_selector = req.target_selector.map(|v| TargetSelector {
+            instance_ids: instance_ids.clone(),
+            instance_group_ids: v.instance_group_ids,
+            tag_selector: v.tag_selector,
+            namespaces: v.namespaces,
+        });
+
+        let ret = svc
+            .job
+            .dispatch_job(
                 secret,
-                req.endpoints.into_iter().map(|v| v.instance_id).collect(),
+                instance_ids,
                 req.eid,
                 req.is_sync,
                 req.schedule_name,
                 schedule_type,
                 action,
-                req.timer_expr.map(|v| v.into()),
+                req.timer_expr.map(|v| v.to_expr()).transpose()?,
+                TimerOptions {
+                    misfire_policy: req
+                        .misfire_policy
+                        .as_deref()
+                        .map(TryInto::try_into)
+                        .transpose()?
+                        .unwrap_or_default(),
+                    misfire_limit: req.misfire_limit.unwrap_or_default(),
+                    timezone,
+                    calendar,
+                    jitter_mode: req
+                        .jitter_mode
+                        .as_deref()
+                        .map(TryInto::try_into)
+                        .transpose()?
+                        .unwrap_or_default(),
+                    jitter_secs: req.jitter_secs.unwrap_or_default(),
+                },
                 req.restart_interval.map(|v| Duration::from_secs(v)),
+                SupervisorOptions {
+                    restart_policy: req
+                        .restart_policy
+                        .as_deref()
+                        .map(TryInto::try_into)
+                        .transpose()?
+                        .unwrap_or_default(),
+                    expected_exit_codes: req.expected_exit_codes.unwrap_or_default(),
+                    restart_backoff: req
+                        .restart_backoff
+                        .as_deref()
+                        .map(TryInto::try_into)
+                        .transpose()?
+                        .unwrap_or_default(),
+                    max_restart_delay: req.max_restart_delay.unwrap_or_default(),
+                    max_restarts: req.max_restarts.unwrap_or_default(),
+                    restart_window: req.restart_window.unwrap_or_default(),
+                    liveness_probe: req.liveness_probe.map(TryInto::try_into).transpose()?,
+                },
+                target_selector,
                 user_info.username.clone(),
+                None,
             )
             .await?;
         return_ok!(types::DispatchJobResp { result: ret })
@@ -843,40 +1511,54 @@ impl JobApi {
         let list: Vec<types::RunRecord> = ret
             .0
             .into_iter()
-            .map(|v| types::RunRecord {
-                id: v.id,
-                instance_id: v.instance_id,
-                eid: v.eid,
-                executor_id: v.executor_id,
-                executor_name: v.executor_name,
-                team_id: v.team_id,
-                team_name: v.team_name,
-                updated_user: v.updated_user,
-                updated_time: local_time!(v.updated_time),
-                bind_ip: v.bind_ip,
-                bind_namespace: v.bind_namespace,
-                dispatch_data: v.dispatch_data.map(|mut v| {
-                    if let Some(o) = v.as_object_mut() {
-                        o.remove("target");
-                        v
-                    } else {
-                        return v;
-                    }
-                }),
-                schedule_type: v.schedule_type,
-                schedule_id: v.schedule_id,
-                schedule_name: v.schedule_name,
-                schedule_status: v.schedule_status,
-                schedule_snapshot_data: v.schedule_snapshot_data,
-                run_status: v.run_status,
-                exit_status: v.exit_status,
-                exit_code: v.exit_code,
-                job_type: v.job_type,
-                dispatch_result: v.dispatch_result,
-                start_time: v.start_time.map_or("".to_string(), |t| local_time!(t)),
-                end_time: v.end_time.map_or("".to_string(), |t| local_time!(t)),
-                next_time: v.next_time.map_or("".to_string(), |t| local_time!(t)),
-                prev_time: v.prev_time.map_or("".to_string(), |t| local_time!(t)),
+            .map(|v| {
+                let timezone = v
+                    .dispatch_data
+                    .as_ref()
+                    .and_then(|d| d["params"]["timer_options"]["timezone"].as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                let tz = parse_timezone(&timezone).ok().flatten();
+                types::RunRecord {
+                    id: v.id,
+                    instance_id: v.instance_id,
+                    eid: v.eid,
+                    executor_id: v.executor_id,
+                    executor_name: v.executor_name,
+                    team_id: v.team_id,
+                    team_name: v.team_name,
+                    updated_user: v.updated_user,
+                    updated_time: local_time!(v.updated_time),
+                    bind_ip: v.bind_ip,
+                    bind_namespace: v.bind_namespace,
+                    dispatch_data: v.dispatch_data.map(|mut v| {
+                        if let Some(o) = v.as_object_mut() {
+                            o.remove("target");
+                            v
+                        } else {
+                            return v;
+                        }
+                    }),
+                    schedule_type: v.schedule_type,
+                    schedule_id: v.schedule_id,
+                    schedule_name: v.schedule_name,
+                    schedule_status: v.schedule_status,
+                    schedule_snapshot_data: v.schedule_snapshot_data,
+                    run_status: v.run_status,
+                    exit_status: v.exit_status,
+                    exit_code: v.exit_code,
+                    parallel_decision: v.parallel_decision,
+                    restart_count: v.restart_count,
+                    termination_reason: v.termination_reason,
+                    probe_result: v.probe_result,
+                    job_type: v.job_type,
+                    dispatch_result: v.dispatch_result,
+                    start_time: v.start_time.map_or("".to_string(), |t| local_time!(t)),
+                    end_time: v.end_time.map_or("".to_string(), |t| local_time!(t)),
+                    next_time: v.next_time.map_or("".to_string(), |t| zoned_time!(t, tz)),
+                    prev_time: v.prev_time.map_or("".to_string(), |t| zoned_time!(t, tz)),
+                    timezone,
+                }
             })
             .collect();
         return_ok!(types::QueryRunResp {
@@ -960,6 +1642,33 @@ impl JobApi {
         })
     }
 
+    /// Read the untruncated output of a run, available as long as the agent keeps it in its output_dir
+    #[oai(path = "/exec-log", method = "get", transform = "set_middleware")]
+    pub async fn read_exec_log(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        Query(schedule_id): Query<String>,
+        Query(instance_id): Query<String>,
+        #[oai(default = "types::default_attempt")] Query(attempt): Query<u32>,
+        /// run_id of the exec record, the log is found by the attempt when it is empty
+        #[oai(default)]
+        Query(run_id): Query<String>,
+        #[oai(default)] Query(offset): Query<u64>,
+    ) -> Result<ApiStdResponse<types::ReadExecLogResp>> {
+        let svc = state.service();
+        if !svc.job.can_read_schedule(&user_info, &schedule_id).await? {
+            return Err(NoPermission().into());
+        }
+
+        let ret = svc
+            .job
+            .read_job_log(&schedule_id, &instance_id, attempt, &run_id, offset, 0)
+            .await?;
+        let ret: types::ReadExecLogResp = serde_json::from_value(ret).map_err(std_into_error)?;
+        return_ok!(ret)
+    }
+
     #[oai(path = "/exec-list", method = "get", transform = "set_middleware")]
     pub async fn query_exec(
         &self,
@@ -980,6 +1689,10 @@ impl JobApi {
         Query(schedule_type): Query<Option<String>>,
         #[oai(default)] Query(schedule_id): Query<Option<String>>,
         #[oai(default)] Query(eid): Query<Option<String>>,
+        #[oai(validator(
+            custom = "super::OneOfValidator::new(vec![\"exited\",\"timeout\",\"killed_by_user\",\"killed_by_signal\",\"spawn_failed\",\"agent_error\",\"replaced\",\"skipped\",\"liveness_failed\"])"
+        ))]
+        Query(termination_reason): Query<Option<String>>,
 
         /// Search based on time range
         #[oai(validator(max_items = 2, min_items = 2))]
@@ -1012,6 +1725,7 @@ impl JobApi {
                 schedule_name,
                 search_username,
                 instance_id.filter(|v| v != ""),
+                termination_reason,
                 bind_namespace,
                 bind_ip,
                 start_time_range,
@@ -1029,7 +1743,13 @@ impl JobApi {
                 bind_ip: v.ip,
                 exit_status: v.exit_status,
                 exit_code: v.exit_code,
+                termination_reason: v.termination_reason,
+                term_signal: v.term_signal,
+                attempt: v.attempt,
+                run_id: v.run_id,
+                start_offset_ms: v.start_offset_ms,
                 output: v.output,
+                stderr: v.stderr,
                 job_type: v.job_type,
                 team_id: v.team_id,
                 team_name: v.team_name,
@@ -1293,6 +2013,10 @@ impl JobApi {
                 name: v.name,
                 job_name: v.job_name,
                 timer_expr: v.timer_expr.map_or(json!("null"), |v| v),
+                timezone: v.timezone,
+                calendar_id: v.calendar_id,
+                jitter_mode: v.jitter_mode,
+                jitter_secs: v.jitter_secs,
                 job_type: v.job_type,
                 info: v.info,
                 team_id: v.team_id,
@@ -1326,6 +2050,13 @@ impl JobApi {
         if !svc.job.can_write_job(&user_info, team_id, &req.eid).await? {
             return Err(NoPermission().into());
         }
+        let timezone = req.timezone.unwrap_or_default();
+        parse_timezone(&timezone)?;
+        if req.calendar_id > 0 {
+            svc.job.get_timer_calendar(req.calendar_id).await?;
+        }
+        let mut timer_expr = req.timer_expr;
+        timer_expr.expr = Some(timer_expr.to_expr()?);
 
         let ret = svc
             .job
@@ -1334,8 +2065,12 @@ impl JobApi {
                 name: Set(req.name),
                 eid: Set(req.eid),
                 timer_expr: Set(Some(
-                    serde_json::to_value(req.timer_expr).map_err(std_into_error)?,
+                    serde_json::to_value(timer_expr).map_err(std_into_error)?,
                 )),
+                timezone: Set(timezone),
+                calendar_id: Set(req.calendar_id),
+                jitter_mode: Set(req.jitter_mode.unwrap_or(JitterMode::None.to_string())),
+                jitter_secs: Set(req.jitter_secs),
                 job_type: Set(req.job_type),
                 info: Set(req.info),
                 created_user: Set(user_info.username.clone()),
@@ -1349,6 +2084,23 @@ impl JobApi {
         });
     }
 
+    /// normalize the timer expr and compute its next fire times without saving it
+    #[oai(path = "/timer/preview", method = "post", transform = "set_middleware")]
+    pub async fn preview_timer(
+        &self,
+        Json(req): Json<types::PreviewTimerReq>,
+    ) -> Result<ApiStdResponse<types::PreviewTimerResp>> {
+        let expr = req.timer_expr.to_expr()?;
+        let timezone = req.timezone.unwrap_or_default();
+        let tz = parse_timezone(&timezone)?;
+
+        let times = ticks_after(&expr, &timezone, Utc::now())?
+            .take(req.count)
+            .map(|t| zoned_time!(t, tz))
+            .collect();
+        return_ok!(types::PreviewTimerResp { expr, times })
+    }
+
     #[oai(path = "/delete-timer", method = "post", transform = "set_middleware")]
     pub async fn delete_timer(
         &self,
@@ -1483,6 +2235,13 @@ impl JobApi {
                 updated_time: local_time!(v.updated_time),
                 executor_name: v.executor_name,
                 restart_interval: v.restart_interval,
+                restart_policy: v.restart_policy,
+                expected_exit_codes: v.expected_exit_codes,
+                restart_backoff: v.restart_backoff,
+                max_restart_delay: v.max_restart_delay,
+                max_restarts: v.max_restarts,
+                restart_window: v.restart_window,
+                liveness_probe: v.liveness_probe,
                 executor_platform: v.executor_platform,
             })
             .collect();
@@ -1524,6 +2283,17 @@ impl JobApi {
                         req.restart_interval
                     }
                 }),
+                restart_policy: Set(req
+                    .restart_policy
+                    .unwrap_or(RestartPolicy::Always.to_string())),
+                expected_exit_codes: Set(Some(json!(req.expected_exit_codes))),
+                restart_backoff: Set(req
+                    .restart_backoff
+                    .unwrap_or(RetryBackoff::Fixed.to_string())),
+                max_restart_delay: Set(req.max_restart_delay),
+                max_restarts: Set(req.max_restarts),
+                restart_window: Set(req.restart_window),
+                liveness_probe: Set(req.liveness_probe.map(|v| json!(v))),
                 info: Set(req.info),
                 created_user: Set(user_info.username.clone()),
                 updated_user: Set(user_info.username.clone()),
@@ -1535,4 +2305,625 @@ impl JobApi {
             result: ret.id.as_ref().to_owned()
         });
     }
+
+    #[oai(
+        path = "/save-organizer",
+        method = "post",
+        transform = "set_middleware"
+    )]
+    pub async fn save_organizer(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        #[oai(name = "X-Team-Id")] Header(team_id): Header<Option<u64>>,
+        Json(req): Json<types::SaveOrganizerReq>,
+    ) -> api_response!(types::SaveOrganizerResp) {
+        let svc = state.service();
+
+        if let Some(id) = req.id {
+            if !svc.job.can_write_organizer(&user_info, id).await? {
+                return Err(NoPermission().into());
+            }
+        }
+
+        let nodes: Vec<logic::job::types::OrganizerNode> = req
+            .nodes
+            .into_iter()
+            .map(|v| logic::job::types::OrganizerNode {
+                id: v.id,
+                name: v.name,
+                node_type: v.node_type,
+                eid: v.eid,
+                bind_instance: v.bind_instance,
+                condition: v.condition,
+                props: v.props,
+            })
+            .collect();
+        let edges: Vec<logic::job::types::OrganizerEdge> = req
+            .edges
+            .into_iter()
+            .map(|v| logic::job::types::OrganizerEdge {
+                id: v.id,
+                source_node_id: v.source_node_id,
+                target_node_id: v.target_node_id,
+                edge_val: v.edge_val,
+                props: v.props,
+            })
+            .collect();
+
+        for v in nodes.iter().filter(|v| !v.eid.is_empty()) {
+            if !svc.job.can_write_job(&user_info, team_id, &v.eid).await? {
+                return_err!(format!("no permission to use job {}", v.eid));
+            }
+        }
+
+        let ret = svc
+            .job
+            .save_organizer(job_organizer::ActiveModel {
+                id: req.id.map_or(NotSet, Set),
+                name: Set(req.name),
+                info: Set(req.info),
+                nodes: Set(Some(serde_json::to_value(nodes).map_err(std_into_error)?)),
+                edges: Set(Some(serde_json::to_value(edges).map_err(std_into_error)?)),
+                created_user: req.id.map_or(Set(user_info.username.clone()), |_| NotSet),
+                updated_user: Set(user_info.username.clone()),
+                ..Default::default()
+            })
+            .await?;
+
+        return_ok!(types::SaveOrganizerResp {
+            result: ret.id.as_ref().to_owned()
+        });
+    }
+
+    #[oai(path = "/organizer-list", method = "get", transform = "set_middleware")]
+    pub async fn query_organizer(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        #[oai(default)] Query(name): Query<Option<String>>,
+        Query(search_username): Query<Option<String>>,
+        #[oai(default = "types::default_page", validator(maximum(value = "10000")))]
+        Query(page): Query<u64>,
+        #[oai(
+            default = "types::default_page_size",
+            validator(maximum(value = "10000"))
+        )]
+        Query(page_size): Query<u64>,
+    ) -> api_response!(types::QueryOrganizerResp) {
+        let svc = state.service();
+        let search_username = if state.can_manage_job(&user_info.user_id).await? {
+            search_username.as_ref()
+        } else {
+            Some(&user_info.username)
+        };
+
+        let ret = svc
+            .job
+            .query_organizer(
+                search_username,
+                name.filter(|v| !v.is_empty()),
+                page - 1,
+                page_size,
+            )
+            .await?;
+
+        let list = ret
+            .0
+            .into_iter()
+            .map(|v| types::OrganizerRecord {
+                id: v.id,
+                name: v.name,
+                info: v.info,
+                nodes: v.nodes,
+                edges: v.edges,
+                created_user: v.created_user,
+                updated_user: v.updated_user,
+                created_time: local_time!(v.created_time),
+                updated_time: local_time!(v.updated_time),
+            })
+            .collect();
+
+        return_ok!(types::QueryOrganizerResp { total: ret.1, list })
+    }
+
+    #[oai(
+        path = "/release-organizer",
+        method = "post",
+        transform = "set_middleware"
+    )]
+    pub async fn release_organizer(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        Json(req): Json<types::ReleaseOrganizerReq>,
+    ) -> api_response!(types::ReleaseOrganizerResp) {
+        let svc = state.service();
+        if !svc.job.can_write_organizer(&user_info, req.id).await? {
+            return Err(NoPermission().into());
+        }
+
+        let version = svc
+            .job
+            .release_organizer(req.id, req.name, req.info, user_info.username.clone())
+            .await?;
+        return_ok!(types::ReleaseOrganizerResp { version })
+    }
+
+    #[oai(path = "/start-process", method = "post", transform = "set_middleware")]
+    pub async fn start_process(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        Json(req): Json<types::StartProcessReq>,
+    ) -> api_response!(types::StartProcessResp) {
+        let svc = state.service();
+        if !svc
+            .job
+            .can_write_organizer(&user_info, req.organizer_id)
+            .await?
+        {
+            return Err(NoPermission().into());
+        }
+
+        let release = svc
+            .job
+            .get_organizer_release(req.organizer_id, req.version.filter(|v| !v.is_empty()))
+            .await?
+            .ok_or(anyhow::anyhow!("the organizer has not been released"))?;
+
+        let process_id = svc
+            .job
+            .start_process(release, req.name, user_info.username.clone())
+            .await?;
+        return_ok!(types::StartProcessResp { process_id })
+    }
+
+    /// fail a running process whose tasks wait for agents that will never report
+    #[oai(
+        path = "/cancel-process",
+        method = "post",
+        transform = "set_middleware"
+    )]
+    pub async fn cancel_process(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        Json(req): Json<types::CancelProcessReq>,
+    ) -> api_response!(u64) {
+        let svc = state.service();
+        let (process, _, _) = svc.job.get_process_detail(&req.process_id).await?;
+        if !svc
+            .job
+            .can_write_organizer(&user_info, process.organizer_id)
+            .await?
+        {
+            return Err(NoPermission().into());
+        }
+
+        let ret = svc.job.cancel_process(&req.process_id).await?;
+        return_ok!(ret);
+    }
+
+    #[oai(path = "/process-list", method = "get", transform = "set_middleware")]
+    pub async fn query_process(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        Query(organizer_id): Query<Option<u64>>,
+        #[oai(default)] Query(status): Query<Option<String>>,
+        #[oai(default = "types::default_page", validator(maximum(value = "10000")))]
+        Query(page): Query<u64>,
+        #[oai(
+            default = "types::default_page_size",
+            validator(maximum(value = "10000"))
+        )]
+        Query(page_size): Query<u64>,
+    ) -> api_response!(types::QueryProcessResp) {
+        let svc = state.service();
+        let search_username = if state.can_manage_job(&user_info.user_id).await? {
+            None
+        } else {
+            Some(&user_info.username)
+        };
+
+        let ret = svc
+            .job
+            .query_process(
+                search_username,
+                organizer_id,
+                status.filter(|v| !v.is_empty()),
+                page - 1,
+                page_size,
+            )
+            .await?;
+
+        let list = ret
+            .0
+            .into_iter()
+            .map(|v| types::ProcessRecord {
+                id: v.id,
+                name: v.name,
+                organizer_id: v.organizer_id,
+                organizer_name: v.organizer_name,
+                organizer_version: v.organizer_version,
+                process_id: v.process_id,
+                status: v.status,
+                current_node: v.current_node,
+                created_user: v.created_user,
+                created_time: local_time!(v.created_time),
+                updated_time: local_time!(v.updated_time),
+            })
+            .collect();
+
+        return_ok!(types::QueryProcessResp { total: ret.1, list })
+    }
+
+    #[oai(path = "/process-detail", method = "get", transform = "set_middleware")]
+    pub async fn get_process_detail(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        Query(process_id): Query<String>,
+    ) -> api_response!(types::GetProcessDetailResp) {
+        let svc = state.service();
+        let (process, tasks, results) = svc.job.get_process_detail(&process_id).await?;
+
+        if !svc
+            .job
+            .can_write_organizer(&user_info, process.organizer_id)
+            .await?
+        {
+            return Err(NoPermission().into());
+        }
+
+        return_ok!(types::GetProcessDetailResp {
+            process: types::ProcessRecord {
+                id: process.id,
+                name: process.name,
+                organizer_id: process.organizer_id,
+                organizer_name: None,
+                organizer_version: process.organizer_version,
+                process_id: process.process_id,
+                status: process.status,
+                current_node: process.current_node,
+                created_user: process.created_user,
+                created_time: local_time!(process.created_time),
+                updated_time: local_time!(process.updated_time),
+            },
+            tasks: tasks
+                .into_iter()
+                .map(|v| types::ProcessTaskRecord {
+                    node_id: v.node_id,
+                    schedule_id: v.schedule_id,
+                    status: v.status,
+                    output: v.output,
+                    bind_total: v.bind_total,
+                    created_time: local_time!(v.created_time),
+                    updated_time: local_time!(v.updated_time),
+                })
+                .collect(),
+            results: results
+                .into_iter()
+                .map(|v| types::ProcessTaskResultRecord {
+                    node_id: v.node_id,
+                    instance_id: v.instance_id,
+                    bind_ip: v.bind_ip,
+                    exit_code: v.exit_code,
+                    exit_status: v.exit_status,
+                    output: v.output,
+                    status: v.status,
+                    created_time: local_time!(v.created_time),
+                    updated_time: local_time!(v.updated_time),
+                })
+                .collect(),
+        })
+    }
+
+    #[oai(path = "/save-trigger", method = "post", transform = "set_middleware")]
+    pub async fn save_trigger(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        #[oai(name = "X-Team-Id")] Header(team_id): Header<Option<u64>>,
+        Json(req): Json<types::SaveTriggerReq>,
+    ) -> api_response!(types::SaveTriggerResp) {
+        let svc = state.service();
+
+        if let Some(id) = req.id {
+            if !svc.job.can_write_trigger(&user_info, id).await? {
+                return Err(NoPermission().into());
+            }
+        }
+
+        svc.job
+            .check_trigger_cycle(req.id, &req.eid, &req.target_eid)
+            .await?;
+
+        for eid in [&req.eid, &req.target_eid] {
+            if !svc
+                .job
+                .can_dispatch_job(&user_info, team_id, None, eid)
+                .await?
+            {
+                return_err!(format!("no permission to use job {eid}"));
+            }
+        }
+
+        let ret = svc
+            .job
+            .save_trigger(job_trigger::ActiveModel {
+                id: req.id.map_or(NotSet, Set),
+                name: Set(req.name),
+                eid: Set(req.eid),
+                instance_id: Set(req.instance_id),
+                trigger_on: Set(req.trigger_on),
+                target_eid: Set(req.target_eid),
+                target_instances: Set(Some(
+                    serde_json::to_value(req.target_instances).map_err(std_into_error)?,
+                )),
+                is_enabled: Set(req.is_enabled),
+                info: Set(req.info),
+                created_user: req.id.map_or(Set(user_info.username.clone()), |_| NotSet),
+                updated_user: Set(user_info.username.clone()),
+                ..Default::default()
+            })
+            .await?;
+
+        return_ok!(types::SaveTriggerResp {
+            result: ret.id.as_ref().to_owned()
+        });
+    }
+
+    #[oai(path = "/trigger-list", method = "get", transform = "set_middleware")]
+    pub async fn query_trigger(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        /// match triggers of which the job is either upstream or downstream
+        #[oai(default)]
+        Query(eid): Query<Option<String>>,
+        #[oai(default)] Query(name): Query<Option<String>>,
+        Query(search_username): Query<Option<String>>,
+        #[oai(default = "types::default_page", validator(maximum(value = "10000")))]
+        Query(page): Query<u64>,
+        #[oai(
+            default = "types::default_page_size",
+            validator(maximum(value = "10000"))
+        )]
+        Query(page_size): Query<u64>,
+    ) -> api_response!(types::QueryTriggerResp) {
+        let svc = state.service();
+        let search_username = if state.can_manage_job(&user_info.user_id).await? {
+            search_username.as_ref()
+        } else {
+            Some(&user_info.username)
+        };
+
+        let ret = svc
+            .job
+            .query_trigger(
+                search_username,
+                eid.filter(|v| !v.is_empty()),
+                name.filter(|v| !v.is_empty()),
+                page - 1,
+                page_size,
+            )
+            .await?;
+
+        let list = ret
+            .0
+            .into_iter()
+            .map(|v| types::TriggerRecord {
+                id: v.id,
+                name: v.name,
+                eid: v.eid,
+                instance_id: v.instance_id,
+                trigger_on: v.trigger_on,
+                target_eid: v.target_eid,
+                target_instances: v.target_instances,
+                is_enabled: v.is_enabled,
+                info: v.info,
+                created_user: v.created_user,
+                updated_user: v.updated_user,
+                created_time: local_time!(v.created_time),
+                updated_time: local_time!(v.updated_time),
+            })
+            .collect();
+
+        return_ok!(types::QueryTriggerResp { total: ret.1, list })
+    }
+
+    #[oai(
+        path = "/delete-trigger",
+        method = "post",
+        transform = "set_middleware"
+    )]
+    pub async fn delete_trigger(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        Json(req): Json<types::DeleteTriggerReq>,
+    ) -> api_response!(u64) {
+        let svc = state.service();
+        if !svc.job.can_write_trigger(&user_info, req.id).await? {
+            return Err(NoPermission().into());
+        }
+
+        let ret = svc.job.delete_trigger(req.id).await?;
+        return_ok!(ret);
+    }
+
+    #[oai(path = "/save-calendar", method = "post", transform = "set_middleware")]
+    pub async fn save_calendar(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        Json(req): Json<types::SaveCalendarReq>,
+    ) -> api_response!(types::SaveCalendarResp) {
+        let svc = state.service();
+        if let Some(id) = req.id {
+            if !svc.job.can_write_calendar(&user_info, id).await? {
+                return Err(NoPermission().into());
+            }
+        }
+
+        if let Some(v) = req.weekdays.iter().find(|v| **v > 6) {
+            return_err!(format!("invalid weekday {v}, Sunday is 0"));
+        }
+        let parse_date = |v: &String| {
+            v.parse::<NaiveDate>()
+                .map_err(|e| anyhow::anyhow!("invalid date {v} - {e}"))
+        };
+        let parse_time = |v: &String| {
+            NaiveDateTime::parse_from_str(v, "%Y-%m-%dT%H:%M:%S")
+                .or_else(|_| NaiveDateTime::parse_from_str(v, "%Y-%m-%d %H:%M:%S"))
+                .map_err(|e| anyhow::anyhow!("invalid time {v} - {e}"))
+        };
+        let calendar = TimerCalendar {
+            id: req.id.unwrap_or_default(),
+            name: req.name,
+            weekdays: req.weekdays,
+            include_dates: req
+                .include_dates
+                .iter()
+                .map(parse_date)
+                .collect::<anyhow::Result<_>>()?,
+            exclude_dates: req
+                .exclude_dates
+                .iter()
+                .map(parse_date)
+                .collect::<anyhow::Result<_>>()?,
+            freeze_windows: req
+                .freeze_windows
+                .iter()
+                .map(|v| {
+                    Ok(FreezeWindow {
+                        start: parse_time(&v.start)?,
+                        end: parse_time(&v.end)?,
+                    })
+                })
+                .collect::<anyhow::Result<_>>()?,
+        };
+        if calendar.freeze_windows.iter().any(|v| v.start >= v.end) {
+            return_err!("the start of a freeze window must be before its end");
+        }
+
+        let ret = svc
+            .job
+            .save_calendar(job_calendar::ActiveModel {
+                id: req.id.map_or(NotSet, Set),
+                name: Set(calendar.name),
+                weekdays: Set(Some(json!(calendar.weekdays))),
+                include_dates: Set(Some(json!(calendar.include_dates))),
+                exclude_dates: Set(Some(json!(calendar.exclude_dates))),
+                freeze_windows: Set(Some(json!(calendar.freeze_windows))),
+                info: Set(req.info),
+                created_user: req.id.map_or(Set(user_info.username.clone()), |_| NotSet),
+                updated_user: Set(user_info.username.clone()),
+                ..Default::default()
+            })
+            .await?;
+        if let Some(id) = req.id {
+            svc.job.repush_calendar_timers(id).await?;
+        }
+
+        return_ok!(types::SaveCalendarResp {
+            result: ret.id.as_ref().to_owned()
+        });
+    }
+
+    #[oai(path = "/calendar-list", method = "get", transform = "set_middleware")]
+    pub async fn query_calendar(
+        &self,
+        state: Data<&AppState>,
+        #[oai(default)] Query(name): Query<Option<String>>,
+        #[oai(default = "types::default_page", validator(maximum(value = "10000")))]
+        Query(page): Query<u64>,
+        #[oai(
+            default = "types::default_page_size",
+            validator(maximum(value = "10000"))
+        )]
+        Query(page_size): Query<u64>,
+    ) -> api_response!(types::QueryCalendarResp) {
+        let svc = state.service();
+        let ret = svc
+            .job
+            .query_calendar(name.filter(|v| !v.is_empty()), page - 1, page_size)
+            .await?;
+
+        let list = ret
+            .0
+            .into_iter()
+            .map(|v| types::CalendarRecord {
+                id: v.id,
+                name: v.name,
+                weekdays: v.weekdays,
+                include_dates: v.include_dates,
+                exclude_dates: v.exclude_dates,
+                freeze_windows: v.freeze_windows,
+                info: v.info,
+                created_user: v.created_user,
+                updated_user: v.updated_user,
+                created_time: local_time!(v.created_time),
+                updated_time: local_time!(v.updated_time),
+            })
+            .collect();
+
+        return_ok!(types::QueryCalendarResp { total: ret.1, list })
+    }
+
+    #[oai(
+        path = "/delete-calendar",
+        method = "post",
+        transform = "set_middleware"
+    )]
+    pub async fn delete_calendar(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        Json(req): Json<types::DeleteCalendarReq>,
+    ) -> api_response!(u64) {
+        let svc = state.service();
+        if !svc.job.can_write_calendar(&user_info, req.id).await? {
+            return Err(NoPermission().into());
+        }
+
+        let ret = svc.job.delete_calendar(req.id).await?;
+        return_ok!(ret);
+    }
+
+    /// whether the calendar allows a run at the time
+    #[oai(
+        path = "/calendar/check",
+        method = "post",
+        transform = "set_middleware"
+    )]
+    pub async fn check_calendar(
+        &self,
+        state: Data<&AppState>,
+        Json(req): Json<types::CheckCalendarReq>,
+    ) -> api_response!(bool) {
+        let svc = state.service();
+        let calendar = svc.job.get_timer_calendar(req.id).await?;
+        let timezone = req.timezone.unwrap_or_default();
+        let tz = parse_timezone(&timezone)?;
+
+        let time = match req.time.filter(|v| !v.is_empty()) {
+            Some(v) => {
+                let naive = NaiveDateTime::parse_from_str(&v, "%Y-%m-%d %H:%M:%S")
+                    .map_err(|e| anyhow::anyhow!("invalid time {v} - {e}"))?;
+                let time = match tz {
+                    Some(tz) => naive.and_local_timezone(tz).earliest().map(|t| t.to_utc()),
+                    None => naive
+                        .and_local_timezone(Local)
+                        .earliest()
+                        .map(|t| t.to_utc()),
+                };
+                time.ok_or(anyhow::anyhow!("time {v} does not exist in the time zone"))?
+            }
+            None => Utc::now(),
+        };
+        return_ok!(calendar.allows(time, &timezone));
+    }
 }
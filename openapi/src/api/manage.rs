@@ -137,22 +137,7 @@ impl ManageApi {
                 let permissions = state.get_permissions_for_user(&record.user_id).await?;
                 sess.set(
                     UserLogic::SESS_KEY,
-                    logic::types::UserInfo {
-                        username: record.username,
-                        nickname: record.nickname,
-                        avatar: record.avatar,
-                        email: record.email,
-                        is_root: record.is_root,
-                        role_id: record.role_id,
-                        introduction: record.introduction,
-                        phone: record.phone,
-                        created_time: local_time!(record.created_time),
-                        updated_time: local_time!(record.updated_time),
-                        user_id: record.user_id,
-                        gender: record.gender,
-                        permissions,
-                        role: record.role.unwrap_or_default(),
-                    },
+                    UserLogic::user_info(record, permissions),
                 );
             }
             None => (),
@@ -0,0 +1,434 @@
+use chrono::Local;
+use poem::web::Data;
+use poem_openapi::{param::Query, payload::Json, OpenApi};
+use sea_orm::{ActiveValue::NotSet, Set};
+
+use crate::{
+    api_response,
+    entity::{notify_channel, notify_rule},
+    error::NoPermission,
+    local_time,
+    logic::{self, notify::NotifyEvent},
+    response::std_into_error,
+    return_err, return_ok,
+    state::AppState,
+};
+
+mod types {
+    use std::collections::HashMap;
+
+    use poem_openapi::Object;
+    use serde::{Deserialize, Serialize};
+    use serde_json::Value;
+
+    pub fn default_true() -> bool {
+        true
+    }
+
+    #[derive(Object, Serialize, Deserialize, Default)]
+    pub struct ChannelConfig {
+        /// webhook, slack, dingtalk and feishu channels
+        #[oai(default)]
+        pub url: String,
+        /// http method of the webhook, POST by default
+        #[oai(default)]
+        pub method: String,
+        #[oai(default)]
+        pub headers: HashMap<String, String>,
+        /// email recipients
+        #[oai(default)]
+        pub to: Vec<String>,
+        /// email subject template
+        #[oai(default)]
+        pub subject: String,
+        /// {{event_type}}, {{job_name}}, {{eid}}, {{bind_ip}}, {{exit_code}}, {{output}} and
+        /// the other event fields are replaced, an empty template uses the built-in one
+        #[oai(default)]
+        pub template: String,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    #[oai(skip_serializing_if_is_none)]
+    pub struct SaveChannelReq {
+        pub id: Option<u64>,
+        #[oai(validator(min_length = 1, max_length = 50))]
+        pub name: String,
+        /// webhook, email, slack, dingtalk or feishu
+        #[oai(validator(pattern = r"^(webhook|email|slack|dingtalk|feishu)$"))]
+        pub channel_type: String,
+        pub config: ChannelConfig,
+        #[oai(validator(min_length = 0, max_length = 500))]
+        #[oai(default)]
+        pub info: String,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct SaveChannelResp {
+        pub result: u64,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct ChannelRecord {
+        pub id: u64,
+        pub name: String,
+        pub channel_type: String,
+        pub config: Option<Value>,
+        pub info: String,
+        pub created_user: String,
+        pub updated_user: String,
+        pub created_time: String,
+        pub updated_time: String,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct QueryChannelResp {
+        pub total: u64,
+        pub list: Vec<ChannelRecord>,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct DeleteReq {
+        pub id: u64,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct TestChannelReq {
+        pub id: u64,
+        /// event type used to render the test message
+        #[oai(default)]
+        pub event_type: String,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    #[oai(skip_serializing_if_is_none)]
+    pub struct SaveRuleReq {
+        pub id: Option<u64>,
+        #[oai(validator(min_length = 1, max_length = 50))]
+        pub name: String,
+        /// failure, success, timeout, daemon_restart_storm or agent_offline
+        #[oai(validator(
+            pattern = r"^(failure|success|timeout|daemon_restart_storm|agent_offline)$"
+        ))]
+        pub event_type: String,
+        /// job of the rule, empty means any job
+        #[oai(default)]
+        pub eid: String,
+        /// team of the rule, 0 means any team
+        #[oai(default)]
+        pub team_id: u64,
+        pub channel_id: u64,
+        #[oai(default = "default_true")]
+        pub is_enabled: bool,
+        #[oai(validator(min_length = 0, max_length = 500))]
+        #[oai(default)]
+        pub info: String,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct SaveRuleResp {
+        pub result: u64,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct RuleRecord {
+        pub id: u64,
+        pub name: String,
+        pub event_type: String,
+        pub eid: String,
+        pub team_id: u64,
+        pub channel_id: u64,
+        pub is_enabled: bool,
+        pub info: String,
+        pub created_user: String,
+        pub updated_user: String,
+        pub created_time: String,
+        pub updated_time: String,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct QueryRuleResp {
+        pub total: u64,
+        pub list: Vec<RuleRecord>,
+    }
+}
+
+pub struct NotifyApi;
+
+#[OpenApi(prefix_path = "/notify", tag = super::Tag::Notify)]
+impl NotifyApi {
+    #[oai(path = "/save-channel", method = "post")]
+    pub async fn save_channel(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        Json(req): Json<types::SaveChannelReq>,
+    ) -> api_response!(types::SaveChannelResp) {
+        let svc = state.service();
+        if let Some(id) = req.id {
+            if !svc.notify.can_write_channel(&user_info, id).await? {
+                return Err(NoPermission().into());
+            }
+        }
+
+        let ret = svc
+            .notify
+            .save_channel(notify_channel::ActiveModel {
+                id: req.id.map_or(NotSet, Set),
+                name: Set(req.name),
+                channel_type: Set(req.channel_type),
+                config: Set(Some(
+                    serde_json::to_value(req.config).map_err(std_into_error)?,
+                )),
+                info: Set(req.info),
+                created_user: req.id.map_or(Set(user_info.username.clone()), |_| NotSet),
+                updated_user: Set(user_info.username.clone()),
+                ..Default::default()
+            })
+            .await?;
+
+        return_ok!(types::SaveChannelResp {
+            result: ret.id.as_ref().to_owned()
+        });
+    }
+
+    #[oai(path = "/channel-list", method = "get")]
+    pub async fn query_channel(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        #[oai(default)] Query(name): Query<Option<String>>,
+        Query(search_username): Query<Option<String>>,
+        #[oai(
+            default = "crate::api::default_page",
+            validator(maximum(value = "10000"))
+        )]
+        Query(page): Query<u64>,
+        #[oai(
+            default = "crate::api::default_page_size",
+            validator(maximum(value = "10000"))
+        )]
+        Query(page_size): Query<u64>,
+    ) -> api_response!(types::QueryChannelResp) {
+        let svc = state.service();
+        let search_username = if state.can_manage_job(&user_info.user_id).await? {
+            search_username.as_ref()
+        } else {
+            Some(&user_info.username)
+        };
+
+        let ret = svc
+            .notify
+            .query_channel(
+                search_username,
+                name.filter(|v| !v.is_empty()),
+                page - 1,
+                page_size,
+            )
+            .await?;
+
+        let list = ret
+            .0
+            .into_iter()
+            .map(|v| types::ChannelRecord {
+                id: v.id,
+                name: v.name,
+                channel_type: v.channel_type,
+                config: v.config,
+                info: v.info,
+                created_user: v.created_user,
+                updated_user: v.updated_user,
+                created_time: local_time!(v.created_time),
+                updated_time: local_time!(v.updated_time),
+            })
+            .collect();
+
+        return_ok!(types::QueryChannelResp { total: ret.1, list })
+    }
+
+    #[oai(path = "/delete-channel", method = "post")]
+    pub async fn delete_channel(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        Json(req): Json<types::DeleteReq>,
+    ) -> api_response!(u64) {
+        let svc = state.service();
+        if !svc.notify.can_write_channel(&user_info, req.id).await? {
+            return Err(NoPermission().into());
+        }
+
+        let ret = svc.notify.delete_channel(req.id).await?;
+        return_ok!(ret);
+    }
+
+    /// send a sample event through the channel
+    #[oai(path = "/test-channel", method = "post")]
+    pub async fn test_channel(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        Json(req): Json<types::TestChannelReq>,
+    ) -> api_response!(bool) {
+        let svc = state.service();
+        if !svc.notify.can_write_channel(&user_info, req.id).await? {
+            return Err(NoPermission().into());
+        }
+        let Some(channel) = svc.notify.get_channel(req.id).await? else {
+            return_err!("channel not found");
+        };
+
+        svc.notify
+            .send(
+                &channel,
+                &NotifyEvent {
+                    event_type: if req.event_type.is_empty() {
+                        logic::notify::EVENT_FAILURE.to_string()
+                    } else {
+                        req.event_type
+                    },
+                    job_name: "test".to_string(),
+                    output: "this is a test notification".to_string(),
+                    exit_code: 1,
+                    termination_reason: "exited".to_string(),
+                    time: Local::now().to_rfc3339(),
+                    ..Default::default()
+                },
+            )
+            .await?;
+        return_ok!(true);
+    }
+
+    #[oai(path = "/save-rule", method = "post")]
+    pub async fn save_rule(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        Json(req): Json<types::SaveRuleReq>,
+    ) -> api_response!(types::SaveRuleResp) {
+        let svc = state.service();
+        if let Some(id) = req.id {
+            if !svc.notify.can_write_rule(&user_info, id).await? {
+                return Err(NoPermission().into());
+            }
+        }
+
+        if !svc
+            .notify
+            .can_write_channel(&user_info, req.channel_id)
+            .await?
+        {
+            return_err!("no permission to use the channel");
+        }
+
+        let team_id = Some(req.team_id).filter(|v| *v > 0);
+        let ok = if !req.eid.is_empty() {
+            svc.job.can_write_job(&user_info, team_id, &req.eid).await?
+        } else if team_id.is_some() {
+            svc.team
+                .can_read_team(team_id, user_info.user_id.clone())
+                .await?
+        } else {
+            // a rule on every job can only be set by job administrators
+            state.can_manage_job(&user_info.user_id).await?
+        };
+        if !ok {
+            return Err(NoPermission().into());
+        }
+
+        let ret = svc
+            .notify
+            .save_rule(notify_rule::ActiveModel {
+                id: req.id.map_or(NotSet, Set),
+                name: Set(req.name),
+                event_type: Set(req.event_type),
+                eid: Set(req.eid),
+                team_id: Set(req.team_id),
+                channel_id: Set(req.channel_id),
+                is_enabled: Set(req.is_enabled),
+                info: Set(req.info),
+                created_user: req.id.map_or(Set(user_info.username.clone()), |_| NotSet),
+                updated_user: Set(user_info.username.clone()),
+                ..Default::default()
+            })
+            .await?;
+
+        return_ok!(types::SaveRuleResp {
+            result: ret.id.as_ref().to_owned()
+        });
+    }
+
+    #[oai(path = "/rule-list", method = "get")]
+    pub async fn query_rule(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        #[oai(default)] Query(event_type): Query<Option<String>>,
+        #[oai(default)] Query(eid): Query<Option<String>>,
+        Query(search_username): Query<Option<String>>,
+        #[oai(
+            default = "crate::api::default_page",
+            validator(maximum(value = "10000"))
+        )]
+        Query(page): Query<u64>,
+        #[oai(
+            default = "crate::api::default_page_size",
+            validator(maximum(value = "10000"))
+        )]
+        Query(page_size): Query<u64>,
+    ) -> api_response!(types::QueryRuleResp) {
+        let svc = state.service();
+        let search_username = if state.can_manage_job(&user_info.user_id).await? {
+            search_username.as_ref()
+        } else {
+            Some(&user_info.username)
+        };
+
+        let ret = svc
+            .notify
+            .query_rule(
+                search_username,
+                event_type.filter(|v| !v.is_empty()),
+                eid.filter(|v| !v.is_empty()),
+                page - 1,
+                page_size,
+            )
+            .await?;
+
+        let list = ret
+            .0
+            .into_iter()
+            .map(|v| types::RuleRecord {
+                id: v.id,
+                name: v.name,
+                event_type: v.event_type,
+                eid: v.eid,
+                team_id: v.team_id,
+                channel_id: v.channel_id,
+                is_enabled: v.is_enabled,
+                info: v.info,
+                created_user: v.created_user,
+                updated_user: v.updated_user,
+                created_time: local_time!(v.created_time),
+                updated_time: local_time!(v.updated_time),
+            })
+            .collect();
+
+        return_ok!(types::QueryRuleResp { total: ret.1, list })
+    }
+
+    #[oai(path = "/delete-rule", method = "post")]
+    pub async fn delete_rule(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        Json(req): Json<types::DeleteReq>,
+    ) -> api_response!(u64) {
+        let svc = state.service();
+        if !svc.notify.can_write_rule(&user_info, req.id).await? {
+            return Err(NoPermission().into());
+        }
+
+        let ret = svc.notify.delete_rule(req.id).await?;
+        return_ok!(ret);
+    }
+}
@@ -0,0 +1,173 @@
+use poem::web::Data;
+use poem_openapi::{
+    param::Query,
+    payload::{Attachment, AttachmentType, PlainText},
+    OpenApi,
+};
+
+use crate::{api_response, error::NoPermission, local_time, logic, return_ok, state::AppState};
+
+mod types {
+    use poem_openapi::{
+        payload::{Attachment, PlainText},
+        ApiResponse, Object,
+    };
+    use serde::Serialize;
+    use serde_json::Value;
+
+    #[derive(Object, Serialize, Default)]
+    pub struct RecordingRecord {
+        pub id: u64,
+        pub recording_id: String,
+        pub instance_id: String,
+        pub ip: String,
+        pub namespace: String,
+        pub user_id: String,
+        pub username: String,
+        pub sys_user: String,
+        pub via_tunnel: bool,
+        pub file_size: u64,
+        pub width: u32,
+        pub height: u32,
+        pub start_time: String,
+        /// empty while the session is still open
+        pub end_time: String,
+        pub created_time: String,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct QueryRecordingResp {
+        pub total: u64,
+        pub list: Vec<RecordingRecord>,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct ReplayResp {
+        /// asciicast v2 header
+        pub header: Value,
+        /// [time, code, data] events, code o is output and r is resize
+        pub events: Vec<Value>,
+    }
+
+    #[derive(Debug, ApiResponse)]
+    pub enum GetRecordingResponse {
+        #[oai(status = 200)]
+        Ok(Attachment<Vec<u8>>),
+        #[oai(status = 403)]
+        NotAllow,
+        #[oai(status = 500)]
+        InternalError(PlainText<String>),
+    }
+}
+
+pub struct RecordingApi;
+
+#[OpenApi(prefix_path = "/recording", tag = super::Tag::Recording)]
+impl RecordingApi {
+    #[oai(path = "/list", method = "get")]
+    pub async fn query_recording(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        Query(search_username): Query<Option<String>>,
+        Query(instance_id): Query<Option<String>>,
+        #[oai(
+            default = "crate::api::default_page",
+            validator(maximum(value = "10000"))
+        )]
+        Query(page): Query<u64>,
+        #[oai(
+            default = "crate::api::default_page_size",
+            validator(maximum(value = "10000"))
+        )]
+        Query(page_size): Query<u64>,
+    ) -> api_response!(types::QueryRecordingResp) {
+        if !state.can_audit_terminal(&user_info.user_id).await? {
+            return Err(NoPermission().into());
+        }
+
+        let ret = state
+            .service()
+            .recording
+            .query_recording(
+                search_username.filter(|v| !v.is_empty()),
+                instance_id.filter(|v| !v.is_empty()),
+                page - 1,
+                page_size,
+            )
+            .await?;
+
+        let list = ret
+            .0
+            .into_iter()
+            .map(|v| types::RecordingRecord {
+                id: v.id,
+                recording_id: v.recording_id,
+                instance_id: v.instance_id,
+                ip: v.ip,
+                namespace: v.namespace,
+                user_id: v.user_id,
+                username: v.username,
+                sys_user: v.sys_user,
+                via_tunnel: v.via_tunnel,
+                file_size: v.file_size,
+                width: v.width,
+                height: v.height,
+                start_time: v.start_time.map_or("".to_string(), |v| local_time!(v)),
+                end_time: v.end_time.map_or("".to_string(), |v| local_time!(v)),
+                created_time: local_time!(v.created_time),
+            })
+            .collect();
+
+        return_ok!(types::QueryRecordingResp { total: ret.1, list })
+    }
+
+    /// download the asciicast file of the recording
+    #[oai(path = "/download", method = "get")]
+    pub async fn download(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        Query(recording_id): Query<String>,
+    ) -> types::GetRecordingResponse {
+        match state.can_audit_terminal(&user_info.user_id).await {
+            Ok(true) => {}
+            Ok(false) => return types::GetRecordingResponse::NotAllow,
+            Err(e) => {
+                return types::GetRecordingResponse::InternalError(PlainText(e.to_string()));
+            }
+        }
+
+        let svc = state.service();
+        let ret = match svc.recording.get_recording(&recording_id).await {
+            Ok(record) => svc.recording.read_recording(&record).await,
+            Err(e) => Err(e),
+        };
+        match ret {
+            Ok(data) => types::GetRecordingResponse::Ok(
+                Attachment::new(data)
+                    .attachment_type(AttachmentType::Attachment)
+                    .filename(format!("{recording_id}.cast")),
+            ),
+            Err(e) => types::GetRecordingResponse::InternalError(PlainText(e.to_string())),
+        }
+    }
+
+    /// header and events of the recording for the replay player
+    #[oai(path = "/replay", method = "get")]
+    pub async fn replay(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        Query(recording_id): Query<String>,
+    ) -> api_response!(types::ReplayResp) {
+        if !state.can_audit_terminal(&user_info.user_id).await? {
+            return Err(NoPermission().into());
+        }
+
+        let svc = state.service();
+        let record = svc.recording.get_recording(&recording_id).await?;
+        let (header, events) = svc.recording.parse_recording(&record).await?;
+        return_ok!(types::ReplayResp { header, events })
+    }
+}
@@ -0,0 +1,258 @@
+use poem::web::Data;
+use poem_openapi::{param::Query, payload::Json, OpenApi};
+use sea_orm::{ActiveValue::NotSet, Set};
+
+use crate::{
+    api_response,
+    entity::tag,
+    error::NoPermission,
+    local_time,
+    logic::{
+        self,
+        tag::{RESOURCE_INSTANCE, RESOURCE_JOB},
+    },
+    return_err, return_ok,
+    state::AppState,
+};
+
+mod types {
+    use poem_openapi::Object;
+    use serde::Serialize;
+
+    #[derive(Object, Serialize, Default)]
+    #[oai(skip_serializing_if_is_none)]
+    pub struct SaveTagReq {
+        pub id: Option<u64>,
+        #[oai(validator(min_length = 1, max_length = 100))]
+        pub tag_key: String,
+        #[oai(validator(min_length = 1, max_length = 100))]
+        pub tag_val: String,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct SaveTagResp {
+        pub result: u64,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct TagRecord {
+        pub id: u64,
+        pub tag_key: String,
+        pub tag_val: String,
+        /// agent means the tag comes from the labels reported by agents
+        pub created_user: String,
+        pub instance_num: u64,
+        pub job_num: u64,
+        pub created_time: String,
+        pub updated_time: String,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct QueryTagResp {
+        pub total: u64,
+        pub list: Vec<TagRecord>,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct DeleteTagReq {
+        pub id: u64,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct BindTagReq {
+        #[oai(validator(min_items = 1, max_items = 100))]
+        pub tag_ids: Vec<u64>,
+        /// instance or job
+        #[oai(validator(pattern = r"^(instance|job)$"))]
+        pub resource_type: String,
+        /// instance ids of instances, or eids of jobs
+        #[oai(validator(min_items = 1, max_items = 1000))]
+        pub resource_ids: Vec<String>,
+    }
+}
+
+pub struct TagApi;
+
+impl TagApi {
+    /// the resource values of the resources the user may tag
+    async fn resource_vals(
+        state: &AppState,
+        user_info: &logic::types::UserInfo,
+        resource_type: &str,
+        resource_ids: Vec<String>,
+    ) -> poem::Result<Vec<String>> {
+        let svc = state.service();
+        match resource_type {
+            RESOURCE_INSTANCE => {
+                if !state.can_manage_instance(&user_info.user_id).await? {
+                    return Err(NoPermission().into());
+                }
+                Ok(svc.tag.instance_resource_vals(resource_ids).await?)
+            }
+            RESOURCE_JOB => {
+                for eid in &resource_ids {
+                    if !svc.job.can_write_job(user_info, None, eid).await? {
+                        return Err(NoPermission().into());
+                    }
+                }
+                Ok(resource_ids)
+            }
+            _ => return_err!("invalid resource type"),
+        }
+    }
+}
+
+#[OpenApi(prefix_path = "/tag", tag = super::Tag::Tag)]
+impl TagApi {
+    #[oai(path = "/save", method = "post")]
+    pub async fn save_tag(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        Json(req): Json<types::SaveTagReq>,
+    ) -> api_response!(types::SaveTagResp) {
+        let svc = state.service();
+        if let Some(id) = req.id {
+            if !svc.tag.can_write_tag(&user_info, id).await? {
+                return Err(NoPermission().into());
+            }
+        }
+
+        let ret = svc
+            .tag
+            .save_tag(tag::ActiveModel {
+                id: req.id.map_or(NotSet, Set),
+                tag_key: Set(req.tag_key),
+                tag_val: Set(req.tag_val),
+                created_user: req.id.map_or(Set(user_info.username.clone()), |_| NotSet),
+                ..Default::default()
+            })
+            .await?;
+
+        return_ok!(types::SaveTagResp {
+            result: ret.id.as_ref().to_owned()
+        });
+    }
+
+    #[oai(path = "/list", method = "get")]
+    pub async fn query_tag(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        #[oai(default)] Query(keyword): Query<Option<String>>,
+        Query(search_username): Query<Option<String>>,
+        #[oai(
+            default = "crate::api::default_page",
+            validator(maximum(value = "10000"))
+        )]
+        Query(page): Query<u64>,
+        #[oai(
+            default = "crate::api::default_page_size",
+            validator(maximum(value = "10000"))
+        )]
+        Query(page_size): Query<u64>,
+    ) -> api_response!(types::QueryTagResp) {
+        let svc = state.service();
+        let search_username = if state.can_manage_instance(&user_info.user_id).await? {
+            search_username.as_ref()
+        } else {
+            Some(&user_info.username)
+        };
+
+        let ret = svc
+            .tag
+            .query_tag(
+                search_username,
+                keyword.filter(|v| !v.is_empty()),
+                page - 1,
+                page_size,
+            )
+            .await?;
+        let counts = svc
+            .tag
+            .count_resource(ret.0.iter().map(|v| v.id).collect())
+            .await?;
+        let count = |id: u64, resource_type: &str| {
+            counts
+                .get(&(id, resource_type.to_string()))
+                .copied()
+                .unwrap_or_default()
+        };
+
+        let list = ret
+            .0
+            .into_iter()
+            .map(|v| types::TagRecord {
+                instance_num: count(v.id, RESOURCE_INSTANCE),
+                job_num: count(v.id, RESOURCE_JOB),
+                id: v.id,
+                tag_key: v.tag_key,
+                tag_val: v.tag_val,
+                created_user: v.created_user,
+                created_time: local_time!(v.created_time),
+                updated_time: local_time!(v.updated_time),
+            })
+            .collect();
+
+        return_ok!(types::QueryTagResp { total: ret.1, list })
+    }
+
+    #[oai(path = "/delete", method = "post")]
+    pub async fn delete_tag(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        Json(req): Json<types::DeleteTagReq>,
+    ) -> api_response!(u64) {
+        let svc = state.service();
+        if !svc.tag.can_write_tag(&user_info, req.id).await? {
+            return Err(NoPermission().into());
+        }
+
+        let ret = svc.tag.delete_tag(req.id).await?;
+        crate::job::spawn_reconcile_targets(state.clone());
+        return_ok!(ret);
+    }
+
+    /// tag resources in bulk
+    #[oai(path = "/bind", method = "post")]
+    pub async fn bind_tag(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        Json(req): Json<types::BindTagReq>,
+    ) -> api_response!(u64) {
+        let vals =
+            Self::resource_vals(&state, &user_info, &req.resource_type, req.resource_ids).await?;
+        let ret = state
+            .service()
+            .tag
+            .bind_tag(req.tag_ids, &req.resource_type, vals, &user_info.username)
+            .await?;
+        if req.resource_type == RESOURCE_INSTANCE {
+            crate::job::spawn_reconcile_targets(state.clone());
+        }
+        return_ok!(ret);
+    }
+
+    /// untag resources in bulk
+    #[oai(path = "/unbind", method = "post")]
+    pub async fn unbind_tag(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        Json(req): Json<types::BindTagReq>,
+    ) -> api_response!(u64) {
+        let vals =
+            Self::resource_vals(&state, &user_info, &req.resource_type, req.resource_ids).await?;
+        let ret = state
+            .service()
+            .tag
+            .unbind_tag(req.tag_ids, &req.resource_type, vals)
+            .await?;
+        if req.resource_type == RESOURCE_INSTANCE {
+            crate::job::spawn_reconcile_targets(state.clone());
+        }
+        return_ok!(ret);
+    }
+}
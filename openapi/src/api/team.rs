@@ -15,11 +15,15 @@ mod types {
     use poem_openapi::Object;
     use serde::{Deserialize, Serialize};
 
+    use crate::api::job::EnvVar;
+
     #[derive(Object, Serialize, Default)]
     pub struct SaveTeamReq {
         pub id: Option<u64>,
         pub name: String,
         pub info: Option<String>,
+        /// inherited by every job of the team, a job variable of the same name wins
+        pub env: Option<Vec<EnvVar>>,
     }
 
     #[derive(Object, Serialize, Deserialize)]
@@ -55,6 +59,7 @@ mod types {
         pub id: u64,
         pub name: String,
         pub info: String,
+        pub env: Vec<EnvVar>,
         pub user_total: i64,
         pub is_admin: bool,
         pub created_time: String,
@@ -107,12 +112,22 @@ impl TeamApi {
             return_err!("no permission");
         }
 
+        let env = match req.env {
+            Some(v) => Set(Some(
+                svc.job
+                    .encrypt_team_env(req.id, v.into_iter().map(Into::into).collect())
+                    .await?,
+            )),
+            None => NotSet,
+        };
+
         let ret = svc
             .team
             .save_team(team::ActiveModel {
                 name: Set(req.name),
                 id: req.id.map_or(NotSet, |v| Set(v)),
                 info: req.info.map_or(NotSet, |v| Set(v)),
+                env,
                 created_user: req.id.map_or(Set(user_info.username.clone()), |_| NotSet),
                 updated_user: Set(user_info.username.clone()),
                 ..Default::default()
@@ -164,6 +179,10 @@ impl TeamApi {
                 id: v.id,
                 name: v.name,
                 info: v.info,
+                env: logic::job::JobLogic::mask_env(v.env)
+                    .into_iter()
+                    .map(Into::into)
+                    .collect(),
                 is_admin: v.is_admin.unwrap_or(v.created_user == user_info.username),
                 user_total: team_member_count
                     .get_by_team_id(v.id)
@@ -1,20 +1,23 @@
 use std::sync::Arc;
 
-use crate::logic::ssh::{ConnectParams, Session};
+use crate::logic::recording::Recorder;
 use crate::state::AppState;
 use crate::{logic, return_err_to_wsconn};
 
+use automate::bus::Bus;
 use automate::Logic;
 use futures::{SinkExt, StreamExt};
 use poem::http::HeaderMap;
 use poem::session::Session as WebSession;
-use poem::web::websocket::WebSocket;
+use poem::web::websocket::{Message, WebSocket};
 use poem::web::{Data, Path, Query};
 use poem::{handler, FromRequest, IntoResponse, Request};
-use tokio::sync::RwLock;
+use tokio::select;
+use tokio::sync::{Mutex, RwLock};
 use tokio_tungstenite::connect_async;
 
 use tracing::{debug, error};
+use url::form_urlencoded;
 
 pub mod types {
     use serde::{Deserialize, Serialize};
@@ -56,6 +59,7 @@ pub async fn webssh(
     ws: WebSocket,
 ) -> impl IntoResponse {
     let state_clone = state.clone();
+    let user_info = user_info.0.clone();
     let user_id = user_info.user_id.clone();
 
     ws.on_upgrade(move |socket| async move {
@@ -90,33 +94,40 @@ pub async fn webssh(
             }
         };
 
-        let password = match state_clone.decrypt(instance_record.password.unwrap_or_default()) {
+        let mut ssh = match svc
+            .ssh
+            .connect(&instance_record.instance_id, instance_record.ip.clone())
+            .await
+        {
             Ok(v) => v,
             Err(e) => {
                 return_err_to_wsconn!(
                     sink,
-                    format!("Notice: failed decrypt instance password, {e}")
+                    format!("Notice: failed connect to target server, {e}")
                 );
             }
         };
 
-        let mut ssh = match Session::connect(ConnectParams {
-            user: instance_record.sys_user.unwrap_or_default(),
-            password,
-            addrs: (instance_record.ip, instance_record.ssh_port.unwrap_or(22)),
-        })
-        .await
+        let mut recorder = match svc
+            .recording
+            .start(&user_info, &instance_record, false, cols, rows)
+            .await
         {
             Ok(v) => v,
             Err(e) => {
-                return_err_to_wsconn!(
-                    sink,
-                    format!("Notice: failed connect to target server, {e}")
-                );
+                return_err_to_wsconn!(sink, format!("Notice: failed start recording, {e}"));
             }
         };
 
-        let code = match ssh.call("bash", cols, rows, &mut sink, stream).await {
+        let ret = ssh
+            .call("bash", cols, rows, &mut sink, stream, &mut recorder)
+            .await;
+
+        if let Err(e) = svc.recording.finish(recorder).await {
+            error!("failed finish recording - {e}");
+        }
+
+        let code = match ret {
             Ok(v) => v,
             Err(e) => {
                 return_err_to_wsconn!(sink, format!("Notice: connection closed, {e}"));
@@ -141,6 +152,7 @@ pub async fn proxy_webssh(
     Query(types::WebSshQuery { rows, cols }): Query<types::WebSshQuery>,
 ) -> impl IntoResponse {
     let state_clone = state.clone();
+    let user_info = user_info.0.clone();
     let user_id = user_info.user_id.clone();
 
     let ws = WebSocket::from_request_without_body(req)
@@ -198,40 +210,39 @@ pub async fn proxy_webssh(
             }
         };
 
-        let password = match state_clone.decrypt(instance_record.password.unwrap_or_default()) {
+        let cred = match svc
+            .ssh
+            .tunnel_credential(
+                &instance_record.instance_id,
+                instance_record.namespace.clone(),
+                instance_record.ip.clone(),
+                instance_record.mac_addr.clone(),
+            )
+            .await
+        {
             Ok(v) => v,
             Err(e) => {
                 return_err_to_wsconn!(
                     clientsink,
-                    format!("Notice: failed decrypt instance password, {e}")
+                    format!("Notice: failed get ssh login of the instance, {e}")
                 );
             }
         };
 
-        let user = if let Some(v) = instance_record.sys_user {
-            v
-        } else {
-            return_err_to_wsconn!(clientsink, "Notice: failed get system user");
-        };
-
-        let port = if let Some(v) = instance_record.ssh_port {
-            v
-        } else {
-            return_err_to_wsconn!(clientsink, "Notice: failed get system ssh port");
-        };
-
-        let uri = format!(
-            "ws://{}/ssh/tunnel?cols={}&rows={}&user={}&password={}&ip={}&port={}&namespace={}&mac_addr={}",
-            pair.1.comet_addr,
-            cols,
-            rows,
-            user,
-            password,
-            instance_record.ip,
-            port,
-            instance_record.namespace,
-            instance_record.mac_addr,
-        );
+        let query = form_urlencoded::Serializer::new(String::new())
+            .append_pair("cols", &cols.to_string())
+            .append_pair("rows", &rows.to_string())
+            .append_pair("user", &cred.user)
+            .append_pair("password", &cred.auth.password)
+            .append_pair("private_key", &cred.auth.private_key)
+            .append_pair("passphrase", &cred.auth.passphrase)
+            .append_pair("host_key", &cred.host_key)
+            .append_pair("ip", &instance_record.ip)
+            .append_pair("port", &cred.port.to_string())
+            .append_pair("namespace", &instance_record.namespace)
+            .append_pair("mac_addr", &instance_record.mac_addr)
+            .finish();
+        let uri = format!("ws://{}/ssh/tunnel?{query}", pair.1.comet_addr);
 
         let mut ws_request = http::Request::builder()
             .header(
@@ -254,6 +265,18 @@ pub async fn proxy_webssh(
                 );
             }
         };
+        let recorder = match svc
+            .recording
+            .start(&user_info, &instance_record, true, cols, rows)
+            .await
+        {
+            Ok(v) => Arc::new(Mutex::new(Some(v))),
+            Err(e) => {
+                return_err_to_wsconn!(clientsink, format!("Notice: failed start recording, {e}"));
+            }
+        };
+        let client_recorder = recorder.clone();
+
         let (mut serversink, mut serverstream) = serversocket.split();
         let client_live = Arc::new(RwLock::new(true));
         let server_live = client_live.clone();
@@ -266,6 +289,9 @@ pub async fn proxy_webssh(
                         if let poem::web::websocket::Message::Close(_) = msg {
                             break;
                         }
+                        if let Message::Text(ref text) = msg {
+                            record_resize(&client_recorder, text).await;
+                        }
                         if let Err(_) = serversink.send(msg.into()).await {
                             break;
                         }
@@ -287,6 +313,13 @@ pub async fn proxy_webssh(
             while let Some(ret) = serverstream.next().await {
                 match ret {
                     Ok(msg) => {
+                        if let tokio_tungstenite::tungstenite::Message::Text(ref text) = msg {
+                            if let Some(recorder) = recorder.lock().await.as_mut() {
+                                if let Err(e) = recorder.output(text).await {
+                                    error!("failed record output - {e}");
+                                }
+                            }
+                        }
                         if let Err(_) = clientsink.send(msg.into()).await {
                             break;
                         };
@@ -300,6 +333,84 @@ pub async fn proxy_webssh(
             }
             *server_live.write().await = false;
             let _ = clientsink.close().await;
+
+            let Some(recorder) = recorder.lock().await.take() else {
+                return;
+            };
+            if let Err(e) = state_clone.service().recording.finish(recorder).await {
+                error!("failed finish recording - {e}");
+            }
         });
     })
 }
+
+/// record the resize messages sent by the browser terminal
+async fn record_resize(recorder: &Mutex<Option<Recorder>>, text: &str) {
+    let Ok(msg) = serde_json::from_str::<types::Msg>(text) else {
+        return;
+    };
+    if !matches!(msg.r#type, types::MsgType::Resize) {
+        return;
+    }
+    if let Some(recorder) = recorder.lock().await.as_mut() {
+        if let Err(e) = recorder.resize(msg.cols, msg.rows).await {
+            error!("failed record resize - {e}");
+        }
+    }
+}
+
+#[handler]
+pub async fn job_output(
+    Path((schedule_id, instance_id)): Path<(String, String)>,
+    state: Data<&AppState>,
+    user_info: Data<&logic::types::UserInfo>,
+    ws: WebSocket,
+) -> impl IntoResponse {
+    let state_clone = state.clone();
+    let user_info = user_info.0.clone();
+
+    ws.on_upgrade(move |socket| async move {
+        let (mut sink, mut stream) = socket.split();
+
+        match state_clone
+            .service()
+            .job
+            .can_read_schedule(&user_info, &schedule_id)
+            .await
+        {
+            Ok(true) => {}
+            Ok(false) => {
+                return_err_to_wsconn!(sink, "Notice: no permission to read the job output");
+            }
+            Err(e) => {
+                return_err_to_wsconn!(sink, format!("Notice: failed to valid permissions, {e}"));
+            }
+        }
+
+        let output = match Bus::new(state_clone.redis())
+            .subscribe_job_output(&schedule_id, &instance_id)
+            .await
+        {
+            Ok(v) => v,
+            Err(e) => {
+                return_err_to_wsconn!(sink, format!("Notice: failed subscribe job output, {e}"));
+            }
+        };
+        tokio::pin!(output);
+
+        loop {
+            select! {
+                Some(v) = output.next() => {
+                    if let Err(e) = sink.send(Message::Text(v.data)).await {
+                        debug!("job output connection closed - {e}");
+                        return;
+                    }
+                },
+                msg = stream.next() => match msg {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                    _ => {},
+                },
+            }
+        }
+    })
+}
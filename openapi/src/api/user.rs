@@ -2,19 +2,24 @@ use crate::{
     entity::user,
     error::NoPermission,
     local_time,
-    logic::{self, user::UserLogic},
+    logic::{
+        self,
+        user::{OidcSession, UserLogic},
+    },
     response::ApiStdResponse,
-    return_ok, AppState,
+    return_err, return_ok, AppState,
 };
 
 pub struct UserApi;
 
+use anyhow::anyhow;
+use chrono::{Local, NaiveDateTime, Utc};
 use poem::{session::Session, web::Data, Result};
 use poem_openapi::{param::Query, payload::Json, OpenApi};
 use sea_orm::{ActiveValue::NotSet, Set};
 
 pub mod types {
-    use poem_openapi::Object;
+    use poem_openapi::{ApiResponse, Object};
     use serde::{Deserialize, Serialize};
 
     #[derive(Object)]
@@ -101,6 +106,57 @@ pub mod types {
         pub created_time: String,
         pub updated_time: String,
     }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct OidcConfig {
+        pub enable: bool,
+    }
+
+    #[derive(Debug, ApiResponse)]
+    pub enum OidcRedirect {
+        #[oai(status = 302)]
+        Found(#[oai(header = "Location")] String),
+    }
+
+    #[derive(Object, Serialize, Deserialize)]
+    pub struct CreateApiTokenReq {
+        pub name: String,
+        /// `*`, a module such as `job`, or a module with access such as `job:read`
+        pub scopes: Vec<String>,
+        /// the token never expires when it is empty
+        pub expire_time: Option<String>,
+    }
+
+    #[derive(Object, Serialize)]
+    pub struct CreateApiTokenResp {
+        pub id: u64,
+        /// the plain token, it is only shown once
+        pub token: String,
+    }
+
+    #[derive(Object, Serialize, Deserialize)]
+    pub struct RevokeApiTokenReq {
+        pub id: u64,
+    }
+
+    #[derive(Object, Serialize)]
+    pub struct QueryApiTokenResp {
+        pub total: u64,
+        pub list: Vec<ApiTokenRecord>,
+    }
+
+    #[derive(Object, Serialize, Default)]
+    pub struct ApiTokenRecord {
+        pub id: u64,
+        pub name: String,
+        pub token_prefix: String,
+        pub scopes: Vec<String>,
+        pub expire_time: Option<String>,
+        pub is_revoked: bool,
+        pub last_used_time: Option<String>,
+        pub last_used_ip: String,
+        pub created_time: String,
+    }
 }
 
 #[OpenApi(prefix_path = "/user", tag = super::Tag::User)]
@@ -121,24 +177,12 @@ impl UserApi {
 
         let permissions = state.get_permissions_for_user(&login_user.user_id).await?;
 
+        if login_user.ldap_dn.is_some() {
+            session.set(UserLogic::LDAP_CHECKED_KEY, Utc::now().timestamp());
+        }
         session.set(
             UserLogic::SESS_KEY,
-            logic::types::UserInfo {
-                username: login_req.username,
-                nickname: login_user.nickname,
-                avatar: login_user.avatar,
-                email: login_user.email,
-                role_id: login_user.role_id,
-                is_root: login_user.is_root,
-                introduction: login_user.introduction,
-                phone: login_user.phone,
-                created_time: local_time!(login_user.created_time),
-                updated_time: local_time!(login_user.updated_time),
-                user_id: login_user.user_id,
-                gender: login_user.gender,
-                permissions,
-                role: login_user.role.unwrap_or_default(),
-            },
+            UserLogic::user_info(login_user, permissions),
         );
 
         return_ok!(types::Logined {
@@ -146,6 +190,66 @@ impl UserApi {
         });
     }
 
+    /// whether the login page offers single sign-on
+    #[oai(path = "/oidc/config", method = "get")]
+    pub async fn oidc_config(
+        &self,
+        state: Data<&AppState>,
+    ) -> Result<ApiStdResponse<types::OidcConfig>> {
+        return_ok!(types::OidcConfig {
+            enable: state.conf.oidc.enable,
+        })
+    }
+
+    #[oai(path = "/oidc/login", method = "get")]
+    pub async fn oidc_login(
+        &self,
+        session: &Session,
+        state: Data<&AppState>,
+    ) -> Result<types::OidcRedirect> {
+        let conf = &state.conf.oidc;
+        let provider = conf.discover().await?;
+        let oidc_sess = OidcSession::new();
+        let url = conf.authorize_url(&provider, &oidc_sess)?;
+        session.set(OidcSession::SESS_KEY, oidc_sess);
+        Ok(types::OidcRedirect::Found(url))
+    }
+
+    #[oai(path = "/oidc/callback", method = "get")]
+    pub async fn oidc_callback(
+        &self,
+        session: &Session,
+        state: Data<&AppState>,
+        Query(code): Query<Option<String>>,
+        #[oai(name = "state")] Query(oidc_state): Query<Option<String>>,
+        Query(error): Query<Option<String>>,
+    ) -> Result<types::OidcRedirect> {
+        if let Some(e) = error {
+            return_err!(format!("oidc login failed, {e}"));
+        }
+        let Some(oidc_sess) = session.get::<OidcSession>(OidcSession::SESS_KEY) else {
+            return_err!("oidc login expired, please sign in again");
+        };
+        session.remove(OidcSession::SESS_KEY);
+        if oidc_state.as_deref() != Some(oidc_sess.state.as_str()) {
+            return_err!("invalid oidc state");
+        }
+
+        let conf = &state.conf.oidc;
+        let provider = conf.discover().await?;
+        let claims = conf
+            .fetch_claims(&provider, &oidc_sess, &code.unwrap_or_default())
+            .await?;
+
+        let login_user = state.service().user.provision_oidc_user(claims).await?;
+        let permissions = state.get_permissions_for_user(&login_user.user_id).await?;
+        session.set(
+            UserLogic::SESS_KEY,
+            UserLogic::user_info(login_user, permissions),
+        );
+        Ok(types::OidcRedirect::Found("/".to_string()))
+    }
+
     #[oai(path = "/logout", method = "post")]
     pub async fn logout(
         &self,
@@ -251,22 +355,7 @@ impl UserApi {
             Some(record) => {
                 sess.set(
                     UserLogic::SESS_KEY,
-                    logic::types::UserInfo {
-                        username: record.username,
-                        nickname: record.nickname,
-                        avatar: record.avatar,
-                        email: record.email,
-                        role_id: record.role_id,
-                        is_root: record.is_root,
-                        introduction: record.introduction,
-                        phone: record.phone,
-                        created_time: local_time!(record.created_time),
-                        updated_time: local_time!(record.updated_time),
-                        user_id: record.user_id,
-                        gender: record.gender,
-                        permissions,
-                        role: record.role.unwrap_or_default(),
-                    },
+                    UserLogic::user_info(record, permissions),
                 );
             }
             None => (),
@@ -335,4 +424,92 @@ impl UserApi {
             list: list,
         })
     }
+
+    #[oai(path = "/token/create", method = "post")]
+    pub async fn create_api_token(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        Json(req): Json<types::CreateApiTokenReq>,
+    ) -> Result<ApiStdResponse<types::CreateApiTokenResp>> {
+        let expire_time = match req.expire_time.filter(|v| v != "") {
+            Some(v) => Some(
+                NaiveDateTime::parse_from_str(&v, "%Y-%m-%d %H:%M:%S")
+                    .map_err(|e| anyhow!("invalid expire_time {v}, {e}"))?
+                    .and_local_timezone(Local)
+                    .single()
+                    .ok_or(anyhow!("invalid expire_time {v}"))?
+                    .to_utc(),
+            ),
+            None => None,
+        };
+        if expire_time.is_some_and(|v| v <= Utc::now()) {
+            return_err!("expire_time must be in the future");
+        }
+
+        let (id, token) = state
+            .service()
+            .user
+            .create_api_token(&user_info.user_id, req.name, req.scopes, expire_time)
+            .await?;
+        return_ok!(types::CreateApiTokenResp { id, token })
+    }
+
+    #[oai(path = "/token/list", method = "get")]
+    pub async fn query_api_token(
+        &self,
+        state: Data<&AppState>,
+        #[oai(
+            default = "crate::api::default_page_size",
+            validator(maximum(value = "10000"))
+        )]
+        Query(page_size): Query<u64>,
+        #[oai(
+            default = "crate::api::default_page",
+            validator(maximum(value = "10000"))
+        )]
+        Query(page): Query<u64>,
+        user_info: Data<&logic::types::UserInfo>,
+    ) -> Result<ApiStdResponse<types::QueryApiTokenResp>> {
+        let ret = state
+            .service()
+            .user
+            .query_api_token(&user_info.user_id, page - 1, page_size)
+            .await?;
+        let list = ret
+            .0
+            .into_iter()
+            .map(|v| types::ApiTokenRecord {
+                id: v.id,
+                name: v.name,
+                token_prefix: v.token_prefix,
+                scopes: v
+                    .scopes
+                    .and_then(|v| serde_json::from_value(v).ok())
+                    .unwrap_or_default(),
+                expire_time: v.expire_time.map(|v| local_time!(v)),
+                is_revoked: v.is_revoked,
+                last_used_time: v.last_used_time.map(|v| local_time!(v)),
+                last_used_ip: v.last_used_ip,
+                created_time: local_time!(v.created_time),
+            })
+            .collect();
+
+        return_ok!(types::QueryApiTokenResp { total: ret.1, list })
+    }
+
+    #[oai(path = "/token/revoke", method = "post")]
+    pub async fn revoke_api_token(
+        &self,
+        state: Data<&AppState>,
+        user_info: Data<&logic::types::UserInfo>,
+        Json(req): Json<types::RevokeApiTokenReq>,
+    ) -> Result<ApiStdResponse<u64>> {
+        let affected = state
+            .service()
+            .user
+            .revoke_api_token(&user_info.user_id, req.id)
+            .await?;
+        return_ok!(affected)
+    }
 }
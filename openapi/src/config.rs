@@ -1,4 +1,4 @@
-use std::{fs, path::Path};
+use std::{collections::BTreeMap, fs, path::Path};
 
 use anyhow::Result;
 use config::{Config, File};
@@ -15,6 +15,89 @@ pub struct Admin {
     pub password: String,
 }
 
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct Smtp {
+    pub host: String,
+    pub port: Option<u16>,
+    pub username: String,
+    pub password: String,
+    pub from: String,
+    /// tls, starttls or none
+    pub security: String,
+}
+
+/// openid connect single sign-on, the authorization code flow with pkce
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct Oidc {
+    pub enable: bool,
+    /// the endpoints are read from {issuer}/.well-known/openid-configuration
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// the callback registered at the identity provider, such as http://127.0.0.1:9090/api/user/oidc/callback
+    pub redirect_url: String,
+    /// openid, profile and email by default
+    #[serde(default)]
+    pub scopes: Vec<String>,
+    /// claim used as the username, preferred_username by default
+    #[serde(default)]
+    pub username_claim: String,
+    /// claim listing the groups of the user, groups by default
+    #[serde(default)]
+    pub group_claim: String,
+    /// group to role id, the first group of the user which has a role wins
+    #[serde(default)]
+    pub role_mapping: BTreeMap<String, u64>,
+    /// role of a user none of whose groups has a role
+    #[serde(default)]
+    pub default_role_id: u64,
+    /// add the user to the teams named after their groups, and remove them from the teams
+    /// joined that way once they left the group
+    #[serde(default)]
+    pub sync_team: bool,
+}
+
+/// ldap bind authentication, local users which are not in the directory still use their password
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct Ldap {
+    pub enable: bool,
+    /// such as ldap://127.0.0.1:389 or ldaps://ldap.example.com
+    pub url: String,
+    #[serde(default)]
+    pub starttls: bool,
+    /// account used to search users, anonymous when it is empty
+    #[serde(default)]
+    pub bind_dn: String,
+    #[serde(default)]
+    pub bind_password: String,
+    pub search_base: String,
+    /// {username} is replaced with the escaped username, leave disabled accounts out of it,
+    /// such as (&(uid={username})(!(nsAccountLock=TRUE)))
+    pub user_filter: String,
+    /// cn by default
+    #[serde(default)]
+    pub nickname_attr: String,
+    /// mail by default
+    #[serde(default)]
+    pub email_attr: String,
+    /// attribute listing the group dns of the user, memberOf by default
+    #[serde(default)]
+    pub group_attr: String,
+    /// group cn to role id, the first group of the user which has a role wins
+    #[serde(default)]
+    pub role_mapping: BTreeMap<String, u64>,
+    /// role of a user none of whose groups has a role
+    #[serde(default)]
+    pub default_role_id: u64,
+    /// add the user to the teams named after their groups, and remove them from the teams
+    /// joined that way once they left the group
+    #[serde(default)]
+    pub sync_team: bool,
+    /// seconds between two checks of a signed in user against the directory, 0 checks every request
+    #[serde(default)]
+    pub recheck_interval: u64,
+}
+
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct Conf {
     /// if enable debug mode
@@ -27,6 +110,17 @@ pub struct Conf {
     pub comet_secret: String,
     pub database_url: String,
     pub admin: Admin,
+    /// smtp server used by email notifications
+    #[serde(default)]
+    pub smtp: Smtp,
+    /// directory of the webssh session recordings, ~/.jiascheduler/recordings by default
+    #[serde(default)]
+    pub recording_dir: String,
+    /// single sign-on, the password login keeps working next to it
+    #[serde(default)]
+    pub oidc: Oidc,
+    #[serde(default)]
+    pub ldap: Ldap,
     #[serde(skip)]
     config_file: String,
 }
@@ -35,6 +129,15 @@ impl Conf {
     pub fn get_config_file(&self) -> String {
         self.config_file.to_owned()
     }
+
+    pub fn get_recording_dir(&self) -> Result<String> {
+        let dir = if self.recording_dir.is_empty() {
+            "~/.jiascheduler/recordings"
+        } else {
+            &self.recording_dir
+        };
+        Ok(shellexpand::full(dir)?.to_string())
+    }
 }
 
 impl Conf {
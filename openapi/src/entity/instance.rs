@@ -17,7 +17,11 @@ pub struct Model {
     pub status: i8,
     pub sys_user: String,
     pub password: String,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub private_key: Option<String>,
+    pub passphrase: String,
     pub ssh_port: u16,
+    pub host_key: String,
     pub created_time: DateTimeUtc,
     pub updated_time: DateTimeUtc,
 }
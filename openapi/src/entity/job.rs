@@ -24,11 +24,15 @@ pub struct Model {
     pub timeout: u64,
     pub max_retry: u8,
     pub max_parallel: u8,
+    pub parallel_policy: String,
+    pub retry_interval: u64,
+    pub retry_backoff: String,
     pub is_public: i8,
     pub display_on_dashboard: bool,
     pub created_user: String,
     pub updated_user: String,
     pub args: Option<Json>,
+    pub env: Option<Json>,
     pub created_time: DateTimeUtc,
     pub updated_time: DateTimeUtc,
 }
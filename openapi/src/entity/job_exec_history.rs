@@ -15,8 +15,15 @@ pub struct Model {
     pub bundle_script_result: Option<Json>,
     pub exit_status: String,
     pub exit_code: i32,
+    pub termination_reason: String,
+    pub term_signal: i32,
+    pub attempt: u32,
+    pub run_id: String,
+    pub start_offset_ms: u64,
     #[sea_orm(column_type = "Text")]
     pub output: String,
+    #[sea_orm(column_type = "Text")]
+    pub stderr: String,
     pub start_time: Option<DateTimeUtc>,
     pub end_time: Option<DateTimeUtc>,
     pub created_time: DateTimeUtc,
@@ -18,7 +18,7 @@ pub struct Model {
     pub props: Option<Json>,
     #[sea_orm(column_type = "Text")]
     pub condition: String,
-    pub bind_ip: Option<Json>,
+    pub bind_instance: Option<Json>,
     pub created_time: DateTimeUtc,
     pub updated_time: DateTimeUtc,
 }
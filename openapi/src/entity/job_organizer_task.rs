@@ -10,6 +10,7 @@ pub struct Model {
     pub id: u64,
     pub process_id: String,
     pub node_id: String,
+    pub schedule_id: String,
     pub status: String,
     pub output: String,
     pub bind_total: i32,
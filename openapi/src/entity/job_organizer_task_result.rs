@@ -10,8 +10,9 @@ pub struct Model {
     pub id: u64,
     pub process_id: String,
     pub node_id: String,
+    pub instance_id: String,
     pub bind_ip: String,
-    pub exit_code: i8,
+    pub exit_code: i32,
     pub exit_status: String,
     #[sea_orm(column_type = "Text")]
     pub output: String,
@@ -17,6 +17,10 @@ pub struct Model {
     pub run_status: String,
     pub exit_status: String,
     pub exit_code: i32,
+    pub parallel_decision: String,
+    pub restart_count: u32,
+    pub termination_reason: String,
+    pub probe_result: Option<Json>,
     pub dispatch_result: Option<Json>,
     pub start_time: Option<DateTimeUtc>,
     pub end_time: Option<DateTimeUtc>,
@@ -16,6 +16,7 @@ pub struct Model {
     pub schedule_type: String,
     pub action: String,
     pub dispatch_data: Option<Json>,
+    pub target_selector: Option<Json>,
     pub snapshot_data: Option<Json>,
     pub created_user: String,
     pub updated_user: String,
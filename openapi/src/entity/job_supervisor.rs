@@ -12,6 +12,13 @@ pub struct Model {
     pub name: String,
     pub eid: String,
     pub restart_interval: u64,
+    pub restart_policy: String,
+    pub expected_exit_codes: Option<Json>,
+    pub restart_backoff: String,
+    pub max_restart_delay: u64,
+    pub max_restarts: u32,
+    pub restart_window: u64,
+    pub liveness_probe: Option<Json>,
     pub info: String,
     pub created_user: String,
     pub updated_user: String,
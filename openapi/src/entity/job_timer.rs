@@ -12,6 +12,10 @@ pub struct Model {
     pub name: String,
     pub eid: String,
     pub timer_expr: Option<Json>,
+    pub timezone: String,
+    pub calendar_id: u64,
+    pub jitter_mode: String,
+    pub jitter_secs: u32,
     pub job_type: String,
     pub info: String,
     pub created_user: String,
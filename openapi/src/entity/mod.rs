@@ -10,6 +10,7 @@ pub mod instance_group;
 pub mod instance_role;
 pub mod job;
 pub mod job_bundle_script;
+pub mod job_calendar;
 pub mod job_exec_history;
 pub mod job_organizer;
 pub mod job_organizer_process;
@@ -22,10 +23,15 @@ pub mod job_running_status;
 pub mod job_schedule_history;
 pub mod job_supervisor;
 pub mod job_timer;
+pub mod job_trigger;
+pub mod notify_channel;
+pub mod notify_rule;
 pub mod role;
 pub mod tag;
 pub mod tag_resource;
 pub mod team;
 pub mod team_member;
+pub mod terminal_recording;
 pub mod user;
+pub mod user_api_token;
 pub mod user_server;
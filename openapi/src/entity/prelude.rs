@@ -8,6 +8,7 @@ pub use super::instance_group::Entity as InstanceGroup;
 pub use super::instance_role::Entity as InstanceRole;
 pub use super::job::Entity as Job;
 pub use super::job_bundle_script::Entity as JobBundleScript;
+pub use super::job_calendar::Entity as JobCalendar;
 pub use super::job_exec_history::Entity as JobExecHistory;
 pub use super::job_organizer::Entity as JobOrganizer;
 pub use super::job_organizer_process::Entity as JobOrganizerProcess;
@@ -20,10 +21,15 @@ pub use super::job_running_status::Entity as JobRunningStatus;
 pub use super::job_schedule_history::Entity as JobScheduleHistory;
 pub use super::job_supervisor::Entity as JobSupervisor;
 pub use super::job_timer::Entity as JobTimer;
+pub use super::job_trigger::Entity as JobTrigger;
+pub use super::notify_channel::Entity as NotifyChannel;
+pub use super::notify_rule::Entity as NotifyRule;
 pub use super::role::Entity as Role;
 pub use super::tag::Entity as Tag;
 pub use super::tag_resource::Entity as TagResource;
 pub use super::team::Entity as Team;
 pub use super::team_member::Entity as TeamMember;
+pub use super::terminal_recording::Entity as TerminalRecording;
 pub use super::user::Entity as User;
+pub use super::user_api_token::Entity as UserApiToken;
 pub use super::user_server::Entity as UserServer;
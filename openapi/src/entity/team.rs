@@ -11,6 +11,7 @@ pub struct Model {
     #[sea_orm(unique)]
     pub name: String,
     pub info: String,
+    pub env: Option<Json>,
     pub created_user: String,
     pub updated_user: String,
     pub created_time: DateTimeUtc,
@@ -22,6 +22,9 @@ pub struct Model {
     pub phone: String,
     pub gender: String,
     pub introduction: String,
+    #[sea_orm(unique)]
+    pub oidc_subject: Option<String>,
+    pub ldap_dn: Option<String>,
     pub created_time: DateTimeUtc,
     pub updated_time: DateTimeUtc,
 }
@@ -1,12 +1,16 @@
+use std::time::Duration;
+
 use anyhow::Result;
 use automate::{
-    bridge::msg::{AgentOfflineParams, AgentOnlineParams, HeartbeatParams},
+    bridge::msg::{AgentOfflineParams, AgentOnlineParams, HeartbeatParams, UpdateJobParams},
     bus::{Bus, Msg},
 };
 use tracing::{error, info};
 
 use crate::AppState;
 
+const FLOW_TASK_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
 async fn heartbeat(state: AppState, msg: HeartbeatParams) -> Result<()> {
     state
         .service()
@@ -40,30 +44,95 @@ async fn agent_online(state: AppState, msg: AgentOnlineParams) -> Result<()> {
         }
     }
 
-    Ok(svc
-        .instance
+    svc.instance
         .update_status(
             Some(msg.namespace),
-            msg.agent_ip,
-            msg.mac_addr,
+            msg.agent_ip.clone(),
+            msg.mac_addr.clone(),
             1,
             msg.secret_header.assign_user,
             msg.secret_header.ssh_connection_params,
         )
-        .await?)
+        .await?;
+
+    if let Err(e) = svc
+        .tag
+        .sync_agent_labels(&msg.agent_ip, &msg.mac_addr, msg.secret_header.labels)
+        .await
+    {
+        error!("failed sync labels of {} - {e}", msg.agent_ip);
+    }
+
+    svc.job
+        .reconcile_endpoint_targets(&msg.agent_ip, &msg.mac_addr)
+        .await
 }
 
 async fn agent_offline(state: AppState, msg: AgentOfflineParams) -> Result<()> {
     info!("{}:{} offline", msg.agent_ip, msg.mac_addr,);
 
-    Ok(state
+    state
         .service()
         .instance
-        .update_status(None, msg.agent_ip, msg.mac_addr, 0, None, None)
-        .await?)
+        .update_status(
+            None,
+            msg.agent_ip.clone(),
+            msg.mac_addr.clone(),
+            0,
+            None,
+            None,
+        )
+        .await?;
+
+    // notifications are delivered in the background so that a slow channel never blocks the bus
+    tokio::spawn(async move {
+        if let Err(e) = state
+            .service()
+            .notify
+            .notify_agent_offline(msg.agent_ip, msg.mac_addr)
+            .await
+        {
+            error!("failed notify agent offline - {e}");
+        }
+    });
+    Ok(())
+}
+
+async fn update_job(state: AppState, msg: UpdateJobParams) -> Result<()> {
+    let _ = state.service().job.update_job_status(msg.clone()).await?;
+
+    tokio::spawn(async move {
+        if let Err(e) = state.service().notify.notify_job_status(msg).await {
+            error!("failed notify job status - {e}");
+        }
+    });
+    Ok(())
+}
+
+/// reconcile the targets of the timers in the background after instance groups or tags change
+pub fn spawn_reconcile_targets(state: AppState) {
+    tokio::spawn(async move {
+        if let Err(e) = state.service().job.reconcile_targets(None).await {
+            error!("failed reconcile timer targets - {e}");
+        }
+    });
+}
+
+/// fail the flow tasks whose agents never reported
+fn spawn_expire_flow_tasks(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(FLOW_TASK_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = state.service().job.expire_flow_tasks().await {
+                error!("failed expire flow tasks - {e}");
+            }
+        }
+    });
 }
 
 pub async fn start(state: AppState) -> Result<()> {
+    spawn_expire_flow_tasks(state.clone());
     let bus = Bus::new(state.redis().clone());
 
     tokio::spawn(async move {
@@ -73,9 +142,7 @@ pub async fn start(state: AppState) -> Result<()> {
                     let state = state.clone();
                     Box::pin(async move {
                         match msg {
-                            Msg::UpdateJob(v) => {
-                                let _ = state.service().job.update_job_status(v).await?;
-                            }
+                            Msg::UpdateJob(v) => update_job(state.clone(), v).await?,
                             Msg::Heartbeat(v) => {
                                 let _ = heartbeat(state.clone(), v).await?;
                             }
@@ -4,7 +4,8 @@ pub mod response;
 use anyhow::{anyhow, Context, Result};
 use api::{
     executor::ExecutorApi, file::FileApi, instance::InstanceApi, job::JobApi, manage::ManageApi,
-    migration::MigrationApi, role::RoleApi, team::TeamApi, terminal, user::UserApi,
+    migration::MigrationApi, notify::NotifyApi, recording::RecordingApi, role::RoleApi,
+    tag::TagApi, team::TeamApi, terminal, user::UserApi,
 };
 use casbin::{CoreApi, DefaultModel, Enforcer};
 
@@ -85,6 +86,7 @@ impl IdGenerator {
     const FLOW_JOB_PREFIX: &'static str = "f";
     const SCHEDULE_ID_PREFIX: &'static str = "s";
     const INSTANCE_PREFIX: &'static str = "i";
+    const PROCESS_PREFIX: &'static str = "p";
 
     pub fn get_job_eid() -> String {
         Self::get_id(Self::JOB_PREFIX)
@@ -109,6 +111,10 @@ impl IdGenerator {
         Self::get_id(Self::INSTANCE_PREFIX)
     }
 
+    pub fn get_process_uid() -> String {
+        Self::get_id(Self::PROCESS_PREFIX)
+    }
+
     fn get_id(prefix: &str) -> String {
         format!("{prefix}-{}", nanoid!(10)).into()
     }
@@ -270,6 +276,9 @@ pub async fn run(opts: WebapiOptions) -> Result<()> {
             RoleApi,
             MigrationApi,
             ManageApi,
+            NotifyApi,
+            RecordingApi,
+            TagApi,
         ),
         "jiascheduler web api",
         "1.0",
@@ -303,6 +312,10 @@ pub async fn run(opts: WebapiOptions) -> Result<()> {
             "/terminal/tunnel/:instance_id",
             get(terminal::proxy_webssh).with(AuthMiddleware),
         )
+        .at(
+            "/terminal/job-output/:schedule_id/:instance_id",
+            get(terminal::job_output).with(AuthMiddleware),
+        )
         .nest("/api", api_service.with(AuthMiddleware))
         .nest("/doc", ui)
         .catch_all_error(custom_error)
@@ -4,8 +4,11 @@ pub mod executor;
 pub mod instance;
 pub mod job;
 pub mod migration;
+pub mod notify;
+pub mod recording;
 pub(crate) mod role;
 pub mod ssh;
+pub mod tag;
 pub mod team;
 pub mod types;
 pub(crate) mod user;
@@ -1,4 +1,5 @@
 use automate::scheduler::types::SshConnectionOption;
+use automate::ssh::SshAuth;
 use chrono::Local;
 
 use sea_orm::ActiveValue::NotSet;
@@ -21,10 +22,11 @@ use crate::entity::tag;
 use crate::entity::tag_resource;
 use crate::entity::user;
 use crate::entity::{self, instance, instance_group, prelude::*, user_server};
+use crate::logic::tag::RESOURCE_JOB;
 use crate::state::AppContext;
 use crate::state::AppState;
 use crate::IdGenerator;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 
 use super::job::types::InstanceStatSummary;
 use super::types;
@@ -178,6 +180,7 @@ impl<'a> InstanceLogic<'a> {
                 instance::Column::SysUser,
                 instance::Column::SshPort,
                 instance::Column::Password,
+                instance::Column::HostKey,
                 instance::Column::InstanceGroupId,
                 instance::Column::CreatedTime,
                 instance::Column::UpdatedTime,
@@ -367,6 +370,7 @@ impl<'a> InstanceLogic<'a> {
                 query.filter(tag::Column::CreatedUser.eq(v))
             })
             .filter(instance::Column::Id.gt(0))
+            .filter(tag_resource::Column::ResourceType.ne(RESOURCE_JOB))
             .apply_if(tag_id, |query, v| query.filter(tag::Column::Id.is_in(v)));
 
         let total = model.clone().count(&self.ctx.db).await?;
@@ -690,6 +694,55 @@ impl<'a> InstanceLogic<'a> {
         Ok(model)
     }
 
+    /// decrypted ssh login of the instance together with its pinned host key
+    pub async fn get_ssh_credential(&self, instance_id: &str) -> Result<types::SshCredential> {
+        let ins = Instance::find()
+            .filter(instance::Column::InstanceId.eq(instance_id))
+            .one(&self.ctx.db)
+            .await?
+            .ok_or(anyhow!("cannot found instance {instance_id}"))?;
+
+        let decrypt = |v: String| match v.as_str() {
+            "" => Ok(v),
+            _ => self.ctx.decrypt(v),
+        };
+
+        Ok(types::SshCredential {
+            user: ins.sys_user,
+            port: ins.ssh_port,
+            auth: SshAuth {
+                password: decrypt(ins.password)?,
+                private_key: decrypt(ins.private_key.unwrap_or_default())?,
+                passphrase: decrypt(ins.passphrase)?,
+            },
+            host_key: ins.host_key,
+        })
+    }
+
+    /// trust on first use, the fingerprint is only kept when none is pinned yet
+    pub async fn pin_host_key(&self, instance_id: &str, host_key: &str) -> Result<()> {
+        if host_key.is_empty() {
+            return Ok(());
+        }
+        Instance::update_many()
+            .col_expr(instance::Column::HostKey, Expr::value(host_key))
+            .filter(instance::Column::InstanceId.eq(instance_id))
+            .filter(instance::Column::HostKey.eq(""))
+            .exec(&self.ctx.db)
+            .await?;
+        Ok(())
+    }
+
+    /// forget the pinned host key, the next connection pins the key it sees
+    pub async fn reset_host_key(&self, instance_id: &str) -> Result<u64> {
+        let ret = Instance::update_many()
+            .col_expr(instance::Column::HostKey, Expr::value(""))
+            .filter(instance::Column::InstanceId.eq(instance_id))
+            .exec(&self.ctx.db)
+            .await?;
+        Ok(ret.rows_affected)
+    }
+
     pub async fn save_group(
         &self,
         model: instance_group::ActiveModel,
@@ -1,11 +1,17 @@
 use anyhow::Result;
 
 mod bundle_script;
+mod calendar;
 mod dashboard;
+mod env;
 mod exec_history;
+mod misfire;
+mod organizer;
 mod schedule;
 mod supervisor;
+mod target;
 mod timer;
+mod trigger;
 
 use sea_orm::{
     ActiveModelTrait, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
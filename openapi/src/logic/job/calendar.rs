@@ -0,0 +1,230 @@
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Result};
+use automate::{
+    bridge::msg::UpdateJobParams,
+    scheduler::types::{ScheduleStatus, ScheduleType, TimerCalendar},
+    JobAction,
+};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
+    QueryTrait, Set,
+};
+use serde_json::json;
+use tracing::{error, info};
+
+use super::{types::DispatchData, JobLogic};
+use crate::{
+    entity::{job_calendar, job_running_status, job_schedule_history, job_timer, prelude::*},
+    logic::types::UserInfo,
+    IdGenerator,
+};
+
+/// action of the schedule history recording a timer run skipped by its calendar
+pub const ACTION_CALENDAR_SKIPPED: &str = "calendar_skipped";
+
+impl TryFrom<job_calendar::Model> for TimerCalendar {
+    type Error = anyhow::Error;
+
+    fn try_from(value: job_calendar::Model) -> Result<Self> {
+        fn parse<T: serde::de::DeserializeOwned + Default>(
+            v: Option<serde_json::Value>,
+        ) -> Result<T> {
+            Ok(v.map(serde_json::from_value)
+                .transpose()?
+                .unwrap_or_default())
+        }
+        Ok(Self {
+            id: value.id,
+            name: value.name,
+            weekdays: parse(value.weekdays)?,
+            include_dates: parse(value.include_dates)?,
+            exclude_dates: parse(value.exclude_dates)?,
+            freeze_windows: parse(value.freeze_windows)?,
+        })
+    }
+}
+
+impl<'a> JobLogic<'a> {
+    pub async fn save_calendar(
+        &self,
+        active_model: job_calendar::ActiveModel,
+    ) -> Result<job_calendar::ActiveModel> {
+        Ok(active_model.save(&self.ctx.db).await?)
+    }
+
+    pub async fn can_write_calendar(&self, user_info: &UserInfo, id: u64) -> Result<bool> {
+        if self.ctx.can_manage_job(&user_info.user_id).await? {
+            return Ok(true);
+        }
+        let ok = JobCalendar::find()
+            .filter(job_calendar::Column::Id.eq(id))
+            .filter(job_calendar::Column::CreatedUser.eq(&user_info.username))
+            .one(&self.ctx.db)
+            .await?
+            .is_some();
+        Ok(ok)
+    }
+
+    pub async fn query_calendar(
+        &self,
+        name: Option<String>,
+        page: u64,
+        page_size: u64,
+    ) -> Result<(Vec<job_calendar::Model>, u64)> {
+        let model = JobCalendar::find().apply_if(name, |q, v| {
+            q.filter(job_calendar::Column::Name.contains(v))
+        });
+
+        let total = model.clone().count(&self.ctx.db).await?;
+        let list = model
+            .order_by_desc(job_calendar::Column::Id)
+            .paginate(&self.ctx.db, page_size)
+            .fetch_page(page)
+            .await?;
+        Ok((list, total))
+    }
+
+    pub async fn delete_calendar(&self, id: u64) -> Result<u64> {
+        let used = JobTimer::find()
+            .filter(job_timer::Column::CalendarId.eq(id))
+            .count(&self.ctx.db)
+            .await?;
+        if used > 0 {
+            anyhow::bail!("the calendar is used by {used} timers");
+        }
+
+        let ret = JobCalendar::delete_by_id(id).exec(&self.ctx.db).await?;
+        Ok(ret.rows_affected)
+    }
+
+    /// the calendar sent to the agent with the dispatch
+    pub async fn get_timer_calendar(&self, id: u64) -> Result<TimerCalendar> {
+        JobCalendar::find_by_id(id)
+            .one(&self.ctx.db)
+            .await?
+            .ok_or(anyhow!("cannot found calendar {id}"))?
+            .try_into()
+    }
+
+    /// start the running timers using the calendar again, so that the agents get its changes
+    pub async fn repush_calendar_timers(&self, id: u64) -> Result<()> {
+        let calendar = self.get_timer_calendar(id).await?;
+        let schedules = JobScheduleHistory::find()
+            .filter(job_schedule_history::Column::ScheduleType.eq(ScheduleType::Timer.to_string()))
+            .filter(job_schedule_history::Column::Action.eq(JobAction::StartTimer.to_string()))
+            .order_by_desc(job_schedule_history::Column::Id)
+            .all(&self.ctx.db)
+            .await?;
+
+        // a newer timer of the job replaces the older ones on the agents
+        let mut seen = HashSet::new();
+        for record in schedules {
+            if !seen.insert(record.eid.clone()) {
+                continue;
+            }
+            let Some(dispatch_data) = record.dispatch_data else {
+                continue;
+            };
+            let mut dispatch_data: DispatchData = dispatch_data.try_into()?;
+            if dispatch_data
+                .params
+                .timer_options
+                .calendar
+                .as_ref()
+                .map_or(true, |v| v.id != id)
+            {
+                continue;
+            }
+
+            dispatch_data.params.timer_options.calendar = Some(calendar.clone());
+            // the instances where the timer was stopped by hand keep it stopped
+            let scheduling: HashSet<String> = JobRunningStatus::find()
+                .filter(job_running_status::Column::Eid.eq(&record.eid))
+                .filter(job_running_status::Column::ScheduleId.eq(&record.schedule_id))
+                .filter(
+                    job_running_status::Column::ScheduleType.eq(ScheduleType::Timer.to_string()),
+                )
+                .filter(job_running_status::Column::ScheduleStatus.is_in([
+                    ScheduleStatus::Scheduling.to_string(),
+                    ScheduleStatus::Prepare.to_string(),
+                ]))
+                .all(&self.ctx.db)
+                .await?
+                .into_iter()
+                .map(|v| v.instance_id)
+                .collect();
+            let targets: Vec<_> = dispatch_data
+                .target
+                .iter()
+                .filter(|v| scheduling.contains(&v.instance_id))
+                .cloned()
+                .collect();
+
+            if !targets.is_empty() {
+                info!(
+                    "push calendar {} to timer {} again",
+                    calendar.name, record.schedule_id
+                );
+            }
+            let mut params = dispatch_data.params.clone();
+            params.base_job.env = self.get_job_env(&params.base_job.eid).await?;
+            params.action = JobAction::StartTimer;
+            for ret in self.push_dispatch(targets, params).await {
+                match ret {
+                    Ok(v) if v.has_err => error!(
+                        "failed push calendar to timer {} on {} - {}",
+                        record.schedule_id,
+                        v.instance_id,
+                        v.err.unwrap_or_default()
+                    ),
+                    Err(e) => error!("failed push calendar to timer {} - {e}", record.schedule_id),
+                    _ => {}
+                }
+            }
+
+            job_schedule_history::ActiveModel {
+                id: Set(record.id),
+                dispatch_data: Set(Some(serde_json::to_value(&dispatch_data)?)),
+                ..Default::default()
+            }
+            .update(&self.ctx.db)
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// record the timer run skipped by its calendar in the schedule history
+    pub async fn record_calendar_skip(
+        &self,
+        params: &UpdateJobParams,
+        calendar: &str,
+    ) -> Result<()> {
+        let job_type = if params.base_job.bundle_script.is_some() {
+            "bundle"
+        } else {
+            "default"
+        };
+
+        JobScheduleHistory::insert(job_schedule_history::ActiveModel {
+            schedule_id: Set(IdGenerator::get_schedule_uid()),
+            name: Set(format!("calendar-{calendar}")),
+            eid: Set(params.base_job.eid.clone()),
+            job_type: Set(job_type.to_string()),
+            schedule_type: Set(ScheduleType::Timer.to_string()),
+            action: Set(ACTION_CALENDAR_SKIPPED.to_string()),
+            dispatch_result: Set(Some(json!({
+                "instance_id": params.instance_id,
+                "calendar": calendar,
+                "skipped_time": params.prev_time,
+                "timer_schedule_id": params.schedule_id,
+            }))),
+            created_user: Set(params.created_user.clone()),
+            updated_user: Set(params.created_user.clone()),
+            ..Default::default()
+        })
+        .exec(&self.ctx.db)
+        .await?;
+        Ok(())
+    }
+}
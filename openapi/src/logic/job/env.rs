@@ -0,0 +1,184 @@
+use anyhow::{anyhow, Result};
+use automate::scheduler::types::{EnvVar, SECRET_MASK};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::Value;
+
+use super::JobLogic;
+use crate::entity::{job, prelude::*};
+
+impl<'a> JobLogic<'a> {
+    pub async fn encrypt_job_env(&self, id: Option<u64>, env: Vec<EnvVar>) -> Result<Value> {
+        let old_env = match id {
+            Some(id) => Job::find_by_id(id)
+                .one(&self.ctx.db)
+                .await?
+                .and_then(|v| v.env),
+            None => None,
+        };
+        self.encrypt_env(env, old_env)
+    }
+
+    pub async fn encrypt_team_env(&self, id: Option<u64>, env: Vec<EnvVar>) -> Result<Value> {
+        let old_env = match id {
+            Some(id) => Team::find_by_id(id)
+                .one(&self.ctx.db)
+                .await?
+                .and_then(|v| v.env),
+            None => None,
+        };
+        self.encrypt_env(env, old_env)
+    }
+
+    fn encrypt_env(&self, env: Vec<EnvVar>, old_env: Option<Value>) -> Result<Value> {
+        Self::encrypt_env_with(env, old_env, |v| self.ctx.encrypt(v))
+    }
+
+    /// encrypt secret values before saving, a masked secret keeps the stored value of the same name
+    fn encrypt_env_with(
+        env: Vec<EnvVar>,
+        old_env: Option<Value>,
+        encrypt: impl Fn(String) -> Result<String>,
+    ) -> Result<Value> {
+        let old_env = Self::parse_env(old_env)?;
+        let mut ret = Vec::with_capacity(env.len());
+        for mut v in env {
+            if v.is_secret {
+                v.value = match old_env
+                    .iter()
+                    .find(|o| o.is_secret && o.name == v.name && v.value == SECRET_MASK)
+                {
+                    Some(o) => o.value.clone(),
+                    None => encrypt(v.value)?,
+                };
+            }
+            ret.push(v);
+        }
+        Ok(serde_json::to_value(ret)?)
+    }
+
+    /// env list with secret values replaced by the mask, used in api responses
+    pub fn mask_env(env: Option<Value>) -> Vec<EnvVar> {
+        Self::parse_env(env)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|mut v| {
+                if v.is_secret {
+                    v.value = SECRET_MASK.to_string();
+                }
+                v
+            })
+            .collect()
+    }
+
+    /// decrypted env of the job merged over the env of its team
+    pub async fn get_job_env(&self, eid: &str) -> Result<Vec<EnvVar>> {
+        let job_record = Job::find()
+            .filter(job::Column::Eid.eq(eid))
+            .one(&self.ctx.db)
+            .await?
+            .ok_or(anyhow!("cannot found job {}", eid))?;
+
+        let team_env = match job_record.team_id {
+            0 => vec![],
+            team_id => Self::parse_env(
+                Team::find_by_id(team_id)
+                    .one(&self.ctx.db)
+                    .await?
+                    .and_then(|v| v.env),
+            )?,
+        };
+
+        let mut env = Self::merge_env(team_env, Self::parse_env(job_record.env)?);
+        for v in env.iter_mut().filter(|v| v.is_secret) {
+            v.value = self.ctx.decrypt(v.value.clone())?;
+        }
+        Ok(env)
+    }
+
+    /// the env of the job overrides the env of its team with the same name
+    fn merge_env(team_env: Vec<EnvVar>, job_env: Vec<EnvVar>) -> Vec<EnvVar> {
+        let mut env = team_env;
+        for v in job_env {
+            env.retain(|o| o.name != v.name);
+            env.push(v);
+        }
+        env
+    }
+
+    fn parse_env(env: Option<Value>) -> Result<Vec<EnvVar>> {
+        match env {
+            Some(v) if !v.is_null() => Ok(serde_json::from_value(v)?),
+            _ => Ok(vec![]),
+        }
+    }
+}
+
+#[cfg(test)]
+fn env_var(name: &str, value: &str, is_secret: bool) -> EnvVar {
+    EnvVar {
+        name: name.to_string(),
+        value: value.to_string(),
+        is_secret,
+    }
+}
+
+#[test]
+fn test_encrypt_env() {
+    let encrypt = |v: String| Ok(format!("enc({v})"));
+    let stored = JobLogic::encrypt_env_with(
+        vec![
+            env_var("TOKEN", "s3cr3t", true),
+            env_var("PASSWORD", "p4ss", true),
+            env_var("USER", "admin", false),
+        ],
+        None,
+        encrypt,
+    )
+    .unwrap();
+    assert_eq!(
+        JobLogic::parse_env(Some(stored.clone())).unwrap(),
+        vec![
+            env_var("TOKEN", "enc(s3cr3t)", true),
+            env_var("PASSWORD", "enc(p4ss)", true),
+            env_var("USER", "admin", false),
+        ]
+    );
+
+    // the api returns the secrets masked, saving them back keeps the stored values
+    let masked = JobLogic::mask_env(Some(stored.clone()));
+    assert_eq!(masked[0].value, SECRET_MASK);
+    assert_eq!(masked[2].value, "admin");
+
+    let mut env = masked;
+    env[1].value = "changed".to_string();
+    env.push(env_var("KEY", SECRET_MASK, true));
+    let updated = JobLogic::encrypt_env_with(env, Some(stored), encrypt).unwrap();
+    assert_eq!(
+        JobLogic::parse_env(Some(updated)).unwrap(),
+        vec![
+            env_var("TOKEN", "enc(s3cr3t)", true),
+            env_var("PASSWORD", "enc(changed)", true),
+            env_var("USER", "admin", false),
+            env_var("KEY", &format!("enc({SECRET_MASK})"), true),
+        ]
+    );
+}
+
+#[test]
+fn test_merge_env() {
+    let env = JobLogic::merge_env(
+        vec![
+            env_var("REGION", "cn", false),
+            env_var("TOKEN", "team", true),
+        ],
+        vec![env_var("TOKEN", "job", true), env_var("DEBUG", "1", false)],
+    );
+    assert_eq!(
+        env,
+        vec![
+            env_var("REGION", "cn", false),
+            env_var("TOKEN", "job", true),
+            env_var("DEBUG", "1", false),
+        ]
+    );
+}
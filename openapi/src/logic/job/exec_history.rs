@@ -21,6 +21,7 @@ impl<'a> JobLogic<'a> {
         schedule_name: Option<String>,
         username: Option<String>,
         instance_id: Option<String>,
+        termination_reason: Option<String>,
         bind_namespace: Option<String>,
         bind_ip: Option<String>,
         start_time_range: Option<(String, String)>,
@@ -86,6 +87,9 @@ impl<'a> JobLogic<'a> {
             .apply_if(eid, |query, v| {
                 query.filter(job_exec_history::Column::Eid.eq(v))
             })
+            .apply_if(termination_reason, |query, v| {
+                query.filter(job_exec_history::Column::TerminationReason.eq(v))
+            })
             .apply_if(start_time_range, |query, v| {
                 query.filter(
                     job_exec_history::Column::StartTime
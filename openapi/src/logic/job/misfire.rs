@@ -0,0 +1,201 @@
+use anyhow::Result;
+use automate::{
+    scheduler::types::{missed_ticks, ParallelPolicy, ScheduleType},
+    JobAction,
+};
+use chrono::{DateTime, Duration, Utc};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, Set};
+use sea_query::Expr;
+use serde_json::{json, Value};
+use tracing::{error, info};
+
+use super::{
+    types::{DispatchData, DispatchTarget},
+    JobLogic,
+};
+use crate::{
+    entity::{job_running_status, job_schedule_history, prelude::*},
+    IdGenerator,
+};
+
+/// action of the schedule history recording the missed ticks of a timer
+pub const ACTION_MISSED: &str = "missed";
+/// at most this many missed ticks are detected at once
+const MAX_MISSED_TICKS: usize = 1000;
+/// a tick this close to now may be running on the agent and is not missed yet
+const MISFIRE_GRACE_SECS: i64 = 30;
+
+impl<'a> JobLogic<'a> {
+    /// find the ticks of the timer missed since its last run, record them in the schedule history
+    /// and dispatch the catch-up runs asked by the misfire policy
+    pub async fn catch_up_timer(
+        &self,
+        target: DispatchTarget,
+        dispatch_data: &DispatchData,
+        last_tick: DateTime<Utc>,
+    ) -> Result<()> {
+        let params = &dispatch_data.params;
+        let Some(ref timer_expr) = params.timer_expr else {
+            return Ok(());
+        };
+
+        let until = Utc::now() - Duration::seconds(MISFIRE_GRACE_SECS);
+        let mut ticks = missed_ticks(
+            timer_expr,
+            &params.timer_options.timezone,
+            last_tick,
+            until,
+            MAX_MISSED_TICKS,
+        )?;
+        // a tick outside the calendar would have been skipped anyway
+        if let Some(ref calendar) = params.timer_options.calendar {
+            ticks.retain(|t| calendar.allows(*t, &params.timer_options.timezone));
+        }
+        let Some(last_missed) = ticks.last().cloned() else {
+            return Ok(());
+        };
+
+        let runs = params.timer_options.catch_up_runs(ticks.len());
+        let schedule_id = IdGenerator::get_schedule_uid();
+        info!(
+            "timer {} missed {} ticks on {}, {} catch-up runs",
+            params.base_job.eid,
+            ticks.len(),
+            target.ip,
+            runs
+        );
+
+        let mut exec_params = Self::catch_up_params(params, &schedule_id, &target.instance_id);
+
+        let mut results = Vec::with_capacity(runs);
+        for _ in 0..runs {
+            let ret = match self.dispatch_to_target(&target, &exec_params).await {
+                Ok(v) => v,
+                Err(e) => {
+                    error!(
+                        "failed dispatch catch-up run of {} on {} - {e}",
+                        params.base_job.eid, target.ip
+                    );
+                    json!({"code": 50000, "msg": e.to_string()})
+                }
+            };
+            results.push(ret);
+        }
+
+        exec_params.base_job.env.clear();
+        exec_params.base_job.upload_file = None;
+        let job_type = if params.base_job.bundle_script.is_some() {
+            "bundle"
+        } else {
+            "default"
+        };
+
+        JobScheduleHistory::insert(job_schedule_history::ActiveModel {
+            schedule_id: Set(schedule_id),
+            name: Set(format!("missed-{}", ticks.len())),
+            eid: Set(params.base_job.eid.clone()),
+            job_type: Set(job_type.to_string()),
+            schedule_type: Set(ScheduleType::Timer.to_string()),
+            action: Set(ACTION_MISSED.to_string()),
+            dispatch_result: Set(Some(json!({
+                "instance_id": target.instance_id,
+                "missed_ticks": ticks,
+                "misfire_policy": params.timer_options.misfire_policy.to_string(),
+                "catch_up_runs": results,
+            }))),
+            dispatch_data: Set(Some(serde_json::to_value(DispatchData {
+                target: vec![target.clone()],
+                params: exec_params,
+            })?)),
+            created_user: Set(params.created_user.clone()),
+            updated_user: Set(params.created_user.clone()),
+            ..Default::default()
+        })
+        .exec(&self.ctx.db)
+        .await?;
+
+        // the missed ticks are handled, they must not be caught up again on the next reconnect
+        JobRunningStatus::update_many()
+            .col_expr(
+                job_running_status::Column::PrevTime,
+                Expr::value(last_missed),
+            )
+            .filter(job_running_status::Column::Eid.eq(&params.base_job.eid))
+            .filter(job_running_status::Column::InstanceId.eq(&target.instance_id))
+            .filter(job_running_status::Column::ScheduleType.eq(ScheduleType::Timer.to_string()))
+            .exec(&self.ctx.db)
+            .await?;
+        Ok(())
+    }
+
+    /// the catch-up runs are dispatched back to back, they are queued on the agent so that
+    /// max_parallel does not skip them
+    fn catch_up_params(
+        params: &automate::DispatchJobParams,
+        schedule_id: &str,
+        instance_id: &str,
+    ) -> automate::DispatchJobParams {
+        let mut exec_params = params.clone();
+        exec_params.action = JobAction::Exec;
+        exec_params.is_sync = false;
+        exec_params.schedule_id = schedule_id.to_string();
+        exec_params.instance_id = Some(instance_id.to_string());
+        exec_params.base_job.parallel_policy = ParallelPolicy::Queue;
+        exec_params
+    }
+
+    async fn dispatch_to_target(
+        &self,
+        target: &DispatchTarget,
+        params: &automate::DispatchJobParams,
+    ) -> Result<Value> {
+        let pair = automate::Logic::new(self.ctx.redis().clone())
+            .get_link_pair(target.ip.clone(), target.mac_addr.clone())
+            .await?;
+        let body = automate::DispatchJobRequest {
+            agent_ip: target.ip.clone(),
+            mac_addr: target.mac_addr.clone(),
+            dispatch_params: params.clone(),
+        };
+        let ret = self
+            .ctx
+            .http_client
+            .post(format!("http://{}/dispatch", pair.1.comet_addr))
+            .json(&body)
+            .send()
+            .await?
+            .json::<Value>()
+            .await?;
+        Ok(ret)
+    }
+}
+
+#[test]
+fn test_catch_up_params() {
+    let params = automate::DispatchJobParams {
+        base_job: automate::scheduler::types::BaseJob {
+            eid: "eid".to_string(),
+            max_parallel: 1,
+            parallel_policy: ParallelPolicy::Skip,
+            ..Default::default()
+        },
+        schedule_id: "timer".to_string(),
+        instance_id: None,
+        fields: None,
+        timer_expr: Some("0 * * * * *".to_string()),
+        timer_options: Default::default(),
+        restart_interval: None,
+        supervisor_options: Default::default(),
+        is_sync: true,
+        created_user: "admin".to_string(),
+        action: JobAction::StartTimer,
+        schedule_type: None,
+    };
+    let ret = JobLogic::catch_up_params(&params, "missed", "instance_id");
+    assert_eq!(ret.action, JobAction::Exec);
+    assert_eq!(ret.base_job.parallel_policy, ParallelPolicy::Queue);
+    assert_eq!(ret.base_job.max_parallel, 1);
+    assert_eq!(ret.schedule_id, "missed");
+    assert_eq!(ret.instance_id.as_deref(), Some("instance_id"));
+    assert!(!ret.is_sync);
+}
@@ -0,0 +1,1011 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use anyhow::{anyhow, Result};
+use automate::{
+    bridge::msg::UpdateJobParams,
+    scheduler::types::{BaseJob, ScheduleType, SupervisorOptions, TimerOptions},
+    JobAction,
+};
+use chrono::Utc;
+use evalexpr::{eval_boolean_with_context, ContextWithMutableVariables, HashMapContext};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, JoinType, PaginatorTrait, QueryFilter, QueryOrder,
+    QuerySelect, QueryTrait, Set, SqlErr, TransactionTrait,
+};
+use sea_query::OnConflict;
+use serde_json::json;
+use tracing::error;
+
+use crate::{
+    entity::{
+        instance, job, job_organizer, job_organizer_process, job_organizer_release,
+        job_organizer_release_edge, job_organizer_release_node, job_organizer_task,
+        job_organizer_task_result, prelude::*,
+    },
+    logic::types::UserInfo,
+    IdGenerator,
+};
+
+use super::{
+    types::{FlowTaskData, OrganizerEdge, OrganizerNode, OrganizerProcessModel},
+    JobLogic, JobOrganizer, JobOrganizerProcess, JobOrganizerRelease, JobOrganizerReleaseEdge,
+    JobOrganizerReleaseNode, JobOrganizerTask, JobOrganizerTaskResult,
+};
+
+pub const NODE_START: &str = "start";
+pub const NODE_END: &str = "end";
+pub const NODE_TASK: &str = "task";
+
+pub const PROCESS_RUNNING: &str = "running";
+pub const PROCESS_FINISHED: &str = "finished";
+pub const PROCESS_FAILED: &str = "failed";
+
+pub const TASK_RUNNING: &str = "running";
+pub const TASK_SUCCESS: &str = "success";
+pub const TASK_FAILURE: &str = "failure";
+pub const TASK_SKIPPED: &str = "skipped";
+
+/// output of a task failed because its agents did not report in time
+pub const TASK_OUTPUT_TIMEOUT: &str = "timeout";
+/// output of a task failed by canceling its process
+pub const TASK_OUTPUT_CANCELED: &str = "canceled";
+/// time allowed to an agent for reporting a result after the job timed out
+const FLOW_TASK_GRACE_SECS: u64 = 300;
+
+/// how long the agents may take to report a flow task before it fails, None if the job has
+/// no timeout and may run forever
+fn flow_task_deadline(job: &BaseJob) -> Option<std::time::Duration> {
+    if job.timeout == 0 {
+        return None;
+    }
+    let attempts = u32::from(job.max_retry) + 1;
+    let retry_delay = (1..attempts)
+        .map(|v| job.retry_delay(v))
+        .sum::<std::time::Duration>();
+    Some(
+        std::time::Duration::from_secs(job.timeout * u64::from(attempts) + FLOW_TASK_GRACE_SECS)
+            + retry_delay,
+    )
+}
+
+#[derive(Debug, PartialEq)]
+enum NextStep {
+    Run,
+    Skip,
+}
+
+/// check that the organizer is a runnable dag
+pub fn validate_organizer(nodes: &[OrganizerNode], edges: &[OrganizerEdge]) -> Result<()> {
+    let mut node_ids = HashSet::new();
+    for n in nodes {
+        if !node_ids.insert(n.id.as_str()) {
+            anyhow::bail!("duplicate node {}", n.id);
+        }
+        match n.node_type.as_str() {
+            NODE_START | NODE_END => {}
+            NODE_TASK => {
+                if n.eid.is_empty() {
+                    anyhow::bail!("task node {} has no job", n.name);
+                }
+                if n.bind_instance.is_empty() {
+                    anyhow::bail!("task node {} has no bind instance", n.name);
+                }
+            }
+            v => anyhow::bail!("invalid node type {v} of node {}", n.name),
+        }
+    }
+
+    let start_num = nodes.iter().filter(|v| v.node_type == NODE_START).count();
+    if start_num != 1 {
+        anyhow::bail!("organizer must have exactly one start node");
+    }
+    if !nodes.iter().any(|v| v.node_type == NODE_END) {
+        anyhow::bail!("organizer must have at least one end node");
+    }
+
+    let mut in_degree: HashMap<&str, usize> = node_ids.iter().map(|&v| (v, 0)).collect();
+    for e in edges {
+        if !node_ids.contains(e.source_node_id.as_str())
+            || !node_ids.contains(e.target_node_id.as_str())
+        {
+            anyhow::bail!("edge {} links to an unknown node", e.id);
+        }
+        *in_degree.entry(e.target_node_id.as_str()).or_default() += 1;
+    }
+
+    for n in nodes {
+        let in_num = in_degree[n.id.as_str()];
+        if n.node_type == NODE_START && in_num > 0 {
+            anyhow::bail!("start node cannot have upstream nodes");
+        }
+        if n.node_type != NODE_START && in_num == 0 {
+            anyhow::bail!("node {} is unreachable", n.name);
+        }
+        if n.node_type == NODE_END && edges.iter().any(|e| e.source_node_id == n.id) {
+            anyhow::bail!("end node {} cannot have downstream nodes", n.name);
+        }
+    }
+
+    let mut queue: VecDeque<&str> = in_degree
+        .iter()
+        .filter(|(_, &v)| v == 0)
+        .map(|(&k, _)| k)
+        .collect();
+    let mut visited = 0;
+    while let Some(node_id) = queue.pop_front() {
+        visited += 1;
+        for e in edges.iter().filter(|e| e.source_node_id == node_id) {
+            let v = in_degree.get_mut(e.target_node_id.as_str()).unwrap();
+            *v -= 1;
+            if *v == 0 {
+                queue.push_back(e.target_node_id.as_str());
+            }
+        }
+    }
+    if visited != nodes.len() {
+        anyhow::bail!("organizer contains a cycle");
+    }
+    Ok(())
+}
+
+fn edge_taken(edge: &OrganizerEdge, source: &job_organizer_task::Model) -> bool {
+    match source.status.as_str() {
+        TASK_SUCCESS => edge.edge_val.is_empty() || edge.edge_val == source.output,
+        TASK_FAILURE => edge.edge_val == source.output,
+        _ => false,
+    }
+}
+
+/// nodes whose upstream edges are all resolved, a node runs when at least one
+/// incoming edge is taken, otherwise it is skipped and the skip propagates downstream
+fn next_steps(
+    nodes: &[OrganizerNode],
+    edges: &[OrganizerEdge],
+    tasks: &HashMap<String, job_organizer_task::Model>,
+) -> Vec<(String, NextStep)> {
+    nodes
+        .iter()
+        .filter(|n| !tasks.contains_key(&n.id))
+        .filter_map(|n| {
+            if n.node_type == NODE_START {
+                return Some((n.id.clone(), NextStep::Run));
+            }
+            let mut taken = false;
+            for e in edges.iter().filter(|e| e.target_node_id == n.id) {
+                match tasks.get(&e.source_node_id) {
+                    Some(t) if t.status != TASK_RUNNING => taken |= edge_taken(e, t),
+                    _ => return None,
+                }
+            }
+            Some((
+                n.id.clone(),
+                if taken { NextStep::Run } else { NextStep::Skip },
+            ))
+        })
+        .collect()
+}
+
+/// result status of one instance, a node with condition outputs true or false
+fn eval_result_status(condition: &str, exit_code: i32, stdout: &str) -> Result<String> {
+    if condition.trim().is_empty() {
+        return Ok(if exit_code == 0 {
+            TASK_SUCCESS.to_string()
+        } else {
+            TASK_FAILURE.to_string()
+        });
+    }
+    let mut ctx = HashMapContext::new();
+    ctx.set_value("exit_code".into(), (exit_code as i64).into())?;
+    ctx.set_value("stdout".into(), stdout.trim().into())?;
+    Ok(eval_boolean_with_context(condition, &ctx)?.to_string())
+}
+
+/// aggregate the instance results into the (status, output) of the task
+fn eval_task_output(
+    has_condition: bool,
+    results: &[job_organizer_task_result::Model],
+) -> (&'static str, String) {
+    if results.iter().any(|v| v.status == TASK_FAILURE) {
+        return (TASK_FAILURE, TASK_FAILURE.to_string());
+    }
+    if has_condition {
+        let ok = results.iter().all(|v| v.status == "true");
+        return (TASK_SUCCESS, ok.to_string());
+    }
+    (TASK_SUCCESS, TASK_SUCCESS.to_string())
+}
+
+impl<'a> JobLogic<'a> {
+    pub async fn save_organizer(
+        &self,
+        active_model: job_organizer::ActiveModel,
+    ) -> Result<job_organizer::ActiveModel> {
+        Ok(active_model.save(&self.ctx.db).await?)
+    }
+
+    pub async fn can_write_organizer(&self, user_info: &UserInfo, id: u64) -> Result<bool> {
+        if self.ctx.can_manage_job(&user_info.user_id).await? {
+            return Ok(true);
+        }
+        let ok = JobOrganizer::find()
+            .filter(job_organizer::Column::Id.eq(id))
+            .filter(job_organizer::Column::CreatedUser.eq(&user_info.username))
+            .one(&self.ctx.db)
+            .await?
+            .is_some();
+        Ok(ok)
+    }
+
+    pub async fn query_organizer(
+        &self,
+        created_user: Option<&String>,
+        name: Option<String>,
+        page: u64,
+        page_size: u64,
+    ) -> Result<(Vec<job_organizer::Model>, u64)> {
+        let model = JobOrganizer::find()
+            .apply_if(created_user, |q, v| {
+                q.filter(job_organizer::Column::CreatedUser.eq(v))
+            })
+            .apply_if(name, |q, v| {
+                q.filter(job_organizer::Column::Name.contains(v))
+            });
+
+        let total = model.clone().count(&self.ctx.db).await?;
+        let list = model
+            .order_by_desc(job_organizer::Column::Id)
+            .paginate(&self.ctx.db, page_size)
+            .fetch_page(page)
+            .await?;
+        Ok((list, total))
+    }
+
+    /// publish the current nodes and edges of the organizer as an immutable version
+    pub async fn release_organizer(
+        &self,
+        organizer_id: u64,
+        name: String,
+        info: String,
+        created_user: String,
+    ) -> Result<String> {
+        let organizer = JobOrganizer::find_by_id(organizer_id)
+            .one(&self.ctx.db)
+            .await?
+            .ok_or(anyhow!("cannot found organizer {organizer_id}"))?;
+
+        let nodes: Vec<OrganizerNode> = organizer
+            .nodes
+            .clone()
+            .map(serde_json::from_value)
+            .transpose()?
+            .unwrap_or_default();
+        let edges: Vec<OrganizerEdge> = organizer
+            .edges
+            .clone()
+            .map(serde_json::from_value)
+            .transpose()?
+            .unwrap_or_default();
+
+        validate_organizer(&nodes, &edges)?;
+
+        let version = IdGenerator::get_flow_job_uid();
+        let txn = self.ctx.db.begin().await?;
+
+        JobOrganizerRelease::insert(job_organizer_release::ActiveModel {
+            organizer_id: Set(organizer_id),
+            version: Set(version.clone()),
+            name: Set(name),
+            info: Set(info),
+            is_public: Set(organizer.is_public),
+            nodes: Set(organizer.nodes),
+            edges: Set(organizer.edges),
+            created_user: Set(created_user),
+            ..Default::default()
+        })
+        .exec(&txn)
+        .await?;
+
+        let release_nodes = nodes
+            .into_iter()
+            .map(|v| {
+                Ok(job_organizer_release_node::ActiveModel {
+                    version: Set(version.clone()),
+                    node_id: Set(v.id),
+                    name: Set(v.name),
+                    task_type: Set(if v.node_type == NODE_TASK {
+                        "job".to_string()
+                    } else {
+                        "".to_string()
+                    }),
+                    node_type: Set(v.node_type),
+                    dispatch_data: Set(Some(serde_json::to_value(FlowTaskData { eid: v.eid })?)),
+                    props: Set(v.props),
+                    condition: Set(v.condition),
+                    bind_instance: Set(Some(serde_json::to_value(v.bind_instance)?)),
+                    ..Default::default()
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        JobOrganizerReleaseNode::insert_many(release_nodes)
+            .exec(&txn)
+            .await?;
+
+        if !edges.is_empty() {
+            JobOrganizerReleaseEdge::insert_many(edges.into_iter().map(|v| {
+                job_organizer_release_edge::ActiveModel {
+                    version: Set(version.clone()),
+                    edge_id: Set(v.id),
+                    edge_type: Set("default".to_string()),
+                    props: Set(v.props),
+                    source_node_id: Set(v.source_node_id),
+                    target_node_id: Set(v.target_node_id),
+                    edge_val: Set(v.edge_val),
+                    ..Default::default()
+                }
+            }))
+            .exec(&txn)
+            .await?;
+        }
+
+        txn.commit().await?;
+        Ok(version)
+    }
+
+    pub async fn get_organizer_release(
+        &self,
+        organizer_id: u64,
+        version: Option<String>,
+    ) -> Result<Option<job_organizer_release::Model>> {
+        let ret = JobOrganizerRelease::find()
+            .filter(job_organizer_release::Column::OrganizerId.eq(organizer_id))
+            .apply_if(version, |q, v| {
+                q.filter(job_organizer_release::Column::Version.eq(v))
+            })
+            .order_by_desc(job_organizer_release::Column::Id)
+            .one(&self.ctx.db)
+            .await?;
+        Ok(ret)
+    }
+
+    async fn get_release_graph(
+        &self,
+        version: &str,
+    ) -> Result<(Vec<OrganizerNode>, Vec<OrganizerEdge>)> {
+        let nodes = JobOrganizerReleaseNode::find()
+            .filter(job_organizer_release_node::Column::Version.eq(version))
+            .all(&self.ctx.db)
+            .await?
+            .into_iter()
+            .map(|v| {
+                let data: FlowTaskData = v
+                    .dispatch_data
+                    .map(serde_json::from_value)
+                    .transpose()?
+                    .unwrap_or_default();
+                Ok(OrganizerNode {
+                    id: v.node_id,
+                    name: v.name,
+                    node_type: v.node_type,
+                    eid: data.eid,
+                    bind_instance: v
+                        .bind_instance
+                        .map(serde_json::from_value)
+                        .transpose()?
+                        .unwrap_or_default(),
+                    condition: v.condition,
+                    props: v.props,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let edges = JobOrganizerReleaseEdge::find()
+            .filter(job_organizer_release_edge::Column::Version.eq(version))
+            .all(&self.ctx.db)
+            .await?
+            .into_iter()
+            .map(|v| OrganizerEdge {
+                id: v.edge_id,
+                source_node_id: v.source_node_id,
+                target_node_id: v.target_node_id,
+                edge_val: v.edge_val,
+                props: v.props,
+            })
+            .collect();
+
+        Ok((nodes, edges))
+    }
+
+    pub async fn start_process(
+        &self,
+        release: job_organizer_release::Model,
+        name: String,
+        created_user: String,
+    ) -> Result<String> {
+        let process_id = IdGenerator::get_process_uid();
+        JobOrganizerProcess::insert(job_organizer_process::ActiveModel {
+            name: Set(name),
+            organizer_id: Set(release.organizer_id),
+            organizer_version: Set(release.version),
+            process_id: Set(process_id.clone()),
+            status: Set(PROCESS_RUNNING.to_string()),
+            created_user: Set(created_user.clone()),
+            updated_user: Set(created_user),
+            ..Default::default()
+        })
+        .exec(&self.ctx.db)
+        .await?;
+
+        self.advance_process(&process_id).await?;
+        Ok(process_id)
+    }
+
+    /// insert the task of the node, returns None if another worker has already claimed it
+    async fn claim_task(
+        &self,
+        process_id: &str,
+        node_id: &str,
+        schedule_id: String,
+        status: &str,
+        bind_total: i32,
+    ) -> Result<Option<job_organizer_task::Model>> {
+        let output = match status {
+            TASK_SUCCESS | TASK_FAILURE => status.to_string(),
+            _ => "".to_string(),
+        };
+        let ret = JobOrganizerTask::insert(job_organizer_task::ActiveModel {
+            process_id: Set(process_id.to_string()),
+            node_id: Set(node_id.to_string()),
+            schedule_id: Set(schedule_id.clone()),
+            status: Set(status.to_string()),
+            output: Set(output.clone()),
+            bind_total: Set(bind_total),
+            ..Default::default()
+        })
+        .exec(&self.ctx.db)
+        .await;
+
+        match ret {
+            Ok(v) => Ok(Some(job_organizer_task::Model {
+                id: v.last_insert_id,
+                process_id: process_id.to_string(),
+                node_id: node_id.to_string(),
+                schedule_id,
+                status: status.to_string(),
+                output,
+                bind_total,
+                ..Default::default()
+            })),
+            Err(e) if matches!(e.sql_err(), Some(SqlErr::UniqueConstraintViolation(_))) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn finish_task(&self, id: u64, status: &str, output: String) -> Result<bool> {
+        let ret = JobOrganizerTask::update_many()
+            .set(job_organizer_task::ActiveModel {
+                status: Set(status.to_string()),
+                output: Set(output),
+                ..Default::default()
+            })
+            .filter(job_organizer_task::Column::Id.eq(id))
+            .filter(job_organizer_task::Column::Status.eq(TASK_RUNNING))
+            .exec(&self.ctx.db)
+            .await?;
+        Ok(ret.rows_affected > 0)
+    }
+
+    async fn run_node(
+        &self,
+        process: &job_organizer_process::Model,
+        node: &OrganizerNode,
+    ) -> Result<Option<job_organizer_task::Model>> {
+        if node.node_type != NODE_TASK {
+            return self
+                .claim_task(
+                    &process.process_id,
+                    &node.id,
+                    "".to_string(),
+                    TASK_SUCCESS,
+                    0,
+                )
+                .await;
+        }
+
+        // a missing bind instance never reports, the task would wait for it forever
+        let bind_instance: Vec<String> = node
+            .bind_instance
+            .iter()
+            .cloned()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        let found = Instance::find()
+            .filter(instance::Column::InstanceId.is_in(bind_instance.clone()))
+            .count(&self.ctx.db)
+            .await?;
+        if bind_instance.is_empty() || (found as usize) < bind_instance.len() {
+            error!(
+                "flow node {} of process {} binds missing instances",
+                node.id, process.process_id
+            );
+            return self
+                .claim_task(
+                    &process.process_id,
+                    &node.id,
+                    "".to_string(),
+                    TASK_FAILURE,
+                    0,
+                )
+                .await;
+        }
+
+        let schedule_id = IdGenerator::get_schedule_uid();
+        let Some(mut task) = self
+            .claim_task(
+                &process.process_id,
+                &node.id,
+                schedule_id.clone(),
+                TASK_RUNNING,
+                bind_instance.len() as i32,
+            )
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        JobOrganizerProcess::update_many()
+            .set(job_organizer_process::ActiveModel {
+                current_node: Set(node.id.clone()),
+                ..Default::default()
+            })
+            .filter(job_organizer_process::Column::Id.eq(process.id))
+            .exec(&self.ctx.db)
+            .await?;
+
+        if let Err(e) = self
+            .dispatch_job(
+                self.ctx.conf.comet_secret.clone(),
+                bind_instance,
+                node.eid.clone(),
+                false,
+                format!("{}-{}", process.name, node.name),
+                ScheduleType::Flow,
+                JobAction::Exec,
+                None,
+                TimerOptions::default(),
+                None,
+                SupervisorOptions::default(),
+                None,
+                process.created_user.clone(),
+                Some(schedule_id),
+            )
+            .await
+        {
+            error!(
+                "failed dispatch flow node {} of process {} - {e}",
+                node.id, process.process_id
+            );
+            self.finish_task(task.id, TASK_FAILURE, TASK_FAILURE.to_string())
+                .await?;
+            task.status = TASK_FAILURE.to_string();
+            task.output = TASK_FAILURE.to_string();
+        }
+        Ok(Some(task))
+    }
+
+    /// run all nodes that became runnable and settle the process status
+    pub async fn advance_process(&self, process_id: &str) -> Result<()> {
+        let process = JobOrganizerProcess::find()
+            .filter(job_organizer_process::Column::ProcessId.eq(process_id))
+            .one(&self.ctx.db)
+            .await?
+            .ok_or(anyhow!("cannot found process {process_id}"))?;
+        if process.status != PROCESS_RUNNING {
+            return Ok(());
+        }
+
+        let (nodes, edges) = self.get_release_graph(&process.organizer_version).await?;
+
+        let mut tasks: HashMap<String, job_organizer_task::Model> = JobOrganizerTask::find()
+            .filter(job_organizer_task::Column::ProcessId.eq(process_id))
+            .all(&self.ctx.db)
+            .await?
+            .into_iter()
+            .map(|v| (v.node_id.clone(), v))
+            .collect();
+
+        loop {
+            let steps = next_steps(&nodes, &edges, &tasks);
+            if steps.is_empty() {
+                break;
+            }
+            for (node_id, step) in steps {
+                let node = nodes
+                    .iter()
+                    .find(|v| v.id == node_id)
+                    .ok_or(anyhow!("cannot found node {node_id}"))?;
+                let task = match step {
+                    NextStep::Run => self.run_node(&process, node).await?,
+                    NextStep::Skip => {
+                        self.claim_task(process_id, &node_id, "".to_string(), TASK_SKIPPED, 0)
+                            .await?
+                    }
+                };
+                // claimed by another worker, it advances the process from there
+                let Some(task) = task else {
+                    return Ok(());
+                };
+                tasks.insert(node_id, task);
+            }
+        }
+
+        if tasks.values().any(|v| v.status == TASK_RUNNING) {
+            return Ok(());
+        }
+
+        let finished = nodes
+            .iter()
+            .filter(|v| v.node_type == NODE_END)
+            .any(|v| tasks.get(&v.id).is_some_and(|t| t.status == TASK_SUCCESS));
+
+        JobOrganizerProcess::update_many()
+            .set(job_organizer_process::ActiveModel {
+                status: Set(if finished {
+                    PROCESS_FINISHED.to_string()
+                } else {
+                    PROCESS_FAILED.to_string()
+                }),
+                ..Default::default()
+            })
+            .filter(job_organizer_process::Column::Id.eq(process.id))
+            .exec(&self.ctx.db)
+            .await?;
+        Ok(())
+    }
+
+    /// record the result of a flow task instance and advance the process once
+    /// every bind instance has reported
+    pub async fn update_flow_task(&self, params: &UpdateJobParams) -> Result<()> {
+        let Some(task) = JobOrganizerTask::find()
+            .filter(job_organizer_task::Column::ScheduleId.eq(&params.schedule_id))
+            .one(&self.ctx.db)
+            .await?
+        else {
+            return Ok(());
+        };
+
+        let process = JobOrganizerProcess::find()
+            .filter(job_organizer_process::Column::ProcessId.eq(&task.process_id))
+            .one(&self.ctx.db)
+            .await?
+            .ok_or(anyhow!("cannot found process {}", task.process_id))?;
+
+        let node = JobOrganizerReleaseNode::find()
+            .filter(job_organizer_release_node::Column::Version.eq(&process.organizer_version))
+            .filter(job_organizer_release_node::Column::NodeId.eq(&task.node_id))
+            .one(&self.ctx.db)
+            .await?
+            .ok_or(anyhow!("cannot found node {}", task.node_id))?;
+
+        let exit_code = params.exit_code.unwrap_or_default();
+        let stdout = params.stdout.clone().unwrap_or_default();
+        let mut output = params
+            .stderr
+            .clone()
+            .map_or(stdout.clone(), |v| format!("{v}\n{stdout}"));
+
+        let status = match eval_result_status(&node.condition, exit_code, &stdout) {
+            Ok(v) => v,
+            Err(e) => {
+                output = format!("{output}\nfailed eval condition - {e}");
+                TASK_FAILURE.to_string()
+            }
+        };
+
+        JobOrganizerTaskResult::insert(job_organizer_task_result::ActiveModel {
+            process_id: Set(task.process_id.clone()),
+            node_id: Set(task.node_id.clone()),
+            instance_id: Set(params.instance_id.clone()),
+            bind_ip: Set(params.bind_ip.clone()),
+            exit_code: Set(exit_code),
+            exit_status: Set(params.exit_status.clone().unwrap_or_default()),
+            output: Set(output.clone()),
+            status: Set(status.clone()),
+            dispatch_result: Set(Some(json!({"schedule_id": params.schedule_id}))),
+            ..Default::default()
+        })
+        .on_conflict(
+            OnConflict::columns([
+                job_organizer_task_result::Column::ProcessId,
+                job_organizer_task_result::Column::NodeId,
+                job_organizer_task_result::Column::InstanceId,
+            ])
+            .values([
+                (
+                    job_organizer_task_result::Column::ExitCode,
+                    exit_code.into(),
+                ),
+                (
+                    job_organizer_task_result::Column::ExitStatus,
+                    params.exit_status.clone().unwrap_or_default().into(),
+                ),
+                (job_organizer_task_result::Column::Output, output.into()),
+                (job_organizer_task_result::Column::Status, status.into()),
+            ])
+            .to_owned(),
+        )
+        .exec(&self.ctx.db)
+        .await?;
+
+        if task.status != TASK_RUNNING {
+            return Ok(());
+        }
+
+        let results = JobOrganizerTaskResult::find()
+            .filter(job_organizer_task_result::Column::ProcessId.eq(&task.process_id))
+            .filter(job_organizer_task_result::Column::NodeId.eq(&task.node_id))
+            .all(&self.ctx.db)
+            .await?;
+        if (results.len() as i32) < task.bind_total {
+            return Ok(());
+        }
+
+        let (status, output) = eval_task_output(!node.condition.trim().is_empty(), &results);
+        if !self.finish_task(task.id, status, output).await? {
+            return Ok(());
+        }
+
+        self.advance_process(&task.process_id).await
+    }
+
+    /// fail the flow tasks whose agents did not report before the deadline of their job
+    pub async fn expire_flow_tasks(&self) -> Result<()> {
+        let tasks = JobOrganizerTask::find()
+            .filter(job_organizer_task::Column::Status.eq(TASK_RUNNING))
+            .all(&self.ctx.db)
+            .await?;
+
+        for task in tasks {
+            let Some(process) = JobOrganizerProcess::find()
+                .filter(job_organizer_process::Column::ProcessId.eq(&task.process_id))
+                .one(&self.ctx.db)
+                .await?
+            else {
+                continue;
+            };
+            let (nodes, _) = self.get_release_graph(&process.organizer_version).await?;
+            let Some(eid) = nodes
+                .into_iter()
+                .find(|v| v.id == task.node_id)
+                .map(|v| v.eid)
+            else {
+                continue;
+            };
+            let Some(job_record) = Job::find()
+                .filter(job::Column::Eid.eq(&eid))
+                .one(&self.ctx.db)
+                .await?
+            else {
+                continue;
+            };
+            let Some(deadline) = flow_task_deadline(&BaseJob {
+                timeout: job_record.timeout,
+                max_retry: job_record.max_retry,
+                retry_interval: job_record.retry_interval,
+                retry_backoff: job_record
+                    .retry_backoff
+                    .as_str()
+                    .try_into()
+                    .unwrap_or_default(),
+                ..Default::default()
+            }) else {
+                continue;
+            };
+            if Utc::now() < task.created_time + deadline {
+                continue;
+            }
+
+            error!(
+                "flow task {} of process {} timed out",
+                task.node_id, task.process_id
+            );
+            if self
+                .finish_task(task.id, TASK_FAILURE, TASK_OUTPUT_TIMEOUT.to_string())
+                .await?
+            {
+                self.advance_process(&task.process_id).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// fail the running tasks and the process, the runs already dispatched are left to finish
+    pub async fn cancel_process(&self, process_id: &str) -> Result<u64> {
+        JobOrganizerTask::update_many()
+            .set(job_organizer_task::ActiveModel {
+                status: Set(TASK_FAILURE.to_string()),
+                output: Set(TASK_OUTPUT_CANCELED.to_string()),
+                ..Default::default()
+            })
+            .filter(job_organizer_task::Column::ProcessId.eq(process_id))
+            .filter(job_organizer_task::Column::Status.eq(TASK_RUNNING))
+            .exec(&self.ctx.db)
+            .await?;
+        let ret = JobOrganizerProcess::update_many()
+            .set(job_organizer_process::ActiveModel {
+                status: Set(PROCESS_FAILED.to_string()),
+                ..Default::default()
+            })
+            .filter(job_organizer_process::Column::ProcessId.eq(process_id))
+            .filter(job_organizer_process::Column::Status.eq(PROCESS_RUNNING))
+            .exec(&self.ctx.db)
+            .await?;
+        Ok(ret.rows_affected)
+    }
+
+    pub async fn query_process(
+        &self,
+        created_user: Option<&String>,
+        organizer_id: Option<u64>,
+        status: Option<String>,
+        page: u64,
+        page_size: u64,
+    ) -> Result<(Vec<OrganizerProcessModel>, u64)> {
+        let model = JobOrganizerProcess::find()
+            .column_as(job_organizer::Column::Name, "organizer_name")
+            .join_rev(
+                JoinType::LeftJoin,
+                JobOrganizer::belongs_to(JobOrganizerProcess)
+                    .from(job_organizer::Column::Id)
+                    .to(job_organizer_process::Column::OrganizerId)
+                    .into(),
+            )
+            .apply_if(created_user, |q, v| {
+                q.filter(job_organizer_process::Column::CreatedUser.eq(v))
+            })
+            .apply_if(organizer_id, |q, v| {
+                q.filter(job_organizer_process::Column::OrganizerId.eq(v))
+            })
+            .apply_if(status, |q, v| {
+                q.filter(job_organizer_process::Column::Status.eq(v))
+            });
+
+        let total = model.clone().count(&self.ctx.db).await?;
+        let list = model
+            .order_by_desc(job_organizer_process::Column::Id)
+            .into_model()
+            .paginate(&self.ctx.db, page_size)
+            .fetch_page(page)
+            .await?;
+        Ok((list, total))
+    }
+
+    pub async fn get_process_detail(
+        &self,
+        process_id: &str,
+    ) -> Result<(
+        job_organizer_process::Model,
+        Vec<job_organizer_task::Model>,
+        Vec<job_organizer_task_result::Model>,
+    )> {
+        let process = JobOrganizerProcess::find()
+            .filter(job_organizer_process::Column::ProcessId.eq(process_id))
+            .one(&self.ctx.db)
+            .await?
+            .ok_or(anyhow!("cannot found process {process_id}"))?;
+        let tasks = JobOrganizerTask::find()
+            .filter(job_organizer_task::Column::ProcessId.eq(process_id))
+            .order_by_asc(job_organizer_task::Column::Id)
+            .all(&self.ctx.db)
+            .await?;
+        let results = JobOrganizerTaskResult::find()
+            .filter(job_organizer_task_result::Column::ProcessId.eq(process_id))
+            .order_by_asc(job_organizer_task_result::Column::Id)
+            .all(&self.ctx.db)
+            .await?;
+        Ok((process, tasks, results))
+    }
+}
+
+#[test]
+fn test_flow_next_steps() {
+    let node = |id: &str, node_type: &str| OrganizerNode {
+        id: id.to_string(),
+        name: id.to_string(),
+        node_type: node_type.to_string(),
+        eid: "j".to_string(),
+        bind_instance: vec!["i".to_string()],
+        ..Default::default()
+    };
+    let edge = |source: &str, target: &str, edge_val: &str| OrganizerEdge {
+        id: format!("{source}-{target}"),
+        source_node_id: source.to_string(),
+        target_node_id: target.to_string(),
+        edge_val: edge_val.to_string(),
+        ..Default::default()
+    };
+    let task = |status: &str, output: &str| job_organizer_task::Model {
+        status: status.to_string(),
+        output: output.to_string(),
+        ..Default::default()
+    };
+
+    let nodes = vec![
+        node("start", NODE_START),
+        node("check", NODE_TASK),
+        node("yes", NODE_TASK),
+        node("no", NODE_TASK),
+        node("end", NODE_END),
+    ];
+    let edges = vec![
+        edge("start", "check", ""),
+        edge("check", "yes", "true"),
+        edge("check", "no", "false"),
+        edge("yes", "end", ""),
+        edge("no", "end", ""),
+    ];
+    validate_organizer(&nodes, &edges).unwrap();
+
+    let mut tasks = HashMap::new();
+    assert_eq!(
+        next_steps(&nodes, &edges, &tasks),
+        vec![("start".to_string(), NextStep::Run)]
+    );
+
+    tasks.insert("start".to_string(), task(TASK_SUCCESS, TASK_SUCCESS));
+    tasks.insert("check".to_string(), task(TASK_RUNNING, ""));
+    assert!(next_steps(&nodes, &edges, &tasks).is_empty());
+
+    tasks.insert("check".to_string(), task(TASK_SUCCESS, "true"));
+    assert_eq!(
+        next_steps(&nodes, &edges, &tasks),
+        vec![
+            ("yes".to_string(), NextStep::Run),
+            ("no".to_string(), NextStep::Skip)
+        ]
+    );
+
+    tasks.insert("yes".to_string(), task(TASK_SUCCESS, TASK_SUCCESS));
+    tasks.insert("no".to_string(), task(TASK_SKIPPED, ""));
+    assert_eq!(
+        next_steps(&nodes, &edges, &tasks),
+        vec![("end".to_string(), NextStep::Run)]
+    );
+
+    let mut cyclic = edges.clone();
+    cyclic.push(edge("yes", "check", ""));
+    assert!(validate_organizer(&nodes, &cyclic).is_err());
+
+    assert_eq!(eval_result_status("", 0, "").unwrap(), TASK_SUCCESS);
+    assert_eq!(
+        eval_result_status("exit_code == 0 && stdout == \"ok\"", 0, "ok\n").unwrap(),
+        "true"
+    );
+}
+
+#[test]
+fn test_flow_task_deadline() {
+    use std::time::Duration;
+
+    assert_eq!(flow_task_deadline(&BaseJob::default()), None);
+
+    let job = BaseJob {
+        timeout: 60,
+        ..Default::default()
+    };
+    assert_eq!(
+        flow_task_deadline(&job),
+        Some(Duration::from_secs(60 + FLOW_TASK_GRACE_SECS))
+    );
+
+    let job = BaseJob {
+        timeout: 60,
+        max_retry: 2,
+        retry_interval: 10,
+        ..job
+    };
+    assert_eq!(
+        flow_task_deadline(&job),
+        Some(Duration::from_secs(3 * 60 + 2 * 10 + FLOW_TASK_GRACE_SECS))
+    );
+}
@@ -1,37 +1,44 @@
-use std::{path::PathBuf, time::Duration};
+use std::{collections::HashMap, path::PathBuf, time::Duration};
 
 use anyhow::{anyhow, Result};
 
 use automate::{
-    bridge::msg::{BundleOutputParams, UpdateJobParams},
-    scheduler::types::{BundleScript, RunStatus, ScheduleStatus, ScheduleType, UploadFile},
+    bridge::msg::{BundleOutputParams, ReadJobLogParams, ReconcileParams, UpdateJobParams},
+    scheduler::types::{
+        BundleScript, RunStatus, ScheduleStatus, ScheduleType, SupervisorOptions,
+        TerminationReason, TimerOptions, UploadFile,
+    },
     JobAction,
 };
 
 use evalexpr::eval_boolean;
 
 use sea_orm::{
-    ActiveValue::NotSet, ColumnTrait, EntityTrait, JoinType, PaginatorTrait, QueryFilter,
-    QueryOrder, QuerySelect, QueryTrait, Set,
+    prelude::DateTimeUtc, ActiveValue::NotSet, ColumnTrait, Condition, EntityTrait, JoinType,
+    PaginatorTrait, QueryFilter, QueryOrder, QuerySelect, QueryTrait, Set,
 };
 
 use sea_query::OnConflict;
 
 use serde_json::{json, Value};
 use tokio::fs;
-use tracing::error;
+use tracing::{error, info};
 
 use crate::{
     entity::{
         self, executor, instance, job, job_running_status, job_schedule_history, prelude::*, team,
     },
     file_name,
-    logic::{executor::ExecutorLogic, job::types::DispatchResult, types::UserInfo},
+    logic::{
+        executor::ExecutorLogic, job::types::DispatchResult, team::TeamLogic, types::UserInfo,
+    },
     utils, IdGenerator,
 };
 
 use super::{
-    types::{self, BundleScriptRecord, BundleScriptResult, DispatchData, DispatchTarget},
+    types::{
+        self, BundleScriptRecord, BundleScriptResult, DispatchData, DispatchTarget, TargetSelector,
+    },
     JobLogic,
 };
 
@@ -137,6 +144,34 @@ impl<'a> JobLogic<'a> {
             update_values.push((job_running_status::Column::ExitCode, exit_code.into()))
         }
 
+        if let Some(parallel_decision) = params.parallel_decision {
+            update_values.push((
+                job_running_status::Column::ParallelDecision,
+                parallel_decision.to_string().into(),
+            ))
+        }
+
+        if let Some(restart_count) = params.restart_count {
+            update_values.push((
+                job_running_status::Column::RestartCount,
+                restart_count.into(),
+            ))
+        }
+
+        if let Some(termination_reason) = params.termination_reason {
+            update_values.push((
+                job_running_status::Column::TerminationReason,
+                termination_reason.to_string().into(),
+            ))
+        }
+
+        if let Some(ref probe_result) = params.probe_result {
+            update_values.push((
+                job_running_status::Column::ProbeResult,
+                serde_json::to_value(probe_result)?.into(),
+            ))
+        }
+
         if let Some(schedule_status) = params.schedule_status.clone() {
             update_values.push((
                 job_running_status::Column::ScheduleStatus,
@@ -171,10 +206,22 @@ impl<'a> JobLogic<'a> {
             schedule_id: Set(params.schedule_id.clone()),
             schedule_status,
             run_status,
+            parallel_decision: params
+                .parallel_decision
+                .map_or(NotSet, |v| Set(v.to_string())),
+            restart_count: params.restart_count.map_or(NotSet, Set),
+            termination_reason: params
+                .termination_reason
+                .map_or(NotSet, |v| Set(v.to_string())),
+            probe_result: params
+                .probe_result
+                .as_ref()
+                .map_or(NotSet, |v| Set(serde_json::to_value(v).ok())),
             start_time: Set(params.start_time),
             job_type: Set(params
                 .base_job
                 .bundle_script
+                .as_ref()
                 .map_or("default".to_string(), |_v| "bundle".to_string())),
             prev_time: Set(params.prev_time),
             updated_user: Set(params.created_user.clone()),
@@ -192,8 +239,25 @@ impl<'a> JobLogic<'a> {
 
         let ret = active_model.exec(&self.ctx.db).await?;
 
+        if let Some(ref calendar) = params.skipped_by_calendar {
+            if let Err(e) = self.record_calendar_skip(&params, calendar).await {
+                error!(
+                    "failed record calendar skip of {} - {e}",
+                    params.base_job.eid
+                );
+            }
+        }
+
+        if params.run_status == Some(RunStatus::Stop) {
+            if let Err(e) = self.update_flow_task(&params).await {
+                error!("failed update flow task {} - {e}", params.schedule_id);
+            }
+        }
+
         match params.run_status {
-            Some(RunStatus::Stop) => {
+            // every finished attempt, including the ones that will be retried, has its own history
+            Some(run_status @ (RunStatus::Stop | RunStatus::Retrying)) => {
+                let mut succeeded = types::is_run_succeeded(params.exit_code, None);
                 let (bundle_script_result, job_type) = if params.bundle_output.is_some() {
                     let schedule_record = self.get_schedule(&params.schedule_id).await?.ok_or(
                         anyhow::format_err!("cannot get schedule record {}", params.schedule_id),
@@ -209,25 +273,35 @@ impl<'a> JobLogic<'a> {
                             .bundle_script
                             .ok_or(anyhow::format_err!("cannot get bundle_sciprt"))?,
                     )?;
-                    let val = serde_json::to_value(
-                        &self.eval(bundle_script, params.bundle_output.unwrap()),
-                    )?;
+                    let results = self.eval(bundle_script, params.bundle_output.unwrap());
+                    succeeded = types::is_run_succeeded(params.exit_code, Some(&results));
+                    let val = serde_json::to_value(&results)?;
                     (Set(Some(val)), Set("bundle".to_string()))
                 } else {
                     (NotSet, Set("default".to_string()))
                 };
-
-                let output = params.stdout.unwrap_or_default();
-                let output = params
-                    .stderr
-                    .map_or(output.clone(), |v| format!("{v}\n{output}"));
+                let eid = params.base_job.eid.clone();
+                let instance_id = params.instance_id.clone();
+                let skipped = params.termination_reason == Some(TerminationReason::Skipped);
 
                 let ret = JobExecHistory::insert(entity::job_exec_history::ActiveModel {
                     schedule_id: Set(params.schedule_id),
                     instance_id: Set(params.instance_id),
                     exit_status: Set(params.exit_status.clone().unwrap_or_default()),
                     exit_code: Set(params.exit_code.unwrap_or_default()),
-                    output: Set(output),
+                    termination_reason: Set(params
+                        .termination_reason
+                        .map(|v| v.name().to_string())
+                        .unwrap_or_default()),
+                    term_signal: Set(params
+                        .termination_reason
+                        .and_then(|v| v.signal())
+                        .unwrap_or_default()),
+                    attempt: Set(params.attempt.unwrap_or(1)),
+                    run_id: Set(params.run_id.unwrap_or_default()),
+                    start_offset_ms: Set(params.start_offset_ms.unwrap_or_default()),
+                    output: Set(params.stdout.unwrap_or_default()),
+                    stderr: Set(params.stderr.unwrap_or_default()),
                     eid: Set(params.base_job.eid),
                     start_time: Set(params.start_time),
                     end_time: Set(params.end_time),
@@ -238,6 +312,13 @@ impl<'a> JobLogic<'a> {
                 })
                 .exec(&self.ctx.db)
                 .await?;
+
+                // a run skipped by the parallel policy never ran, so it triggers nothing
+                if run_status == RunStatus::Stop && !skipped {
+                    if let Err(e) = self.fire_triggers(&eid, &instance_id, succeeded).await {
+                        error!("failed fire triggers of job {eid} - {e}");
+                    }
+                }
                 Ok(ret.last_insert_id)
             }
             _ => Ok(ret.last_insert_id),
@@ -266,7 +347,11 @@ impl<'a> JobLogic<'a> {
                     anyhow::bail!("cannot {action} job with once schedule type")
                 }
             }
-            ScheduleType::Flow => unimplemented!("not support flow schedule type"),
+            ScheduleType::Flow => {
+                if !matches!(action, JobAction::Exec | JobAction::Kill) {
+                    anyhow::bail!("cannot {action} job with flow schedule type")
+                }
+            }
             ScheduleType::Daemon => {
                 if !matches!(
                     action,
@@ -290,18 +375,39 @@ impl<'a> JobLogic<'a> {
         schedule_type: ScheduleType,
         action: automate::JobAction,
         timer_expr: Option<String>,
+        timer_options: TimerOptions,
         restart_interval: Option<Duration>,
+        supervisor_options: SupervisorOptions,
+        target_selector: Option<TargetSelector>,
         created_user: String,
+        schedule_id: Option<String>,
     ) -> Result<u64> {
         self.check_schedule_type(action.clone(), schedule_type.clone())?;
-        let schedule_id = IdGenerator::get_schedule_uid();
-        let endpoints = Instance::find()
-            .filter(instance::Column::InstanceId.is_in(instance_ids))
-            .all(&self.ctx.db)
-            .await?;
-        if endpoints.len() == 0 {
-            anyhow::bail!("cannot found valid instance");
-        }
+        let schedule_id = schedule_id.unwrap_or_else(IdGenerator::get_schedule_uid);
+        let target_selector = target_selector.filter(TargetSelector::is_dynamic);
+        let endpoints = match &target_selector {
+            Some(selector) => {
+                if action != JobAction::StartTimer {
+                    anyhow::bail!("target selector only applies to starting a timer");
+                }
+                // offline instances start the timer when they come online
+                self.resolve_targets(selector)
+                    .await?
+                    .into_iter()
+                    .filter(|v| v.status == 1)
+                    .collect()
+            }
+            None => {
+                let endpoints = Instance::find()
+                    .filter(instance::Column::InstanceId.is_in(instance_ids))
+                    .all(&self.ctx.db)
+                    .await?;
+                if endpoints.len() == 0 {
+                    anyhow::bail!("cannot found valid instance");
+                }
+                endpoints
+            }
+        };
 
         let job_record = Job::find()
             .filter(entity::job::Column::Eid.eq(eid.clone()))
@@ -382,16 +488,31 @@ impl<'a> JobLogic<'a> {
                 timeout: job_record.timeout,
                 max_retry: job_record.max_retry as u8,
                 max_parallel: job_record.max_parallel as u8,
+                parallel_policy: job_record
+                    .parallel_policy
+                    .as_str()
+                    .try_into()
+                    .unwrap_or_default(),
+                retry_interval: job_record.retry_interval,
+                retry_backoff: job_record
+                    .retry_backoff
+                    .as_str()
+                    .try_into()
+                    .unwrap_or_default(),
                 read_code_from_stdin: false,
+                env: self.get_job_env(&job_record.eid).await?,
             },
             instance_id: None,
             fields: None,
             restart_interval,
+            supervisor_options,
             created_user: created_user.clone(),
             schedule_id: schedule_id.clone(),
             timer_expr: timer_expr.clone(),
+            timer_options,
             is_sync,
             action: action.clone(),
+            schedule_type: Some(schedule_type.clone()),
         };
 
         let mut dispatch_data = DispatchData {
@@ -510,12 +631,23 @@ impl<'a> JobLogic<'a> {
             dispatch_result.push(v)
         });
 
+        if target_selector.is_some() {
+            // the failed instances are retried by the next reconciliation
+            dispatch_data.target.retain(|t| {
+                dispatch_result
+                    .iter()
+                    .any(|v| v.instance_id == t.instance_id && !v.has_err)
+            });
+        }
+
         dispatch_data
             .params
             .base_job
             .upload_file
             .iter_mut()
             .for_each(|v| v.data = None);
+        // decrypted secrets must not be persisted, env is loaded again on redispatch
+        dispatch_data.params.base_job.env.clear();
 
         let ret = JobScheduleHistory::insert(entity::job_schedule_history::ActiveModel {
             schedule_id: Set(schedule_id.clone()),
@@ -526,6 +658,7 @@ impl<'a> JobLogic<'a> {
             dispatch_result: Set(Some(serde_json::to_value(&dispatch_result)?)),
             action: Set(action.to_string()),
             dispatch_data: Set(Some(serde_json::to_value(&dispatch_data)?)),
+            target_selector: Set(target_selector.map(serde_json::to_value).transpose()?),
             snapshot_data: Set(Some(serde_json::to_value(job_record)?)),
             created_user: Set(created_user.clone()),
             updated_user: Set(created_user.clone()),
@@ -534,6 +667,15 @@ impl<'a> JobLogic<'a> {
         .exec(&self.ctx.db)
         .await?;
 
+        if action == JobAction::StartTimer {
+            self.release_target_selector(
+                Condition::all()
+                    .add(job_schedule_history::Column::Eid.eq(&eid))
+                    .add(job_schedule_history::Column::Id.ne(ret.last_insert_id)),
+            )
+            .await?;
+        }
+
         if has_err {
             anyhow::bail!("Partial job scheduling failed");
         }
@@ -554,39 +696,101 @@ impl<'a> JobLogic<'a> {
             .await?
             .ok_or(anyhow!("cannot found instance"))?;
 
-        let runnable: Vec<(serde_json::Value, String)> = JobRunningStatus::find()
-            .select_only()
-            .column(job_schedule_history::Column::DispatchData)
-            .column(instance::Column::MacAddr)
-            .join_rev(
-                JoinType::LeftJoin,
-                Instance::belongs_to(JobRunningStatus)
-                    .from(instance::Column::InstanceId)
-                    .to(job_running_status::Column::InstanceId)
-                    .into(),
-            )
-            .join_rev(
-                JoinType::LeftJoin,
-                JobScheduleHistory::belongs_to(JobRunningStatus)
-                    .from(job_schedule_history::Column::ScheduleId)
-                    .to(job_running_status::Column::ScheduleId)
-                    .into(),
-            )
-            .filter(job_running_status::Column::ScheduleStatus.is_in([
-                ScheduleStatus::Scheduling.to_string(),
-                ScheduleStatus::Prepare.to_string(),
-            ]))
-            .filter(job_running_status::Column::InstanceId.eq(ins.instance_id.clone()))
-            .into_tuple()
-            .all(&self.ctx.db)
-            .await?;
+        let runnable: Vec<(serde_json::Value, String, Option<DateTimeUtc>, DateTimeUtc)> =
+            JobRunningStatus::find()
+                .select_only()
+                .column(job_schedule_history::Column::DispatchData)
+                .column(instance::Column::MacAddr)
+                .column(job_running_status::Column::PrevTime)
+                .column(job_schedule_history::Column::CreatedTime)
+                .join_rev(
+                    JoinType::LeftJoin,
+                    Instance::belongs_to(JobRunningStatus)
+                        .from(instance::Column::InstanceId)
+                        .to(job_running_status::Column::InstanceId)
+                        .into(),
+                )
+                .join_rev(
+                    JoinType::LeftJoin,
+                    JobScheduleHistory::belongs_to(JobRunningStatus)
+                        .from(job_schedule_history::Column::ScheduleId)
+                        .to(job_running_status::Column::ScheduleId)
+                        .into(),
+                )
+                .filter(job_running_status::Column::ScheduleStatus.is_in([
+                    ScheduleStatus::Scheduling.to_string(),
+                    ScheduleStatus::Prepare.to_string(),
+                ]))
+                .filter(job_running_status::Column::InstanceId.eq(ins.instance_id.clone()))
+                .into_tuple()
+                .all(&self.ctx.db)
+                .await?;
+
+        let runnable = runnable
+            .into_iter()
+            .map(|(v, mac_addr, prev_time, scheduled_time)| {
+                Ok((
+                    DispatchData::try_from(v)?,
+                    mac_addr,
+                    prev_time,
+                    scheduled_time,
+                ))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let timers = runnable
+            .iter()
+            .filter(|v| v.0.params.action == JobAction::StartTimer)
+            .map(|v| v.0.params.base_job.eid.clone())
+            .collect();
+
+        // the agent may have fired its restored timers while the console could not see them,
+        // so the ticks are caught up from the last fire time it reports
+        let last_ticks = match self
+            .reconcile_endpoint(bind_ip.clone(), ins.mac_addr.clone(), timers)
+            .await
+        {
+            Ok(v) => v,
+            Err(e) => {
+                error!("failed reconcile timers on {bind_ip}, {e}");
+                HashMap::new()
+            }
+        };
 
         let http_client = self.ctx.http_client.clone();
         let logic = automate::Logic::new(self.ctx.redis().clone());
 
-        for (dispatch_data_val, mac_addr) in runnable {
-            let mut dispatch_data: DispatchData = dispatch_data_val.try_into()?;
+        for (mut dispatch_data, mac_addr, prev_time, scheduled_time) in runnable {
             dispatch_data.params.instance_id = Some(ins.instance_id.clone());
+            dispatch_data.params.base_job.env =
+                match self.get_job_env(&dispatch_data.params.base_job.eid).await {
+                    Ok(v) => v,
+                    Err(e) => {
+                        error!(
+                            "failed load env of runnable job {}, {}",
+                            dispatch_data.params.base_job.eid, e
+                        );
+                        continue;
+                    }
+                };
+
+            if dispatch_data.params.action == JobAction::StartTimer {
+                let last_tick = prev_time.unwrap_or(scheduled_time);
+                let last_tick = last_ticks
+                    .get(&dispatch_data.params.base_job.eid)
+                    .map_or(last_tick, |v| last_tick.max(*v));
+                let target = DispatchTarget {
+                    ip: bind_ip.clone(),
+                    namespace: ins.namespace.clone(),
+                    mac_addr: ins.mac_addr.clone(),
+                    instance_id: ins.instance_id.clone(),
+                };
+                if let Err(e) = self.catch_up_timer(target, &dispatch_data, last_tick).await {
+                    error!(
+                        "failed catch up timer {} on {bind_ip}, {e}",
+                        dispatch_data.params.base_job.eid
+                    );
+                }
+            }
 
             let body = automate::DispatchJobRequest {
                 agent_ip: bind_ip.clone(),
@@ -647,6 +851,42 @@ impl<'a> JobLogic<'a> {
         Ok(())
     }
 
+    /// stop the timers restored by the agent which are no longer scheduled on the console,
+    /// returns the last fire time of the timers kept by the agent
+    async fn reconcile_endpoint(
+        &self,
+        bind_ip: String,
+        mac_addr: String,
+        timers: Vec<String>,
+    ) -> Result<HashMap<String, DateTimeUtc>> {
+        let pair = automate::Logic::new(self.ctx.redis().clone())
+            .get_link_pair(bind_ip.clone(), mac_addr.clone())
+            .await?;
+        let body = automate::ReconcileRequest {
+            agent_ip: bind_ip.clone(),
+            mac_addr,
+            params: ReconcileParams { timers },
+        };
+        let ret = self
+            .ctx
+            .http_client
+            .post(format!("http://{}/reconcile", pair.1.comet_addr))
+            .json(&body)
+            .send()
+            .await?
+            .json::<serde_json::Value>()
+            .await?;
+        if ret["code"] != 20000 {
+            anyhow::bail!("failed reconcile timers on {bind_ip}, {}", ret["msg"]);
+        }
+        info!(
+            "reconcile timers on {bind_ip}, stopped {}",
+            ret["data"]["stopped"]
+        );
+        // agents before the last fire times answer with the stopped timers only
+        Ok(serde_json::from_value(ret["data"]["last_ticks"].clone()).unwrap_or_default())
+    }
+
     pub async fn redispatch_job(
         &self,
         schedule_id: &str,
@@ -654,23 +894,70 @@ impl<'a> JobLogic<'a> {
         job_schedule_record: job_schedule_history::Model,
         created_user: String,
     ) -> Result<Vec<Result<DispatchResult>>> {
-        let dispatch_data: DispatchData = job_schedule_record
+        let mut dispatch_data: DispatchData = job_schedule_record
             .dispatch_data
             .ok_or(anyhow!("cannot found job dispatch data"))?
             .try_into()?;
+        dispatch_data.params.base_job.env =
+            self.get_job_env(&dispatch_data.params.base_job.eid).await?;
+
+        dispatch_data.params.action = action;
+        dispatch_data.params.created_user = created_user;
+        let batch_push_ret = self
+            .push_dispatch(dispatch_data.target, dispatch_data.params)
+            .await;
+
+        let mut dispatch_result = Vec::new();
 
+        let mut has_err = false;
+        batch_push_ret.iter().for_each(|v| {
+            let v = v.as_ref().unwrap().to_owned();
+            if v.has_err {
+                has_err = true;
+            }
+            dispatch_result.push(v)
+        });
+
+        JobScheduleHistory::update_many()
+            .set(job_schedule_history::ActiveModel {
+                action: Set(action.to_string()),
+                dispatch_result: Set(Some(serde_json::to_value(&dispatch_result)?)),
+                ..Default::default()
+            })
+            .filter(job_schedule_history::Column::ScheduleId.eq(schedule_id.to_string()))
+            .exec(&self.ctx.db)
+            .await?;
+
+        if action == JobAction::StopTimer {
+            self.release_target_selector(
+                Condition::all().add(job_schedule_history::Column::ScheduleId.eq(schedule_id)),
+            )
+            .await?;
+        }
+
+        if has_err {
+            anyhow::bail!("Partial job scheduling failed");
+        }
+
+        Ok(batch_push_ret)
+    }
+
+    /// push the dispatch params to every target through the comet the agent is linked to
+    pub(super) async fn push_dispatch(
+        &self,
+        targets: Vec<DispatchTarget>,
+        params: automate::DispatchJobParams,
+    ) -> Vec<Result<DispatchResult>> {
         let logic = automate::Logic::new(self.ctx.redis().clone());
 
         let http_client = self.ctx.http_client.clone();
 
-        let batch_push_ret = utils::async_batch_do(dispatch_data.target, move |v| {
-            let mut dispatch_params = dispatch_data.params.clone();
+        utils::async_batch_do(targets, move |v| {
+            let mut dispatch_params = params.clone();
             let logic = logic.clone();
             let http_client = http_client.clone();
             let instance_id = v.instance_id.clone();
-            dispatch_params.action = action;
             dispatch_params.instance_id = Some(instance_id.clone());
-            dispatch_params.created_user = created_user.clone();
             Box::pin(async move {
                 let body = automate::DispatchJobRequest {
                     agent_ip: v.ip.clone(),
@@ -736,34 +1023,7 @@ impl<'a> JobLogic<'a> {
                 })
             })
         })
-        .await;
-
-        let mut dispatch_result = Vec::new();
-
-        let mut has_err = false;
-        batch_push_ret.iter().for_each(|v| {
-            let v = v.as_ref().unwrap().to_owned();
-            if v.has_err {
-                has_err = true;
-            }
-            dispatch_result.push(v)
-        });
-
-        JobScheduleHistory::update_many()
-            .set(job_schedule_history::ActiveModel {
-                action: Set(action.to_string()),
-                dispatch_result: Set(Some(serde_json::to_value(&dispatch_result)?)),
-                ..Default::default()
-            })
-            .filter(job_schedule_history::Column::ScheduleId.eq(schedule_id.to_string()))
-            .exec(&self.ctx.db)
-            .await?;
-
-        if has_err {
-            anyhow::bail!("Partial job scheduling failed");
-        }
-
-        Ok(batch_push_ret)
+        .await
     }
 
     pub async fn query_schedule(
@@ -887,6 +1147,8 @@ impl<'a> JobLogic<'a> {
         let api_url = format!("http://{}/dispatch", pair.1.comet_addr);
         dispatch_data.params.instance_id = Some(ins.instance_id.clone());
         dispatch_data.params.created_user = user_info.username.clone();
+        dispatch_data.params.base_job.env =
+            self.get_job_env(&dispatch_data.params.base_job.eid).await?;
 
         let mut body = automate::DispatchJobRequest {
             agent_ip: ins.ip.clone(),
@@ -909,6 +1171,14 @@ impl<'a> JobLogic<'a> {
             anyhow::bail!("failed to dispatch job, {}", ret["msg"].to_string());
         }
 
+        // a timer stopped by hand must not be started again by the reconciliation
+        if action == JobAction::StopTimer {
+            self.release_target_selector(
+                Condition::all().add(job_schedule_history::Column::ScheduleId.eq(&schedule_id)),
+            )
+            .await?;
+        }
+
         // JobRunningStatus::update_many()
         //     .set(job_running_status::ActiveModel {
         //         dispatch_result: Set(Some(ret.clone())),
@@ -948,4 +1218,89 @@ impl<'a> JobLogic<'a> {
 
         Ok(ret)
     }
+
+    /// read the untruncated output of a run from the agent's output_dir
+    pub async fn read_job_log(
+        &self,
+        schedule_id: &str,
+        instance_id: &str,
+        attempt: u32,
+        run_id: &str,
+        offset: u64,
+        limit: u64,
+    ) -> Result<Value> {
+        let ins = Instance::find()
+            .filter(instance::Column::InstanceId.eq(instance_id))
+            .one(&self.ctx.db)
+            .await?
+            .ok_or(anyhow!("cannot found instance"))?;
+
+        let schedule_record = self
+            .get_schedule(schedule_id)
+            .await?
+            .ok_or(anyhow!("cannot get shedule by {schedule_id}"))?;
+
+        let logic = automate::Logic::new(self.ctx.redis());
+        let pair = logic.get_link_pair(&ins.ip, &ins.mac_addr).await?;
+        let api_url = format!("http://{}/job/tunnel/read-log", pair.1.comet_addr);
+
+        let body = automate::ReadJobLogRequest {
+            agent_ip: ins.ip,
+            mac_addr: ins.mac_addr,
+            namespace: ins.namespace,
+            params: ReadJobLogParams {
+                eid: schedule_record.eid,
+                schedule_id: schedule_id.to_string(),
+                attempt,
+                run_id: run_id.to_string(),
+                offset,
+                limit,
+            },
+        };
+
+        let mut ret = self
+            .ctx
+            .http_client
+            .post(api_url)
+            .json(&body)
+            .send()
+            .await?
+            .json::<serde_json::Value>()
+            .await?;
+
+        if ret["code"] != 20000 {
+            anyhow::bail!(ret["msg"].take().to_string())
+        } else {
+            Ok(ret["data"].take())
+        }
+    }
+
+    pub async fn can_read_schedule(&self, user_info: &UserInfo, schedule_id: &str) -> Result<bool> {
+        if self.ctx.can_manage_job(&user_info.user_id).await? {
+            return Ok(true);
+        }
+
+        let Some(schedule_record) = self.get_schedule(schedule_id).await? else {
+            return Ok(false);
+        };
+        if schedule_record.created_user == user_info.username {
+            return Ok(true);
+        }
+
+        let Some(job_record) = Job::find()
+            .filter(job::Column::Eid.eq(&schedule_record.eid))
+            .one(&self.ctx.db)
+            .await?
+        else {
+            return Ok(false);
+        };
+
+        if job_record.team_id == 0 {
+            return Ok(job_record.created_user == user_info.username);
+        }
+
+        TeamLogic::new(self.ctx)
+            .can_read_team(Some(job_record.team_id), user_info.user_id.clone())
+            .await
+    }
 }
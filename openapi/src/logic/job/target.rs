@@ -0,0 +1,222 @@
+use std::collections::HashSet;
+
+use anyhow::{anyhow, Result};
+use automate::{scheduler::types::ScheduleType, JobAction};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, EntityTrait, QueryFilter, QueryOrder, Set,
+};
+use tokio::sync::Mutex;
+use tracing::{error, info};
+
+use crate::{
+    entity::{instance, job_schedule_history, prelude::*},
+    logic::tag::TagLogic,
+};
+
+use super::{
+    types::{DispatchData, DispatchTarget, TargetSelector},
+    JobLogic,
+};
+
+/// reconciliations run one at a time so that a timer is never started twice on an instance
+static RECONCILE_LOCK: Mutex<()> = Mutex::const_new(());
+
+impl<'a> JobLogic<'a> {
+    /// the static instances of the selector together with the instances matching all its conditions
+    pub async fn resolve_targets(&self, selector: &TargetSelector) -> Result<Vec<instance::Model>> {
+        let mut matched = Condition::all();
+        if !selector.instance_group_ids.is_empty() {
+            matched = matched
+                .add(instance::Column::InstanceGroupId.is_in(selector.instance_group_ids.clone()));
+        }
+        if !selector.namespaces.is_empty() {
+            matched = matched.add(instance::Column::Namespace.is_in(selector.namespaces.clone()));
+        }
+        if !selector.tag_selector.trim().is_empty() {
+            let instance_ids = TagLogic::new(self.ctx)
+                .resolve_selector(&selector.tag_selector)
+                .await?;
+            matched = matched.add(instance::Column::InstanceId.is_in(instance_ids));
+        }
+
+        let mut cond =
+            Condition::any().add(instance::Column::InstanceId.is_in(selector.instance_ids.clone()));
+        if selector.is_dynamic() {
+            cond = cond.add(matched);
+        }
+
+        Ok(Instance::find().filter(cond).all(&self.ctx.db).await?)
+    }
+
+    /// start the timers with a target selector on the instances which joined their targets and
+    /// stop them on the ones which left, only the given instance is reconciled when it is set
+    pub async fn reconcile_targets(&self, instance_id: Option<&str>) -> Result<()> {
+        let _guard = RECONCILE_LOCK.lock().await;
+        let schedules = JobScheduleHistory::find()
+            .filter(job_schedule_history::Column::ScheduleType.eq(ScheduleType::Timer.to_string()))
+            .filter(job_schedule_history::Column::Action.eq(JobAction::StartTimer.to_string()))
+            .filter(job_schedule_history::Column::TargetSelector.is_not_null())
+            .order_by_desc(job_schedule_history::Column::Id)
+            .all(&self.ctx.db)
+            .await?;
+
+        // a newer timer of the job replaces the older ones on the agents
+        let mut seen = HashSet::new();
+        for record in schedules {
+            if !seen.insert(record.eid.clone()) {
+                continue;
+            }
+            let schedule_id = record.schedule_id.clone();
+            if let Err(e) = self.reconcile_schedule_targets(record, instance_id).await {
+                error!("failed reconcile targets of schedule {schedule_id} - {e}");
+            }
+        }
+        Ok(())
+    }
+
+    /// a timer which was stopped or superseded by a newer dispatch of its job keeps its
+    /// targets but no longer follows its selector
+    pub(super) async fn release_target_selector(&self, cond: Condition) -> Result<()> {
+        JobScheduleHistory::update_many()
+            .set(job_schedule_history::ActiveModel {
+                target_selector: Set(None),
+                ..Default::default()
+            })
+            .filter(cond)
+            .filter(job_schedule_history::Column::TargetSelector.is_not_null())
+            .exec(&self.ctx.db)
+            .await?;
+        Ok(())
+    }
+
+    /// reconcile the instance of the agent which came online
+    pub async fn reconcile_endpoint_targets(&self, bind_ip: &str, mac_addr: &str) -> Result<()> {
+        let ins = Instance::find()
+            .filter(instance::Column::Ip.eq(bind_ip))
+            .filter(instance::Column::MacAddr.eq(mac_addr))
+            .one(&self.ctx.db)
+            .await?
+            .ok_or(anyhow!("cannot found instance"))?;
+        self.reconcile_targets(Some(&ins.instance_id)).await
+    }
+
+    async fn reconcile_schedule_targets(
+        &self,
+        record: job_schedule_history::Model,
+        instance_id: Option<&str>,
+    ) -> Result<()> {
+        let selector: TargetSelector = serde_json::from_value(
+            record
+                .target_selector
+                .ok_or(anyhow!("cannot found target selector"))?,
+        )?;
+        let mut dispatch_data: DispatchData = record
+            .dispatch_data
+            .ok_or(anyhow!("cannot found job dispatch data"))?
+            .try_into()?;
+
+        let in_scope = |v: &str| instance_id.map_or(true, |id| id == v);
+        let desired = self.resolve_targets(&selector).await?;
+        let desired_ids = desired
+            .iter()
+            .map(|v| v.instance_id.clone())
+            .collect::<HashSet<_>>();
+        let current_ids = dispatch_data
+            .target
+            .iter()
+            .map(|v| v.instance_id.clone())
+            .collect::<HashSet<_>>();
+
+        // offline instances join when they come online
+        let joined = desired
+            .into_iter()
+            .filter(|v| v.status == 1 && in_scope(&v.instance_id))
+            .filter(|v| !current_ids.contains(&v.instance_id))
+            .map(|v| DispatchTarget {
+                ip: v.ip,
+                namespace: v.namespace,
+                mac_addr: v.mac_addr,
+                instance_id: v.instance_id,
+            })
+            .collect::<Vec<_>>();
+        let left = dispatch_data
+            .target
+            .iter()
+            .filter(|v| in_scope(&v.instance_id) && !desired_ids.contains(&v.instance_id))
+            .cloned()
+            .collect::<Vec<_>>();
+        if joined.is_empty() && left.is_empty() {
+            return Ok(());
+        }
+
+        info!(
+            "reconcile targets of schedule {}, {} joined, {} left",
+            record.schedule_id,
+            joined.len(),
+            left.len()
+        );
+
+        let mut params = dispatch_data.params.clone();
+        params.base_job.env = self.get_job_env(&params.base_job.eid).await?;
+
+        let succeeded = |ret: Vec<Result<super::types::DispatchResult>>| {
+            ret.into_iter()
+                .filter_map(|v| v.ok())
+                .filter(|v| {
+                    if v.has_err {
+                        error!(
+                            "failed dispatch timer {} to {} - {}",
+                            record.schedule_id,
+                            v.instance_id,
+                            v.err.clone().unwrap_or_default()
+                        );
+                    }
+                    !v.has_err
+                })
+                .map(|v| v.instance_id)
+                .collect::<HashSet<_>>()
+        };
+
+        let mut stop_params = params.clone();
+        stop_params.action = JobAction::StopTimer;
+        // a stop which failed is retried by the next reconciliation
+        let stopped = succeeded(self.push_dispatch(left, stop_params).await);
+        dispatch_data
+            .target
+            .retain(|v| !stopped.contains(&v.instance_id));
+
+        params.action = JobAction::StartTimer;
+        let started = succeeded(self.push_dispatch(joined.clone(), params).await);
+        dispatch_data.target.extend(
+            joined
+                .into_iter()
+                .filter(|v| started.contains(&v.instance_id)),
+        );
+
+        job_schedule_history::ActiveModel {
+            id: Set(record.id),
+            dispatch_data: Set(Some(serde_json::to_value(&dispatch_data)?)),
+            ..Default::default()
+        }
+        .update(&self.ctx.db)
+        .await?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_target_selector() {
+    let selector: TargetSelector =
+        serde_json::from_value(serde_json::json!({"instance_ids": ["a"]})).unwrap();
+    assert!(!selector.is_dynamic());
+
+    let selector: TargetSelector =
+        serde_json::from_value(serde_json::json!({"tag_selector": "env=prod"})).unwrap();
+    assert!(selector.is_dynamic());
+    assert!(selector.instance_ids.is_empty());
+    let blank = TargetSelector {
+        tag_selector: " ".to_string(),
+        ..Default::default()
+    };
+    assert!(!blank.is_dynamic());
+}
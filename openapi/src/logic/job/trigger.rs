@@ -0,0 +1,211 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use automate::{
+    scheduler::types::{ScheduleType, SupervisorOptions, TimerOptions},
+    JobAction,
+};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
+    QueryTrait,
+};
+use tracing::{error, info};
+
+use super::JobLogic;
+use crate::{
+    entity::{job_trigger, prelude::*},
+    logic::types::UserInfo,
+};
+
+pub const TRIGGER_ON_SUCCESS: &str = "success";
+pub const TRIGGER_ON_FAILURE: &str = "failure";
+pub const TRIGGER_ON_COMPLETE: &str = "complete";
+
+/// whether a trigger fires for a finished run of the upstream job
+fn should_fire(trigger_on: &str, succeeded: bool) -> bool {
+    match trigger_on {
+        TRIGGER_ON_SUCCESS => succeeded,
+        TRIGGER_ON_FAILURE => !succeeded,
+        TRIGGER_ON_COMPLETE => true,
+        _ => false,
+    }
+}
+
+/// whether adding the trigger from eid to target_eid closes a loop of triggers
+fn creates_cycle(edges: &[(String, String)], eid: &str, target_eid: &str) -> bool {
+    let graph = edges
+        .iter()
+        .fold(HashMap::<&str, Vec<&str>>::new(), |mut acc, (from, to)| {
+            acc.entry(from.as_str()).or_default().push(to.as_str());
+            acc
+        });
+    let mut visited = HashSet::new();
+    let mut stack = vec![target_eid];
+    while let Some(v) = stack.pop() {
+        if v == eid {
+            return true;
+        }
+        if visited.insert(v) {
+            stack.extend(graph.get(v).into_iter().flatten());
+        }
+    }
+    false
+}
+
+impl<'a> JobLogic<'a> {
+    /// a job must not trigger itself, neither directly nor through other triggers
+    pub async fn check_trigger_cycle(
+        &self,
+        id: Option<u64>,
+        eid: &str,
+        target_eid: &str,
+    ) -> Result<()> {
+        let edges = JobTrigger::find()
+            .apply_if(id, |q, v| q.filter(job_trigger::Column::Id.ne(v)))
+            .all(&self.ctx.db)
+            .await?
+            .into_iter()
+            .map(|v| (v.eid, v.target_eid))
+            .collect::<Vec<_>>();
+        if creates_cycle(&edges, eid, target_eid) {
+            anyhow::bail!("the trigger from {eid} to {target_eid} would loop back to {eid}");
+        }
+        Ok(())
+    }
+
+    pub async fn save_trigger(
+        &self,
+        active_model: job_trigger::ActiveModel,
+    ) -> Result<job_trigger::ActiveModel> {
+        Ok(active_model.save(&self.ctx.db).await?)
+    }
+
+    pub async fn can_write_trigger(&self, user_info: &UserInfo, id: u64) -> Result<bool> {
+        if self.ctx.can_manage_job(&user_info.user_id).await? {
+            return Ok(true);
+        }
+        let ok = JobTrigger::find()
+            .filter(job_trigger::Column::Id.eq(id))
+            .filter(job_trigger::Column::CreatedUser.eq(&user_info.username))
+            .one(&self.ctx.db)
+            .await?
+            .is_some();
+        Ok(ok)
+    }
+
+    pub async fn query_trigger(
+        &self,
+        created_user: Option<&String>,
+        eid: Option<String>,
+        name: Option<String>,
+        page: u64,
+        page_size: u64,
+    ) -> Result<(Vec<job_trigger::Model>, u64)> {
+        let model = JobTrigger::find()
+            .apply_if(created_user, |q, v| {
+                q.filter(job_trigger::Column::CreatedUser.eq(v))
+            })
+            .apply_if(eid, |q, v| {
+                q.filter(
+                    Condition::any()
+                        .add(job_trigger::Column::Eid.eq(&v))
+                        .add(job_trigger::Column::TargetEid.eq(&v)),
+                )
+            })
+            .apply_if(name, |q, v| q.filter(job_trigger::Column::Name.contains(v)));
+
+        let total = model.clone().count(&self.ctx.db).await?;
+        let list = model
+            .order_by_desc(job_trigger::Column::Id)
+            .paginate(&self.ctx.db, page_size)
+            .fetch_page(page)
+            .await?;
+        Ok((list, total))
+    }
+
+    pub async fn delete_trigger(&self, id: u64) -> Result<u64> {
+        let ret = JobTrigger::delete_by_id(id).exec(&self.ctx.db).await?;
+        Ok(ret.rows_affected)
+    }
+
+    /// dispatch the downstream jobs of every enabled trigger matching the finished run
+    pub async fn fire_triggers(&self, eid: &str, instance_id: &str, succeeded: bool) -> Result<()> {
+        let triggers = JobTrigger::find()
+            .filter(job_trigger::Column::Eid.eq(eid))
+            .filter(job_trigger::Column::IsEnabled.eq(true))
+            .filter(
+                Condition::any()
+                    .add(job_trigger::Column::InstanceId.eq(""))
+                    .add(job_trigger::Column::InstanceId.eq(instance_id)),
+            )
+            .all(&self.ctx.db)
+            .await?;
+
+        for v in triggers
+            .into_iter()
+            .filter(|v| should_fire(&v.trigger_on, succeeded))
+        {
+            let target_instances: Vec<String> = v
+                .target_instances
+                .map(serde_json::from_value)
+                .transpose()?
+                .unwrap_or_default();
+
+            info!(
+                "trigger {} fired by {eid} on {instance_id}, dispatch {}",
+                v.name, v.target_eid
+            );
+            if let Err(e) = self
+                .dispatch_job(
+                    self.ctx.conf.comet_secret.clone(),
+                    target_instances,
+                    v.target_eid.clone(),
+                    false,
+                    format!("trigger-{}", v.name),
+                    ScheduleType::Once,
+                    JobAction::Exec,
+                    None,
+                    TimerOptions::default(),
+                    None,
+                    SupervisorOptions::default(),
+                    None,
+                    v.created_user.clone(),
+                    None,
+                )
+                .await
+            {
+                error!(
+                    "failed dispatch job {} of trigger {} - {e}",
+                    v.target_eid, v.name
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_should_fire() {
+    assert!(should_fire(TRIGGER_ON_SUCCESS, true));
+    assert!(!should_fire(TRIGGER_ON_SUCCESS, false));
+    assert!(should_fire(TRIGGER_ON_FAILURE, false));
+    assert!(!should_fire(TRIGGER_ON_FAILURE, true));
+    assert!(should_fire(TRIGGER_ON_COMPLETE, true));
+    assert!(should_fire(TRIGGER_ON_COMPLETE, false));
+    assert!(!should_fire("unknown", true));
+}
+
+#[test]
+fn test_creates_cycle() {
+    let edges = vec![
+        ("a".to_string(), "b".to_string()),
+        ("b".to_string(), "c".to_string()),
+        ("x".to_string(), "y".to_string()),
+    ];
+    assert!(creates_cycle(&edges, "c", "a"));
+    assert!(creates_cycle(&edges, "b", "a"));
+    assert!(creates_cycle(&edges, "a", "a"));
+    assert!(!creates_cycle(&edges, "a", "c"));
+    assert!(!creates_cycle(&edges, "c", "x"));
+    assert!(!creates_cycle(&[], "a", "b"));
+}
@@ -23,6 +23,10 @@ pub struct RunStatusRelatedScheduleJobModel {
     pub run_status: String,
     pub exit_status: String,
     pub exit_code: i32,
+    pub parallel_decision: String,
+    pub restart_count: u32,
+    pub termination_reason: String,
+    pub probe_result: Option<serde_json::Value>,
     pub dispatch_data: Option<serde_json::Value>,
     pub dispatch_result: Option<serde_json::Value>,
     pub start_time: Option<DateTimeUtc>,
@@ -41,12 +45,18 @@ pub struct ExecHistoryRelatedScheduleModel {
     pub namespace: String,
     pub job_type: String,
     pub output: String,
+    pub stderr: String,
     pub team_id: Option<u64>,
     pub team_name: Option<String>,
     pub bundle_script_result: Option<serde_json::Value>,
     pub created_user: String,
     pub exit_code: i64,
     pub exit_status: String,
+    pub termination_reason: String,
+    pub term_signal: i32,
+    pub attempt: u32,
+    pub run_id: String,
+    pub start_offset_ms: u64,
     pub start_time: Option<DateTimeUtc>,
     pub end_time: Option<DateTimeUtc>,
     pub created_time: DateTimeUtc,
@@ -74,12 +84,16 @@ pub struct JobRelatedExecutorModel {
     pub upload_file: String,
     pub max_retry: u8,
     pub max_parallel: u8,
+    pub parallel_policy: String,
+    pub retry_interval: u64,
+    pub retry_backoff: String,
     pub timeout: u64,
     pub is_public: i8,
     pub created_user: String,
     pub updated_user: String,
     pub display_on_dashboard: bool,
     pub args: Option<serde_json::Value>,
+    pub env: Option<serde_json::Value>,
     pub created_time: DateTimeUtc,
     pub updated_time: DateTimeUtc,
 }
@@ -115,6 +129,10 @@ pub struct JobTimerRelatedJobModel {
     pub team_id: Option<u64>,
     pub team_name: Option<String>,
     pub timer_expr: Option<serde_json::Value>,
+    pub timezone: String,
+    pub calendar_id: u64,
+    pub jitter_mode: String,
+    pub jitter_secs: u32,
     pub info: String,
     pub created_user: String,
     pub updated_user: String,
@@ -136,6 +154,29 @@ pub struct DispatchData {
     pub params: DispatchJobParams,
 }
 
+/// targets of a timer kept on its schedule, re-evaluated when instance groups, tags or agents change
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
+pub struct TargetSelector {
+    /// the static endpoints, always targeted
+    #[serde(default)]
+    pub instance_ids: Vec<String>,
+    #[serde(default)]
+    pub instance_group_ids: Vec<u64>,
+    /// such as env=prod,role=web
+    #[serde(default)]
+    pub tag_selector: String,
+    #[serde(default)]
+    pub namespaces: Vec<String>,
+}
+
+impl TargetSelector {
+    pub fn is_dynamic(&self) -> bool {
+        !self.instance_group_ids.is_empty()
+            || !self.tag_selector.trim().is_empty()
+            || !self.namespaces.is_empty()
+    }
+}
+
 impl TryFrom<Value> for DispatchData {
     type Error = anyhow::Error;
 
@@ -169,6 +210,17 @@ pub struct BundleScriptResult {
     pub result: bool,
 }
 
+/// a bundle run succeeds when every condition holds, other runs when they exit with 0
+pub fn is_run_succeeded(
+    exit_code: Option<i32>,
+    bundle_script_result: Option<&[BundleScriptResult]>,
+) -> bool {
+    match bundle_script_result {
+        Some(v) => v.iter().all(|v| v.result),
+        None => exit_code == Some(0),
+    }
+}
+
 #[derive(Deserialize, Serialize, PartialEq, Debug, Clone, Default)]
 pub struct BundleScriptRecord {
     pub eid: String,
@@ -232,6 +284,13 @@ pub struct JobSupervisorRelatedJobModel {
     pub name: String,
     pub job_name: String,
     pub restart_interval: u64,
+    pub restart_policy: String,
+    pub expected_exit_codes: Option<serde_json::Value>,
+    pub restart_backoff: String,
+    pub max_restart_delay: u64,
+    pub max_restarts: u32,
+    pub restart_window: u64,
+    pub liveness_probe: Option<serde_json::Value>,
     pub executor_id: u64,
     pub executor_name: String,
     pub executor_platform: String,
@@ -272,3 +331,57 @@ pub struct ScheduleJobTeamModel {
     pub created_time: DateTimeUtc,
     pub updated_time: DateTimeUtc,
 }
+
+#[derive(Deserialize, Serialize, PartialEq, Debug, Clone, Default)]
+pub struct OrganizerNode {
+    pub id: String,
+    pub name: String,
+    /// start, end or task
+    pub node_type: String,
+    /// job eid executed by a task node
+    #[serde(default)]
+    pub eid: String,
+    /// instance id list the task node is dispatched to
+    #[serde(default)]
+    pub bind_instance: Vec<String>,
+    /// evalexpr boolean expression evaluated on every instance result,
+    /// `exit_code` and `stdout` are available as variables
+    #[serde(default)]
+    pub condition: String,
+    #[serde(default)]
+    pub props: Option<Value>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug, Clone, Default)]
+pub struct OrganizerEdge {
+    pub id: String,
+    pub source_node_id: String,
+    pub target_node_id: String,
+    /// empty means the edge is taken when the source node succeeds,
+    /// otherwise it must equal the output of the source node
+    #[serde(default)]
+    pub edge_val: String,
+    #[serde(default)]
+    pub props: Option<Value>,
+}
+
+#[derive(Deserialize, Serialize, PartialEq, Debug, Clone, Default)]
+pub struct FlowTaskData {
+    pub eid: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromQueryResult)]
+pub struct OrganizerProcessModel {
+    pub id: u64,
+    pub name: String,
+    pub organizer_id: u64,
+    pub organizer_name: Option<String>,
+    pub organizer_version: String,
+    pub process_id: String,
+    pub status: String,
+    pub current_node: String,
+    pub created_user: String,
+    pub updated_user: String,
+    pub created_time: DateTimeUtc,
+    pub updated_time: DateTimeUtc,
+}
@@ -0,0 +1,537 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+use automate::{
+    bridge::msg::UpdateJobParams,
+    scheduler::types::{RunStatus, ScheduleType, TerminationReason},
+};
+use chrono::{Duration, Local, Utc};
+use lettre::{
+    message::header::ContentType, transport::smtp::authentication::Credentials, AsyncSmtpTransport,
+    AsyncTransport, Message, Tokio1Executor,
+};
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
+    QueryTrait,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::error;
+
+use crate::{
+    entity::{instance, job, job_exec_history, notify_channel, notify_rule, prelude::*},
+    logic::job::types::{is_run_succeeded, BundleScriptResult},
+    state::AppContext,
+};
+
+use super::types::UserInfo;
+
+pub const EVENT_FAILURE: &str = "failure";
+pub const EVENT_SUCCESS: &str = "success";
+pub const EVENT_TIMEOUT: &str = "timeout";
+pub const EVENT_DAEMON_RESTART_STORM: &str = "daemon_restart_storm";
+pub const EVENT_AGENT_OFFLINE: &str = "agent_offline";
+
+pub const CHANNEL_WEBHOOK: &str = "webhook";
+pub const CHANNEL_EMAIL: &str = "email";
+pub const CHANNEL_SLACK: &str = "slack";
+pub const CHANNEL_DINGTALK: &str = "dingtalk";
+pub const CHANNEL_FEISHU: &str = "feishu";
+
+/// a daemon exiting this many times within RESTART_STORM_WINDOW_SECS is a restart storm
+const RESTART_STORM_THRESHOLD: u64 = 5;
+const RESTART_STORM_WINDOW_SECS: i64 = 300;
+/// chars of the run output kept in a notification
+const OUTPUT_EXCERPT_LIMIT: usize = 2000;
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct NotifyEvent {
+    pub event_type: String,
+    pub eid: String,
+    pub job_name: String,
+    pub team_id: u64,
+    pub schedule_id: String,
+    pub instance_id: String,
+    pub bind_ip: String,
+    pub namespace: String,
+    pub exit_code: i32,
+    pub exit_status: String,
+    pub termination_reason: String,
+    pub attempt: u32,
+    pub output: String,
+    pub time: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+pub struct ChannelConfig {
+    /// webhook and chat channels
+    #[serde(default)]
+    pub url: String,
+    /// http method of the webhook, POST by default
+    #[serde(default)]
+    pub method: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    /// email recipients
+    #[serde(default)]
+    pub to: Vec<String>,
+    /// email subject template
+    #[serde(default)]
+    pub subject: String,
+    /// message template, {{field}} is replaced with the field of the event
+    #[serde(default)]
+    pub template: String,
+}
+
+fn default_template(event_type: &str) -> &'static str {
+    match event_type {
+        EVENT_AGENT_OFFLINE => {
+            "[jiascheduler] agent {{bind_ip}} ({{namespace}} {{instance_id}}) went offline at {{time}}"
+        }
+        EVENT_DAEMON_RESTART_STORM => {
+            "[jiascheduler] daemon {{job_name}} ({{eid}}) on {{bind_ip}} keeps restarting, last exit_code {{exit_code}} {{exit_status}}\n{{output}}"
+        }
+        _ => {
+            "[jiascheduler] {{event_type}}: job {{job_name}} ({{eid}}) on {{bind_ip}} attempt {{attempt}} exit_code {{exit_code}} {{exit_status}} ({{termination_reason}}) at {{time}}\n{{output}}"
+        }
+    }
+}
+
+/// replace every {{field}} of the template with the event field, json_escape is used
+/// when the template is a json document
+fn render(template: &str, event: &NotifyEvent, json_escape: bool) -> String {
+    let Ok(Value::Object(fields)) = serde_json::to_value(event) else {
+        return template.to_string();
+    };
+    let mut ret = template.to_string();
+    for (k, v) in fields {
+        let v = match v {
+            Value::String(v) if json_escape => {
+                let v = Value::String(v).to_string();
+                v[1..v.len() - 1].to_string()
+            }
+            Value::String(v) => v,
+            v => v.to_string(),
+        };
+        ret = ret
+            .replace(&format!("{{{{{k}}}}}"), &v)
+            .replace(&format!("{{{{ {k} }}}}"), &v);
+    }
+    ret
+}
+
+fn output_excerpt(output: &str) -> String {
+    let total = output.chars().count();
+    if total <= OUTPUT_EXCERPT_LIMIT {
+        return output.to_string();
+    }
+    output.chars().skip(total - OUTPUT_EXCERPT_LIMIT).collect()
+}
+
+pub struct NotifyLogic<'a> {
+    ctx: &'a AppContext,
+}
+
+impl<'a> NotifyLogic<'a> {
+    pub fn new(ctx: &'a AppContext) -> Self {
+        Self { ctx }
+    }
+
+    pub async fn save_channel(
+        &self,
+        active_model: notify_channel::ActiveModel,
+    ) -> Result<notify_channel::ActiveModel> {
+        Ok(active_model.save(&self.ctx.db).await?)
+    }
+
+    pub async fn can_write_channel(&self, user_info: &UserInfo, id: u64) -> Result<bool> {
+        if self.ctx.can_manage_job(&user_info.user_id).await? {
+            return Ok(true);
+        }
+        let ok = NotifyChannel::find()
+            .filter(notify_channel::Column::Id.eq(id))
+            .filter(notify_channel::Column::CreatedUser.eq(&user_info.username))
+            .one(&self.ctx.db)
+            .await?
+            .is_some();
+        Ok(ok)
+    }
+
+    pub async fn get_channel(&self, id: u64) -> Result<Option<notify_channel::Model>> {
+        Ok(NotifyChannel::find_by_id(id).one(&self.ctx.db).await?)
+    }
+
+    pub async fn query_channel(
+        &self,
+        created_user: Option<&String>,
+        name: Option<String>,
+        page: u64,
+        page_size: u64,
+    ) -> Result<(Vec<notify_channel::Model>, u64)> {
+        let model = NotifyChannel::find()
+            .apply_if(created_user, |q, v| {
+                q.filter(notify_channel::Column::CreatedUser.eq(v))
+            })
+            .apply_if(name, |q, v| {
+                q.filter(notify_channel::Column::Name.contains(v))
+            });
+
+        let total = model.clone().count(&self.ctx.db).await?;
+        let list = model
+            .order_by_desc(notify_channel::Column::Id)
+            .paginate(&self.ctx.db, page_size)
+            .fetch_page(page)
+            .await?;
+        Ok((list, total))
+    }
+
+    pub async fn delete_channel(&self, id: u64) -> Result<u64> {
+        if NotifyRule::find()
+            .filter(notify_rule::Column::ChannelId.eq(id))
+            .one(&self.ctx.db)
+            .await?
+            .is_some()
+        {
+            anyhow::bail!("the channel is still used by notify rules");
+        }
+        let ret = NotifyChannel::delete_by_id(id).exec(&self.ctx.db).await?;
+        Ok(ret.rows_affected)
+    }
+
+    pub async fn save_rule(
+        &self,
+        active_model: notify_rule::ActiveModel,
+    ) -> Result<notify_rule::ActiveModel> {
+        Ok(active_model.save(&self.ctx.db).await?)
+    }
+
+    pub async fn can_write_rule(&self, user_info: &UserInfo, id: u64) -> Result<bool> {
+        if self.ctx.can_manage_job(&user_info.user_id).await? {
+            return Ok(true);
+        }
+        let ok = NotifyRule::find()
+            .filter(notify_rule::Column::Id.eq(id))
+            .filter(notify_rule::Column::CreatedUser.eq(&user_info.username))
+            .one(&self.ctx.db)
+            .await?
+            .is_some();
+        Ok(ok)
+    }
+
+    pub async fn query_rule(
+        &self,
+        created_user: Option<&String>,
+        event_type: Option<String>,
+        eid: Option<String>,
+        page: u64,
+        page_size: u64,
+    ) -> Result<(Vec<notify_rule::Model>, u64)> {
+        let model = NotifyRule::find()
+            .apply_if(created_user, |q, v| {
+                q.filter(notify_rule::Column::CreatedUser.eq(v))
+            })
+            .apply_if(event_type, |q, v| {
+                q.filter(notify_rule::Column::EventType.eq(v))
+            })
+            .apply_if(eid, |q, v| q.filter(notify_rule::Column::Eid.eq(v)));
+
+        let total = model.clone().count(&self.ctx.db).await?;
+        let list = model
+            .order_by_desc(notify_rule::Column::Id)
+            .paginate(&self.ctx.db, page_size)
+            .fetch_page(page)
+            .await?;
+        Ok((list, total))
+    }
+
+    pub async fn delete_rule(&self, id: u64) -> Result<u64> {
+        let ret = NotifyRule::delete_by_id(id).exec(&self.ctx.db).await?;
+        Ok(ret.rows_affected)
+    }
+
+    /// turn a finished run into a notify event and deliver it
+    pub async fn notify_job_status(&self, params: UpdateJobParams) -> Result<()> {
+        if params.run_status != Some(RunStatus::Stop) {
+            return Ok(());
+        }
+
+        let Some(job_record) = Job::find()
+            .filter(job::Column::Eid.eq(&params.base_job.eid))
+            .one(&self.ctx.db)
+            .await?
+        else {
+            return Ok(());
+        };
+
+        let Some(record) = JobExecHistory::find()
+            .filter(job_exec_history::Column::ScheduleId.eq(&params.schedule_id))
+            .filter(job_exec_history::Column::InstanceId.eq(&params.instance_id))
+            .order_by_desc(job_exec_history::Column::Id)
+            .one(&self.ctx.db)
+            .await?
+        else {
+            return Ok(());
+        };
+
+        let event_type = if params.schedule_type == Some(ScheduleType::Daemon) {
+            if !self.is_restart_storm(&params).await? {
+                return Ok(());
+            }
+            EVENT_DAEMON_RESTART_STORM
+        } else {
+            let bundle_script_result: Option<Vec<BundleScriptResult>> = record
+                .bundle_script_result
+                .clone()
+                .map(serde_json::from_value)
+                .transpose()?;
+            if is_run_succeeded(Some(record.exit_code), bundle_script_result.as_deref()) {
+                EVENT_SUCCESS
+            } else if params.termination_reason == Some(TerminationReason::Timeout) {
+                EVENT_TIMEOUT
+            } else {
+                EVENT_FAILURE
+            }
+        };
+
+        let output = if record.stderr.is_empty() {
+            record.output
+        } else {
+            format!("{}\n{}", record.stderr, record.output)
+        };
+
+        self.notify(NotifyEvent {
+            event_type: event_type.to_string(),
+            eid: job_record.eid,
+            job_name: job_record.name,
+            team_id: job_record.team_id,
+            schedule_id: params.schedule_id,
+            instance_id: params.instance_id,
+            bind_ip: params.bind_ip,
+            namespace: params.bind_namespace,
+            exit_code: record.exit_code,
+            exit_status: record.exit_status,
+            termination_reason: params
+                .termination_reason
+                .map(|v| v.to_string())
+                .unwrap_or_default(),
+            attempt: record.attempt,
+            output: output_excerpt(&output),
+            time: Local::now().to_rfc3339(),
+        })
+        .await
+    }
+
+    pub async fn notify_agent_offline(&self, agent_ip: String, mac_addr: String) -> Result<()> {
+        let ins = Instance::find()
+            .filter(instance::Column::Ip.eq(&agent_ip))
+            .filter(instance::Column::MacAddr.eq(&mac_addr))
+            .one(&self.ctx.db)
+            .await?;
+
+        self.notify(NotifyEvent {
+            event_type: EVENT_AGENT_OFFLINE.to_string(),
+            instance_id: ins
+                .as_ref()
+                .map_or(String::new(), |v| v.instance_id.clone()),
+            namespace: ins.map_or(String::new(), |v| v.namespace),
+            bind_ip: agent_ip,
+            time: Local::now().to_rfc3339(),
+            ..Default::default()
+        })
+        .await
+    }
+
+    /// only the exit that reaches the threshold is reported, so a storm notifies once
+    async fn is_restart_storm(&self, params: &UpdateJobParams) -> Result<bool> {
+        let total = JobExecHistory::find()
+            .filter(job_exec_history::Column::ScheduleId.eq(&params.schedule_id))
+            .filter(job_exec_history::Column::InstanceId.eq(&params.instance_id))
+            .filter(
+                job_exec_history::Column::CreatedTime
+                    .gte(Utc::now() - Duration::seconds(RESTART_STORM_WINDOW_SECS)),
+            )
+            .count(&self.ctx.db)
+            .await?;
+        Ok(total == RESTART_STORM_THRESHOLD)
+    }
+
+    /// deliver the event to the channels of every enabled rule matching it
+    pub async fn notify(&self, event: NotifyEvent) -> Result<()> {
+        let rules = NotifyRule::find()
+            .filter(notify_rule::Column::EventType.eq(&event.event_type))
+            .filter(notify_rule::Column::IsEnabled.eq(true))
+            .filter(
+                Condition::any()
+                    .add(notify_rule::Column::Eid.eq(""))
+                    .add(notify_rule::Column::Eid.eq(&event.eid)),
+            )
+            .filter(
+                Condition::any()
+                    .add(notify_rule::Column::TeamId.eq(0))
+                    .add(notify_rule::Column::TeamId.eq(event.team_id)),
+            )
+            .all(&self.ctx.db)
+            .await?;
+        if rules.is_empty() {
+            return Ok(());
+        }
+
+        let channel_ids: HashSet<u64> = rules.iter().map(|v| v.channel_id).collect();
+        let channels = NotifyChannel::find()
+            .filter(notify_channel::Column::Id.is_in(channel_ids))
+            .all(&self.ctx.db)
+            .await?;
+
+        for v in channels {
+            if let Err(e) = self.send(&v, &event).await {
+                error!(
+                    "failed send {} notification to channel {} - {e}",
+                    event.event_type, v.name
+                );
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn send(&self, channel: &notify_channel::Model, event: &NotifyEvent) -> Result<()> {
+        let config: ChannelConfig = channel
+            .config
+            .clone()
+            .map(serde_json::from_value)
+            .transpose()?
+            .unwrap_or_default();
+        let template = if config.template.is_empty() {
+            default_template(&event.event_type)
+        } else {
+            &config.template
+        };
+
+        match channel.channel_type.as_str() {
+            CHANNEL_WEBHOOK => {
+                let body = if config.template.is_empty() {
+                    serde_json::to_string(event)?
+                } else {
+                    render(template, event, true)
+                };
+                let method = if config.method.is_empty() {
+                    reqwest::Method::POST
+                } else {
+                    reqwest::Method::from_bytes(config.method.to_uppercase().as_bytes())?
+                };
+                let mut req = self
+                    .ctx
+                    .http_client
+                    .request(method, &config.url)
+                    .header(reqwest::header::CONTENT_TYPE, "application/json")
+                    .body(body);
+                for (k, v) in config.headers.iter() {
+                    req = req.header(k, v);
+                }
+                req.send().await?.error_for_status()?;
+            }
+            CHANNEL_SLACK => {
+                self.post_json(&config.url, json!({"text": render(template, event, false)}))
+                    .await?
+            }
+            CHANNEL_DINGTALK => {
+                self.post_json(
+                    &config.url,
+                    json!({"msgtype": "text", "text": {"content": render(template, event, false)}}),
+                )
+                .await?
+            }
+            CHANNEL_FEISHU => {
+                self.post_json(
+                    &config.url,
+                    json!({"msg_type": "text", "content": {"text": render(template, event, false)}}),
+                )
+                .await?
+            }
+            CHANNEL_EMAIL => {
+                let subject = if config.subject.is_empty() {
+                    "[jiascheduler] {{event_type}} {{job_name}} {{bind_ip}}"
+                } else {
+                    &config.subject
+                };
+                self.send_email(
+                    &config.to,
+                    render(subject, event, false),
+                    render(template, event, false),
+                )
+                .await?
+            }
+            v => anyhow::bail!("unsupported channel type {v}"),
+        }
+        Ok(())
+    }
+
+    async fn post_json(&self, url: &str, body: Value) -> Result<()> {
+        self.ctx
+            .http_client
+            .post(url)
+            .json(&body)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    async fn send_email(&self, to: &[String], subject: String, body: String) -> Result<()> {
+        let smtp = &self.ctx.conf.smtp;
+        if smtp.host.is_empty() {
+            anyhow::bail!("smtp is not configured");
+        }
+        if to.is_empty() {
+            anyhow::bail!("email channel has no recipient");
+        }
+
+        let mut builder = Message::builder().from(smtp.from.parse()?).subject(subject);
+        for v in to {
+            builder = builder.to(v.parse()?);
+        }
+        let message = builder.header(ContentType::TEXT_PLAIN).body(body)?;
+
+        let mut transport = match smtp.security.as_str() {
+            "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&smtp.host),
+            "starttls" => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&smtp.host)?,
+            _ => AsyncSmtpTransport::<Tokio1Executor>::relay(&smtp.host)?,
+        };
+        if let Some(port) = smtp.port {
+            transport = transport.port(port);
+        }
+        if !smtp.username.is_empty() {
+            transport = transport.credentials(Credentials::new(
+                smtp.username.clone(),
+                smtp.password.clone(),
+            ));
+        }
+        transport
+            .build()
+            .send(message)
+            .await
+            .map_err(|e| anyhow!("failed send email - {e}"))?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_render() {
+    let event = NotifyEvent {
+        event_type: EVENT_FAILURE.to_string(),
+        job_name: "backup \"db\"".to_string(),
+        exit_code: 2,
+        ..Default::default()
+    };
+    assert_eq!(
+        render(
+            "{{event_type}}: {{ job_name }} exit {{exit_code}}",
+            &event,
+            false
+        ),
+        "failure: backup \"db\" exit 2"
+    );
+    assert_eq!(
+        render(r#"{"job":"{{job_name}}"}"#, &event, true),
+        r#"{"job":"backup \"db\""}"#
+    );
+}
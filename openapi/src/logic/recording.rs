@@ -0,0 +1,204 @@
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+use anyhow::{anyhow, Result};
+use chrono::{Local, Utc};
+use nanoid::nanoid;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
+    QueryTrait, Set,
+};
+use serde_json::{json, Value};
+use tokio::fs::{self, File};
+use tokio::io::{AsyncWriteExt, BufWriter};
+
+use crate::{
+    entity::{prelude::*, terminal_recording},
+    logic::types::{UserInfo, UserServer},
+    state::AppContext,
+};
+
+/// one asciicast v2 event line, the time is in seconds since the session started
+fn event_line(time: f64, code: &str, data: &str) -> Result<String> {
+    Ok(serde_json::to_string(&(time, code, data))? + "\n")
+}
+
+/// writer of a terminal session in asciicast v2 format
+pub struct Recorder {
+    id: u64,
+    pub recording_id: String,
+    start: Instant,
+    size: u64,
+    writer: BufWriter<File>,
+}
+
+impl Recorder {
+    async fn create(
+        id: u64,
+        recording_id: String,
+        filepath: &Path,
+        width: u32,
+        height: u32,
+    ) -> Result<Self> {
+        let mut recorder = Self {
+            id,
+            recording_id,
+            start: Instant::now(),
+            size: 0,
+            writer: BufWriter::new(File::create(filepath).await?),
+        };
+
+        let header = json!({
+            "version": 2,
+            "width": width,
+            "height": height,
+            "timestamp": Utc::now().timestamp(),
+            "env": {"TERM": "xterm"},
+        });
+        recorder.write(header.to_string() + "\n").await?;
+        Ok(recorder)
+    }
+
+    async fn write(&mut self, line: String) -> Result<()> {
+        self.writer.write_all(line.as_bytes()).await?;
+        self.size += line.len() as u64;
+        Ok(())
+    }
+
+    /// output frame sent to the browser terminal
+    pub async fn output(&mut self, data: &str) -> Result<()> {
+        let line = event_line(self.start.elapsed().as_secs_f64(), "o", data)?;
+        self.write(line).await
+    }
+
+    pub async fn resize(&mut self, cols: u32, rows: u32) -> Result<()> {
+        let line = event_line(
+            self.start.elapsed().as_secs_f64(),
+            "r",
+            &format!("{cols}x{rows}"),
+        )?;
+        self.write(line).await
+    }
+}
+
+pub struct RecordingLogic<'a> {
+    ctx: &'a AppContext,
+}
+
+impl<'a> RecordingLogic<'a> {
+    pub fn new(ctx: &'a AppContext) -> Self {
+        Self { ctx }
+    }
+
+    /// create the recording of a webssh session, the file is saved under the recording dir
+    /// of the console grouped by day
+    pub async fn start(
+        &self,
+        user_info: &UserInfo,
+        server: &UserServer,
+        via_tunnel: bool,
+        width: u32,
+        height: u32,
+    ) -> Result<Recorder> {
+        let recording_id = nanoid!();
+        let dir = PathBuf::from(self.ctx.conf.get_recording_dir()?)
+            .join(Local::now().format("%Y%m%d").to_string());
+        fs::create_dir_all(&dir).await?;
+        let filepath = dir.join(format!("{recording_id}.cast"));
+
+        let record = terminal_recording::ActiveModel {
+            recording_id: Set(recording_id.clone()),
+            instance_id: Set(server.instance_id.clone()),
+            ip: Set(server.ip.clone()),
+            namespace: Set(server.namespace.clone()),
+            user_id: Set(user_info.user_id.clone()),
+            username: Set(user_info.username.clone()),
+            sys_user: Set(server.sys_user.clone().unwrap_or_default()),
+            via_tunnel: Set(via_tunnel),
+            filepath: Set(filepath.to_string_lossy().to_string()),
+            width: Set(width),
+            height: Set(height),
+            start_time: Set(Some(Utc::now())),
+            ..Default::default()
+        }
+        .insert(&self.ctx.db)
+        .await?;
+
+        Recorder::create(record.id, recording_id, &filepath, width, height).await
+    }
+
+    /// flush the recording and set the end time of the session
+    pub async fn finish(&self, mut recorder: Recorder) -> Result<()> {
+        recorder.writer.flush().await?;
+        terminal_recording::ActiveModel {
+            id: Set(recorder.id),
+            file_size: Set(recorder.size),
+            end_time: Set(Some(Utc::now())),
+            ..Default::default()
+        }
+        .update(&self.ctx.db)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn query_recording(
+        &self,
+        username: Option<String>,
+        instance_id: Option<String>,
+        page: u64,
+        page_size: u64,
+    ) -> Result<(Vec<terminal_recording::Model>, u64)> {
+        let model = TerminalRecording::find()
+            .apply_if(username, |q, v| {
+                q.filter(terminal_recording::Column::Username.eq(v))
+            })
+            .apply_if(instance_id, |q, v| {
+                q.filter(terminal_recording::Column::InstanceId.eq(v))
+            });
+
+        let total = model.clone().count(&self.ctx.db).await?;
+        let list = model
+            .order_by_desc(terminal_recording::Column::Id)
+            .paginate(&self.ctx.db, page_size)
+            .fetch_page(page)
+            .await?;
+        Ok((list, total))
+    }
+
+    pub async fn get_recording(&self, recording_id: &str) -> Result<terminal_recording::Model> {
+        TerminalRecording::find()
+            .filter(terminal_recording::Column::RecordingId.eq(recording_id))
+            .one(&self.ctx.db)
+            .await?
+            .ok_or(anyhow!("cannot found recording {recording_id}"))
+    }
+
+    pub async fn read_recording(&self, record: &terminal_recording::Model) -> Result<Vec<u8>> {
+        Ok(fs::read(&record.filepath).await?)
+    }
+
+    /// header and events of the recording, an event is [time, code, data]
+    pub async fn parse_recording(
+        &self,
+        record: &terminal_recording::Model,
+    ) -> Result<(Value, Vec<Value>)> {
+        let data = fs::read_to_string(&record.filepath).await?;
+        let mut lines = data.lines().filter(|v| !v.is_empty());
+        let header = serde_json::from_str(lines.next().ok_or(anyhow!("empty recording"))?)?;
+        // the last line may be cut off when the console exits during a session
+        let events = lines.filter_map(|v| serde_json::from_str(v).ok()).collect();
+        Ok((header, events))
+    }
+}
+
+#[test]
+fn test_event_line() {
+    assert_eq!(
+        event_line(1.5, "o", "ls\r\n\"a\"").unwrap(),
+        "[1.5,\"o\",\"ls\\r\\n\\\"a\\\"\"]\n"
+    );
+    assert_eq!(
+        event_line(0.25, "r", "80x24").unwrap(),
+        "[0.25,\"r\",\"80x24\"]\n"
+    );
+}
@@ -44,6 +44,12 @@ const POLICY_ALLOW_UPLOAD_FILE: Permission = Permission {
     action: "upload",
 };
 
+const POLICY_ALLOW_AUDIT_TERMINAL: Permission = Permission {
+    name: "Allow audit terminal recordings",
+    object: "terminal",
+    action: "audit",
+};
+
 pub static PERMISSIONS: LazyLock<Vec<Permission>> = LazyLock::new(|| {
     // vec![
     //     Permission {
@@ -68,6 +74,7 @@ pub static PERMISSIONS: LazyLock<Vec<Permission>> = LazyLock::new(|| {
         POLICY_DO_NOT_ALLOW_CHANGE_DATA,
         POLICY_ALLOW_CHANGE_ALL_JOB,
         POLICY_ALLOW_UPLOAD_FILE,
+        POLICY_ALLOW_AUDIT_TERMINAL,
     ]
 });
 
@@ -4,57 +4,49 @@ use std::time::Duration;
 
 use anyhow::Result;
 
-use async_trait::async_trait;
 use automate::bridge::msg::{
-    SftpDownloadParams, SftpReadDirParams, SftpRemoveParams, SftpUploadParams,
+    SftpDownloadParams, SftpReadDirParams, SftpRemoveParams, SftpUploadParams, SshHostKeyParams,
 };
+use automate::ssh::{handshake, Client, SshAuth};
 use futures::stream::{SplitSink, SplitStream};
 use futures::{SinkExt, StreamExt};
 use poem::web::websocket::{Message, WebSocketStream};
 use russh::*;
-use russh_keys::*;
 use russh_sftp::client::SftpSession;
 use serde_json::Value;
 
 use crate::api::terminal::types::{Msg, MsgType};
+use crate::logic::instance::InstanceLogic;
+use crate::logic::recording::Recorder;
+use crate::logic::types::SshCredential;
 use crate::state::AppContext;
 
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::ToSocketAddrs;
-use tokio::time::timeout;
 use tracing::info;
 
-struct Client {}
-
-#[async_trait]
-impl client::Handler for Client {
-    type Error = russh::Error;
-
-    async fn check_server_key(
-        &mut self,
-        _server_public_key: &key::PublicKey,
-    ) -> Result<bool, Self::Error> {
-        Ok(true)
-    }
-}
-
 pub struct Session {
     session: client::Handle<Client>,
+    /// fingerprint of the host key offered by the server
+    pub host_key: String,
 }
 
-pub struct ConnectParams<A: ToSocketAddrs, U: Into<String>, P: Into<String>> {
+pub struct ConnectParams<A: ToSocketAddrs, U: Into<String>> {
     pub user: U,
-    pub password: P,
+    pub auth: SshAuth,
     pub addrs: A,
+    /// pinned host key fingerprint, empty to trust the first key seen
+    pub host_key: String,
 }
 
 impl Session {
-    pub async fn connect<A: ToSocketAddrs, U: Into<String>, P: Into<String>>(
+    pub async fn connect<A: ToSocketAddrs, U: Into<String>>(
         ConnectParams {
             user,
-            password,
+            auth,
             addrs,
-        }: ConnectParams<A, U, P>,
+            host_key,
+        }: ConnectParams<A, U>,
     ) -> Result<Self> {
         let config = client::Config {
             inactivity_timeout: Some(Duration::from_secs(90)),
@@ -63,24 +55,20 @@ impl Session {
         };
 
         let config = Arc::new(config);
-        let sh = Client {};
-
-        let mut session =
-            timeout(Duration::from_secs(1), client::connect(config, addrs, sh)).await??;
-
-        let auth_res = session.authenticate_password(user, password).await?;
 
-        if !auth_res {
-            anyhow::bail!("Authentication failed");
-        }
+        let (session, host_key) = handshake(host_key, &auth, user.into(), |sh| {
+            client::connect(config, addrs, sh)
+        })
+        .await?;
 
-        Ok(Self { session })
+        Ok(Self { session, host_key })
     }
 
     #[allow(dead_code)]
     pub async fn connect_stream<T: AsyncRead + AsyncWrite + Unpin + Send + 'static>(
         user: String,
-        password: String,
+        auth: SshAuth,
+        host_key: String,
         stream: T,
     ) -> Result<Self> {
         let config = client::Config {
@@ -90,23 +78,17 @@ impl Session {
         };
 
         let config = Arc::new(config);
-        let sh = Client {};
-
-        let mut session = timeout(
-            Duration::from_secs(1),
-            client::connect_stream(config, stream, sh),
-        )
-        .await??;
 
-        let auth_res = session.authenticate_password(user, password).await?;
-
-        if !auth_res {
-            anyhow::bail!("Authentication failed");
-        }
+        let (session, host_key) = handshake(host_key, &auth, user, |sh| {
+            client::connect_stream(config, stream, sh)
+        })
+        .await?;
 
-        Ok(Self { session })
+        Ok(Self { session, host_key })
     }
 
+    /// relay the shell between the websocket and the server, output and resize events are
+    /// written to the recorder
     pub async fn call(
         &self,
         _command: &str,
@@ -114,6 +96,7 @@ impl Session {
         rows: u32,
         sink: &mut SplitSink<WebSocketStream, Message>,
         mut stream: SplitStream<WebSocketStream>,
+        recorder: &mut Recorder,
     ) -> Result<u32> {
         let mut channel = self.session.channel_open_session().await?;
 
@@ -161,6 +144,7 @@ impl Session {
                         MsgType::Resize => {
                             info!("resize {},{}",msg.cols,msg.rows);
                             channel.window_change(msg.cols, msg.rows, 0, 0).await.expect("failed resize windows");
+                            recorder.resize(msg.cols, msg.rows).await?;
 
                         },
                         MsgType::Data => {
@@ -176,7 +160,9 @@ impl Session {
                     match msg {
                         // Write data to the terminal
                         ChannelMsg::Data { ref data } => {
-                            sink.send(Message::Text(String::from_utf8_lossy(&data.to_vec()).to_string())).await?;
+                            let text = String::from_utf8_lossy(data).to_string();
+                            recorder.output(&text).await?;
+                            sink.send(Message::Text(text)).await?;
                         }
                         // The command has returned an exit code
                         ChannelMsg::ExitStatus { exit_status } => {
@@ -208,7 +194,6 @@ impl Session {
 }
 
 pub struct SshLogic<'a> {
-    #[allow(dead_code)]
     ctx: &'a AppContext,
 }
 
@@ -217,37 +202,70 @@ impl<'a> SshLogic<'a> {
         Self { ctx }
     }
 
-    pub async fn sftp_read_dir(
+    /// connect to the instance from the console, the host key seen on first use is pinned
+    pub async fn connect(&self, instance_id: &str, ip: String) -> Result<Session> {
+        let instance = InstanceLogic::new(self.ctx);
+        let cred = instance.get_ssh_credential(instance_id).await?;
+        let session = Session::connect(ConnectParams {
+            user: cred.user,
+            auth: cred.auth,
+            addrs: (ip, if cred.port == 0 { 22 } else { cred.port }),
+            host_key: cred.host_key,
+        })
+        .await?;
+        instance
+            .pin_host_key(instance_id, &session.host_key)
+            .await?;
+        Ok(session)
+    }
+
+    /// ssh login used by the agent of the instance, the host key is read through the agent
+    /// and pinned when the instance has none yet
+    pub async fn tunnel_credential(
         &self,
+        instance_id: &str,
         namespace: String,
         ip: String,
         mac_addr: String,
-        port: u16,
-        dir: Option<String>,
-        user: String,
-        password: String,
+    ) -> Result<SshCredential> {
+        let instance = InstanceLogic::new(self.ctx);
+        let mut cred = instance.get_ssh_credential(instance_id).await?;
+        if cred.user.is_empty() {
+            anyhow::bail!("no system user");
+        }
+        if cred.auth.password.is_empty() && cred.auth.private_key.is_empty() {
+            anyhow::bail!("no password or private key");
+        }
+        if cred.port == 0 {
+            anyhow::bail!("no ssh port");
+        }
+
+        if cred.host_key.is_empty() {
+            let host_key = self
+                .ssh_host_key(namespace, ip, mac_addr, cred.port)
+                .await?;
+            instance.pin_host_key(instance_id, &host_key).await?;
+            cred.host_key = instance.get_ssh_credential(instance_id).await?.host_key;
+        }
+        Ok(cred)
+    }
+
+    async fn tunnel_request<T: serde::Serialize>(
+        &self,
+        ip: &str,
+        mac_addr: &str,
+        path: &str,
+        body: &T,
     ) -> Result<Value> {
         let logic = automate::Logic::new(self.ctx.redis().clone());
-        let pair = logic.get_link_pair(ip.clone(), mac_addr.clone()).await?;
-        let api_url = format!("http://{}/sftp/tunnel/read-dir", pair.1.comet_addr);
+        let pair = logic.get_link_pair(ip, mac_addr).await?;
+        let api_url = format!("http://{}{path}", pair.1.comet_addr);
 
-        let body = automate::SftpReadDirRequest {
-            agent_ip: ip.clone(),
-            namespace: namespace.clone(),
-            params: SftpReadDirParams {
-                user,
-                password,
-                ip,
-                dir,
-                port,
-            },
-            mac_addr,
-        };
         let mut ret = self
             .ctx
             .http_client
             .post(api_url)
-            .json(&body)
+            .json(body)
             .send()
             .await?
             .json::<serde_json::Value>()
@@ -260,50 +278,81 @@ impl<'a> SshLogic<'a> {
         }
     }
 
-    pub async fn sftp_upload(
+    pub async fn ssh_host_key(
         &self,
         namespace: String,
         ip: String,
         mac_addr: String,
         port: u16,
-        user: String,
-        password: String,
+    ) -> Result<String> {
+        let body = automate::SshHostKeyRequest {
+            agent_ip: ip.clone(),
+            namespace,
+            mac_addr: mac_addr.clone(),
+            params: SshHostKeyParams {
+                ip: ip.clone(),
+                port,
+            },
+        };
+        let ret = self
+            .tunnel_request(&ip, &mac_addr, "/ssh/tunnel/host-key", &body)
+            .await?;
+        Ok(serde_json::from_value(ret)?)
+    }
+
+    pub async fn sftp_read_dir(
+        &self,
+        namespace: String,
+        ip: String,
+        mac_addr: String,
+        cred: SshCredential,
+        dir: Option<String>,
+    ) -> Result<Value> {
+        let body = automate::SftpReadDirRequest {
+            agent_ip: ip.clone(),
+            namespace,
+            params: SftpReadDirParams {
+                user: cred.user,
+                auth: cred.auth,
+                host_key: cred.host_key,
+                ip: ip.clone(),
+                dir,
+                port: cred.port,
+            },
+            mac_addr: mac_addr.clone(),
+        };
+        self.tunnel_request(&ip, &mac_addr, "/sftp/tunnel/read-dir", &body)
+            .await
+    }
+
+    pub async fn sftp_upload(
+        &self,
+        namespace: String,
+        ip: String,
+        mac_addr: String,
+        cred: SshCredential,
         filepath: String,
         data: Vec<u8>,
     ) -> Result<String> {
-        let logic = automate::Logic::new(self.ctx.redis());
-        let pair = logic.get_link_pair(ip.clone(), mac_addr.clone()).await?;
-        let api_url = format!("http://{}/sftp/tunnel/upload", pair.1.comet_addr);
-
         let body = automate::SftpUploadRequest {
             agent_ip: ip.clone(),
-            namespace: namespace.clone(),
-            mac_addr,
+            namespace,
+            mac_addr: mac_addr.clone(),
             params: SftpUploadParams {
-                ip,
-                port,
-                user,
-                password,
+                ip: ip.clone(),
+                port: cred.port,
+                user: cred.user,
+                auth: cred.auth,
+                host_key: cred.host_key,
                 filepath,
                 data,
             },
         };
 
-        let mut ret = self
-            .ctx
-            .http_client
-            .post(api_url)
-            .json(&body)
-            .send()
-            .await?
-            .json::<serde_json::Value>()
+        let ret = self
+            .tunnel_request(&ip, &mac_addr, "/sftp/tunnel/upload", &body)
             .await?;
-
-        if ret["code"] != 20000 {
-            anyhow::bail!(ret["msg"].take().to_string())
-        } else {
-            Ok(ret["data"].to_string())
-        }
+        Ok(ret.to_string())
     }
 
     /// remove type, dir or file
@@ -312,45 +361,29 @@ impl<'a> SshLogic<'a> {
         namespace: String,
         ip: String,
         mac_addr: String,
-        port: u16,
-        user: String,
-        password: String,
+        cred: SshCredential,
         filepath: String,
         remove_type: String,
     ) -> Result<String> {
-        let logic = automate::Logic::new(self.ctx.redis().clone());
-        let pair = logic.get_link_pair(ip.clone(), mac_addr.clone()).await?;
-        let api_url = format!("http://{}/sftp/tunnel/remove", pair.1.comet_addr);
-
         let body = automate::SftpRemoveRequest {
             agent_ip: ip.clone(),
-            namespace: namespace.clone(),
-            mac_addr,
+            namespace,
+            mac_addr: mac_addr.clone(),
             params: SftpRemoveParams {
-                ip,
-                port,
-                user,
-                password,
+                ip: ip.clone(),
+                port: cred.port,
+                user: cred.user,
+                auth: cred.auth,
+                host_key: cred.host_key,
                 filepath,
                 remove_type,
             },
         };
 
-        let mut ret = self
-            .ctx
-            .http_client
-            .post(api_url)
-            .json(&body)
-            .send()
-            .await?
-            .json::<serde_json::Value>()
+        let ret = self
+            .tunnel_request(&ip, &mac_addr, "/sftp/tunnel/remove", &body)
             .await?;
-
-        if ret["code"] != 20000 {
-            anyhow::bail!(ret["msg"].take().to_string())
-        } else {
-            Ok(ret["data"].to_string())
-        }
+        Ok(ret.to_string())
     }
 
     pub async fn sftp_download(
@@ -358,43 +391,27 @@ impl<'a> SshLogic<'a> {
         namespace: String,
         ip: String,
         mac_addr: String,
-        port: u16,
-        user: String,
-        password: String,
+        cred: SshCredential,
         filepath: String,
     ) -> Result<Vec<u8>> {
-        let logic = automate::Logic::new(self.ctx.redis().clone());
-        let pair = logic.get_link_pair(ip.clone(), mac_addr.clone()).await?;
-        let api_url = format!("http://{}/sftp/tunnel/download", pair.1.comet_addr);
-
         let body = automate::SftpDownloadRequest {
             agent_ip: ip.clone(),
-            namespace: namespace.clone(),
-            mac_addr,
+            namespace,
+            mac_addr: mac_addr.clone(),
             params: SftpDownloadParams {
-                ip,
-                port,
-                user,
-                password,
+                ip: ip.clone(),
+                port: cred.port,
+                user: cred.user,
+                auth: cred.auth,
+                host_key: cred.host_key,
                 filepath,
             },
         };
 
-        let mut ret = self
-            .ctx
-            .http_client
-            .post(api_url)
-            .json(&body)
-            .send()
-            .await?
-            .json::<serde_json::Value>()
+        let ret = self
+            .tunnel_request(&ip, &mac_addr, "/sftp/tunnel/download", &body)
             .await?;
-
-        if ret["code"] != 20000 {
-            anyhow::bail!(ret["msg"].take().to_string())
-        } else {
-            let data: Vec<u8> = serde_json::from_value(ret["data"].take())?;
-            Ok(data)
-        }
+        let data: Vec<u8> = serde_json::from_value(ret)?;
+        Ok(data)
     }
 }
@@ -0,0 +1,297 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::Result;
+use automate::scheduler::types::parse_labels;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, Condition, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder,
+    QueryTrait, Set,
+};
+
+use crate::{
+    entity::{instance, prelude::*, tag, tag_resource},
+    state::AppContext,
+};
+
+use super::types::UserInfo;
+
+pub const RESOURCE_INSTANCE: &str = "instance";
+pub const RESOURCE_JOB: &str = "job";
+/// created_user of the tags and bindings made from the labels reported by agents
+pub const AGENT_LABEL_USER: &str = "agent";
+
+#[derive(Clone)]
+pub struct TagLogic<'a> {
+    ctx: &'a AppContext,
+}
+
+impl<'a> TagLogic<'a> {
+    pub fn new(ctx: &'a AppContext) -> Self {
+        Self { ctx }
+    }
+
+    pub fn check_tag(tag_key: &str, tag_val: &str) -> Result<()> {
+        let invalid = |v: &str| v.trim().is_empty() || v.contains([',', '=']);
+        if invalid(tag_key) || invalid(tag_val) {
+            anyhow::bail!("invalid tag {tag_key}={tag_val}, key and value must not be empty or contain , and =");
+        }
+        Ok(())
+    }
+
+    pub async fn save_tag(&self, active_model: tag::ActiveModel) -> Result<tag::ActiveModel> {
+        if let (Some(key), Some(val)) = (
+            active_model.tag_key.try_as_ref(),
+            active_model.tag_val.try_as_ref(),
+        ) {
+            Self::check_tag(key, val)?;
+        }
+        Ok(active_model.save(&self.ctx.db).await?)
+    }
+
+    pub async fn can_write_tag(&self, user_info: &UserInfo, id: u64) -> Result<bool> {
+        if self.ctx.can_manage_instance(&user_info.user_id).await? {
+            return Ok(true);
+        }
+        let ok = Tag::find()
+            .filter(tag::Column::Id.eq(id))
+            .filter(tag::Column::CreatedUser.eq(&user_info.username))
+            .one(&self.ctx.db)
+            .await?
+            .is_some();
+        Ok(ok)
+    }
+
+    pub async fn query_tag(
+        &self,
+        created_user: Option<&String>,
+        keyword: Option<String>,
+        page: u64,
+        page_size: u64,
+    ) -> Result<(Vec<tag::Model>, u64)> {
+        let model = Tag::find()
+            .apply_if(created_user, |q, v| {
+                q.filter(tag::Column::CreatedUser.is_in([v.as_str(), AGENT_LABEL_USER]))
+            })
+            .apply_if(keyword, |q, v| {
+                q.filter(
+                    Condition::any()
+                        .add(tag::Column::TagKey.contains(&v))
+                        .add(tag::Column::TagVal.contains(&v)),
+                )
+            });
+
+        let total = model.clone().count(&self.ctx.db).await?;
+        let list = model
+            .order_by_desc(tag::Column::Id)
+            .paginate(&self.ctx.db, page_size)
+            .fetch_page(page)
+            .await?;
+        Ok((list, total))
+    }
+
+    /// how many resources of each type every tag is bound to
+    pub async fn count_resource(&self, tag_ids: Vec<u64>) -> Result<HashMap<(u64, String), u64>> {
+        let list = TagResource::find()
+            .filter(tag_resource::Column::TagId.is_in(tag_ids))
+            .all(&self.ctx.db)
+            .await?;
+        Ok(list.into_iter().fold(HashMap::new(), |mut acc, v| {
+            *acc.entry((v.tag_id, v.resource_type)).or_default() += 1;
+            acc
+        }))
+    }
+
+    pub async fn delete_tag(&self, id: u64) -> Result<u64> {
+        TagResource::delete_many()
+            .filter(tag_resource::Column::TagId.eq(id))
+            .exec(&self.ctx.db)
+            .await?;
+        let ret = Tag::delete_by_id(id).exec(&self.ctx.db).await?;
+        Ok(ret.rows_affected)
+    }
+
+    /// bind every tag to every resource, existing bindings are kept
+    pub async fn bind_tag(
+        &self,
+        tag_ids: Vec<u64>,
+        resource_type: &str,
+        resource_vals: Vec<String>,
+        created_user: &str,
+    ) -> Result<u64> {
+        let bound = TagResource::find()
+            .filter(tag_resource::Column::TagId.is_in(tag_ids.clone()))
+            .filter(tag_resource::Column::ResourceType.eq(resource_type))
+            .filter(tag_resource::Column::ResourceVal.is_in(resource_vals.clone()))
+            .all(&self.ctx.db)
+            .await?
+            .into_iter()
+            .map(|v| (v.tag_id, v.resource_val))
+            .collect::<HashSet<_>>();
+
+        let models = tag_ids
+            .iter()
+            .flat_map(|&tag_id| resource_vals.iter().map(move |val| (tag_id, val)))
+            .filter(|(tag_id, val)| !bound.contains(&(*tag_id, val.to_string())))
+            .map(|(tag_id, val)| tag_resource::ActiveModel {
+                tag_id: Set(tag_id),
+                resource_type: Set(resource_type.to_string()),
+                resource_val: Set(val.to_string()),
+                created_user: Set(created_user.to_string()),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+
+        let num = models.len() as u64;
+        if num > 0 {
+            TagResource::insert_many(models).exec(&self.ctx.db).await?;
+        }
+        Ok(num)
+    }
+
+    /// instances are bound by their primary key
+    pub async fn instance_resource_vals(&self, instance_ids: Vec<String>) -> Result<Vec<String>> {
+        let list = Instance::find()
+            .filter(instance::Column::InstanceId.is_in(instance_ids))
+            .all(&self.ctx.db)
+            .await?;
+        Ok(list.into_iter().map(|v| v.id.to_string()).collect())
+    }
+
+    pub async fn unbind_tag(
+        &self,
+        tag_ids: Vec<u64>,
+        resource_type: &str,
+        resource_vals: Vec<String>,
+    ) -> Result<u64> {
+        let ret = TagResource::delete_many()
+            .filter(tag_resource::Column::TagId.is_in(tag_ids))
+            .filter(tag_resource::Column::ResourceType.eq(resource_type))
+            .filter(tag_resource::Column::ResourceVal.is_in(resource_vals))
+            .exec(&self.ctx.db)
+            .await?;
+        Ok(ret.rows_affected)
+    }
+
+    async fn get_or_create_tag(
+        &self,
+        tag_key: &str,
+        tag_val: &str,
+        created_user: &str,
+    ) -> Result<u64> {
+        if let Some(v) = Tag::find()
+            .filter(tag::Column::TagKey.eq(tag_key))
+            .filter(tag::Column::TagVal.eq(tag_val))
+            .filter(tag::Column::CreatedUser.eq(created_user))
+            .one(&self.ctx.db)
+            .await?
+        {
+            return Ok(v.id);
+        }
+        let ret = Tag::insert(tag::ActiveModel {
+            tag_key: Set(tag_key.to_string()),
+            tag_val: Set(tag_val.to_string()),
+            created_user: Set(created_user.to_string()),
+            ..Default::default()
+        })
+        .exec(&self.ctx.db)
+        .await?;
+        Ok(ret.last_insert_id)
+    }
+
+    /// tag the instance with the labels reported by its agent, labels it stopped reporting are untagged
+    pub async fn sync_agent_labels(
+        &self,
+        ip: &str,
+        mac_addr: &str,
+        labels: Vec<(String, String)>,
+    ) -> Result<()> {
+        let Some(ins) = Instance::find()
+            .filter(instance::Column::Ip.eq(ip))
+            .filter(instance::Column::MacAddr.eq(mac_addr))
+            .one(&self.ctx.db)
+            .await?
+        else {
+            return Ok(());
+        };
+        let resource_val = ins.id.to_string();
+
+        let mut tag_ids = Vec::new();
+        for (key, val) in labels {
+            Self::check_tag(&key, &val)?;
+            tag_ids.push(self.get_or_create_tag(&key, &val, AGENT_LABEL_USER).await?);
+        }
+
+        TagResource::delete_many()
+            .filter(tag_resource::Column::ResourceType.eq(RESOURCE_INSTANCE))
+            .filter(tag_resource::Column::ResourceVal.eq(&resource_val))
+            .filter(tag_resource::Column::CreatedUser.eq(AGENT_LABEL_USER))
+            .apply_if(Some(tag_ids.clone()).filter(|v| !v.is_empty()), |q, v| {
+                q.filter(tag_resource::Column::TagId.is_not_in(v))
+            })
+            .exec(&self.ctx.db)
+            .await?;
+
+        if !tag_ids.is_empty() {
+            self.bind_tag(
+                tag_ids,
+                RESOURCE_INSTANCE,
+                vec![resource_val],
+                AGENT_LABEL_USER,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// instance ids of the instances which have every tag of a selector such as env=prod,role=web
+    pub async fn resolve_selector(&self, selector: &str) -> Result<Vec<String>> {
+        let labels = parse_labels(selector)?;
+        if labels.is_empty() {
+            anyhow::bail!("empty tag selector");
+        }
+
+        let mut matched: Option<HashSet<String>> = None;
+        for (key, val) in labels {
+            let tag_ids = Tag::find()
+                .filter(tag::Column::TagKey.eq(&key))
+                .filter(tag::Column::TagVal.eq(&val))
+                .all(&self.ctx.db)
+                .await?
+                .into_iter()
+                .map(|v| v.id)
+                .collect::<Vec<_>>();
+
+            let vals = TagResource::find()
+                .filter(tag_resource::Column::TagId.is_in(tag_ids))
+                .filter(tag_resource::Column::ResourceType.ne(RESOURCE_JOB))
+                .all(&self.ctx.db)
+                .await?
+                .into_iter()
+                .map(|v| v.resource_val)
+                .collect::<HashSet<_>>();
+
+            matched = Some(match matched {
+                Some(prev) => prev.intersection(&vals).cloned().collect(),
+                None => vals,
+            });
+        }
+
+        let ids = matched
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|v| v.parse::<u64>().ok())
+            .collect::<Vec<_>>();
+        let list = Instance::find()
+            .filter(instance::Column::Id.is_in(ids))
+            .all(&self.ctx.db)
+            .await?;
+        Ok(list.into_iter().map(|v| v.instance_id).collect())
+    }
+}
+
+#[test]
+fn test_check_tag() {
+    assert!(TagLogic::check_tag("env", "prod").is_ok());
+    assert!(TagLogic::check_tag("env", "").is_err());
+    assert!(TagLogic::check_tag(" ", "prod").is_err());
+    assert!(TagLogic::check_tag("env", "prod,role=web").is_err());
+}
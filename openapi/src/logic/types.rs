@@ -1,5 +1,6 @@
 use std::fmt::Display;
 
+use automate::ssh::SshAuth;
 use sea_orm::{prelude::DateTimeUtc, FromQueryResult};
 use serde::{Deserialize, Serialize};
 
@@ -19,6 +20,8 @@ pub struct UserInfo {
     pub permissions: Vec<String>,
     pub created_time: String,
     pub updated_time: String,
+    /// set when the user signs in through ldap
+    pub ldap_dn: Option<String>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Default, FromQueryResult)]
@@ -37,6 +40,7 @@ pub struct UserRecord {
     pub gender: String,
     pub role: Option<String>,
     pub introduction: String,
+    pub ldap_dn: Option<String>,
     pub created_time: DateTimeUtc,
     pub updated_time: DateTimeUtc,
 }
@@ -91,10 +95,21 @@ pub struct InstanceRecord {
     pub instance_group: Option<String>,
     pub instance_group_id: u64,
     pub ssh_port: u16,
+    pub host_key: String,
     pub created_time: DateTimeUtc,
     pub updated_time: DateTimeUtc,
 }
 
+/// decrypted ssh login of an instance
+#[derive(Clone, Default)]
+pub struct SshCredential {
+    pub user: String,
+    pub port: u16,
+    pub auth: SshAuth,
+    /// pinned host key fingerprint, empty until the first connection
+    pub host_key: String,
+}
+
 #[derive(Clone, Serialize, Deserialize, Default)]
 pub struct VersionRecord {
     pub name: String,
@@ -133,6 +148,7 @@ pub struct TeamRecord {
     pub id: u64,
     pub name: String,
     pub info: String,
+    pub env: Option<serde_json::Value>,
     pub is_admin: Option<bool>,
     pub created_user: String,
     pub updated_user: String,
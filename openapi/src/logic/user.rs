@@ -1,5 +1,6 @@
 use crate::{
-    entity::{self, role, user},
+    entity::{self, role, team, team_member, user},
+    local_time,
     state::AppContext,
     AppState,
 };
@@ -15,12 +16,24 @@ use sea_orm::*;
 
 use super::{omit_empty_active_value, types};
 
+mod api_token;
+mod ldap;
+mod oidc;
+
+pub use ldap::LdapCheck;
+pub use oidc::OidcSession;
+
+/// created_user of the team memberships synced from the groups of an identity provider
+pub const GROUP_SYNC_USER: &str = "group-sync";
+
 #[derive(Clone)]
 pub struct UserLogic<'a> {
     ctx: &'a AppContext,
 }
 impl<'a> UserLogic<'a> {
     pub const SESS_KEY: &'static str = "USER_SESSION";
+    /// when the session user was last checked against the ldap directory
+    pub const LDAP_CHECKED_KEY: &'static str = "LDAP_CHECKED_TIME";
 
     pub fn new(ctx: &'a AppContext) -> Self {
         Self { ctx }
@@ -32,6 +45,27 @@ impl<'a> UserLogic<'a> {
         md5.result_str()
     }
 
+    /// the session user of a user record, shared by the cookie session and api tokens
+    pub fn user_info(record: types::UserRecord, permissions: Vec<String>) -> types::UserInfo {
+        types::UserInfo {
+            username: record.username,
+            nickname: record.nickname,
+            avatar: record.avatar,
+            email: record.email,
+            role_id: record.role_id,
+            is_root: record.is_root,
+            introduction: record.introduction,
+            phone: record.phone,
+            created_time: local_time!(record.created_time),
+            updated_time: local_time!(record.updated_time),
+            user_id: record.user_id,
+            gender: record.gender,
+            permissions,
+            role: record.role.unwrap_or_default(),
+            ldap_dn: record.ldap_dn,
+        }
+    }
+
     pub async fn get_user(
         &self,
         username: Option<&str>,
@@ -57,6 +91,12 @@ impl<'a> UserLogic<'a> {
     }
 
     pub async fn valid_user(&self, username: &str, password: &str) -> Result<types::UserRecord> {
+        if self.ctx.conf.ldap.enable {
+            if let Some(v) = self.valid_ldap_user(username, password).await? {
+                return Ok(v);
+            }
+        }
+
         let got_user = self
             .get_user(Some(username), None)
             .await?
@@ -71,6 +111,65 @@ impl<'a> UserLogic<'a> {
         }
     }
 
+    /// give a user of an identity provider the role mapped from their groups, a user whose
+    /// groups no longer map to a role falls back to the default role
+    pub async fn sync_group_role(&self, user_id: &str, role_id: u64) -> Result<()> {
+        User::update_many()
+            .set(user::ActiveModel {
+                role_id: Set(role_id),
+                ..Default::default()
+            })
+            .filter(user::Column::UserId.eq(user_id))
+            .filter(user::Column::RoleId.ne(role_id))
+            .exec(&self.ctx.db)
+            .await?;
+        match role_id {
+            0 => self.ctx.delete_role_for_user(user_id).await,
+            v => self.ctx.set_role_for_user(user_id, &v.to_string()).await,
+        }
+    }
+
+    /// join the teams named after the groups of an identity provider and leave the ones
+    /// joined that way whose group the user left, memberships added by hand are kept
+    pub async fn sync_group_teams(&self, user_id: &str, groups: &[String]) -> Result<()> {
+        let teams = Team::find()
+            .filter(team::Column::Name.is_in(groups))
+            .all(&self.ctx.db)
+            .await?;
+        let joined = TeamMember::find()
+            .filter(team_member::Column::UserId.eq(user_id))
+            .all(&self.ctx.db)
+            .await?;
+
+        let left = joined
+            .iter()
+            .filter(|m| m.created_user == GROUP_SYNC_USER)
+            .filter(|m| !teams.iter().any(|t| t.id == m.team_id))
+            .map(|m| m.id)
+            .collect::<Vec<_>>();
+        if !left.is_empty() {
+            TeamMember::delete_many()
+                .filter(team_member::Column::Id.is_in(left))
+                .exec(&self.ctx.db)
+                .await?;
+        }
+
+        let members = teams
+            .into_iter()
+            .filter(|t| !joined.iter().any(|m| m.team_id == t.id))
+            .map(|t| team_member::ActiveModel {
+                team_id: Set(t.id),
+                user_id: Set(user_id.to_string()),
+                created_user: Set(GROUP_SYNC_USER.to_string()),
+                ..Default::default()
+            })
+            .collect::<Vec<_>>();
+        if !members.is_empty() {
+            TeamMember::insert_many(members).exec(&self.ctx.db).await?;
+        }
+        Ok(())
+    }
+
     pub async fn save(db: &DbConn, user: user::Model) -> Result<user::ActiveModel, DbErr> {
         user::ActiveModel {
             username: Set(user.username.to_owned()),
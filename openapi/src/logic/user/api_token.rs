@@ -0,0 +1,213 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use crypto::{digest::Digest, sha2::Sha256};
+use nanoid::nanoid;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder, Set,
+};
+use sea_query::Expr;
+use serde_json::json;
+
+use super::UserLogic;
+use crate::{
+    entity::{prelude::*, user_api_token},
+    logic::types,
+};
+
+/// prefix of every api token, which makes a leaked token easy to spot
+pub const API_TOKEN_PREFIX: &str = "jst_";
+/// the last use of a token is written at most once within this many seconds
+const LAST_USED_UPDATE_SECS: i64 = 60;
+/// modules a scope may name, the first segment of the api path
+const SCOPE_MODULES: [&str; 12] = [
+    "job",
+    "instance",
+    "executor",
+    "team",
+    "user",
+    "role",
+    "notify",
+    "recording",
+    "terminal",
+    "file",
+    "manage",
+    "tag",
+];
+/// GET routes which upgrade to a websocket running a shell, they need write access
+const INTERACTIVE_PATHS: [&str; 2] = ["/terminal/webssh/", "/terminal/tunnel/"];
+
+impl<'a> UserLogic<'a> {
+    pub fn hash_api_token(token: &str) -> String {
+        let mut sha = Sha256::new();
+        sha.input_str(token);
+        sha.result_str()
+    }
+
+    /// `*`, a module such as `job`, or a module with `read` or `write` such as `job:read`
+    pub fn check_token_scope(scope: &str) -> Result<()> {
+        if scope == "*" {
+            return Ok(());
+        }
+        let (module, access) = scope.split_once(':').unwrap_or((scope, "*"));
+        if !SCOPE_MODULES.contains(&module) || !matches!(access, "read" | "write" | "*") {
+            anyhow::bail!("invalid token scope {scope}");
+        }
+        Ok(())
+    }
+
+    /// GET requests are reads, except the websocket upgrades opening a shell on an instance
+    pub fn is_read_request(is_get: bool, path: &str) -> bool {
+        is_get && !INTERACTIVE_PATHS.iter().any(|v| path.starts_with(v))
+    }
+
+    /// whether the scopes allow the request, read only allows GET and write allows every method
+    pub fn token_scope_allows(scopes: &[String], path: &str, is_read: bool) -> bool {
+        let module = path
+            .trim_start_matches('/')
+            .split('/')
+            .next()
+            .unwrap_or_default();
+        scopes.iter().any(|scope| {
+            if scope == "*" {
+                return true;
+            }
+            match scope.split_once(':').unwrap_or((scope, "*")) {
+                (m, "*" | "write") => m == module,
+                (m, "read") => m == module && is_read,
+                _ => false,
+            }
+        })
+    }
+
+    /// the plain token is only returned here, the table keeps its hash
+    pub async fn create_api_token(
+        &self,
+        user_id: &str,
+        name: String,
+        scopes: Vec<String>,
+        expire_time: Option<DateTime<Utc>>,
+    ) -> Result<(u64, String)> {
+        for scope in &scopes {
+            Self::check_token_scope(scope)?;
+        }
+        let token = format!("{API_TOKEN_PREFIX}{}", nanoid!(40));
+
+        let ret = UserApiToken::insert(user_api_token::ActiveModel {
+            user_id: Set(user_id.to_string()),
+            name: Set(name),
+            token_prefix: Set(token[..API_TOKEN_PREFIX.len() + 6].to_string()),
+            token_hash: Set(Self::hash_api_token(&token)),
+            scopes: Set(Some(json!(scopes))),
+            expire_time: Set(expire_time),
+            ..Default::default()
+        })
+        .exec(&self.ctx.db)
+        .await?;
+        Ok((ret.last_insert_id, token))
+    }
+
+    pub async fn query_api_token(
+        &self,
+        user_id: &str,
+        page: u64,
+        page_size: u64,
+    ) -> Result<(Vec<user_api_token::Model>, u64)> {
+        let model = UserApiToken::find().filter(user_api_token::Column::UserId.eq(user_id));
+
+        let total = model.clone().count(&self.ctx.db).await?;
+        let list = model
+            .order_by_desc(user_api_token::Column::Id)
+            .paginate(&self.ctx.db, page_size)
+            .fetch_page(page)
+            .await?;
+        Ok((list, total))
+    }
+
+    pub async fn revoke_api_token(&self, user_id: &str, id: u64) -> Result<u64> {
+        let ret = UserApiToken::update_many()
+            .col_expr(user_api_token::Column::IsRevoked, Expr::value(true))
+            .filter(user_api_token::Column::Id.eq(id))
+            .filter(user_api_token::Column::UserId.eq(user_id))
+            .exec(&self.ctx.db)
+            .await?;
+        Ok(ret.rows_affected)
+    }
+
+    /// the user and the scopes of a valid token, its last use is recorded
+    pub async fn verify_api_token(
+        &self,
+        token: &str,
+        ip: &str,
+    ) -> Result<Option<(types::UserRecord, Vec<String>)>> {
+        if !token.starts_with(API_TOKEN_PREFIX) {
+            return Ok(None);
+        }
+        let Some(record) = UserApiToken::find()
+            .filter(user_api_token::Column::TokenHash.eq(Self::hash_api_token(token)))
+            .one(&self.ctx.db)
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let now = Utc::now();
+        if record.is_revoked || record.expire_time.is_some_and(|v| v <= now) {
+            return Ok(None);
+        }
+        let Some(user) = self.get_user(None, Some(&record.user_id)).await? else {
+            return Ok(None);
+        };
+        if user.ldap_dn.is_some() && !self.recheck_ldap_user_cached(&user.username).await? {
+            return Ok(None);
+        }
+
+        if record.last_used_time.map_or(true, |v| {
+            now - v >= Duration::seconds(LAST_USED_UPDATE_SECS)
+        }) || record.last_used_ip != ip
+        {
+            user_api_token::ActiveModel {
+                id: Set(record.id),
+                last_used_time: Set(Some(now)),
+                last_used_ip: Set(ip.to_string()),
+                ..Default::default()
+            }
+            .update(&self.ctx.db)
+            .await?;
+        }
+
+        let scopes = record
+            .scopes
+            .map(serde_json::from_value)
+            .transpose()?
+            .unwrap_or_default();
+        Ok(Some((user, scopes)))
+    }
+}
+
+#[test]
+fn test_token_scope() {
+    let scopes = vec!["job:write".to_string(), "instance:read".to_string()];
+    let allows = |path, is_read| UserLogic::token_scope_allows(&scopes, path, is_read);
+    assert!(allows("/job/dispatch", false));
+    assert!(allows("/job/list", true));
+    assert!(allows("/instance/list", true));
+    assert!(!allows("/instance/delete", false));
+    assert!(!allows("/user/info", true));
+
+    let all = vec!["*".to_string()];
+    assert!(UserLogic::token_scope_allows(&all, "/user/info", false));
+    assert!(!UserLogic::token_scope_allows(&[], "/job/list", true));
+
+    let terminal = vec!["terminal:read".to_string()];
+    let allows = |path| {
+        UserLogic::token_scope_allows(&terminal, path, UserLogic::is_read_request(true, path))
+    };
+    assert!(!allows("/terminal/webssh/instance_id"));
+    assert!(!allows("/terminal/tunnel/instance_id"));
+    assert!(allows("/terminal/job-output/schedule_id/instance_id"));
+
+    assert!(UserLogic::check_token_scope("job").is_ok());
+    assert!(UserLogic::check_token_scope("job:read").is_ok());
+    assert!(UserLogic::check_token_scope("job:delete").is_err());
+    assert!(UserLogic::check_token_scope("jobs").is_err());
+}
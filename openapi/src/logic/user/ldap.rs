@@ -0,0 +1,601 @@
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use ldap3::{ldap_escape, LdapConnAsync, LdapConnSettings, Scope, SearchEntry};
+use nanoid::nanoid;
+use sea_orm::{ActiveModelTrait, Set};
+use tracing::warn;
+
+use super::UserLogic;
+use crate::{config::Ldap, entity::user, logic::types};
+
+/// result code of a bind with a wrong password
+const LDAP_INVALID_CREDENTIALS: u32 = 49;
+
+/// a user entry found in the directory
+#[derive(Debug, Clone, Default)]
+pub struct LdapUser {
+    pub dn: String,
+    pub username: String,
+    pub nickname: String,
+    pub email: String,
+    /// cn of the groups the user belongs to
+    pub groups: Vec<String>,
+}
+
+/// the last check of a user against the directory and the groups it found
+#[derive(Debug, Clone, Default)]
+pub struct LdapCheck {
+    pub checked_time: i64,
+    pub groups: Vec<String>,
+}
+
+impl LdapCheck {
+    /// whether the user should be checked again, recheck_interval 0 checks every time
+    pub fn is_due(&self, now: i64, recheck_interval: u64) -> bool {
+        now - self.checked_time >= recheck_interval as i64
+    }
+
+    /// whether the groups differ from the ones found last time, in any order
+    pub fn groups_changed(&self, groups: &[String]) -> bool {
+        let mut last = self.groups.clone();
+        let mut groups = groups.to_vec();
+        last.sort();
+        groups.sort();
+        last != groups
+    }
+}
+
+impl Ldap {
+    async fn connect(&self) -> Result<ldap3::Ldap> {
+        let settings = LdapConnSettings::new()
+            .set_conn_timeout(Duration::from_secs(10))
+            .set_starttls(self.starttls);
+        let (conn, mut ldap) = LdapConnAsync::with_settings(settings, &self.url).await?;
+        tokio::spawn(async move {
+            if let Err(e) = conn.drive().await {
+                warn!("ldap connection closed, {e}");
+            }
+        });
+        ldap.with_timeout(Duration::from_secs(10));
+        if !self.bind_dn.is_empty() {
+            ldap.simple_bind(&self.bind_dn, &self.bind_password)
+                .await?
+                .success()?;
+        }
+        Ok(ldap)
+    }
+
+    fn attr<'a>(v: &'a str, default: &'a str) -> &'a str {
+        if v.is_empty() {
+            default
+        } else {
+            v
+        }
+    }
+
+    /// the user matched by user_filter, None when the user is not in the directory or disabled
+    pub async fn find_user(&self, username: &str) -> Result<Option<LdapUser>> {
+        let mut ldap = self.connect().await?;
+        let ret = self.search_user(&mut ldap, username).await;
+        let _ = ldap.unbind().await;
+        ret
+    }
+
+    async fn search_user(
+        &self,
+        ldap: &mut ldap3::Ldap,
+        username: &str,
+    ) -> Result<Option<LdapUser>> {
+        if !self.enable {
+            anyhow::bail!("ldap login is not enabled");
+        }
+        let nickname_attr = Self::attr(&self.nickname_attr, "cn");
+        let email_attr = Self::attr(&self.email_attr, "mail");
+        let group_attr = Self::attr(&self.group_attr, "memberOf");
+
+        let filter = self
+            .user_filter
+            .replace("{username}", &ldap_escape(username));
+        let (entries, _) = ldap
+            .search(
+                &self.search_base,
+                Scope::Subtree,
+                &filter,
+                vec![nickname_attr, email_attr, group_attr],
+            )
+            .await?
+            .success()?;
+
+        let mut entries = entries.into_iter().map(SearchEntry::construct);
+        let Some(mut entry) = entries.next() else {
+            return Ok(None);
+        };
+        if entries.next().is_some() {
+            anyhow::bail!("more than one ldap entry matches user {username}");
+        }
+
+        let mut first = |attr: &str| {
+            entry
+                .attrs
+                .remove(attr)
+                .and_then(|v| v.into_iter().next())
+                .unwrap_or_default()
+        };
+        let nickname = first(nickname_attr);
+        let email = first(email_attr);
+        let groups = entry
+            .attrs
+            .remove(group_attr)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|v| group_name(v))
+            .collect();
+
+        Ok(Some(LdapUser {
+            dn: entry.dn,
+            username: username.to_string(),
+            nickname,
+            email,
+            groups,
+        }))
+    }
+
+    /// bind as the user, an error is returned when the password is wrong
+    pub async fn authenticate(&self, username: &str, password: &str) -> Result<Option<LdapUser>> {
+        // an empty password makes an unauthenticated bind, which always succeeds
+        if password.is_empty() {
+            anyhow::bail!("invalid username or password");
+        }
+        let mut ldap = self.connect().await?;
+        let ret = match self.search_user(&mut ldap, username).await {
+            Ok(Some(v)) => {
+                let bind = ldap.simple_bind(&v.dn, password).await;
+                match bind {
+                    Ok(r) if r.rc == 0 => Ok(Some(v)),
+                    Ok(r) if r.rc == LDAP_INVALID_CREDENTIALS => {
+                        Err(anyhow!("invalid username or password"))
+                    }
+                    Ok(r) => Err(anyhow!("ldap bind failed, {r}")),
+                    Err(e) => Err(e.into()),
+                }
+            }
+            other => other,
+        };
+        let _ = ldap.unbind().await;
+        ret
+    }
+
+    /// role of the first group which has one in role_mapping, default_role_id when none has
+    pub fn map_role(&self, groups: &[String]) -> u64 {
+        groups
+            .iter()
+            .find_map(|v| self.role_mapping.get(v).copied())
+            .unwrap_or(self.default_role_id)
+    }
+}
+
+/// the value of the first rdn, ops of cn=ops,ou=groups,dc=example,dc=org
+fn group_name(dn: &str) -> Option<String> {
+    let rdn = dn.split(',').next()?;
+    let (_, name) = rdn.split_once('=')?;
+    Some(name.trim().to_string()).filter(|v| !v.is_empty())
+}
+
+impl<'a> UserLogic<'a> {
+    /// sign in through the directory, None lets a local user which is not in it use the password
+    pub async fn valid_ldap_user(
+        &self,
+        username: &str,
+        password: &str,
+    ) -> Result<Option<types::UserRecord>> {
+        let conf = &self.ctx.conf.ldap;
+        let local_user = self.get_user(Some(username), None).await?;
+        if local_user.as_ref().is_some_and(|v| v.is_root) {
+            return Ok(None);
+        }
+
+        let Some(ldap_user) = conf.authenticate(username, password).await? else {
+            if local_user.as_ref().is_some_and(|v| v.ldap_dn.is_some()) {
+                anyhow::bail!("user {username} is disabled in the directory");
+            }
+            return Ok(None);
+        };
+
+        let user_id = match local_user {
+            Some(v) => {
+                user::ActiveModel {
+                    id: Set(v.id),
+                    ldap_dn: Set(Some(ldap_user.dn.clone())),
+                    ..Default::default()
+                }
+                .update(&self.ctx.db)
+                .await?;
+                v.user_id
+            }
+            None => {
+                let salt = nanoid!();
+                let user_id = nanoid!(10);
+                user::ActiveModel {
+                    user_id: Set(user_id.clone()),
+                    username: Set(ldap_user.username.clone()),
+                    nickname: Set(match ldap_user.nickname.as_str() {
+                        "" => ldap_user.username.clone(),
+                        v => v.to_string(),
+                    }),
+                    // the password is checked by the directory
+                    password: Set(Self::encry_password(nanoid!(32), salt.clone())),
+                    salt: Set(salt),
+                    email: Set(ldap_user.email.clone()),
+                    ldap_dn: Set(Some(ldap_user.dn.clone())),
+                    ..Default::default()
+                }
+                .insert(&self.ctx.db)
+                .await?;
+                user_id
+            }
+        };
+
+        self.sync_ldap_groups(&user_id, &ldap_user.groups).await?;
+        let got_user = self
+            .get_user(None, Some(&user_id))
+            .await?
+            .ok_or(anyhow!("invalid user"))?;
+        self.save_ldap_check(&got_user.username, ldap_user.groups)
+            .await;
+        Ok(Some(got_user))
+    }
+
+    /// the role and the teams of the user follow their groups in the directory
+    async fn sync_ldap_groups(&self, user_id: &str, groups: &[String]) -> Result<()> {
+        let conf = &self.ctx.conf.ldap;
+        self.sync_group_role(user_id, conf.map_role(groups)).await?;
+        if conf.sync_team {
+            self.sync_group_teams(user_id, groups).await?;
+        }
+        Ok(())
+    }
+
+    /// remember when the user was checked against the directory and the groups it found
+    async fn save_ldap_check(&self, username: &str, groups: Vec<String>) {
+        self.ctx.ldap_checks.lock().await.insert(
+            username.to_string(),
+            LdapCheck {
+                checked_time: Utc::now().timestamp(),
+                groups,
+            },
+        );
+    }
+
+    /// whether a user which signed in through ldap is still enabled in the directory, the role
+    /// and the teams of an enabled one are synced again when their groups changed
+    pub async fn recheck_ldap_user(&self, username: &str) -> Result<bool> {
+        let conf = &self.ctx.conf.ldap;
+        if !conf.enable {
+            return Ok(false);
+        }
+        let Some(ldap_user) = conf.find_user(username).await? else {
+            self.ctx.ldap_checks.lock().await.remove(username);
+            return Ok(false);
+        };
+        let last = self.ctx.ldap_checks.lock().await.get(username).cloned();
+        if last.map_or(true, |v| v.groups_changed(&ldap_user.groups)) {
+            if let Some(v) = self.get_user(Some(username), None).await? {
+                self.sync_ldap_groups(&v.user_id, &ldap_user.groups).await?;
+            }
+        }
+        self.save_ldap_check(username, ldap_user.groups).await;
+        Ok(true)
+    }
+
+    /// recheck_ldap_user at most once every recheck_interval, for the requests without a session
+    pub async fn recheck_ldap_user_cached(&self, username: &str) -> Result<bool> {
+        let now = Utc::now().timestamp();
+        let due = self
+            .ctx
+            .ldap_checks
+            .lock()
+            .await
+            .get(username)
+            .map_or(true, |v| v.is_due(now, self.ctx.conf.ldap.recheck_interval));
+        if !due {
+            return Ok(true);
+        }
+        self.recheck_ldap_user(username).await
+    }
+}
+
+#[cfg(test)]
+mod mock {
+    //! a directory server speaking just enough ldap for bind, search and unbind
+
+    use std::{
+        collections::{HashMap, HashSet},
+        sync::{Arc, Mutex},
+    };
+
+    use ldap3::asn1::{parse_tag, StructureTag, TagClass, PL};
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    pub struct Entry {
+        pub dn: String,
+        pub password: String,
+        pub attrs: Vec<(String, Vec<String>)>,
+    }
+
+    #[derive(Clone, Default)]
+    pub struct Directory {
+        pub entries: Arc<Mutex<HashMap<String, Entry>>>,
+        pub disabled: Arc<Mutex<HashSet<String>>>,
+    }
+
+    fn encode(tag: &StructureTag, out: &mut Vec<u8>) {
+        let class = match tag.class {
+            TagClass::Universal => 0x00,
+            TagClass::Application => 0x40,
+            TagClass::Context => 0x80,
+            TagClass::Private => 0xc0,
+        };
+        let payload = match &tag.payload {
+            PL::P(v) => {
+                out.push(class | tag.id as u8);
+                v.clone()
+            }
+            PL::C(v) => {
+                out.push(class | 0x20 | tag.id as u8);
+                let mut buf = vec![];
+                v.iter().for_each(|t| encode(t, &mut buf));
+                buf
+            }
+        };
+        match payload.len() {
+            n if n < 0x80 => out.push(n as u8),
+            n => {
+                let len = (n as u32).to_be_bytes();
+                let len = &len[len.iter().position(|&b| b != 0).unwrap()..];
+                out.push(0x80 | len.len() as u8);
+                out.extend_from_slice(len);
+            }
+        }
+        out.extend(payload);
+    }
+
+    fn tag(class: TagClass, id: u64, payload: PL) -> StructureTag {
+        StructureTag { class, id, payload }
+    }
+
+    fn octets(v: &str) -> StructureTag {
+        tag(TagClass::Universal, 4, PL::P(v.as_bytes().to_vec()))
+    }
+
+    fn result(id: u64, rc: u8) -> StructureTag {
+        tag(
+            TagClass::Application,
+            id,
+            PL::C(vec![
+                tag(TagClass::Universal, 10, PL::P(vec![rc])),
+                octets(""),
+                octets(""),
+            ]),
+        )
+    }
+
+    fn string(tag: &StructureTag) -> String {
+        match &tag.payload {
+            PL::P(v) => String::from_utf8_lossy(v).to_string(),
+            PL::C(_) => String::new(),
+        }
+    }
+
+    /// the value of the first (uid=...) equality match in a filter
+    fn find_uid(filter: &StructureTag) -> Option<String> {
+        let PL::C(inner) = &filter.payload else {
+            return None;
+        };
+        if filter.class == TagClass::Context && filter.id == 3 && string(&inner[0]) == "uid" {
+            return Some(string(&inner[1]));
+        }
+        inner.iter().find_map(find_uid)
+    }
+
+    impl Directory {
+        pub fn add(&self, uid: &str, password: &str, attrs: Vec<(&str, Vec<&str>)>) {
+            let dn = format!("uid={uid},ou=people,dc=example,dc=org");
+            self.entries.lock().unwrap().insert(
+                uid.to_string(),
+                Entry {
+                    dn,
+                    password: password.to_string(),
+                    attrs: attrs
+                        .into_iter()
+                        .map(|(k, v)| (k.to_string(), v.into_iter().map(String::from).collect()))
+                        .collect(),
+                },
+            );
+        }
+
+        pub async fn serve(self) -> String {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("ldap://{}", listener.local_addr().unwrap());
+            tokio::spawn(async move {
+                while let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(self.clone().handle(stream));
+                }
+            });
+            url
+        }
+
+        fn respond(&self, op: StructureTag) -> Vec<(u64, StructureTag)> {
+            let PL::C(fields) = op.payload else {
+                return vec![];
+            };
+            match op.id {
+                // bind
+                0 => {
+                    let (dn, password) = (string(&fields[1]), string(&fields[2]));
+                    let ok = (dn == "cn=admin,dc=example,dc=org" && password == "admin")
+                        || self
+                            .entries
+                            .lock()
+                            .unwrap()
+                            .values()
+                            .any(|v| v.dn == dn && v.password == password);
+                    vec![(1, result(1, if ok { 0 } else { 49 }))]
+                }
+                // search
+                3 => {
+                    let uid = find_uid(&fields[6]).unwrap_or_default();
+                    let mut ret = vec![];
+                    let entries = self.entries.lock().unwrap();
+                    match entries.get(&uid) {
+                        Some(v) if !self.disabled.lock().unwrap().contains(&uid) => {
+                            let attrs = v
+                                .attrs
+                                .iter()
+                                .map(|(k, vals)| {
+                                    tag(
+                                        TagClass::Universal,
+                                        16,
+                                        PL::C(vec![
+                                            octets(k),
+                                            tag(
+                                                TagClass::Universal,
+                                                17,
+                                                PL::C(vals.iter().map(|v| octets(v)).collect()),
+                                            ),
+                                        ]),
+                                    )
+                                })
+                                .collect();
+                            ret.push((
+                                4,
+                                tag(
+                                    TagClass::Application,
+                                    4,
+                                    PL::C(vec![
+                                        octets(&v.dn),
+                                        tag(TagClass::Universal, 16, PL::C(attrs)),
+                                    ]),
+                                ),
+                            ));
+                        }
+                        _ => (),
+                    }
+                    ret.push((5, result(5, 0)));
+                    ret
+                }
+                _ => vec![],
+            }
+        }
+
+        async fn handle(self, mut stream: TcpStream) {
+            let mut buf = Vec::new();
+            loop {
+                let mut chunk = [0u8; 4096];
+                match stream.read(&mut chunk).await {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => buf.extend_from_slice(&chunk[..n]),
+                }
+                while let Ok((rest, msg)) = parse_tag(&buf) {
+                    let consumed = buf.len() - rest.len();
+                    let PL::C(mut parts) = msg.payload else {
+                        return;
+                    };
+                    let op = parts.remove(1);
+                    let msg_id = parts.remove(0);
+                    // unbind
+                    if op.class == TagClass::Application && op.id == 2 {
+                        return;
+                    }
+                    for (_, resp) in self.respond(op) {
+                        let mut out = vec![];
+                        encode(
+                            &tag(TagClass::Universal, 16, PL::C(vec![msg_id.clone(), resp])),
+                            &mut out,
+                        );
+                        if stream.write_all(&out).await.is_err() {
+                            return;
+                        }
+                    }
+                    buf.drain(..consumed);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_ldap_check() {
+    let check = LdapCheck {
+        checked_time: 100,
+        groups: vec!["ops".to_string(), "dev".to_string()],
+    };
+    assert!(!check.is_due(159, 60));
+    assert!(check.is_due(160, 60));
+    assert!(check.is_due(100, 0));
+
+    assert!(!check.groups_changed(&["dev".to_string(), "ops".to_string()]));
+    assert!(check.groups_changed(&["dev".to_string()]));
+    assert!(check.groups_changed(&["dev".to_string(), "qa".to_string()]));
+}
+
+#[tokio::test]
+async fn test_ldap_mock_directory() {
+    let directory = mock::Directory::default();
+    directory.add(
+        "alice",
+        "secret",
+        vec![
+            ("cn", vec!["Alice"]),
+            ("mail", vec!["alice@example.com"]),
+            (
+                "memberOf",
+                vec![
+                    "cn=dev,ou=groups,dc=example,dc=org",
+                    "cn=ops,ou=groups,dc=example,dc=org",
+                ],
+            ),
+        ],
+    );
+
+    let conf = Ldap {
+        enable: true,
+        url: directory.clone().serve().await,
+        bind_dn: "cn=admin,dc=example,dc=org".to_string(),
+        bind_password: "admin".to_string(),
+        search_base: "ou=people,dc=example,dc=org".to_string(),
+        user_filter: "(&(objectClass=person)(uid={username}))".to_string(),
+        role_mapping: [("ops".to_string(), 2)].into(),
+        default_role_id: 3,
+        ..Default::default()
+    };
+
+    let user = conf.authenticate("alice", "secret").await.unwrap().unwrap();
+    assert_eq!(user.dn, "uid=alice,ou=people,dc=example,dc=org");
+    assert_eq!(user.nickname, "Alice");
+    assert_eq!(user.email, "alice@example.com");
+    assert_eq!(user.groups, vec!["dev", "ops"]);
+    assert_eq!(conf.map_role(&user.groups), 2);
+    assert_eq!(conf.map_role(&["dev".to_string()]), 3);
+
+    assert!(conf.authenticate("alice", "wrong").await.is_err());
+    assert!(conf.authenticate("alice", "").await.is_err());
+    assert!(conf.authenticate("bob", "secret").await.unwrap().is_none());
+
+    assert!(conf.find_user("alice").await.unwrap().is_some());
+    directory
+        .disabled
+        .lock()
+        .unwrap()
+        .insert("alice".to_string());
+    assert!(conf.find_user("alice").await.unwrap().is_none());
+    assert!(conf
+        .authenticate("alice", "secret")
+        .await
+        .unwrap()
+        .is_none());
+}
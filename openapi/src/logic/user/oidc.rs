@@ -0,0 +1,359 @@
+use anyhow::{anyhow, Result};
+use crypto::{digest::Digest, sha2::Sha256};
+use nanoid::nanoid;
+use rustc_serialize::base64::{ToBase64, URL_SAFE};
+use sea_orm::{ActiveModelTrait, ColumnTrait, EntityTrait, QueryFilter, Set};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use super::UserLogic;
+use crate::{
+    config::Oidc,
+    entity::{prelude::*, user},
+    logic::types,
+};
+
+/// the endpoints of the identity provider read from its discovery document
+#[derive(Debug, Deserialize, Clone)]
+pub struct OidcProvider {
+    pub issuer: String,
+    pub authorization_endpoint: String,
+    pub token_endpoint: String,
+    pub userinfo_endpoint: String,
+}
+
+/// kept in the session between the login redirect and the callback
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct OidcSession {
+    pub state: String,
+    pub code_verifier: String,
+}
+
+impl OidcSession {
+    pub const SESS_KEY: &'static str = "OIDC_SESSION";
+
+    pub fn new() -> Self {
+        Self {
+            state: nanoid!(32),
+            code_verifier: nanoid!(64),
+        }
+    }
+
+    /// the S256 pkce challenge of code_verifier
+    pub fn code_challenge(&self) -> String {
+        let mut sha = Sha256::new();
+        sha.input_str(&self.code_verifier);
+        let mut digest = [0u8; 32];
+        sha.result(&mut digest);
+        digest.to_base64(URL_SAFE)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+}
+
+/// the claims of the userinfo endpoint
+#[derive(Debug, Clone, Default)]
+pub struct OidcClaims {
+    pub subject: String,
+    pub username: String,
+    pub nickname: String,
+    pub email: String,
+    pub groups: Vec<String>,
+}
+
+impl Oidc {
+    fn http_client(&self) -> Result<reqwest::Client> {
+        Ok(reqwest::Client::builder()
+            .timeout(std::time::Duration::from_secs(10))
+            .build()?)
+    }
+
+    pub async fn discover(&self) -> Result<OidcProvider> {
+        if !self.enable {
+            anyhow::bail!("oidc login is not enabled");
+        }
+        let url = format!(
+            "{}/.well-known/openid-configuration",
+            self.issuer.trim_end_matches('/')
+        );
+        let provider: OidcProvider = self
+            .http_client()?
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if provider.issuer.trim_end_matches('/') != self.issuer.trim_end_matches('/') {
+            anyhow::bail!("oidc issuer mismatch, got {}", provider.issuer);
+        }
+        Ok(provider)
+    }
+
+    pub fn authorize_url(&self, provider: &OidcProvider, sess: &OidcSession) -> Result<String> {
+        let scopes = if self.scopes.is_empty() {
+            "openid profile email".to_string()
+        } else {
+            self.scopes.join(" ")
+        };
+        let url = url::Url::parse_with_params(
+            &provider.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", &self.client_id),
+                ("redirect_uri", &self.redirect_url),
+                ("scope", &scopes),
+                ("state", &sess.state),
+                ("code_challenge", &sess.code_challenge()),
+                ("code_challenge_method", "S256"),
+            ],
+        )?;
+        Ok(url.to_string())
+    }
+
+    /// exchange the authorization code and read the claims of the user it belongs to
+    pub async fn fetch_claims(
+        &self,
+        provider: &OidcProvider,
+        sess: &OidcSession,
+        code: &str,
+    ) -> Result<OidcClaims> {
+        let client = self.http_client()?;
+        let token: TokenResponse = client
+            .post(&provider.token_endpoint)
+            .basic_auth(&self.client_id, Some(&self.client_secret))
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", &self.redirect_url),
+                ("client_id", &self.client_id),
+                ("code_verifier", &sess.code_verifier),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let claims: Map<String, Value> = client
+            .get(&provider.userinfo_endpoint)
+            .bearer_auth(&token.access_token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        self.parse_claims(&provider.issuer, &claims)
+    }
+
+    pub fn parse_claims(&self, issuer: &str, claims: &Map<String, Value>) -> Result<OidcClaims> {
+        let get = |key: &str| {
+            claims
+                .get(key)
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string()
+        };
+        let or_default = |v: &str, default: &'static str| match v {
+            "" => default.to_string(),
+            v => v.to_string(),
+        };
+
+        let sub = get("sub");
+        if sub.is_empty() {
+            anyhow::bail!("missing sub claim");
+        }
+        let username = get(&or_default(&self.username_claim, "preferred_username"));
+        if username.is_empty() {
+            anyhow::bail!("missing username claim");
+        }
+
+        let groups = match claims.get(&or_default(&self.group_claim, "groups")) {
+            Some(Value::Array(v)) => v
+                .iter()
+                .filter_map(Value::as_str)
+                .map(str::to_string)
+                .collect(),
+            Some(Value::String(v)) => vec![v.to_owned()],
+            _ => vec![],
+        };
+
+        Ok(OidcClaims {
+            subject: format!("{}#{sub}", issuer.trim_end_matches('/')),
+            nickname: get("name"),
+            email: get("email"),
+            username,
+            groups,
+        })
+    }
+
+    /// role of the first group which has one in role_mapping, default_role_id when none has
+    pub fn map_role(&self, groups: &[String]) -> u64 {
+        groups
+            .iter()
+            .find_map(|v| self.role_mapping.get(v).copied())
+            .unwrap_or(self.default_role_id)
+    }
+}
+
+impl<'a> UserLogic<'a> {
+    /// find the user of the claims, a user seen for the first time is created
+    pub async fn provision_oidc_user(&self, claims: OidcClaims) -> Result<types::UserRecord> {
+        let conf = &self.ctx.conf.oidc;
+        let record = User::find()
+            .filter(user::Column::OidcSubject.eq(&claims.subject))
+            .one(&self.ctx.db)
+            .await?;
+
+        let user_id = match record {
+            Some(v) => v.user_id,
+            None => {
+                if self.get_user(Some(&claims.username), None).await?.is_some() {
+                    anyhow::bail!(
+                        "user {} already exists and is not bound to the identity provider",
+                        claims.username
+                    );
+                }
+                let salt = nanoid!();
+                let user_id = nanoid!(10);
+                user::ActiveModel {
+                    user_id: Set(user_id.clone()),
+                    username: Set(claims.username.clone()),
+                    nickname: Set(match claims.nickname.as_str() {
+                        "" => claims.username.clone(),
+                        v => v.to_string(),
+                    }),
+                    // nobody knows the password, the user can only sign in through the provider
+                    password: Set(Self::encry_password(nanoid!(32), salt.clone())),
+                    salt: Set(salt),
+                    email: Set(claims.email.clone()),
+                    oidc_subject: Set(Some(claims.subject.clone())),
+                    ..Default::default()
+                }
+                .insert(&self.ctx.db)
+                .await?;
+                user_id
+            }
+        };
+
+        // the role and the teams follow the groups on every sign in
+        self.sync_group_role(&user_id, conf.map_role(&claims.groups))
+            .await?;
+        if conf.sync_team {
+            self.sync_group_teams(&user_id, &claims.groups).await?;
+        }
+        let got_user = self
+            .get_user(None, Some(&user_id))
+            .await?
+            .ok_or(anyhow!("invalid user"))?;
+        Ok(got_user)
+    }
+}
+
+#[tokio::test]
+async fn test_oidc_mock_provider() {
+    use poem::{
+        handler,
+        listener::{Acceptor, TcpAcceptor},
+        web::{Data, Form, Json},
+        EndpointExt, Request, Route, Server,
+    };
+
+    #[handler]
+    fn discovery(Data(base): Data<&String>) -> Json<Value> {
+        Json(serde_json::json!({
+            "issuer": base,
+            "authorization_endpoint": format!("{base}/authorize"),
+            "token_endpoint": format!("{base}/token"),
+            "userinfo_endpoint": format!("{base}/userinfo"),
+        }))
+    }
+
+    #[handler]
+    fn token(req: &Request, Form(form): Form<Map<String, Value>>) -> poem::Result<Json<Value>> {
+        let authorized = req.header("Authorization").is_some()
+            && form.get("code") == Some(&Value::from("mock-code"))
+            && form
+                .get("code_verifier")
+                .and_then(Value::as_str)
+                .is_some_and(|v| v.len() >= 43);
+        if !authorized {
+            return Err(poem::Error::from_status(
+                poem::http::StatusCode::BAD_REQUEST,
+            ));
+        }
+        Ok(Json(serde_json::json!({
+            "access_token": "mock-access-token",
+            "token_type": "Bearer",
+        })))
+    }
+
+    #[handler]
+    fn userinfo(req: &Request) -> poem::Result<Json<Value>> {
+        if req.header("Authorization") != Some("Bearer mock-access-token") {
+            return Err(poem::Error::from_status(
+                poem::http::StatusCode::UNAUTHORIZED,
+            ));
+        }
+        Ok(Json(serde_json::json!({
+            "sub": "1001",
+            "preferred_username": "alice",
+            "name": "Alice",
+            "email": "alice@example.com",
+            "groups": ["dev", "ops"],
+        })))
+    }
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    listener.set_nonblocking(true).unwrap();
+    let acceptor = TcpAcceptor::from_std(listener).unwrap();
+    let base = format!(
+        "http://{}",
+        acceptor.local_addr()[0].as_socket_addr().unwrap()
+    );
+    let app = Route::new()
+        .at("/.well-known/openid-configuration", discovery)
+        .at("/token", poem::post(token))
+        .at("/userinfo", userinfo)
+        .data(base.clone());
+    tokio::spawn(Server::new_with_acceptor(acceptor).run(app));
+
+    let conf = Oidc {
+        enable: true,
+        issuer: base.clone(),
+        client_id: "jiascheduler".to_string(),
+        client_secret: "secret".to_string(),
+        redirect_url: "http://127.0.0.1:9090/api/user/oidc/callback".to_string(),
+        role_mapping: [("ops".to_string(), 2)].into(),
+        ..Default::default()
+    };
+
+    let provider = conf.discover().await.unwrap();
+    let sess = OidcSession::new();
+    let url = url::Url::parse(&conf.authorize_url(&provider, &sess).unwrap()).unwrap();
+    let query: Map<String, Value> = url
+        .query_pairs()
+        .map(|(k, v)| (k.to_string(), Value::from(v.to_string())))
+        .collect();
+    assert_eq!(query["state"], Value::from(sess.state.clone()));
+    assert_eq!(query["code_challenge"], Value::from(sess.code_challenge()));
+    assert_eq!(query["scope"], "openid profile email");
+
+    assert!(conf
+        .fetch_claims(&provider, &sess, "bad-code")
+        .await
+        .is_err());
+    let claims = conf
+        .fetch_claims(&provider, &sess, "mock-code")
+        .await
+        .unwrap();
+    assert_eq!(claims.subject, format!("{base}#1001"));
+    assert_eq!(claims.username, "alice");
+    assert_eq!(claims.email, "alice@example.com");
+    assert_eq!(claims.groups, vec!["dev", "ops"]);
+    assert_eq!(conf.map_role(&claims.groups), 2);
+    assert_eq!(conf.map_role(&["dev".to_string()]), conf.default_role_id);
+}
@@ -35,6 +35,18 @@ macro_rules! local_time {
             .to_string()
     };
 }
+
+/// convert DateTime<Utc> to the time(String) in the time zone of a timer, the local time when it is None
+#[macro_export]
+macro_rules! zoned_time {
+    ($time:expr, $tz:expr) => {
+        match $tz {
+            Some(tz) => $time.with_timezone(&tz).naive_local().to_string(),
+            None => $crate::local_time!($time),
+        }
+    };
+}
+
 #[macro_export]
 macro_rules! time_format {
     ($time:expr, $format:expr) => {
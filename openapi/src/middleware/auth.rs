@@ -1,6 +1,11 @@
-use crate::logic::{types, user::UserLogic};
+use crate::{
+    logic::{types, user::UserLogic},
+    state::AppState,
+};
+use chrono::Utc;
 use poem::{
-    session::Session, web::Json, Endpoint, IntoResponse, Middleware, Request, Response, Result,
+    http::Method, session::Session, web::Json, Endpoint, IntoResponse, Middleware, Request,
+    Response, Result,
 };
 
 pub struct AuthMiddleware;
@@ -34,10 +39,78 @@ where
         let sess: &Session = req.extensions().get().expect("not init session");
 
         if let Some(user_info) = sess.get::<types::UserInfo>(UserLogic::SESS_KEY) {
+            // a user disabled in the directory is signed out on the next check
+            if user_info.ldap_dn.is_some() {
+                let state: &AppState = req.extensions().get().expect("not init state");
+                let now = Utc::now().timestamp();
+                let checked_time = sess
+                    .get::<i64>(UserLogic::LDAP_CHECKED_KEY)
+                    .unwrap_or_default();
+                if now - checked_time >= state.conf.ldap.recheck_interval as i64 {
+                    if !state
+                        .service()
+                        .user
+                        .recheck_ldap_user(&user_info.username)
+                        .await?
+                    {
+                        sess.clear();
+                        return Ok(login_resp);
+                    }
+                    sess.set(UserLogic::LDAP_CHECKED_KEY, now);
+                }
+            }
             req.extensions_mut().insert(user_info);
+        } else if let Some(token) = req
+            .header("Authorization")
+            .and_then(|v| v.strip_prefix("Bearer "))
+        {
+            let state: &AppState = req.extensions().get().expect("not init state");
+            let ip = req
+                .remote_addr()
+                .as_socket_addr()
+                .map(|v| v.ip().to_string())
+                .unwrap_or_default();
+            let Some((user, scopes)) = state
+                .service()
+                .user
+                .verify_api_token(token.trim(), &ip)
+                .await?
+            else {
+                return Ok(Json(serde_json::json! ({
+                    "code": 50401,
+                    "msg": "invalid api token",
+                }))
+                .into_response());
+            };
+
+            // tokens can not manage tokens, that needs a login session
+            if req.uri().path().starts_with("/user/token/")
+                || !UserLogic::token_scope_allows(
+                    &scopes,
+                    req.uri().path(),
+                    UserLogic::is_read_request(req.method() == Method::GET, req.uri().path()),
+                )
+            {
+                return Ok(Json(serde_json::json! ({
+                    "code": 50403,
+                    "msg": "the api token has no scope for this request",
+                }))
+                .into_response());
+            }
+
+            let permissions = state.get_permissions_for_user(&user.user_id).await?;
+            req.extensions_mut()
+                .insert(UserLogic::user_info(user, permissions));
         } else {
-            if vec!["/user/login", "/user/logout", "/migration/version/check"]
-                .contains(&req.uri().path())
+            if vec![
+                "/user/login",
+                "/user/logout",
+                "/user/oidc/config",
+                "/user/oidc/login",
+                "/user/oidc/callback",
+                "/migration/version/check",
+            ]
+            .contains(&req.uri().path())
             {
                 return self.ep.call(req).await.map(IntoResponse::into_response);
             }
@@ -1,8 +1,12 @@
 use crate::config::Conf;
+use crate::logic::notify::NotifyLogic;
+use crate::logic::recording::RecordingLogic;
 use crate::logic::role;
 use crate::logic::ssh::SshLogic;
+use crate::logic::tag::TagLogic;
 use crate::logic::team::TeamLogic;
 use crate::logic::types::Permission;
+use crate::logic::user::LdapCheck;
 use crate::logic::{
     executor::ExecutorLogic, instance::InstanceLogic, job::JobLogic, migration::MigrationLogic,
     role::RoleLogic, user::UserLogic,
@@ -16,9 +20,10 @@ use rustc_serialize::hex::{FromHex, ToHex};
 use sea_orm::DatabaseConnection;
 use simple_crypt::{decrypt, encrypt};
 
+use std::collections::HashMap;
 use std::ops::{Deref, DerefMut};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use tokio::sync::{Mutex, RwLock};
 
 pub struct Service<'a> {
     pub user: UserLogic<'a>,
@@ -29,6 +34,9 @@ pub struct Service<'a> {
     pub role: RoleLogic<'a>,
     pub ssh: SshLogic<'a>,
     pub team: TeamLogic<'a>,
+    pub notify: NotifyLogic<'a>,
+    pub recording: RecordingLogic<'a>,
+    pub tag: TagLogic<'a>,
 }
 
 #[derive(Clone)]
@@ -106,6 +114,7 @@ impl AppContextBuilder {
             enforcer: self
                 .enforcer
                 .ok_or(anyhow::anyhow!("enforcer is required"))?,
+            ldap_checks: Arc::new(Mutex::new(HashMap::new())),
         })
     }
 }
@@ -117,6 +126,8 @@ pub struct AppContext {
     pub conf: Conf,
     pub http_client: reqwest::Client,
     pub enforcer: Arc<RwLock<Enforcer>>,
+    /// the last check of each ldap user against the directory, by username
+    pub ldap_checks: Arc<Mutex<HashMap<String, LdapCheck>>>,
 }
 
 impl AppContext {
@@ -140,6 +151,9 @@ impl AppContext {
             migration: MigrationLogic::new(self),
             ssh: SshLogic::new(self),
             team: TeamLogic::new(self),
+            notify: NotifyLogic::new(self),
+            recording: RecordingLogic::new(self),
+            tag: TagLogic::new(self),
         }
     }
 
@@ -247,6 +261,10 @@ impl AppContext {
         Ok(self.enforce((user_id, "file", "upload")).await?)
     }
 
+    pub async fn can_audit_terminal(&self, user_id: &str) -> Result<bool> {
+        Ok(self.enforce((user_id, "terminal", "audit")).await?)
+    }
+
     // can manage job so can manage team
     pub async fn can_manage_job(&self, user_id: &str) -> Result<bool> {
         Ok(self.enforce((user_id, "job", "manage")).await?)
@@ -4,7 +4,7 @@ use clap::Parser;
 use tracing::error;
 
 use automate::scheduler::{
-    types::{AssignUserOption, SshConnectionOption},
+    types::{parse_labels, AssignUserOption, SshConnectionOption},
     Scheduler,
 };
 
@@ -23,9 +23,12 @@ struct AgentArgs {
     bind: String,
     #[arg(long, default_values_t = vec![String::from("ws://127.0.0.1:3000")])]
     comet_addr: Vec<String>,
-    /// Directory for saving job execution logs
+    /// Directory for saving job execution logs and the timers and supervisors restored on restart
     #[arg(long, default_value_t = String::from("./log"))]
     output_dir: String,
+    /// Max bytes of stdout and stderr each kept in the job execution result, the middle part of a longer output is omitted
+    #[arg(long, default_value_t = 64 * 1024)]
+    max_output_bytes: usize,
     #[arg(long, default_value_t = String::from("rYzBYE+cXbtdMg=="))]
     comet_secret: String,
     #[arg(short, long, default_value_t = String::from("default"))]
@@ -46,6 +49,9 @@ struct AgentArgs {
     /// Assign this instance to a user and specify their password
     #[arg(long)]
     assign_password: Option<String>,
+    /// Labels of this instance such as env=prod,role=web, the console tags the instance with them
+    #[arg(long)]
+    labels: Option<String>,
 }
 
 #[tokio::main]
@@ -55,14 +61,19 @@ async fn main() -> Result<()> {
         std::env::set_var("RUST_LOG", "debug");
     }
     tracing_subscriber::fmt::init();
+    if let Some(ref labels) = args.labels {
+        parse_labels(labels)?;
+    }
 
     let mut scheduler = Scheduler::new(
         args.namespace,
         args.comet_addr,
         args.comet_secret,
         args.output_dir,
+        args.max_output_bytes,
         SshConnectionOption::build(args.ssh_user, args.ssh_password, args.ssh_port),
         AssignUserOption::build(args.assign_username, args.assign_password),
+        args.labels,
     );
 
     if let Err(e) = scheduler.connect_comet().await {
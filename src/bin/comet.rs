@@ -49,6 +49,14 @@ async fn main() -> Result<()> {
                     .data(comet.clone()),
             ),
         )
+        .at(
+            "/reconcile",
+            post(
+                handler::reconcile
+                    .with(bearer_auth(&args.secret))
+                    .data(comet.clone()),
+            ),
+        )
         .at(
             "runtime/action",
             post(
@@ -104,6 +112,18 @@ async fn main() -> Result<()> {
             handler::sftp_download
                 .with(bearer_auth(&args.secret))
                 .data(comet.clone()),
+        )
+        .at(
+            "/ssh/tunnel/host-key",
+            handler::ssh_host_key
+                .with(bearer_auth(&args.secret))
+                .data(comet.clone()),
+        )
+        .at(
+            "/job/tunnel/read-log",
+            handler::read_job_log
+                .with(bearer_auth(&args.secret))
+                .data(comet.clone()),
         );
 
     Ok(Server::new(TcpListener::bind(args.bind)).run(app).await?)